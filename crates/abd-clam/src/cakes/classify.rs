@@ -0,0 +1,35 @@
+//! Fast, approximate classification with the centers of `Cluster`s.
+
+use distances::Number;
+
+use crate::{Cluster, Dataset, Instance};
+
+/// Classifies a query with the majority label of its nearest leaf.
+///
+/// The leaf is found with `Cluster::nearest_leaf`. This computes two distances
+/// per level of the tree, so it is much faster than a KNN search, but the leaf
+/// need not contain the nearest neighbor of the query.
+///
+/// # Arguments
+///
+/// * `root`: The root of the tree.
+/// * `data`: The dataset from which the tree was built.
+/// * `query`: The query to classify.
+/// * `labels`: The label of every instance in the dataset, in the order of
+///   the dataset after it was reordered by building the tree.
+///
+/// # Returns
+///
+/// The majority label of the leaf reached by the query. See
+/// `Cluster::majority_label`.
+pub fn nearest_centroid<I, U, D, C>(root: &C, data: &D, query: &I, labels: &[usize]) -> usize
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    root.nearest_leaf(data, query)
+        .majority_label(labels)
+        .unwrap_or_else(|| unreachable!("Leaves are never empty."))
+}
@@ -0,0 +1,189 @@
+//! Coreset selection, i.e. choosing a small subset of instances that
+//! represents a dataset.
+
+use distances::Number;
+
+use crate::{utils, Cluster, Dataset, Instance};
+
+/// A `Cluster` in the tree, with the positions of its children.
+struct Node<'a, C> {
+    /// The `Cluster`.
+    cluster: &'a C,
+    /// The positions of the children of the `Cluster` in the list of `Node`s.
+    children: Option<[usize; 2]>,
+}
+
+/// Chooses `m` instances via farthest-first traversal, i.e. farthest-point
+/// sampling, starting from the `center` of the `root`.
+///
+/// Each subsequent instance is the one whose distance to the closest chosen
+/// instance is largest. The result is the same as that of a brute-force
+/// traversal over all instances, but the tree is used to avoid updating the
+/// distances of instances in `Cluster`s that are too far from a newly chosen
+/// instance to be affected by it, and to find the next instance without
+/// scanning every instance.
+///
+/// Sampling stops early if every remaining instance is at distance zero from
+/// a chosen instance.
+///
+/// # Arguments
+///
+/// * `data`: The dataset from which the tree was built.
+/// * `root`: The root of the tree, or of the subtree to choose from.
+/// * `m`: The maximum number of instances to choose.
+///
+/// # Returns
+///
+/// The indices of the chosen instances, in the order they were chosen.
+pub fn farthest_first<I, U, D, C>(data: &D, root: &C, m: usize) -> Vec<usize>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    if m == 0 {
+        return Vec::new();
+    }
+
+    let mut nodes = Vec::new();
+    add_nodes(root, &mut nodes);
+
+    let first = root.arg_center();
+    let mut chosen = vec![first];
+    let mut min_distances = data.one_to_many(first, &root.indices().collect::<Vec<_>>());
+    let mut max_distances = vec![U::zero(); nodes.len()];
+    init_max_distances(&nodes, 0, root.offset(), &min_distances, &mut max_distances);
+
+    while chosen.len() < m && max_distances[0] > U::zero() {
+        let next = farthest(&nodes, root.offset(), &min_distances, &max_distances);
+        chosen.push(next);
+        update(
+            data,
+            &nodes,
+            0,
+            next,
+            root.offset(),
+            &mut min_distances,
+            &mut max_distances,
+        );
+    }
+
+    chosen
+}
+
+/// Adds the `Node`s for the subtree of `c` to `nodes` in pre-order, and
+/// returns the position of the `Node` for `c`.
+fn add_nodes<'a, U: Number, C: Cluster<U>>(c: &'a C, nodes: &mut Vec<Node<'a, C>>) -> usize {
+    let position = nodes.len();
+    nodes.push(Node {
+        cluster: c,
+        children: None,
+    });
+    if let Some([left, right]) = c.children() {
+        let left = add_nodes(left, nodes);
+        let right = add_nodes(right, nodes);
+        nodes[position].children = Some([left, right]);
+    }
+    position
+}
+
+/// Sets the largest distance to the closest chosen instance for the subtree of
+/// the `Node` at `position`, and returns it.
+fn init_max_distances<U: Number, C: Cluster<U>>(
+    nodes: &[Node<C>],
+    position: usize,
+    offset: usize,
+    min_distances: &[U],
+    max_distances: &mut [U],
+) -> U {
+    let max = match nodes[position].children {
+        Some([left, right]) => {
+            let l = init_max_distances(nodes, left, offset, min_distances, max_distances);
+            let r = init_max_distances(nodes, right, offset, min_distances, max_distances);
+            if l < r {
+                r
+            } else {
+                l
+            }
+        }
+        None => leaf_max(nodes[position].cluster, offset, min_distances),
+    };
+    max_distances[position] = max;
+    max
+}
+
+/// The largest distance to the closest chosen instance among the instances in
+/// a leaf `Cluster`.
+fn leaf_max<U: Number, C: Cluster<U>>(c: &C, offset: usize, min_distances: &[U]) -> U {
+    let distances = &min_distances[(c.offset() - offset)..(c.offset() - offset + c.cardinality())];
+    utils::arg_max(distances).map_or_else(U::zero, |(_, d)| d)
+}
+
+/// Descends the tree to find the instance farthest from the chosen instances.
+///
+/// Ties are broken in favor of the instance with the largest index, as in
+/// `utils::arg_max`.
+fn farthest<U: Number, C: Cluster<U>>(
+    nodes: &[Node<C>],
+    offset: usize,
+    min_distances: &[U],
+    max_distances: &[U],
+) -> usize {
+    let mut position = 0;
+    while let Some([left, right]) = nodes[position].children {
+        position = if max_distances[right] < max_distances[left] {
+            left
+        } else {
+            right
+        };
+    }
+
+    let c = nodes[position].cluster;
+    let distances = &min_distances[(c.offset() - offset)..(c.offset() - offset + c.cardinality())];
+    let Some((i, _)) = utils::arg_max(distances) else {
+        unreachable!("A leaf has at least one instance.")
+    };
+    c.offset() + i
+}
+
+/// Updates the distances to the closest chosen instance after choosing `new`,
+/// for the subtree of the `Node` at `position`.
+///
+/// A subtree is skipped if the `new` instance is too far from its center to be
+/// closer than the already chosen instances to any instance in the subtree.
+fn update<I: Instance, U: Number, D: Dataset<I, U>, C: Cluster<U>>(
+    data: &D,
+    nodes: &[Node<C>],
+    position: usize,
+    new: usize,
+    offset: usize,
+    min_distances: &mut [U],
+    max_distances: &mut [U],
+) {
+    let c = nodes[position].cluster;
+    if data.one_to_one(new, c.arg_center()) >= max_distances[position] + c.radius() {
+        return;
+    }
+
+    max_distances[position] = if let Some([left, right]) = nodes[position].children {
+        update(data, nodes, left, new, offset, min_distances, max_distances);
+        update(data, nodes, right, new, offset, min_distances, max_distances);
+        let (l, r) = (max_distances[left], max_distances[right]);
+        if l < r {
+            r
+        } else {
+            l
+        }
+    } else {
+        let indices = c.indices().collect::<Vec<_>>();
+        let distances = data.one_to_many(new, &indices);
+        for (i, d) in indices.into_iter().zip(distances) {
+            let m = &mut min_distances[i - offset];
+            if d < *m {
+                *m = d;
+            }
+        }
+        leaf_max(c, offset, min_distances)
+    };
+}
@@ -0,0 +1,85 @@
+//! Density-based clustering (DBSCAN) with tree-pruned range queries.
+
+use std::collections::VecDeque;
+
+use distances::Number;
+
+use super::rnn::clustered;
+use crate::{Cluster, Dataset, Instance};
+
+/// Labels the instances of a dataset with DBSCAN.
+///
+/// An instance is a core point if at least `min_pts` instances, including
+/// itself, are within `eps` of it. Clusters are grown from core points by
+/// adding every instance within `eps` of a core point in the cluster. Each
+/// epsilon-neighborhood is found with the same tree search as
+/// `rnn::Algorithm::Clustered`, and is queried at most once per instance.
+///
+/// # Arguments
+///
+/// * `data`: The dataset from which the tree was built.
+/// * `root`: The root of the tree.
+/// * `eps`: The radius of the neighborhood of each instance.
+/// * `min_pts`: The minimum size of the neighborhood of a core point.
+///
+/// # Returns
+///
+/// The label of each instance, in the order in which the instances are
+/// stored in `data`. The labels of the clusters are consecutive, starting
+/// from zero in the order in which the clusters are found, and noise is
+/// labeled `None`.
+pub fn dbscan<I, U, D, C>(data: &D, root: &C, eps: U, min_pts: usize) -> Vec<Option<usize>>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let neighbors_of = |i: usize| {
+        let query = &data[i];
+        let [confirmed, straddlers] = clustered::tree_search(data, root, query, eps);
+        clustered::leaf_search(data, confirmed, straddlers, query, eps)
+            .into_iter()
+            .map(|(j, _)| j)
+            .collect::<Vec<_>>()
+    };
+
+    let mut labels = vec![None; data.cardinality()];
+    let mut visited = vec![false; data.cardinality()];
+    let mut num_clusters = 0;
+
+    for start in 0..data.cardinality() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+
+        let neighbors = neighbors_of(start);
+        if neighbors.len() < min_pts {
+            // This may later be claimed as a border point of a cluster.
+            continue;
+        }
+
+        let label = Some(num_clusters);
+        num_clusters += 1;
+        labels[start] = label;
+
+        let mut frontier = VecDeque::from(neighbors);
+        while let Some(i) = frontier.pop_front() {
+            if labels[i].is_none() {
+                labels[i] = label;
+            }
+            if visited[i] {
+                continue;
+            }
+            visited[i] = true;
+
+            let neighbors = neighbors_of(i);
+            if neighbors.len() >= min_pts {
+                frontier.extend(neighbors.into_iter().filter(|&j| !visited[j] || labels[j].is_none()));
+            }
+        }
+    }
+
+    labels
+}
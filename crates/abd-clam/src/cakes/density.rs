@@ -0,0 +1,165 @@
+//! Kernel density estimation with tree-pruned range counts.
+
+use core::marker::PhantomData;
+
+use distances::Number;
+
+use crate::{Cluster, Dataset, Instance};
+
+/// The default number of concentric shells around a query.
+pub const DEFAULT_NUM_SHELLS: usize = 16;
+
+/// The distance, in multiples of the bandwidth, beyond which the kernel is
+/// treated as zero.
+const KERNEL_SUPPORT: f64 = 3.0;
+
+/// Estimates the density of a dataset at a query with a Gaussian kernel.
+///
+/// Around the query, the ball of radius `3 * bandwidth` is divided into
+/// concentric shells of equal width. The instances in each shell are counted
+/// with range counts, in a single traversal of the tree, that only compute
+/// distances to instances in the `Cluster`s that straddle the boundary of a
+/// shell. Every instance in a shell is weighted by the kernel at the middle
+/// of that shell.
+///
+/// Since a general metric space has no notion of volume, the density is not
+/// normalized by the volume of the kernel. It is the weighted fraction of the
+/// instances near the query, and lies in the range `[0, 1]`.
+pub struct DensityEstimator<'a, U: Number, D, C: Cluster<U>> {
+    /// The dataset from which the tree was built.
+    data: &'a D,
+    /// The root of the tree.
+    root: &'a C,
+    /// The bandwidth of the kernel.
+    bandwidth: f64,
+    /// The number of concentric shells around a query.
+    num_shells: usize,
+    /// The type of the distance values.
+    _u: PhantomData<U>,
+}
+
+impl<'a, U: Number, D, C: Cluster<U>> DensityEstimator<'a, U, D, C> {
+    /// Creates a new `DensityEstimator` with `DEFAULT_NUM_SHELLS` shells.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `root`: The root of the tree.
+    /// * `bandwidth`: The bandwidth of the kernel.
+    ///
+    /// # Errors
+    ///
+    /// * If the `bandwidth` is not positive.
+    pub fn new(data: &'a D, root: &'a C, bandwidth: U) -> Result<Self, String> {
+        let bandwidth = bandwidth.as_f64();
+        if bandwidth > 0.0 {
+            Ok(Self {
+                data,
+                root,
+                bandwidth,
+                num_shells: DEFAULT_NUM_SHELLS,
+                _u: PhantomData,
+            })
+        } else {
+            Err(format!("The bandwidth must be positive. Got {bandwidth}."))
+        }
+    }
+
+    /// Sets the number of concentric shells around a query.
+    ///
+    /// More shells give a finer approximation of the kernel at the cost of
+    /// more `Cluster`s straddling the boundaries of shells.
+    ///
+    /// # Errors
+    ///
+    /// * If `num_shells` is zero.
+    pub fn with_num_shells(mut self, num_shells: usize) -> Result<Self, String> {
+        if num_shells == 0 {
+            Err("The number of shells must be positive.".to_string())
+        } else {
+            self.num_shells = num_shells;
+            Ok(self)
+        }
+    }
+
+    /// The bandwidth of the kernel.
+    #[must_use]
+    pub const fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    /// The number of concentric shells around a query.
+    #[must_use]
+    pub const fn num_shells(&self) -> usize {
+        self.num_shells
+    }
+
+    /// The outer radii of the concentric shells, in increasing order.
+    #[must_use]
+    pub fn shell_radii(&self) -> Vec<f64> {
+        let width = KERNEL_SUPPORT * self.bandwidth / self.num_shells.as_f64();
+        (1..=self.num_shells).map(|j| j.as_f64() * width).collect()
+    }
+
+    /// Estimates the density of the dataset at the `query`.
+    pub fn density<I: Instance>(&self, query: &I) -> f64
+    where
+        D: Dataset<I, U>,
+    {
+        let radii = self.shell_radii();
+        let mut counts = vec![0; radii.len()];
+        let all = radii.iter().copied().enumerate().collect::<Vec<_>>();
+        self.range_counts(self.root, query, &all, &mut counts);
+
+        // Every instance in a shell is weighted by the kernel at the middle of the shell.
+        let (mut weighted, mut inner_count, mut inner_radius) = (0.0, 0, 0.0);
+        for (outer_radius, count) in radii.into_iter().zip(counts) {
+            let middle = (inner_radius + outer_radius) / (2.0 * self.bandwidth);
+            weighted += (count - inner_count).as_f64() * (-middle * middle / 2.0).exp();
+            (inner_count, inner_radius) = (count, outer_radius);
+        }
+
+        weighted / self.root.cardinality().as_f64()
+    }
+
+    /// Adds, for each radius, the number of instances in the subtree of `c`
+    /// within that radius of the `query` to the count for that radius.
+    ///
+    /// # Arguments
+    ///
+    /// * `c`: The `Cluster` whose instances are counted.
+    /// * `query`: The query.
+    /// * `radii`: The positions of the radii in `counts`, and the radii, for
+    ///   which the ball around the `query` overlaps with the parent of `c`.
+    /// * `counts`: The counts for every radius.
+    fn range_counts<I: Instance>(&self, c: &C, query: &I, radii: &[(usize, f64)], counts: &mut [usize])
+    where
+        D: Dataset<I, U>,
+    {
+        let d = c.distance_to_instance(self.data, query).as_f64();
+        let r = c.radius().as_f64();
+
+        let mut straddled = Vec::new();
+        for &(j, radius) in radii {
+            if d + r <= radius {
+                counts[j] += c.cardinality();
+            } else if d <= radius + r {
+                straddled.push((j, radius));
+            }
+        }
+
+        if straddled.is_empty() {
+            return;
+        }
+
+        if let Some([left, right]) = c.children() {
+            self.range_counts(left, query, &straddled, counts);
+            self.range_counts(right, query, &straddled, counts);
+        } else {
+            let distances = self.data.query_to_many(query, &c.indices().collect::<Vec<_>>());
+            for (j, radius) in straddled {
+                counts[j] += distances.iter().filter(|d| d.as_f64() <= radius).count();
+            }
+        }
+    }
+}
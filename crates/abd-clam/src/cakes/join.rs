@@ -0,0 +1,116 @@
+//! Spatial joins between two datasets, using a tree over each.
+
+use distances::Number;
+use rayon::prelude::*;
+
+use crate::{Cluster, Dataset, Instance};
+
+/// Finds all pairs of instances, one from each dataset, that are within a
+/// given radius of each other.
+///
+/// Both trees are descended together. A pair of `Cluster`s is pruned when the
+/// distance between their centers exceeds the sum of their radii and the
+/// `radius`, since no pair of their instances can then be within the `radius`.
+/// Otherwise, the `Cluster` with the larger radius is split, until both are
+/// leaves and their instances are compared directly. Splits of the `Cluster`s
+/// from `data_a` are searched in parallel.
+///
+/// The distances are computed with the `Dataset` methods of `data_b`, with
+/// the instances of `data_a` as queries, so any batched metric or other
+/// override of those methods is used.
+///
+/// # Arguments
+///
+/// * `data_a`: The dataset from which `root_a` was built.
+/// * `root_a`: The root of the tree over `data_a`.
+/// * `data_b`: The dataset from which `root_b` was built.
+/// * `root_b`: The root of the tree over `data_b`.
+/// * `radius`: The search radius.
+///
+/// # Returns
+///
+/// The index in `data_a`, the index in `data_b` and the distance of every
+/// pair within the `radius`, sorted by the index in `data_a` and then by the
+/// index in `data_b`. The indices are of the instances as they are stored
+/// after building the trees.
+pub fn radius_join<I, U, Da, Db, C>(
+    data_a: &Da,
+    root_a: &C,
+    data_b: &Db,
+    root_b: &C,
+    radius: U,
+) -> Vec<(usize, usize, U)>
+where
+    I: Instance,
+    U: Number,
+    Da: Dataset<I, U>,
+    Db: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let mut hits = join_clusters(data_a, root_a, data_b, root_b, radius);
+    hits.par_sort_unstable_by_key(|&(a, b, _)| (a, b));
+    hits
+}
+
+/// Recursively joins a pair of `Cluster`s, one from each tree.
+fn join_clusters<I, U, Da, Db, C>(data_a: &Da, a: &C, data_b: &Db, b: &C, radius: U) -> Vec<(usize, usize, U)>
+where
+    I: Instance,
+    U: Number,
+    Da: Dataset<I, U>,
+    Db: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let d = data_b.query_to_one(&data_a[a.arg_center()], b.arg_center());
+    if d > a.radius() + b.radius() + radius {
+        return Vec::new();
+    }
+
+    match (a.children(), b.children()) {
+        (None, None) => {
+            let b_indices = b.indices().collect::<Vec<_>>();
+            a.indices()
+                .flat_map(|i| {
+                    data_b
+                        .query_to_many(&data_a[i], &b_indices)
+                        .into_iter()
+                        .zip(&b_indices)
+                        .filter(|&(d, _)| d <= radius)
+                        .map(move |(d, &j)| (i, j, d))
+                })
+                .collect()
+        }
+        (Some([left, right]), None) => par_join_children(data_a, [left, right], data_b, b, radius),
+        (Some([left, right]), Some(_)) if a.radius() >= b.radius() => {
+            par_join_children(data_a, [left, right], data_b, b, radius)
+        }
+        (_, Some(children)) => children
+            .into_iter()
+            .flat_map(|child| join_clusters(data_a, a, data_b, child, radius))
+            .collect(),
+    }
+}
+
+/// Joins both children of a `Cluster` from the tree over `data_a`, in parallel,
+/// with a `Cluster` from the tree over `data_b`.
+fn par_join_children<I, U, Da, Db, C>(
+    data_a: &Da,
+    [left, right]: [&C; 2],
+    data_b: &Db,
+    b: &C,
+    radius: U,
+) -> Vec<(usize, usize, U)>
+where
+    I: Instance,
+    U: Number,
+    Da: Dataset<I, U>,
+    Db: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let (mut left, right) = rayon::join(
+        || join_clusters(data_a, left, data_b, b, radius),
+        || join_clusters(data_a, right, data_b, b, radius),
+    );
+    left.extend(right);
+    left
+}
@@ -0,0 +1,87 @@
+//! Batched distance kernels for brute-force search over dense vectors.
+//!
+//! These compute the distances from a query to many instances at once, and
+//! can be used as the `BatchMetric` of a `Dataset` so that `query_to_many`
+//! calls them instead of the metric one pair at a time.
+//!
+//! `batch_distance_matrix` evaluates many queries at once against any
+//! `Dataset`.
+
+use distances::Number;
+use rayon::prelude::*;
+
+use crate::{Dataset, Instance};
+
+/// Squared Euclidean distance between two `f32` vectors.
+///
+/// This is the metric that `batch_euclidean_sq` is a batched version of. See
+/// `VecDataset::with_euclidean_sq_kernel`.
+#[allow(clippy::ptr_arg)]
+#[must_use]
+pub fn euclidean_sq(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    distances::vectors::euclidean_sq(x, y)
+}
+
+/// Squared Euclidean distances from a `query` to each of many `instances`.
+///
+/// This uses `||a - b||^2 = ||a||^2 + ||b||^2 - 2 a.b`, so the work for each
+/// instance is one dot product and one squared norm, with the squared norm of
+/// the `query` computed only once. The identity is subject to cancellation
+/// when the instances are close to the `query`, so the results may differ
+/// slightly from a direct computation, and are clamped to be non-negative.
+///
+/// # Arguments
+///
+/// * `query`: The query vector.
+/// * `instances`: The instances, each with the same dimensionality as the
+///   `query`.
+///
+/// # Returns
+///
+/// The squared Euclidean distance from the `query` to each of the `instances`.
+#[must_use]
+pub fn batched_euclidean_squared(query: &[f32], instances: &[&[f32]]) -> Vec<f32> {
+    let query_norm = dot(query, query);
+    instances
+        .iter()
+        .map(|x| 2.0_f32.mul_add(-dot(query, x), query_norm + dot(x, x)).max(0.0))
+        .collect()
+}
+
+/// `batched_euclidean_squared` with the signature of a `BatchMetric` over
+/// `Vec<f32>` instances.
+#[allow(clippy::ptr_arg)]
+#[must_use]
+pub fn batch_euclidean_sq(query: &Vec<f32>, instances: &[&Vec<f32>]) -> Vec<f32> {
+    let instances = instances.iter().map(|x| x.as_slice()).collect::<Vec<_>>();
+    batched_euclidean_squared(query, &instances)
+}
+
+/// Computes the distances from each of many `queries` to the instances at
+/// `indices`, in parallel over the `queries`.
+///
+/// Each row is computed with `Dataset::query_to_many`, so the batched metric
+/// of the dataset is used if it has one.
+///
+/// # Arguments
+///
+/// * `data`: The dataset.
+/// * `queries`: The queries.
+/// * `indices`: The indices of the candidate instances in `data`.
+///
+/// # Returns
+///
+/// A `queries.len()` x `indices.len()` matrix in which the entry at `[i][j]`
+/// is the distance from `queries[i]` to the instance at `indices[j]`.
+pub fn batch_distance_matrix<I: Instance, U: Number, D: Dataset<I, U>>(
+    data: &D,
+    queries: &[I],
+    indices: &[usize],
+) -> Vec<Vec<U>> {
+    queries.par_iter().map(|q| data.query_to_many(q, indices)).collect()
+}
+
+/// The dot product of two vectors.
+fn dot(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y).map(|(a, b)| a * b).sum()
+}
@@ -1,10 +1,10 @@
 //! Search function and helper functions for knn with expanding threshold.
 
-use distances::Number;
+use distances::{number::Float, Number};
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{Cluster, Dataset, DistanceCache, Instance, Tree};
 
-use super::{OrdNumber, RevNumber};
+use super::{sort_hits, OrdNumber, RevNumber};
 
 /// K-Nearest Neighbor search with expanding threshold.
 ///
@@ -21,6 +21,42 @@ use super::{OrdNumber, RevNumber};
 ///
 /// Contrast this to `SieveV1` and `SieveV2`, which use a (mostly) decreasing threshold.
 pub fn search<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, d_min)
+}
+
+/// K-Nearest Neighbor search with expanding threshold in a tree built with
+/// the squared Euclidean distance.
+///
+/// The search is carried out on squared distances, and the square root is
+/// only taken of the distances to the centers of the clusters that are
+/// visited, to bound the distances to their instances, and of the distances
+/// to the final hits.
+///
+/// # Returns
+///
+/// The hits, as in `search`, with Euclidean distances.
+pub fn search_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, d_min_squared)
+        .into_iter()
+        .map(|(i, d)| (i, d.sqrt()))
+        .collect()
+}
+
+/// K-Nearest Neighbor search with expanding threshold, where `d_min` bounds
+/// the distance from the query to the instances in a cluster.
+fn search_with<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize, d_min: fn(&C, U) -> U) -> Vec<(usize, U)>
 where
     I: Instance,
     U: Number,
@@ -28,11 +64,13 @@ where
     C: Cluster<U>,
 {
     let mut candidates = priority_queue::PriorityQueue::<&C, RevNumber<U>>::new();
-    let mut hits = priority_queue::PriorityQueue::<usize, OrdNumber<U>>::new();
+    let mut hits = priority_queue::PriorityQueue::<usize, (OrdNumber<U>, usize)>::new();
+
+    let mut centers = DistanceCache::default();
 
     let (data, root) = (tree.data(), &tree.root);
 
-    let d = root.distance_to_instance(data, query);
+    let d = data.query_to_one_cached(&mut centers, query, root.arg_center());
     candidates.push(root, RevNumber(d_min(root, d)));
 
     // stop if we have enough hits and the farthest hit is closer than the closest cluster by delta_min.
@@ -40,16 +78,22 @@ where
         || (!candidates.is_empty()
             && hits
                 .peek()
-                .map_or_else(|| unreachable!("`hits` is non-empty."), |(_, &OrdNumber(d))| d)
+                .map_or_else(|| unreachable!("`hits` is non-empty."), |(_, &(OrdNumber(d), _))| d)
                 >= candidates
                     .peek()
                     .map_or_else(|| unreachable!("`candidates` is non-empty."), |(_, &RevNumber(d))| d))
     {
-        pop_till_leaf(tree, query, &mut candidates);
-        leaf_into_hits(tree, query, &mut hits, &mut candidates);
+        pop_till_leaf(tree, query, &mut candidates, &mut centers, d_min);
+        leaf_into_hits(tree, query, &mut hits, &mut candidates, &centers);
         trim_hits(k, &mut hits);
     }
-    hits.into_iter().map(|(i, OrdNumber(d))| (i, d)).collect()
+
+    let mut hits = hits
+        .into_iter()
+        .map(|(i, (OrdNumber(d), _))| (i, d))
+        .collect::<Vec<_>>();
+    sort_hits(&mut hits);
+    hits
 }
 
 /// Calculates the theoretical best case distance for a point in a cluster, i.e.,
@@ -62,11 +106,25 @@ fn d_min<U: Number, C: Cluster<U>>(c: &C, d: U) -> U {
     }
 }
 
+/// Same as `d_min`, but with the squared distance from the query to the
+/// center of a cluster whose radius is a squared distance, and returning a
+/// squared distance.
+fn d_min_squared<U: Float, C: Cluster<U>>(c: &C, d_sq: U) -> U {
+    let d = d_sq.sqrt() - c.radius().sqrt();
+    if d < U::zero() {
+        U::zero()
+    } else {
+        d * d
+    }
+}
+
 /// Pops from the top of `candidates` until the top candidate is a leaf cluster.
 fn pop_till_leaf<I, U, D, C>(
     tree: &Tree<I, U, D, C>,
     query: &I,
     candidates: &mut priority_queue::PriorityQueue<&C, RevNumber<U>>,
+    centers: &mut DistanceCache<U>,
+    d_min: fn(&C, U) -> U,
 ) where
     I: Instance,
     U: Number,
@@ -82,8 +140,8 @@ fn pop_till_leaf<I, U, D, C>(
             |(c, _)| c.children().unwrap_or_else(|| unreachable!("elements are non-leaves")),
         );
         let [dl, dr] = [
-            l.distance_to_instance(tree.data(), query),
-            r.distance_to_instance(tree.data(), query),
+            tree.data().query_to_one_cached(centers, query, l.arg_center()),
+            tree.data().query_to_one_cached(centers, query, r.arg_center()),
         ];
         candidates.push(l, RevNumber(d_min(l, dl)));
         candidates.push(r, RevNumber(d_min(r, dr)));
@@ -94,8 +152,9 @@ fn pop_till_leaf<I, U, D, C>(
 fn leaf_into_hits<I, U, D, C>(
     tree: &Tree<I, U, D, C>,
     query: &I,
-    hits: &mut priority_queue::PriorityQueue<usize, OrdNumber<U>>,
+    hits: &mut priority_queue::PriorityQueue<usize, (OrdNumber<U>, usize)>,
     candidates: &mut priority_queue::PriorityQueue<&C, RevNumber<U>>,
+    centers: &DistanceCache<U>,
 ) where
     I: Instance,
     U: Number,
@@ -108,15 +167,18 @@ fn leaf_into_hits<I, U, D, C>(
     let distances = if leaf.is_singleton() {
         vec![d; leaf.indices().len()]
     } else {
-        tree.data().query_to_many(query, &leaf.indices().collect::<Vec<_>>())
+        tree.data()
+            .query_to_many_cached(centers, query, &leaf.indices().collect::<Vec<_>>())
     };
     leaf.indices().zip(distances).for_each(|(i, d)| {
-        hits.push(i, OrdNumber(d));
+        hits.push(i, (OrdNumber(d), i));
     });
 }
 
 /// Trims hits to contain only the k-nearest neighbors.
-fn trim_hits<U: Number>(k: usize, hits: &mut priority_queue::PriorityQueue<usize, OrdNumber<U>>) {
+///
+/// Among hits at the same distance, those with the largest indices are trimmed first.
+fn trim_hits<U: Number>(k: usize, hits: &mut priority_queue::PriorityQueue<usize, (OrdNumber<U>, usize)>) {
     while hits.len() > k {
         hits.pop()
             .unwrap_or_else(|| unreachable!("`hits` is non-empty and has at least k elements."));
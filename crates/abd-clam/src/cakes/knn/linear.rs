@@ -1,5 +1,7 @@
 //! Linear search for the k nearest neighbors of a query.
 
+use core::cmp::Ordering;
+
 use distances::Number;
 
 use crate::{Dataset, Instance};
@@ -34,3 +36,39 @@ where
         .for_each(|(&i, &d)| hits.push(i, d));
     hits.extract()
 }
+
+/// Linear search for the first `k` instances in a custom ordering of the
+/// hits.
+///
+/// # Arguments
+///
+/// * `data` - The dataset to search.
+/// * `query` - The query to search around.
+/// * `k` - The number of neighbors to search for.
+/// * `indices` - The indices to search.
+/// * `compare` - The ordering of the hits, as 2-tuples of the index and the
+///   distance. Hits that compare as `Less` are preferred.
+///
+/// # Returns
+///
+/// The first `k` hits in the ordering given by `compare`, sorted by it.
+pub fn search_by<I, U, D, F>(data: &D, query: &I, k: usize, indices: &[usize], compare: F) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    F: Fn(&(usize, U), &(usize, U)) -> Ordering,
+{
+    let distances = data.query_to_many(query, indices);
+    let mut hits = indices.iter().copied().zip(distances).collect::<Vec<_>>();
+
+    if k == 0 {
+        return Vec::new();
+    }
+    if k < hits.len() {
+        hits.select_nth_unstable_by(k - 1, &compare);
+        hits.truncate(k);
+    }
+    hits.sort_by(compare);
+    hits
+}
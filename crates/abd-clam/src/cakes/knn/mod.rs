@@ -7,10 +7,13 @@
 //! We will experiment with other algorithms in the future, and they will be added
 //! to this enum as they are being implemented. They should not be considered
 //! stable until they are documented as such.
+//!
+//! All algorithms break ties in distance by ascending index of the instance, so
+//! that repeated searches return identical hits.
 
 use core::{cmp::Ordering, hash::Hash};
 
-use distances::Number;
+use distances::{number::Float, Number};
 use priority_queue::PriorityQueue;
 
 use crate::{Cluster, Dataset, Instance, Tree};
@@ -41,7 +44,7 @@ pub enum Algorithm {
     /// dimension of the neighbors found until enough neighbors are found. This
     /// factor is capped at 2. Once enough neighbors are found, the neighbors
     /// are sorted by distance and the first `k` neighbors are returned. Ties
-    /// are broken arbitrarily.
+    /// are broken by ascending index.
     RepeatedRnn,
 
     /// Uses two priority queues and an increasing threshold to perform search.
@@ -85,6 +88,17 @@ pub enum Algorithm {
     /// This approach treats the center of a cluster separately from the rest
     /// of the points in the cluster.
     SieveSepCenter,
+
+    /// Like `RepeatedRnn`, but the search starts from an estimate of the
+    /// distance to the `k`-th nearest neighbor instead of from a tiny radius.
+    ///
+    /// This algorithm is not stable.
+    ///
+    /// The estimate is made by linear search on a small random sample of the
+    /// dataset, with a fixed seed. Starting near the final radius saves most
+    /// of the RNN searches needed to grow the radius when `k` is large. The
+    /// hits are the same as for `RepeatedRnn`.
+    KnnByKthRadius,
 }
 
 impl Default for Algorithm {
@@ -106,6 +120,8 @@ impl Algorithm {
     ///
     /// A vector of 2-tuples, where the first element is the index of the instance
     /// and the second element is the distance from the query to the instance.
+    /// The hits are sorted by increasing distance, with ties broken by
+    /// increasing index.
     pub fn search<I, U, D, C>(self, tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
     where
         I: Instance,
@@ -122,6 +138,87 @@ impl Algorithm {
             Self::GreedySieve => greedy_sieve::search(tree, query, k),
             Self::Sieve => sieve::search(tree, query, k),
             Self::SieveSepCenter => sieve_sep_center::search(tree, query, k),
+            Self::KnnByKthRadius => repeated_rnn::search_by_kth_radius(tree, query, k),
+        }
+    }
+
+    /// Searches for the nearest neighbors of a query in a tree built with the
+    /// squared Euclidean distance, e.g. `distances::vectors::euclidean_sq`.
+    ///
+    /// The search compares squared distances and only takes the square root
+    /// where a bound on the distances to the instances in a cluster is needed,
+    /// and of the distances to the hits. Each algorithm gives the same hits as
+    /// `search` with that algorithm in a tree built with the Euclidean
+    /// distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to search.
+    /// * `query` - The query to search around.
+    /// * `k` - The number of neighbors to search for.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`, with Euclidean distances.
+    pub fn search_squared<I, U, D, C>(self, tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+    where
+        I: Instance,
+        U: Float,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::Linear => self
+                .search(tree, query, k)
+                .into_iter()
+                .map(|(i, d)| (i, d.sqrt()))
+                .collect(),
+            Self::RepeatedRnn => repeated_rnn::search_squared(tree, query, k),
+            Self::GreedySieve => greedy_sieve::search_squared(tree, query, k),
+            Self::Sieve => sieve::search_squared(tree, query, k),
+            Self::SieveSepCenter => sieve_sep_center::search_squared(tree, query, k),
+            Self::KnnByKthRadius => repeated_rnn::search_by_kth_radius_squared(tree, query, k),
+        }
+    }
+
+    /// Same as `search`, but also returns the number of RNN searches that
+    /// `RepeatedRnn` or `KnnByKthRadius` made to find the nearest neighbors.
+    ///
+    /// This is meant for tuning, e.g. to compare how quickly the radius grows
+    /// to contain the `k` nearest neighbors.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to search.
+    /// * `query` - The query to search around.
+    /// * `k` - The number of neighbors to search for.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`, and the number of RNN searches, or `None` for
+    /// the other algorithms.
+    pub fn search_with_rnn_iterations<I, U, D, C>(
+        self,
+        tree: &Tree<I, U, D, C>,
+        query: &I,
+        k: usize,
+    ) -> (Vec<(usize, U)>, Option<usize>)
+    where
+        I: Instance,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::RepeatedRnn => {
+                let (hits, iterations) = repeated_rnn::search_with_iterations(tree, query, k);
+                (hits, Some(iterations))
+            }
+            Self::KnnByKthRadius => {
+                let (hits, iterations) = repeated_rnn::search_by_kth_radius_with_iterations(tree, query, k);
+                (hits, Some(iterations))
+            }
+            Self::Linear | Self::GreedySieve | Self::Sieve | Self::SieveSepCenter => (self.search(tree, query, k), None),
         }
     }
 
@@ -134,6 +231,7 @@ impl Algorithm {
             Self::GreedySieve => "GreedySieve",
             Self::Sieve => "Sieve",
             Self::SieveSepCenter => "SieveSepCenter",
+            Self::KnnByKthRadius => "KnnByKthRadius",
         }
     }
 
@@ -159,6 +257,7 @@ impl Algorithm {
             "greedysieve" => Ok(Self::GreedySieve),
             "sieve" => Ok(Self::Sieve),
             "sievesepcenter" => Ok(Self::SieveSepCenter),
+            "knnbykthradius" => Ok(Self::KnnByKthRadius),
             _ => Err(format!("Unknown algorithm: {s}")),
         }
     }
@@ -166,19 +265,61 @@ impl Algorithm {
     /// Returns a list of all the algorithms, excluding Linear.
     #[must_use]
     pub const fn variants<'a>() -> &'a [Self] {
-        &[Self::RepeatedRnn, Self::GreedySieve, Self::Sieve, Self::SieveSepCenter]
+        &[
+            Self::RepeatedRnn,
+            Self::GreedySieve,
+            Self::Sieve,
+            Self::SieveSepCenter,
+            Self::KnnByKthRadius,
+        ]
     }
 }
 
+/// Linear search for the `k` nearest neighbors of a query, with a custom
+/// ordering of the hits.
+///
+/// The algorithms in `Algorithm` break ties in distance by ascending index.
+/// This instead keeps the first `k` hits in the order given by `compare`,
+/// e.g. to break ties by descending index, or by some weight in the metadata
+/// of the instances. For a nearest-neighbor search, `compare` should order
+/// the hits by distance first.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `query` - The query to search around.
+/// * `k` - The number of neighbors to search for.
+/// * `compare` - The ordering of the hits, as 2-tuples of the index and the
+///   distance. Hits that compare as `Less` are preferred.
+///
+/// # Returns
+///
+/// The first `k` hits in the ordering given by `compare`, sorted by it.
+pub fn linear_search_by<I, U, D, C, F>(tree: &Tree<I, U, D, C>, query: &I, k: usize, compare: F) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+    F: Fn(&(usize, U), &(usize, U)) -> Ordering,
+{
+    let indices = (0..tree.cardinality()).collect::<Vec<_>>();
+    linear::search_by(tree.data(), query, k, &indices, compare)
+}
+
 /// A priority queue of hits for K-Nearest Neighbor search.
-pub(crate) struct Hits<I: Hash + Eq + Copy, U: Number> {
+///
+/// Hits are ranked by their distance, with ties broken by their index. This
+/// means that, among hits at the same distance, those with the largest indices
+/// are the first to be evicted from the queue.
+pub(crate) struct Hits<I: Hash + Eq + Ord + Copy, U: Number> {
     /// The priority queue of hits.
-    pub queue: PriorityQueue<I, OrdNumber<U>>,
+    pub queue: PriorityQueue<I, (OrdNumber<U>, I)>,
     /// The number of neighbors to search for.
     pub capacity: usize,
 }
 
-impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
+impl<I: Hash + Eq + Ord + Copy, U: Number> Hits<I, U> {
     /// Creates a new priority queue of hits.
     ///
     /// The priority queue is initialized with a `capacity` and is maintained
@@ -198,7 +339,7 @@ impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
     pub fn from_vec(capacity: usize, vec: Vec<(I, U)>) -> Self {
         let mut queue = PriorityQueue::with_capacity(capacity);
         for (i, d) in vec {
-            queue.push(i, OrdNumber(d));
+            queue.push(i, (OrdNumber(d), i));
         }
         while queue.len() > capacity {
             queue.pop();
@@ -222,15 +363,15 @@ impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
     ///
     /// If the queue is empty, returns the result of calling `default`.
     pub fn peek(&self) -> U {
-        self.queue.peek().map_or_else(U::zero, |(_, &OrdNumber(d))| d)
+        self.queue.peek().map_or_else(U::zero, |(_, &(OrdNumber(d), _))| d)
     }
 
     /// Pushes a hit onto the queue.
     ///
     /// If the queue is not full, the hit is pushed onto the queue. If the queue
-    /// is full and the distance of the hit is less than the distance of the
-    /// farthest hit in the queue, the farthest hit is popped from the queue and
-    /// the new hit is pushed onto the queue.
+    /// is full and the hit ranks below the farthest hit in the queue, i.e. it
+    /// is closer, or it is equally far and has a smaller index, the farthest
+    /// hit is popped from the queue and the new hit is pushed onto the queue.
     ///
     /// # Arguments
     ///
@@ -238,10 +379,10 @@ impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
     /// * `d` - The distance of the hit.
     pub fn push(&mut self, i: I, d: U) {
         if self.queue.len() < self.capacity {
-            self.queue.push(i, OrdNumber(d));
-        } else if d < self.peek() {
+            self.queue.push(i, (OrdNumber(d), i));
+        } else if self.queue.peek().is_some_and(|(_, p)| &(OrdNumber(d), i) < p) {
             self.queue.pop();
-            self.queue.push(i, OrdNumber(d));
+            self.queue.push(i, (OrdNumber(d), i));
         }
     }
 
@@ -249,7 +390,7 @@ impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
     /// end.
     pub fn push_batch(&mut self, items: impl Iterator<Item = (I, U)>) {
         items.for_each(|(i, d)| {
-            self.queue.push(i, OrdNumber(d));
+            self.queue.push(i, (OrdNumber(d), i));
         });
         while self.queue.len() > self.capacity {
             self.queue.pop();
@@ -265,12 +406,43 @@ impl<I: Hash + Eq + Copy, U: Number> Hits<I, U> {
         }
     }
 
-    /// Extracts the hits from the queue.
+    /// Extracts the hits from the queue, sorted by increasing distance and
+    /// then by increasing index.
     pub fn extract(&self) -> Vec<(I, U)> {
-        self.queue.iter().map(|(&i, &OrdNumber(d))| (i, d)).collect()
+        let mut hits = self
+            .queue
+            .iter()
+            .map(|(&i, &(OrdNumber(d), _))| (i, d))
+            .collect::<Vec<_>>();
+        sort_hits(&mut hits);
+        hits
     }
 }
 
+/// Returns the bounds, `(d_min, d_max)`, on the distances from a query to the
+/// instances in a cluster, given the distance `d` from the query to its
+/// center.
+pub(crate) fn bounds<U: Number, C: Cluster<U>>(c: &C, d: U) -> (U, U) {
+    let r = c.radius();
+    let d_min = if d > r { d - r } else { U::zero() };
+    (d_min, d + r)
+}
+
+/// Same as `bounds`, but for a tree built with the squared Euclidean distance.
+///
+/// `d_sq`, the radius of the cluster and the returned bounds are all squared
+/// distances.
+pub(crate) fn bounds_squared<U: Float, C: Cluster<U>>(c: &C, d_sq: U) -> (U, U) {
+    let (d, r) = (d_sq.sqrt(), c.radius().sqrt());
+    let d_min = if d > r { d - r } else { U::zero() };
+    (d_min * d_min, (d + r) * (d + r))
+}
+
+/// Sorts hits by increasing distance, breaking ties by increasing index.
+pub(crate) fn sort_hits<I: Ord, U: Number>(hits: &mut [(I, U)]) {
+    hits.sort_by(|(i, a), (j, b)| OrdNumber(*a).cmp(&OrdNumber(*b)).then_with(|| i.cmp(j)));
+}
+
 /// Field by which we rank elements in priority queue of hits.
 #[derive(Debug)]
 pub struct OrdNumber<U: Number>(U);
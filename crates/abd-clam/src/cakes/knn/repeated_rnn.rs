@@ -1,6 +1,6 @@
 //! Repeated RNN search, with increasing radii, for k-nearest neighbors.
 
-use distances::Number;
+use distances::{number::Float, Number};
 
 use crate::{cakes::rnn::clustered, utils, Cluster, Dataset, Instance, Tree};
 
@@ -9,6 +9,13 @@ use super::Hits;
 /// The multiplier to use for increasing the radius in the repeated RNN algorithm.
 const MULTIPLIER: f64 = 2.0;
 
+/// The number of instances sampled to estimate the distance to the `k`-th
+/// nearest neighbor in `search_by_kth_radius`.
+const SAMPLE_SIZE: usize = 256;
+
+/// The seed for sampling instances in `search_by_kth_radius`.
+const SAMPLE_SEED: u64 = 42;
+
 /// K-Nearest Neighbor search using a repeated RNN search.
 ///
 /// # Arguments
@@ -28,37 +35,312 @@ where
     D: Dataset<I, U>,
     C: Cluster<U>,
 {
-    let mut radius = f64::EPSILON + tree.radius().as_f64() / tree.cardinality().as_f64();
-    let [mut confirmed, mut straddlers] = clustered::tree_search(tree.data(), &tree.root, query, U::from(radius));
+    search_with_iterations(tree, query, k).0
+}
 
-    let mut num_confirmed = count_hits(&confirmed);
+/// K-Nearest Neighbor search using a repeated RNN search, starting from an
+/// estimate of the distance to the `k`-th nearest neighbor.
+///
+/// The estimate is made by linear search on a random sample of the dataset.
+/// If the sample has `s` of the `n` instances, about `k * s / n` of the `k`
+/// nearest neighbors are expected to be in the sample, so the distance to the
+/// `ceil(k * s / n)`-th nearest neighbor in the sample is used as the initial
+/// radius. The radius is then increased as in `search`.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `query` - The query to search around.
+/// * `k` - The number of neighbors to search for.
+///
+/// # Returns
+///
+/// A vector of 2-tuples, where the first element is the index of the instance
+/// and the second element is the distance from the query to the instance.
+pub fn search_by_kth_radius<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_by_kth_radius_with_iterations(tree, query, k).0
+}
 
-    while num_confirmed == 0 {
-        radius *= MULTIPLIER;
-        [confirmed, straddlers] = clustered::tree_search(tree.data(), &tree.root, query, U::from(radius));
-        num_confirmed = count_hits(&confirmed);
+/// K-Nearest Neighbor search using a repeated RNN search in a tree built with
+/// the squared Euclidean distance.
+///
+/// The radius grows as in `search`, in Euclidean units, and each RNN search is
+/// carried out as in `rnn::Algorithm::search_squared`.
+///
+/// # Returns
+///
+/// The hits, as in `search`, with Euclidean distances.
+pub fn search_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let radius = f64::EPSILON + tree.radius().as_f64().sqrt() / tree.cardinality().as_f64();
+    search_squared_from(tree, query, k, radius)
+}
+
+/// Same as `search_by_kth_radius`, but in a tree built with the squared
+/// Euclidean distance, as in `search_squared`.
+///
+/// # Returns
+///
+/// The hits, as in `search`, with Euclidean distances.
+pub fn search_by_kth_radius_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let radius = sampled_kth_distance(tree, query, k).map_or_else(
+        || f64::EPSILON + tree.radius().as_f64().sqrt() / tree.cardinality().as_f64(),
+        |d| f64::EPSILON + d.sqrt(),
+    );
+    search_squared_from(tree, query, k, radius)
+}
+
+/// Same as `search`, but also returns the number of RNN searches made.
+pub fn search_with_iterations<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> (Vec<(usize, U)>, usize)
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_from(tree, query, k, initial_radius(tree))
+}
+
+/// Same as `search_by_kth_radius`, but also returns the number of RNN
+/// searches made.
+pub fn search_by_kth_radius_with_iterations<I, U, D, C>(
+    tree: &Tree<I, U, D, C>,
+    query: &I,
+    k: usize,
+) -> (Vec<(usize, U)>, usize)
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_from(tree, query, k, estimated_radius(tree, query, k))
+}
+
+/// The radius from which `search` starts.
+fn initial_radius<I, U, D, C>(tree: &Tree<I, U, D, C>) -> f64
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    f64::EPSILON + tree.radius().as_f64() / tree.cardinality().as_f64()
+}
+
+/// Estimates the distance from the `query` to its `k`-th nearest neighbor by
+/// linear search on a random sample of the dataset.
+fn estimated_radius<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> f64
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    sampled_kth_distance(tree, query, k).map_or_else(|| initial_radius(tree), |d| f64::EPSILON + d)
+}
+
+/// The distance from the `query` to its `k`-th nearest neighbor in a random
+/// sample of the dataset, scaled to the size of the sample, or `None` if the
+/// sample is empty.
+fn sampled_kth_distance<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Option<f64>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let sample = tree.root.sample_indices(SAMPLE_SIZE, SAMPLE_SEED);
+    if sample.is_empty() {
+        return None;
+    }
+
+    let mut distances = tree.data().query_to_many(query, &sample);
+    distances.sort_by(|l, r| l.partial_cmp(r).unwrap_or(core::cmp::Ordering::Equal));
+
+    // The rank, in the sample, of the `k`-th nearest neighbor.
+    let rank = (k * sample.len()).div_ceil(tree.cardinality()).clamp(1, sample.len());
+    Some(distances[rank - 1].as_f64())
+}
+
+/// Performs repeated RNN searches, starting with the given `radius`, until at
+/// least `k` hits are found.
+///
+/// Returns the `k` nearest neighbors and the number of RNN searches made.
+fn search_from<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize, mut radius: f64) -> (Vec<(usize, U)>, usize)
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let mut frontier = Frontier::new(&tree.root);
+    frontier.expand(tree.data(), query, U::from(radius));
+    let mut num_iterations = 1;
+
+    while count_hits(&frontier.confirmed) < k {
+        radius *= growth_factor(k, &frontier.confirmed, &frontier.straddlers);
+        frontier.expand(tree.data(), query, U::from(radius));
+        num_iterations += 1;
     }
 
-    while num_confirmed < k {
-        let lfd = utils::mean(
-            &confirmed
-                .iter()
-                .chain(straddlers.iter())
-                .map(|&(c, _)| c.lfd())
-                .collect::<Vec<_>>(),
-        );
-        let factor = (k.as_f64() / num_confirmed.as_f64()).powf(1. / (lfd + f64::EPSILON));
-
-        radius *= if factor < MULTIPLIER { factor } else { MULTIPLIER };
-        [confirmed, straddlers] = clustered::tree_search(tree.data(), &tree.root, query, U::from(radius));
-        num_confirmed = count_hits(&confirmed);
+    let hits = Hits::from_vec(
+        k,
+        clustered::leaf_search(
+            &tree.data,
+            frontier.confirmed,
+            frontier.straddlers,
+            query,
+            U::from(radius),
+        ),
+    )
+    .extract();
+
+    (hits, num_iterations)
+}
+
+/// Same as `search_from`, but in a tree built with the squared Euclidean
+/// distance. The `radius` is a Euclidean distance.
+///
+/// The tree is searched from the root for each radius.
+fn search_squared_from<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize, mut radius: f64) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let [mut confirmed, mut straddlers] =
+        clustered::tree_search_squared(tree.data(), &tree.root, query, U::from(radius));
+
+    while count_hits(&confirmed) < k {
+        radius *= growth_factor(k, &confirmed, &straddlers);
+        [confirmed, straddlers] = clustered::tree_search_squared(tree.data(), &tree.root, query, U::from(radius));
     }
 
+    let radius = U::from(radius);
     Hits::from_vec(
         k,
-        clustered::leaf_search(&tree.data, confirmed, straddlers, query, U::from(radius)),
+        clustered::leaf_search(&tree.data, confirmed, straddlers, query, radius * radius),
     )
     .extract()
+    .into_iter()
+    .map(|(i, d)| (i, d.sqrt()))
+    .collect()
+}
+
+/// The factor by which to increase the radius when the `confirmed` clusters
+/// hold fewer than `k` instances.
+///
+/// If no instances were confirmed, the radius is doubled. Otherwise, the
+/// factor is estimated from the local fractal dimension of the `confirmed`
+/// and `straddlers` clusters, and is capped at 2.
+fn growth_factor<U: Number, C: Cluster<U>>(k: usize, confirmed: &[(&C, U)], straddlers: &[(&C, U)]) -> f64 {
+    let num_confirmed = count_hits(confirmed);
+    if num_confirmed == 0 {
+        return MULTIPLIER;
+    }
+
+    let lfd = utils::mean(
+        &confirmed
+            .iter()
+            .chain(straddlers.iter())
+            .map(|&(c, _)| c.lfd())
+            .collect::<Vec<_>>(),
+    );
+    let factor = (k.as_f64() / num_confirmed.as_f64()).powf(1. / (lfd + f64::EPSILON));
+    if factor < MULTIPLIER {
+        factor
+    } else {
+        MULTIPLIER
+    }
+}
+
+/// The clusters found by clustered RNN searches with increasing radii.
+///
+/// Instead of searching the tree from the root for each radius, the clusters
+/// that did not overlap the query ball are kept so that only they, and their
+/// descendants, need to be examined when the radius increases.
+struct Frontier<'a, U: Number, C: Cluster<U>> {
+    /// The clusters that are inside the query ball, with the distances from
+    /// the query to their centers.
+    confirmed: Vec<(&'a C, U)>,
+    /// The leaf clusters that overlap the query ball, with the distances from
+    /// the query to their centers.
+    straddlers: Vec<(&'a C, U)>,
+    /// The clusters that did not overlap the query ball, with the distances
+    /// from the query to their centers if they were computed.
+    excluded: Vec<(&'a C, Option<U>)>,
+}
+
+impl<'a, U: Number, C: Cluster<U>> Frontier<'a, U, C> {
+    /// Creates a `Frontier` from which only the `root` has yet to be examined.
+    fn new(root: &'a C) -> Self {
+        Self {
+            confirmed: Vec::new(),
+            straddlers: Vec::new(),
+            excluded: vec![(root, None)],
+        }
+    }
+
+    /// Updates the `Frontier` for a query ball with a radius no smaller than
+    /// that of any previous call.
+    ///
+    /// This finds the same points as `clustered::tree_search` with the same
+    /// `radius`, though they may be divided among different clusters.
+    fn expand<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, query: &I, radius: U) {
+        let straddlers;
+        (straddlers, self.straddlers) = core::mem::take(&mut self.straddlers)
+            .into_iter()
+            .partition(|&(c, d)| (c.radius() + d) <= radius);
+        self.confirmed.extend(straddlers);
+
+        let mut candidates = core::mem::take(&mut self.excluded);
+        while !candidates.is_empty() {
+            let mut next = Vec::new();
+            for (c, d) in candidates {
+                let d = d.unwrap_or_else(|| c.distance_to_instance(data, query));
+                if d > (c.radius() + radius) {
+                    self.excluded.push((c, Some(d)));
+                } else if (c.radius() + d) <= radius {
+                    self.confirmed.push((c, d));
+                } else if let Some(children) = c.children() {
+                    let overlapping = if d < c.radius() {
+                        c.overlapping_children(data, query, radius)
+                    } else {
+                        children.to_vec()
+                    };
+                    for child in children {
+                        if overlapping.iter().any(|&o| core::ptr::eq(o, child)) {
+                            next.push((child, None));
+                        } else {
+                            self.excluded.push((child, None));
+                        }
+                    }
+                } else {
+                    self.straddlers.push((c, d));
+                }
+            }
+            candidates = next;
+        }
+    }
 }
 
 /// Count the total cardinality of the clusters.
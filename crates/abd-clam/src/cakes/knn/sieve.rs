@@ -2,9 +2,11 @@
 //! cluster centers.
 
 use core::cmp::{min, Ordering};
-use distances::Number;
+use distances::{number::Float, Number};
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{Cluster, Dataset, DistanceCache, Instance, Tree};
+
+use super::{bounds, bounds_squared, sort_hits};
 
 /// A Grain is an element of the sieve. It is either a hit or a cluster.
 #[derive(Clone, Copy, Debug)]
@@ -21,9 +23,9 @@ enum Grain<'a, U: Number, C: Cluster<U>> {
         /// The cluster.
         c: &'a C,
         /// Theoretical worst case distance from the query to a point in the cluster.
-        d: U,
-        /// The diameter of the cluster.
-        diameter: U,
+        d_max: U,
+        /// Theoretical best case distance from the query to a point in the cluster.
+        d_min: U,
         /// The number of instances in the cluster.
         multiplicity: usize,
         /// Whether the cluster is a leaf.
@@ -32,13 +34,13 @@ enum Grain<'a, U: Number, C: Cluster<U>> {
 }
 
 impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
-    /// Creates a new `Grain` from a cluster.
-    fn new_cluster(c: &'a C, d: U) -> Self {
-        let r = c.radius();
+    /// Creates a new `Grain` from a cluster and the bounds, `(d_min, d_max)`,
+    /// on the distances from the query to its instances.
+    fn new_cluster(c: &'a C, (d_min, d_max): (U, U)) -> Self {
         Self::Cluster {
             c,
-            d: d + r,
-            diameter: r + r,
+            d_max,
+            d_min,
             multiplicity: c.cardinality(),
             is_leaf: c.is_leaf(),
         }
@@ -52,10 +54,9 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
     /// Returns the theoretical minimum distance from the query to a point in
     /// the cluster if the `Grain` is of the `Cluster` variant; returns the
     /// distance to the instance if the `Grain` is of the `Hit` variant.
-    fn d_min(&self) -> U {
+    const fn d_min(&self) -> U {
         match self {
-            Grain::Hit { d, .. } => *d,
-            Grain::Cluster { d, diameter, .. } => *d - *diameter,
+            Grain::Hit { d, .. } | Grain::Cluster { d_min: d, .. } => *d,
         }
     }
 
@@ -64,7 +65,7 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
     /// distance to the instance if the `Grain` is of the `Hit` variant.
     const fn d(&self) -> U {
         match self {
-            Grain::Hit { d, .. } | Grain::Cluster { d, .. } => *d,
+            Grain::Hit { d, .. } | Grain::Cluster { d_max: d, .. } => *d,
         }
     }
 
@@ -85,11 +86,16 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
 
     /// Returns the indices of the instances in the cluster if the `Grain` is of
     /// the `Cluster` variant
-    fn cluster_to_hits<I: Instance, D: Dataset<I, U>>(self, data: &D, query: &I) -> Vec<Self> {
+    fn cluster_to_hits<I: Instance, D: Dataset<I, U>>(
+        self,
+        data: &D,
+        query: &I,
+        centers: &DistanceCache<U>,
+    ) -> Vec<Self> {
         match self {
             Grain::Hit { .. } => unreachable!("This is only called on non-hits."),
             Grain::Cluster { c, .. } => {
-                let distances = data.query_to_many(query, &c.indices().collect::<Vec<_>>());
+                let distances = data.query_to_many_cached(centers, query, &c.indices().collect::<Vec<_>>());
                 c.indices()
                     .zip(distances)
                     .map(|(index, d)| Grain::new_hit(d, index))
@@ -209,8 +215,45 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
 ///
 /// A vector of 2-tuples, where the first element is an index of an instance,
 /// and the second element is the distance from the query to the instance.
-#[allow(clippy::many_single_char_names)]
 pub fn search<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, bounds)
+}
+
+/// K-Nearest Neighbor search using a thresholds approach with no separate
+/// centers, in a tree built with the squared Euclidean distance.
+///
+/// The search is carried out on squared distances, and the square root is
+/// only taken of the distances to the centers of the clusters that are
+/// visited, to bound the distances to their instances, and of the distances
+/// to the final hits.
+///
+/// # Returns
+///
+/// The hits, as in `search`, with Euclidean distances.
+pub fn search_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, bounds_squared)
+        .into_iter()
+        .map(|(i, d)| (i, d.sqrt()))
+        .collect()
+}
+
+/// K-Nearest Neighbor search using a thresholds approach with no separate
+/// centers, where `bounds` bounds the distances from the query to the
+/// instances in a cluster.
+#[allow(clippy::many_single_char_names)]
+fn search_with<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize, bounds: fn(&C, U) -> (U, U)) -> Vec<(usize, U)>
 where
     I: Instance,
     U: Number,
@@ -219,9 +262,10 @@ where
 {
     let data = tree.data();
     let c = &tree.root;
-    let d = c.distance_to_instance(data, query);
+    let mut centers = DistanceCache::default();
+    let d = data.query_to_one_cached(&mut centers, query, c.arg_center());
 
-    let mut grains = vec![Grain::new_cluster(c, d)];
+    let mut grains = vec![Grain::new_cluster(c, bounds(c, d))];
     let [mut insiders, mut non_insiders]: [Vec<_>; 2];
 
     loop {
@@ -246,25 +290,23 @@ where
 
         // Convert small clusters to hits.
         for cluster in small_clusters {
-            hits.append(&mut cluster.cluster_to_hits(data, query));
+            hits.append(&mut cluster.cluster_to_hits(data, query, &centers));
         }
 
         // If there are no more cluster grains, then the search is complete.
         if clusters.is_empty() {
-            Grain::partition(&mut hits, k);
-
-            // TODO: Fix the panic here.
-            let l = core::cmp::min(k, hits.len());
-
-            return hits[..l].iter().map(|g| (g.index(), g.d())).collect();
+            let mut hits = hits.into_iter().map(|g| (g.index(), g.d())).collect::<Vec<_>>();
+            sort_hits(&mut hits);
+            hits.truncate(k);
+            return hits;
         }
 
         // Partition clusters into children and convert to grains.
         grains = clusters
             .into_iter()
             .flat_map(Grain::cluster_to_children)
-            .map(|c| (c, c.distance_to_instance(data, query)))
-            .map(|(c, d)| Grain::new_cluster(c, d))
+            .map(|c| (c, data.query_to_one_cached(&mut centers, query, c.arg_center())))
+            .map(|(c, d)| Grain::new_cluster(c, bounds(c, d)))
             .chain(hits)
             .collect();
     }
@@ -3,9 +3,11 @@
 
 use core::cmp::{min, Ordering};
 
-use distances::Number;
+use distances::{number::Float, Number};
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{Cluster, Dataset, DistanceCache, Instance, Tree};
+
+use super::{bounds, bounds_squared, sort_hits};
 
 /// A Grain is an element of the sieve. It is either a hit or a cluster.
 #[derive(Debug)]
@@ -40,13 +42,13 @@ enum Grain<'a, U: Number, C: Cluster<U>> {
 }
 
 impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
-    /// Creates a new `Grain` from a cluster.
-    fn new_cluster(c: &'a C, d: U) -> Self {
-        let r = c.radius();
+    /// Creates a new `Grain` from a cluster and the bounds, `(d_min, d_max)`,
+    /// on the distances from the query to its instances.
+    fn new_cluster(c: &'a C, (d_min, d_max): (U, U)) -> Self {
         Self::Cluster {
             c,
-            d_max: d + r,
-            d_min: if d > r { d - r } else { U::zero() },
+            d_max,
+            d_min,
             multiplicity: c.cardinality() - 1,
             is_leaf: c.is_leaf(),
         }
@@ -62,17 +64,24 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
         Self::Center { d }
     }
 
-    /// Creates center and cluster grains from a cluster.
-    fn new_grains<I: Instance, D: Dataset<I, U>>(c: &'a C, data: &D, query: &I) -> Vec<Self> {
+    /// Creates center and cluster grains from a cluster, where `bounds` bounds
+    /// the distances from the query to the instances in the cluster.
+    fn new_grains<I: Instance, D: Dataset<I, U>>(
+        c: &'a C,
+        data: &D,
+        query: &I,
+        centers: &mut DistanceCache<U>,
+        bounds: fn(&C, U) -> (U, U),
+    ) -> Vec<Self> {
         if c.is_singleton() {
-            let d = c.distance_to_instance(data, query);
+            let d = data.query_to_one_cached(centers, query, c.arg_center());
             c.indices().map(|i| Self::new_hit(d, i)).collect()
         } else if c.is_leaf() {
-            let distances = data.query_to_many(query, &c.indices().collect::<Vec<_>>());
+            let distances = data.query_to_many_cached(centers, query, &c.indices().collect::<Vec<_>>());
             c.indices().zip(distances).map(|(i, d)| Self::new_hit(d, i)).collect()
         } else {
-            let d = c.distance_to_instance(data, query);
-            vec![Self::new_cluster(c, d), Self::new_center(d)]
+            let d = data.query_to_one_cached(centers, query, c.arg_center());
+            vec![Self::new_cluster(c, bounds(c, d)), Self::new_center(d)]
         }
     }
 
@@ -111,15 +120,22 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
 
     /// Returns the indices of the instances in the cluster if the `Grain` is of
     /// the `Cluster` variant
-    fn cluster_to_hits<I: Instance, D: Dataset<I, U>>(self, data: &D, query: &I) -> Vec<Self> {
+    fn cluster_to_hits<I: Instance, D: Dataset<I, U>>(
+        self,
+        data: &D,
+        query: &I,
+        centers: &DistanceCache<U>,
+    ) -> Vec<Self> {
         match self {
             Grain::Hit { .. } | Grain::Center { .. } => unreachable!("This is only called on Clusters."),
-            Grain::Cluster { c, d_max, .. } => {
+            Grain::Cluster { c, .. } => {
                 if c.is_singleton() {
-                    let d = d_max - c.radius();
+                    let d = centers
+                        .get_query(c.arg_center())
+                        .unwrap_or_else(|| unreachable!("The distance to the center was cached with the grain."));
                     c.indices().map(|index| Grain::new_hit(d, index)).collect()
                 } else {
-                    let distances = data.query_to_many(query, &c.indices().collect::<Vec<_>>());
+                    let distances = data.query_to_many_cached(centers, query, &c.indices().collect::<Vec<_>>());
                     c.indices()
                         .zip(distances)
                         .map(|(index, d)| Grain::new_hit(d, index))
@@ -245,6 +261,43 @@ impl<'a, U: Number, C: Cluster<U>> Grain<'a, U, C> {
 /// A vector of 2-tuples, where the first element is the index of the instance
 /// and the second element is the distance from the query to the instance.
 pub fn search<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, bounds)
+}
+
+/// K-Nearest Neighbor search using a thresholds approach with separate
+/// centers, in a tree built with the squared Euclidean distance.
+///
+/// The search is carried out on squared distances, and the square root is
+/// only taken of the distances to the centers of the clusters that are
+/// visited, to bound the distances to their instances, and of the distances
+/// to the final hits.
+///
+/// # Returns
+///
+/// The hits, as in `search`, with Euclidean distances.
+pub fn search_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    search_with(tree, query, k, bounds_squared)
+        .into_iter()
+        .map(|(i, d)| (i, d.sqrt()))
+        .collect()
+}
+
+/// K-Nearest Neighbor search using a thresholds approach with separate
+/// centers, where `bounds` bounds the distances from the query to the
+/// instances in a cluster.
+fn search_with<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, k: usize, bounds: fn(&C, U) -> (U, U)) -> Vec<(usize, U)>
 where
     I: Instance,
     U: Number,
@@ -252,7 +305,8 @@ where
     C: Cluster<U>,
 {
     let data = tree.data();
-    let mut grains = Grain::new_grains(&tree.root, data, query);
+    let mut centers = DistanceCache::default();
+    let mut grains = Grain::new_grains(&tree.root, data, query, &mut centers, bounds);
     let [mut insiders, mut non_insiders]: [Vec<_>; 2];
 
     loop {
@@ -278,24 +332,22 @@ where
 
         // Convert small clusters to hits.
         for cluster in small_clusters {
-            hits.append(&mut cluster.cluster_to_hits(data, query));
+            hits.append(&mut cluster.cluster_to_hits(data, query, &centers));
         }
 
         // If there are no more cluster grains, then the search is complete.
         if clusters.is_empty() {
-            Grain::partition(&mut hits, k);
-
-            // TODO: Fix the panic here.
-            let l = core::cmp::min(k, hits.len());
-
-            return hits[..l].iter().map(|g| (g.index(), g.d_max())).collect();
+            let mut hits = hits.into_iter().map(|g| (g.index(), g.d_max())).collect::<Vec<_>>();
+            sort_hits(&mut hits);
+            hits.truncate(k);
+            return hits;
         }
 
         // Partition clusters into children and convert to grains.
         grains = clusters
             .into_iter()
             .flat_map(Grain::cluster_to_children)
-            .flat_map(|c| Grain::new_grains(c, data, query))
+            .flat_map(|c| Grain::new_grains(c, data, query, &mut centers, bounds))
             .chain(hits)
             .collect();
     }
@@ -4,12 +4,20 @@ use core::ops::Index;
 
 use std::path::Path;
 
+pub mod classify;
+pub mod coreset;
+pub mod dbscan;
+pub mod density;
+pub mod join;
+pub mod kernels;
 pub mod knn;
 pub mod rnn;
 mod search;
 mod sharded;
 mod singular;
 
+pub use search::{hit_count_stats, HitStats};
+
 use distances::Number;
 use rayon::prelude::*;
 use search::Search;
@@ -169,6 +177,102 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> Cakes<I, U, D> {
         queries.par_iter().map(|q| self.rnn_search(q, radius, algo)).collect()
     }
 
+    /// Performs RNN search on a batch of queries with the given algorithm and,
+    /// if `verify` is `true`, checks the hits against those of linear search.
+    ///
+    /// Verification runs a linear search for every query, so it is as
+    /// expensive as `batch_linear_rnn_search`. It is meant for debugging, e.g.
+    /// to check that a tree over lossy data still gives exact results.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The queries to search.
+    /// * `radius` - The search radius.
+    /// * `algo` - The algorithm to use.
+    /// * `verify` - Whether to check the hits against linear search.
+    ///
+    /// # Returns
+    ///
+    /// A vector of vectors of tuples containing the index of the instance and
+    /// the distance to the query.
+    ///
+    /// # Errors
+    ///
+    /// * If `verify` is `true` and the hits for any query differ from those of
+    ///   linear search, either in the indices or by more than `U::epsilon()`
+    ///   in the distances. The error lists the differences for each query.
+    pub fn batch_rnn_search_verified(
+        &self,
+        queries: &[&I],
+        radius: U,
+        algo: rnn::Algorithm,
+        verify: bool,
+    ) -> Result<Vec<Vec<(usize, U)>>, String> {
+        let hits = self.batch_rnn_search(queries, radius, algo);
+        if !verify {
+            return Ok(hits);
+        }
+
+        let linear_hits = self.batch_linear_rnn_search(queries, radius);
+        let diffs = linear_hits
+            .iter()
+            .zip(hits.iter())
+            .enumerate()
+            .filter_map(|(i, (expected, actual))| search::diff_hits(expected, actual).map(|d| format!("query {i}: {d}")))
+            .collect::<Vec<_>>();
+
+        if diffs.is_empty() {
+            Ok(hits)
+        } else {
+            Err(format!(
+                "RNN search with {algo:?} differs from linear search for {} of {} queries:\n{}",
+                diffs.len(),
+                queries.len(),
+                diffs.join("\n")
+            ))
+        }
+    }
+
+    /// Same as `batch_rnn_search`, but runs in the given thread `pool` instead
+    /// of the global `rayon` thread pool.
+    pub fn batch_rnn_search_in(
+        &self,
+        pool: &rayon::ThreadPool,
+        queries: &[&I],
+        radius: U,
+        algo: rnn::Algorithm,
+    ) -> Vec<Vec<(usize, U)>> {
+        pool.install(|| self.batch_rnn_search(queries, radius, algo))
+    }
+
+    /// Performs RNN search on a batch of queries with the given algorithm, in
+    /// chunks, passing the hits for each query to a callback.
+    ///
+    /// Each chunk of queries is searched in parallel, and the hits for the
+    /// chunk are passed to `sink`, in order, before the next chunk is searched.
+    /// This bounds the memory used for hits by the `chunk_size`, e.g. when
+    /// streaming the hits for millions of queries to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The queries to search.
+    /// * `radius` - The search radius.
+    /// * `algo` - The algorithm to use.
+    /// * `chunk_size` - The number of queries to search at a time. This is
+    ///   treated as `1` if it is `0`.
+    /// * `sink` - Called with the index of each query in `queries` and the
+    ///   hits for that query.
+    pub fn chunked_batch_rnn_search<F: FnMut(usize, Vec<(usize, U)>)>(
+        &self,
+        queries: &[&I],
+        radius: U,
+        algo: rnn::Algorithm,
+        chunk_size: usize,
+        sink: F,
+    ) {
+        Self::chunked_batch_search(queries, chunk_size, |q| self.rnn_search(q, radius, algo), sink);
+    }
+
     /// Performs an RNN search with the given algorithm.
     ///
     /// # Arguments
@@ -245,6 +349,59 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> Cakes<I, U, D> {
         queries.par_iter().map(|q| self.knn_search(q, k, algo)).collect()
     }
 
+    /// Same as `batch_knn_search`, but runs in the given thread `pool` instead
+    /// of the global `rayon` thread pool.
+    pub fn batch_knn_search_in(
+        &self,
+        pool: &rayon::ThreadPool,
+        queries: &[&I],
+        k: usize,
+        algo: knn::Algorithm,
+    ) -> Vec<Vec<(usize, U)>> {
+        pool.install(|| self.batch_knn_search(queries, k, algo))
+    }
+
+    /// Performs KNN search on a batch of queries with the given algorithm, in
+    /// chunks, passing the hits for each query to a callback.
+    ///
+    /// See `chunked_batch_rnn_search` for more details.
+    ///
+    /// # Arguments
+    ///
+    /// * `queries` - The queries to search.
+    /// * `k` - The number of nearest neighbors to return.
+    /// * `algo` - The algorithm to use.
+    /// * `chunk_size` - The number of queries to search at a time. This is
+    ///   treated as `1` if it is `0`.
+    /// * `sink` - Called with the index of each query in `queries` and the
+    ///   hits for that query.
+    pub fn chunked_batch_knn_search<F: FnMut(usize, Vec<(usize, U)>)>(
+        &self,
+        queries: &[&I],
+        k: usize,
+        algo: knn::Algorithm,
+        chunk_size: usize,
+        sink: F,
+    ) {
+        Self::chunked_batch_search(queries, chunk_size, |q| self.knn_search(q, k, algo), sink);
+    }
+
+    /// Searches the `queries` in parallel, `chunk_size` at a time, and passes
+    /// the hits for each query, in order, to `sink`.
+    fn chunked_batch_search<S, F>(queries: &[&I], chunk_size: usize, search: S, mut sink: F)
+    where
+        S: Fn(&I) -> Vec<(usize, U)> + Sync,
+        F: FnMut(usize, Vec<(usize, U)>),
+    {
+        let chunk_size = chunk_size.max(1);
+        for (c, chunk) in queries.chunks(chunk_size).enumerate() {
+            let hits = chunk.par_iter().map(|q| search(q)).collect::<Vec<_>>();
+            for (i, hits) in hits.into_iter().enumerate() {
+                sink(c * chunk_size + i, hits);
+            }
+        }
+    }
+
     /// Performs a KNN search with the given algorithm.
     ///
     /// # Arguments
@@ -263,6 +420,49 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> Cakes<I, U, D> {
         }
     }
 
+    /// Performs an RNN search with the given algorithm, and reports the leaf
+    /// cluster each hit came from.
+    ///
+    /// See `rnn_search` for the arguments.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing the index of the instance, the distance
+    /// to the query, and the offset of the leaf containing the instance.
+    pub fn rnn_search_with_provenance(&self, query: &I, radius: U, algo: rnn::Algorithm) -> Vec<(usize, U, usize)> {
+        self.with_provenance(self.rnn_search(query, radius, algo))
+    }
+
+    /// Performs a KNN search with the given algorithm, and reports the leaf
+    /// cluster each hit came from.
+    ///
+    /// See `knn_search` for the arguments.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing the index of the instance, the distance
+    /// to the query, and the offset of the leaf containing the instance.
+    pub fn knn_search_with_provenance(&self, query: &I, k: usize, algo: knn::Algorithm) -> Vec<(usize, U, usize)> {
+        self.with_provenance(self.knn_search(query, k, algo))
+    }
+
+    /// Adds the offset of the leaf containing each hit to the `hits`.
+    ///
+    /// Every instance is in exactly one leaf, so this is the leaf among whose
+    /// instances the hit was found.
+    fn with_provenance(&self, hits: Vec<(usize, U)>) -> Vec<(usize, U, usize)> {
+        hits.into_iter()
+            .map(|(i, d)| {
+                let leaf = match self {
+                    Self::SingleShard(ss) => ss.leaf_offset_of(i),
+                    Self::RandomlySharded(rs) => rs.leaf_offset_of(i),
+                }
+                .unwrap_or_else(|| unreachable!("Search results are always in bounds."));
+                (i, d, leaf)
+            })
+            .collect()
+    }
+
     /// Automatically finds the best RNN algorithm to use.
     ///
     /// # Arguments
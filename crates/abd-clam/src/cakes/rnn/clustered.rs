@@ -1,10 +1,10 @@
 //! Clustered search for the ranged nearest neighbors of a query.
 
-use distances::Number;
+use distances::{number::Float, Number};
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{BoundingBoxes, Cluster, CrossDataset, CrossMetric, Dataset, Instance, Tree};
 
-use super::linear;
+use super::SearchStats;
 
 /// Clustered search for the ranged nearest neighbors of a query.
 ///
@@ -29,6 +29,161 @@ where
     leaf_search(tree.data(), confirmed, straddlers, query, radius)
 }
 
+/// Clustered search for the ranged nearest neighbors of a query whose type
+/// differs from that of the instances in the tree.
+///
+/// The clusters are pruned with the radii and poles computed from the
+/// instances, so the `metric` must agree with the metric of the dataset.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `metric` - The distance function from queries to instances.
+/// * `query` - The query to search around.
+/// * `radius` - The radius to search within.
+///
+/// # Returns
+///
+/// A vector of 2-tuples, where the first element is the index of the instance
+/// and the second element is the distance from the query to the instance.
+pub fn search_cross<Q, I, U, D, C>(
+    tree: &Tree<I, U, D, C>,
+    metric: CrossMetric<Q, I, U>,
+    query: &Q,
+    radius: U,
+) -> Vec<(usize, U)>
+where
+    Q: Send + Sync,
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let data = tree.data();
+    let to_one = |index| data.cross_query_to_one(metric, query, index);
+    let [confirmed, straddlers] = tree_search_with(&tree.root, &to_one, &|_| false, radius, &mut SearchStats::default());
+
+    let to_many = |indices: &[usize]| data.cross_query_to_many(metric, query, indices);
+    leaf_search_with(confirmed, straddlers, &to_many, radius)
+}
+
+/// Clustered search for the ranged nearest neighbors of a vector, which
+/// discards clusters whose bounding boxes are outside the query ball before
+/// computing the distances to their centers.
+///
+/// The `boxes` must have been computed from the `tree`, and the metric must be
+/// one for which the distance to a box is a lower bound, as described in
+/// `BoundingBoxes`. Clusters without a box are never discarded by their box.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `boxes` - The bounding boxes of the clusters in the tree.
+/// * `query` - The query to search around.
+/// * `radius` - The radius to search within.
+///
+/// # Returns
+///
+/// A vector of 2-tuples, where the first element is the index of the instance
+/// and the second element is the distance from the query to the instance.
+pub fn search_with_bounding_boxes<F, I, U, D, C>(
+    tree: &Tree<I, U, D, C>,
+    boxes: &BoundingBoxes<F>,
+    query: &I,
+    radius: U,
+) -> Vec<(usize, U)>
+where
+    F: Number,
+    I: Instance + AsRef<[F]>,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let data = tree.data();
+    let is_outside = |c: &C| {
+        boxes
+            .distance_to_box(c, query.as_ref())
+            .is_some_and(|d| d > radius.as_f64())
+    };
+    let [confirmed, straddlers] = tree_search_with(
+        &tree.root,
+        &|index| data.query_to_one(query, index),
+        &is_outside,
+        radius,
+        &mut SearchStats::default(),
+    );
+    leaf_search(data, confirmed, straddlers, query, radius)
+}
+
+/// Clustered search for the ranged nearest neighbors of a query in a tree
+/// built with the squared Euclidean distance.
+///
+/// The `radius` and the returned distances are Euclidean distances. The tree
+/// is pruned by comparing squared distances with squared bounds, so the
+/// square root is only taken of the radii and polar distances of the clusters
+/// that are visited, and of the distances to the hits.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `query` - The query to search around.
+/// * `radius` - The Euclidean radius to search within.
+///
+/// # Returns
+///
+/// A vector of 2-tuples, where the first element is the index of the instance
+/// and the second element is the Euclidean distance from the query to the
+/// instance.
+pub fn search_squared<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, radius: U) -> Vec<(usize, U)>
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let data = tree.data();
+    let [confirmed, straddlers] = tree_search_squared(data, &tree.root, query, radius);
+    leaf_search(data, confirmed, straddlers, query, radius * radius)
+        .into_iter()
+        .map(|(i, d)| (i, d.sqrt()))
+        .collect()
+}
+
+/// Clustered search for the ranged nearest neighbors of a query, while
+/// recording statistics about the work done.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to search.
+/// * `query` - The query to search around.
+/// * `radius` - The radius to search within.
+///
+/// # Returns
+///
+/// The hits, as in `search`, and the `SearchStats` of the search.
+pub fn search_instrumented<I, U, D, C>(tree: &Tree<I, U, D, C>, query: &I, radius: U) -> (Vec<(usize, U)>, SearchStats)
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let mut stats = SearchStats::default();
+    let [confirmed, straddlers] = instrumented_tree_search(tree.data(), &tree.root, query, radius, &mut stats);
+
+    for &(c, _) in confirmed.iter().chain(straddlers.iter()) {
+        stats.leaves_searched += c.subtree().into_iter().filter(|c| c.is_leaf()).count();
+    }
+    stats.distance_calls += confirmed
+        .iter()
+        .filter(|(c, _)| !c.is_singleton())
+        .chain(straddlers.iter())
+        .map(|(c, _)| c.cardinality())
+        .sum::<usize>();
+
+    (leaf_search(tree.data(), confirmed, straddlers, query, radius), stats)
+}
+
 /// Perform coarse-grained tree search.
 ///
 /// # Arguments
@@ -51,6 +206,56 @@ where
     U: Number,
     D: Dataset<I, U>,
     C: Cluster<U>,
+{
+    instrumented_tree_search(data, root, query, radius, &mut SearchStats::default())
+}
+
+/// Perform coarse-grained tree search, while counting the clusters examined
+/// and the distances computed in `stats`.
+///
+/// See `tree_search` for the arguments and return value.
+fn instrumented_tree_search<'a, I, U, D, C>(
+    data: &D,
+    root: &'a C,
+    query: &I,
+    radius: U,
+    stats: &mut SearchStats,
+) -> [Vec<(&'a C, U)>; 2]
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    tree_search_with(
+        root,
+        &|index| data.query_to_one(query, index),
+        &|_| false,
+        radius,
+        stats,
+    )
+}
+
+/// Perform coarse-grained tree search, with the distance from the query to the
+/// instance at a given index computed by `distance_to`.
+///
+/// Clusters for which `is_outside` returns `true` are known to be outside the
+/// query ball and are discarded without computing the distance to their
+/// centers.
+///
+/// See `tree_search` for the return value.
+fn tree_search_with<'a, U, C, F, P>(
+    root: &'a C,
+    distance_to: &F,
+    is_outside: &P,
+    radius: U,
+    stats: &mut SearchStats,
+) -> [Vec<(&'a C, U)>; 2]
+where
+    U: Number,
+    C: Cluster<U>,
+    F: Fn(usize) -> U,
+    P: Fn(&C) -> bool,
 {
     let mut confirmed = Vec::new();
     let mut straddlers = Vec::new();
@@ -58,9 +263,13 @@ where
 
     let (mut terminal, mut non_terminal): (Vec<_>, Vec<_>);
     while !candidates.is_empty() {
+        candidates.retain(|&c| !is_outside(c));
+        stats.clusters_examined += candidates.len();
+        stats.distance_calls += candidates.len();
+
         (terminal, non_terminal) = candidates
             .into_iter()
-            .map(|c| (c, c.distance_to_instance(data, query)))
+            .map(|c| (c, distance_to(c.arg_center())))
             .filter(|&(c, d)| d <= (c.radius() + radius))
             .partition(|&(c, d)| (c.radius() + d) <= radius);
         confirmed.append(&mut terminal);
@@ -72,7 +281,9 @@ where
             .into_iter()
             .flat_map(|(c, d)| {
                 if d < c.radius() {
-                    c.overlapping_children(data, query, radius)
+                    // The distances from the query to the two poles.
+                    stats.distance_calls += 2;
+                    c.overlapping_children_with(distance_to, radius)
                 } else {
                     c.children()
                         .map_or_else(|| unreachable!("Non-leaf cluster without children"), |v| v.to_vec())
@@ -84,6 +295,77 @@ where
     [confirmed, straddlers]
 }
 
+/// Perform coarse-grained tree search in a tree built with the squared
+/// Euclidean distance.
+///
+/// The radii and polar distances of the clusters are squared distances, and
+/// the `radius` is a Euclidean distance. The returned distances from the query
+/// to the cluster centers are squared distances.
+///
+/// See `tree_search` for the return value.
+pub fn tree_search_squared<'a, I, U, D, C>(data: &D, root: &'a C, query: &I, radius: U) -> [Vec<(&'a C, U)>; 2]
+where
+    I: Instance,
+    U: Float,
+    D: Dataset<I, U>,
+    C: Cluster<U>,
+{
+    let mut confirmed = Vec::new();
+    let mut straddlers = Vec::new();
+    let mut candidates = vec![root];
+
+    while let Some(c) = candidates.pop() {
+        let d_sq = c.distance_to_instance(data, query);
+        let r = c.radius().sqrt();
+
+        // The cluster is outside the query ball.
+        let reach = r + radius;
+        if d_sq > reach * reach {
+            continue;
+        }
+
+        if r <= radius && d_sq <= (radius - r) * (radius - r) {
+            // The cluster is inside the query ball.
+            confirmed.push((c, d_sq));
+        } else if c.is_leaf() {
+            straddlers.push((c, d_sq));
+        } else if d_sq < c.radius() {
+            // The query is inside the cluster. As in `overlapping_children`,
+            // a child may be excluded by the hyperplane between the poles, and
+            // the difference of the squared distances to the poles is what
+            // that test needs.
+            let [left, right] = c
+                .children()
+                .unwrap_or_else(|| unreachable!("We checked that the cluster is not a leaf."));
+            let [arg_l, arg_r] = c
+                .arg_poles()
+                .unwrap_or_else(|| unreachable!("We checked that the cluster is not a leaf."));
+            let polar_distance = c
+                .polar_distance()
+                .unwrap_or_else(|| unreachable!("We checked that the cluster is not a leaf."));
+
+            let (ql, qr) = (data.query_to_one(query, arg_l), data.query_to_one(query, arg_r));
+            let swap = ql < qr;
+            let (ql, qr) = if swap { (qr, ql) } else { (ql, qr) };
+
+            if ql - qr <= U::from(2) * polar_distance.sqrt() * radius {
+                candidates.extend([left, right]);
+            } else if swap {
+                candidates.push(left);
+            } else {
+                candidates.push(right);
+            }
+        } else {
+            candidates.extend(
+                c.children()
+                    .unwrap_or_else(|| unreachable!("We checked that the cluster is not a leaf.")),
+            );
+        }
+    }
+
+    [confirmed, straddlers]
+}
+
 /// Perform fine-grained leaf search
 pub fn leaf_search<I, U, D, C>(
     data: &D,
@@ -97,12 +379,33 @@ where
     U: Number,
     D: Dataset<I, U>,
     C: Cluster<U>,
+{
+    leaf_search_with(
+        confirmed,
+        straddlers,
+        &|indices| data.query_to_many(query, indices),
+        radius,
+    )
+}
+
+/// Perform fine-grained leaf search, with the distances from the query to the
+/// instances at given indices computed by `distances_to`.
+fn leaf_search_with<U, C, F>(
+    confirmed: Vec<(&C, U)>,
+    straddlers: Vec<(&C, U)>,
+    distances_to: &F,
+    radius: U,
+) -> Vec<(usize, U)>
+where
+    U: Number,
+    C: Cluster<U>,
+    F: Fn(&[usize]) -> Vec<U>,
 {
     let hits = confirmed.into_iter().flat_map(|(c, d)| {
         let distances = if c.is_singleton() {
             vec![d; c.cardinality()]
         } else {
-            data.query_to_many(query, &c.indices().collect::<Vec<_>>())
+            distances_to(&c.indices().collect::<Vec<_>>())
         };
         c.indices().zip(distances)
     });
@@ -111,6 +414,8 @@ where
         .into_iter()
         .flat_map(|(c, _)| c.indices())
         .collect::<Vec<_>>();
+    let distances = distances_to(&indices);
 
-    hits.chain(linear::search(data, query, radius, &indices)).collect()
+    hits.chain(indices.into_iter().zip(distances).filter(|&(_, d)| d <= radius))
+        .collect()
 }
@@ -2,7 +2,7 @@
 
 use distances::Number;
 
-use crate::{Dataset, Instance};
+use crate::{CrossDataset, CrossMetric, Dataset, Instance};
 
 /// Linear search for the ranged nearest neighbors of a query.
 ///
@@ -31,3 +31,40 @@ where
         .filter(|&(_, d)| d <= radius)
         .collect()
 }
+
+/// Linear search for the ranged nearest neighbors of a query whose type
+/// differs from that of the instances in the dataset.
+///
+/// # Arguments
+///
+/// * `data` - The dataset to search.
+/// * `metric` - The distance function from queries to instances.
+/// * `query` - The query to search around.
+/// * `radius` - The radius to search within.
+/// * `indices` - The indices to search.
+///
+/// # Returns
+///
+/// A vector of 2-tuples, where the first element is the index of the instance
+/// and the second element is the distance from the query to the instance.
+pub fn search_cross<Q, I, U, D>(
+    data: &D,
+    metric: CrossMetric<Q, I, U>,
+    query: &Q,
+    radius: U,
+    indices: &[usize],
+) -> Vec<(usize, U)>
+where
+    Q: Send + Sync,
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+{
+    let distances = data.cross_query_to_many(metric, query, indices);
+    indices
+        .iter()
+        .copied()
+        .zip(distances)
+        .filter(|&(_, d)| d <= radius)
+        .collect()
+}
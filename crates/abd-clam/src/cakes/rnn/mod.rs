@@ -6,13 +6,29 @@
 //! module as they are being implemented. They should not be considered stable until they
 //! are documented as such.
 
-use distances::Number;
+use distances::{number::Float, Number};
+use rayon::prelude::*;
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{BoundingBoxes, Cluster, CrossMetric, Dataset, Instance, Tree};
 
 pub(crate) mod clustered;
 pub(crate) mod linear;
 
+/// Statistics about the work done during a search.
+///
+/// These are meant to help tune the tree, e.g. by showing how well a search
+/// prunes the tree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// The number of clusters whose centers were compared to the query.
+    pub clusters_examined: usize,
+    /// The number of leaves whose instances were compared to the query, or
+    /// were all found to be hits.
+    pub leaves_searched: usize,
+    /// The number of distances computed.
+    pub distance_calls: usize,
+}
+
 /// The algorithm to use for Ranged Nearest Neighbor search.
 ///
 /// The default is `Clustered`, as determined by the benchmarks in the crate.
@@ -27,8 +43,20 @@ pub enum Algorithm {
     ///
     /// This is a stable algorithm.
     Clustered,
+
+    /// Use `Linear` search if the tree has at most the given number of
+    /// instances, and `Clustered` search otherwise.
+    ///
+    /// For small trees, the overhead of the tree makes `Clustered` search
+    /// slower than `Linear` search. `DEFAULT_LINEAR_THRESHOLD` is a rough
+    /// default for the number of instances.
+    Auto(usize),
 }
 
+/// The default number of instances at or below which `Algorithm::Auto` uses
+/// `Linear` search.
+pub const DEFAULT_LINEAR_THRESHOLD: usize = 1_000;
+
 impl Default for Algorithm {
     fn default() -> Self {
         Self::Clustered
@@ -61,6 +89,231 @@ impl Algorithm {
                 linear::search(tree.data(), query, radius, &indices)
             }
             Self::Clustered => clustered::search(tree, query, radius),
+            Self::Auto(_) => self.resolve(tree.cardinality()).search(query, radius, tree),
+        }
+    }
+
+    /// Searches for the nearest neighbors of a query in a tree built with the
+    /// squared Euclidean distance, e.g. `distances::vectors::euclidean_sq`.
+    ///
+    /// The search compares squared distances with squared bounds and only
+    /// takes the square root of the distances to the hits. This gives the same
+    /// hits as `search` in a tree built with the Euclidean distance, without
+    /// taking the square root of every distance.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to search around.
+    /// * `radius` - The Euclidean radius to search within.
+    /// * `tree` - The tree to search.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`, with Euclidean distances.
+    pub fn search_squared<I, U, D, C>(self, query: &I, radius: U, tree: &Tree<I, U, D, C>) -> Vec<(usize, U)>
+    where
+        I: Instance,
+        U: Float,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::Linear => self
+                .search(query, radius * radius, tree)
+                .into_iter()
+                .map(|(i, d)| (i, d.sqrt()))
+                .collect(),
+            Self::Clustered => clustered::search_squared(tree, query, radius),
+            Self::Auto(_) => self.resolve(tree.cardinality()).search_squared(query, radius, tree),
+        }
+    }
+
+    /// Searches for the nearest neighbors of a query, while recording
+    /// statistics about the work done.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to search around.
+    /// * `radius` - The radius to search within.
+    /// * `tree` - The tree to search.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`, and the `SearchStats` of the search.
+    pub fn search_instrumented<I, U, D, C>(
+        self,
+        query: &I,
+        radius: U,
+        tree: &Tree<I, U, D, C>,
+    ) -> (Vec<(usize, U)>, SearchStats)
+    where
+        I: Instance,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::Linear => {
+                let indices = (0..tree.cardinality()).collect::<Vec<_>>();
+                let stats = SearchStats {
+                    distance_calls: indices.len(),
+                    ..SearchStats::default()
+                };
+                (linear::search(tree.data(), query, radius, &indices), stats)
+            }
+            Self::Clustered => clustered::search_instrumented(tree, query, radius),
+            Self::Auto(_) => self
+                .resolve(tree.cardinality())
+                .search_instrumented(query, radius, tree),
+        }
+    }
+
+    /// Searches for the nearest neighbors of a vector, using the bounding
+    /// boxes of the clusters to discard clusters before computing the
+    /// distances to their centers.
+    ///
+    /// See `BoundingBoxes` for the metrics with which this is correct.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to search around.
+    /// * `radius` - The radius to search within.
+    /// * `tree` - The tree to search.
+    /// * `boxes` - The bounding boxes of the clusters in the `tree`.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`.
+    pub fn search_with_bounding_boxes<F, I, U, D, C>(
+        self,
+        query: &I,
+        radius: U,
+        tree: &Tree<I, U, D, C>,
+        boxes: &BoundingBoxes<F>,
+    ) -> Vec<(usize, U)>
+    where
+        F: Number,
+        I: Instance + AsRef<[F]>,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::Linear => self.search(query, radius, tree),
+            Self::Clustered => clustered::search_with_bounding_boxes(tree, boxes, query, radius),
+            Self::Auto(_) => self
+                .resolve(tree.cardinality())
+                .search_with_bounding_boxes(query, radius, tree, boxes),
+        }
+    }
+
+    /// Searches for the nearest neighbors of a query whose type differs from
+    /// that of the instances in the tree, e.g. a sparse query in a tree of
+    /// dense vectors.
+    ///
+    /// The tree is still pruned with the radii computed from the instances, so
+    /// the `metric` must agree with the metric of the dataset.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - The distance function from queries to instances.
+    /// * `query` - The query to search around.
+    /// * `radius` - The radius to search within.
+    /// * `tree` - The tree to search.
+    ///
+    /// # Returns
+    ///
+    /// The hits, as in `search`.
+    pub fn search_cross<Q, I, U, D, C>(
+        self,
+        metric: CrossMetric<Q, I, U>,
+        query: &Q,
+        radius: U,
+        tree: &Tree<I, U, D, C>,
+    ) -> Vec<(usize, U)>
+    where
+        Q: Send + Sync,
+        I: Instance,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        match self {
+            Self::Linear => {
+                let indices = (0..tree.cardinality()).collect::<Vec<_>>();
+                linear::search_cross(tree.data(), metric, query, radius, &indices)
+            }
+            Self::Clustered => clustered::search_cross(tree, metric, query, radius),
+            Self::Auto(_) => self
+                .resolve(tree.cardinality())
+                .search_cross(metric, query, radius, tree),
+        }
+    }
+
+    /// Parallelized version of `search_cross` for a batch of queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - The distance function from queries to instances.
+    /// * `queries` - The queries to search around.
+    /// * `radius` - The radius to search within.
+    /// * `tree` - The tree to search.
+    ///
+    /// # Returns
+    ///
+    /// The hits for each query, in the same order as the `queries`.
+    pub fn par_search_cross<Q, I, U, D, C>(
+        self,
+        metric: CrossMetric<Q, I, U>,
+        queries: &[&Q],
+        radius: U,
+        tree: &Tree<I, U, D, C>,
+    ) -> Vec<Vec<(usize, U)>>
+    where
+        Q: Send + Sync,
+        I: Instance,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        queries
+            .par_iter()
+            .map(|&query| self.search_cross(metric, query, radius, tree))
+            .collect()
+    }
+
+    /// Same as `par_search_cross`, but runs in the given thread `pool` instead
+    /// of the global `rayon` thread pool.
+    pub fn par_search_cross_in<Q, I, U, D, C>(
+        self,
+        pool: &rayon::ThreadPool,
+        metric: CrossMetric<Q, I, U>,
+        queries: &[&Q],
+        radius: U,
+        tree: &Tree<I, U, D, C>,
+    ) -> Vec<Vec<(usize, U)>>
+    where
+        Q: Send + Sync,
+        I: Instance,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        pool.install(|| self.par_search_cross(metric, queries, radius, tree))
+    }
+
+    /// Resolves `Auto` to the algorithm it uses for a tree with the given
+    /// number of instances.
+    const fn resolve(self, cardinality: usize) -> Self {
+        match self {
+            Self::Auto(threshold) => {
+                if cardinality <= threshold {
+                    Self::Linear
+                } else {
+                    Self::Clustered
+                }
+            }
+            algo => algo,
         }
     }
 
@@ -70,12 +323,14 @@ impl Algorithm {
         match self {
             Self::Linear => "Linear",
             Self::Clustered => "Clustered",
+            Self::Auto(_) => "Auto",
         }
     }
 
     /// Returns the algorithm from a string representation of the name.
     ///
-    /// The string is case-insensitive.
+    /// The string is case-insensitive. `"auto"` gives `Auto` with the
+    /// `DEFAULT_LINEAR_THRESHOLD`.
     ///
     /// # Arguments
     ///
@@ -92,11 +347,12 @@ impl Algorithm {
         match s.to_lowercase().as_str() {
             "linear" => Ok(Self::Linear),
             "clustered" => Ok(Self::Clustered),
+            "auto" => Ok(Self::Auto(DEFAULT_LINEAR_THRESHOLD)),
             _ => Err(format!("Unknown algorithm: {s}")),
         }
     }
 
-    /// Returns a list of all the algorithms, excluding Linear.
+    /// Returns a list of all the algorithms, excluding Linear and Auto.
     #[must_use]
     pub const fn variants<'a>() -> &'a [Self] {
         &[Self::Clustered]
@@ -4,7 +4,7 @@ use std::path::Path;
 
 use distances::Number;
 
-use crate::{knn, rnn, Dataset, Instance};
+use crate::{knn, rnn, utils, Dataset, Instance};
 
 /// A trait for performing RNN- and KNN-Search.
 pub trait Search<I: Instance, U: Number, D: Dataset<I, U>>: Send + Sync {
@@ -45,6 +45,11 @@ pub trait Search<I: Instance, U: Number, D: Dataset<I, U>>: Send + Sync {
     /// Returns the cardinalities of the shards.
     fn shard_cardinalities(&self) -> Vec<usize>;
 
+    /// Returns the offset of the leaf that contains the instance at `index`,
+    /// in the same index space as the search results, or `None` if `index` is
+    /// out of bounds.
+    fn leaf_offset_of(&self, index: usize) -> Option<usize>;
+
     /// Returns the best RNN-Search algorithm.
     ///
     /// If the algorithm has not been tuned, this will return the default variant.
@@ -117,3 +122,86 @@ pub trait Search<I: Instance, U: Number, D: Dataset<I, U>>: Send + Sync {
         self.knn_search(query, k, algo)
     }
 }
+
+/// Describes the differences between the `expected` and `actual` hits for a
+/// query, or returns `None` if they agree.
+///
+/// The hits agree if they have the same indices and the distances for each
+/// index differ by no more than `U::epsilon()`.
+pub fn diff_hits<U: Number>(expected: &[(usize, U)], actual: &[(usize, U)]) -> Option<String> {
+    let mut expected = expected.to_vec();
+    expected.sort_by_key(|&(i, _)| i);
+    let mut actual = actual.to_vec();
+    actual.sort_by_key(|&(i, _)| i);
+
+    let missing = expected
+        .iter()
+        .filter(|(i, _)| actual.binary_search_by_key(i, |&(j, _)| j).is_err())
+        .collect::<Vec<_>>();
+    let extra = actual
+        .iter()
+        .filter(|(i, _)| expected.binary_search_by_key(i, |&(j, _)| j).is_err())
+        .collect::<Vec<_>>();
+    let mismatched = expected
+        .iter()
+        .filter_map(|&(i, d)| {
+            let &(_, a) = actual.iter().find(|&&(j, _)| j == i)?;
+            (d.abs_diff(a) > U::epsilon()).then_some((i, d, a))
+        })
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() && extra.is_empty() && mismatched.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "missing: {missing:?}, extra: {extra:?}, mismatched (index, expected, actual): {mismatched:?}"
+        ))
+    }
+}
+
+/// Summary statistics of the number of hits per query in a batch search.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HitStats {
+    /// The fewest hits for any query.
+    pub min: usize,
+    /// The mean number of hits.
+    pub mean: f64,
+    /// The median number of hits. For an even number of queries, this is the
+    /// lower of the two middle values.
+    pub median: usize,
+    /// The most hits for any query.
+    pub max: usize,
+    /// The standard deviation of the number of hits.
+    pub std_dev: f64,
+}
+
+/// Computes summary statistics of the number of hits per query, e.g. for
+/// reporting the results of a batch search in benchmarks.
+///
+/// # Arguments
+///
+/// * `hits` - The hits for each query, as returned by a batch search.
+///
+/// # Returns
+///
+/// The `HitStats` of the number of hits, or the default `HitStats`, with all
+/// values zero, if there are no queries.
+#[must_use]
+pub fn hit_count_stats<U: Number>(hits: &[Vec<(usize, U)>]) -> HitStats {
+    if hits.is_empty() {
+        return HitStats::default();
+    }
+
+    let counts = hits.iter().map(Vec::len).collect::<Vec<_>>();
+    let (Some(&min), Some(&max), Some(median)) = (counts.iter().min(), counts.iter().max(), utils::median(&counts))
+    else {
+        unreachable!("We checked that there is at least one query.")
+    };
+    HitStats {
+        min,
+        mean: utils::mean(&counts),
+        median,
+        max,
+        std_dev: utils::standard_deviation(&counts),
+    }
+}
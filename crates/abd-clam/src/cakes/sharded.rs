@@ -138,6 +138,17 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> Search<I, U, D> for RandomlyShard
             .collect()
     }
 
+    fn leaf_offset_of(&self, index: usize) -> Option<usize> {
+        if index < self.sample_shard.data().cardinality() {
+            return self.sample_shard.leaf_offset_of(index);
+        }
+        self.shards
+            .iter()
+            .zip(self.offsets.iter())
+            .find(|(shard, &o)| (o..(o + shard.data().cardinality())).contains(&index))
+            .and_then(|(shard, &o)| shard.leaf_offset_of(index - o).map(|offset| offset + o))
+    }
+
     fn tuned_rnn_algorithm(&self) -> rnn::Algorithm {
         self.sample_shard.tuned_rnn_algorithm()
     }
@@ -40,7 +40,7 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> SingleShard<I, U, D> {
     /// * `criteria` - The criteria to use for partitioning the tree.
     pub fn new<P: PartitionCriterion<U>>(data: D, seed: Option<u64>, criteria: &P) -> Self {
         Self {
-            tree: Tree::new(data, seed).partition(criteria, seed),
+            tree: Tree::new(data, seed).par_partition(criteria, seed),
             best_rnn: None,
             best_knn: None,
         }
@@ -163,6 +163,10 @@ impl<I: Instance, U: Number, D: Dataset<I, U>> Search<I, U, D> for SingleShard<I
         vec![self.tree.data().cardinality()]
     }
 
+    fn leaf_offset_of(&self, index: usize) -> Option<usize> {
+        self.tree.root().leaf_containing(index).map(Cluster::offset)
+    }
+
     fn auto_tune_rnn(&mut self, radius: U, tuning_depth: usize) {
         let queries = self
             .sample_query_indices(tuning_depth)
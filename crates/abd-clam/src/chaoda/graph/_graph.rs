@@ -9,10 +9,10 @@ use std::collections::{HashMap, HashSet};
 
 use distances::Number;
 
-use crate::{Cluster, Dataset, Instance, Tree};
+use crate::{chaoda::metaml::MetaMLModel, Cluster, Dataset, Instance, Tree};
 
 use super::{
-    criteria::{detect_edges, select_clusters},
+    criteria::{detect_edges, select_clusters, select_clusters_by_model},
     MetaMLScorer, Vertex,
 };
 
@@ -40,6 +40,8 @@ pub struct Edge<'a, U: Number> {
 
     /// The distance between the two `Cluster`s connected by this `Edge`.
     distance: U,
+    /// The estimated overlap between the two `Cluster`s. See `overlap_weight`.
+    weight: f64,
 }
 
 impl<'a, U: Number> PartialEq for Edge<'a, U> {
@@ -78,15 +80,16 @@ impl<'a, U: Number> Edge<'a, U> {
     /// # Returns
     ///
     /// A new `Edge` connecting the provided `Cluster`s with the given distance.
+    /// The `weight` of the `Edge` is computed from the distance and the radii of
+    /// the `Cluster`s with `overlap_weight`.
     pub fn new(left: &'a Vertex<U>, right: &'a Vertex<U>, distance: U) -> Self {
-        if left < right {
-            Self { left, right, distance }
-        } else {
-            Self {
-                left: right,
-                right: left,
-                distance,
-            }
+        let weight = overlap_weight(distance, left.radius(), right.radius());
+        let (left, right) = if left < right { (left, right) } else { (right, left) };
+        Self {
+            left,
+            right,
+            distance,
+            weight,
         }
     }
 
@@ -139,6 +142,12 @@ impl<'a, U: Number> Edge<'a, U> {
         self.distance
     }
 
+    /// Gets the estimated overlap between the two `Cluster`s connected by this
+    /// `Edge`, in the range `[0, 1]`. See `overlap_weight`.
+    pub const fn weight(&self) -> f64 {
+        self.weight
+    }
+
     /// Checks whether this is an edge from a `Cluster` to itself.
     ///
     /// # Returns
@@ -173,6 +182,36 @@ impl<'a, U: Number> Edge<'a, U> {
     }
 }
 
+/// Estimates how much two `Cluster`s overlap from the distance between their
+/// centers and their radii.
+///
+/// Where the two balls overlap, their intersection is a lens. This returns the
+/// width of the lens, along the line between the centers, as a fraction of the
+/// diameter of the smaller ball. This is `0` for balls that do not overlap and
+/// `1` when the smaller ball lies entirely inside the larger one. Unlike the
+/// volume of the lens, this does not depend on the dimensionality of the space.
+///
+/// # Arguments
+///
+/// * `distance`: The distance between the centers of the `Cluster`s.
+/// * `left_radius`: The radius of one `Cluster`.
+/// * `right_radius`: The radius of the other `Cluster`.
+///
+/// # Returns
+///
+/// The estimated overlap, in the range `[0, 1]`.
+pub fn overlap_weight<U: Number>(distance: U, left_radius: U, right_radius: U) -> f64 {
+    let (d, l, r) = (distance.as_f64(), left_radius.as_f64(), right_radius.as_f64());
+    let (small, large) = if l < r { (l, r) } else { (r, l) };
+    if d > small + large {
+        0.
+    } else if d + small <= large {
+        1.
+    } else {
+        ((small + large - d) / (2. * small)).clamp(0., 1.)
+    }
+}
+
 /// A `Graph` represents a collection of `Cluster`s and `Edge`s, i.e.
 /// connections between overlapping `Cluster`s.
 ///
@@ -185,6 +224,8 @@ pub struct Graph<'a, U: Number> {
     edges: EdgeSet<'a, U>,
     /// A map that represents the adjacency relationships between clusters.
     adjacency_map: AdjacencyMap<'a, U>,
+    /// A map from each `Cluster` to the sum of the weights of its `Edge`s.
+    weighted_degrees: HashMap<&'a Vertex<U>, f64>,
     /// The total population represented by the clusters in the graph.
     population: usize,
     /// The minimum depth in the hierarchy of clusters.
@@ -236,6 +277,31 @@ impl<'a, U: Number> Graph<'a, U> {
         Graph::from_clusters_and_edges(selected_clusters, edges)
     }
 
+    /// Same as `from_tree`, but with the clusters scored by the predictions of
+    /// a `MetaMLModel` from their six ratios, instead of by one of the
+    /// pretrained scorers.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree`: The tree from which to select clusters.
+    /// * `model`: The trained meta-ml model used to score each cluster.
+    /// * `min_depth`: The minimum depth at which to select clusters.
+    ///
+    /// # Errors
+    ///
+    /// - If the model fails to predict the score of any cluster.
+    /// - Any of the errors from `from_tree`.
+    pub fn from_tree_with_model<I: Instance, D: Dataset<I, U>, M: MetaMLModel>(
+        tree: &'a Tree<I, U, D, Vertex<U>>,
+        model: &M,
+        min_depth: usize,
+    ) -> Result<Self, String> {
+        let selected_clusters = select_clusters_by_model(tree.root(), model, min_depth)?;
+
+        let edges = detect_edges(&selected_clusters, tree.data());
+        Graph::from_clusters_and_edges(selected_clusters, edges)
+    }
+
     /// Creates a new `Graph` from the provided set of `clusters` and `edges`.
     ///
     /// # Arguments
@@ -260,17 +326,6 @@ impl<'a, U: Number> Graph<'a, U> {
             return Err("Cannot create a graph with no clusters.".to_string());
         }
 
-        let (population, min_depth, max_depth) =
-            clusters
-                .iter()
-                .fold((0, usize::MAX, 0), |(population, min_depth, max_depth), &c| {
-                    (
-                        population + c.cardinality(),
-                        std::cmp::min(min_depth, c.depth()),
-                        std::cmp::max(max_depth, c.depth()),
-                    )
-                });
-
         let adjacency_map = {
             let mut adjacency_map: AdjacencyMap<U> = clusters.iter().map(|&c| (c, HashSet::new())).collect();
             for e in &edges {
@@ -286,21 +341,132 @@ impl<'a, U: Number> Graph<'a, U> {
             }
             adjacency_map
         };
-        let mut ordered_clusters = clusters.iter().copied().collect::<Vec<_>>();
-        ordered_clusters.sort();
 
-        Ok(Self {
+        let mut graph = Self {
             clusters,
             edges,
             adjacency_map,
-            population,
-            min_depth,
-            max_depth,
-            ordered_clusters,
+            weighted_degrees: HashMap::new(),
+            population: 0,
+            min_depth: 0,
+            max_depth: 0,
+            ordered_clusters: Vec::new(),
             distance_matrix: None,
             adjacency_matrix: None,
             frontier_sizes: None,
-        })
+        };
+        graph.refresh_summaries();
+        Ok(graph)
+    }
+
+    /// Recomputes the population, depth range and ordering of the clusters
+    /// from the current set of clusters, and the weighted vertex degrees from
+    /// the current set of edges.
+    fn refresh_summaries(&mut self) {
+        let (population, min_depth, max_depth) =
+            self.clusters
+                .iter()
+                .fold((0, usize::MAX, 0), |(population, min_depth, max_depth), &c| {
+                    (
+                        population + c.cardinality(),
+                        std::cmp::min(min_depth, c.depth()),
+                        std::cmp::max(max_depth, c.depth()),
+                    )
+                });
+        self.population = population;
+        self.min_depth = min_depth;
+        self.max_depth = max_depth;
+
+        self.ordered_clusters = self.clusters.iter().copied().collect();
+        self.ordered_clusters.sort();
+
+        self.weighted_degrees = self.clusters.iter().map(|&c| (c, 0.)).collect();
+        for e in self.edges.iter().filter(|e| !e.is_circular()) {
+            for c in [e.left(), e.right()] {
+                if let Some(degree) = self.weighted_degrees.get_mut(c) {
+                    *degree += e.weight();
+                }
+            }
+        }
+    }
+
+    /// Updates the `Graph` after the selection of clusters from the tree has
+    /// changed locally, without rebuilding it from scratch.
+    ///
+    /// Each of the `changed` clusters becomes a vertex of the `Graph`. Any
+    /// vertex that is an ancestor or descendant of a `changed` cluster is
+    /// removed along with its edges, e.g. a vertex is replaced by its children.
+    /// Edges are then detected only between the `changed` clusters and the
+    /// other vertices, so this takes time linear in the number of vertices for
+    /// each `changed` cluster, instead of quadratic for the whole `Graph`.
+    ///
+    /// Any distance matrix, adjacency matrix or eccentricities that were
+    /// computed are discarded, and can be computed again with
+    /// `with_distance_matrix`, `with_adjacency_matrix` or `with_eccentricities`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset of the tree the clusters come from.
+    /// * `changed`: The clusters to (re-)insert into the `Graph`.
+    ///
+    /// # Errors
+    ///
+    /// * If two of the `changed` clusters are the same, or one is an ancestor
+    ///   of the other.
+    pub fn update_for_changed_clusters<I: Instance, D: Dataset<I, U>>(
+        &mut self,
+        data: &D,
+        changed: &[&'a Vertex<U>],
+    ) -> Result<(), String> {
+        for (i, &c1) in changed.iter().enumerate() {
+            for &c2 in &changed[(i + 1)..] {
+                if c1 == c2 || c1.is_ancestor_of(c2) || c1.is_descendant_of(c2) {
+                    return Err(format!("Changed clusters {c1:?} and {c2:?} overlap."));
+                }
+            }
+        }
+
+        let replaced = self
+            .clusters
+            .iter()
+            .copied()
+            .filter(|&v| {
+                changed
+                    .iter()
+                    .any(|&c| c == v || c.is_ancestor_of(v) || c.is_descendant_of(v))
+            })
+            .collect::<VertexSet<_>>();
+        for &v in &replaced {
+            self.clusters.remove(v);
+            for n in self.adjacency_map.remove(v).unwrap_or_default() {
+                if let Some(neighbors) = self.adjacency_map.get_mut(n) {
+                    neighbors.remove(v);
+                }
+            }
+        }
+        self.edges
+            .retain(|e| !replaced.contains(e.left()) && !replaced.contains(e.right()));
+
+        for &c in changed {
+            let mut neighbors = HashSet::new();
+            for &v in &self.clusters {
+                let distance = c.distance_to_other(data, v);
+                if distance <= c.radius() + v.radius() {
+                    self.edges.insert(Edge::new(c, v, distance));
+                    neighbors.insert(v);
+                    self.adjacency_map.entry(v).or_default().insert(c);
+                }
+            }
+            self.clusters.insert(c);
+            self.adjacency_map.insert(c, neighbors);
+        }
+
+        self.refresh_summaries();
+        self.distance_matrix = None;
+        self.adjacency_matrix = None;
+        self.frontier_sizes = None;
+
+        Ok(())
     }
 
     /// Computes the distance matrix for the clusters in the graph.
@@ -637,6 +803,27 @@ impl<'a, U: Number> Graph<'a, U> {
         }
     }
 
+    /// Returns the weighted vertex degree of a given cluster, i.e. the sum of
+    /// the `weight`s of the edges between it and its neighbors.
+    ///
+    /// # Arguments
+    ///
+    /// * `c`: The cluster for which the weighted vertex degree is calculated.
+    ///
+    /// # Returns
+    ///
+    /// The weighted vertex degree of the specified cluster.
+    ///
+    /// # Errors
+    ///
+    /// If the specified cluster is not present in the graph.
+    pub fn weighted_vertex_degree(&'a self, c: &Vertex<U>) -> Result<f64, String> {
+        self.weighted_degrees
+            .get(c)
+            .copied()
+            .ok_or_else(|| format!("Cluster {c} not found in weighted_degrees"))
+    }
+
     /// Returns a reference to the set of neighbors of a given cluster.
     ///
     /// # Arguments
@@ -829,11 +1016,103 @@ mod tests {
         test_matrix(&graph);
     }
 
+    #[test]
+    fn incremental_update() {
+        let data = gen_dataset(1000, 10, 42, euclidean);
+        let partition_criteria: PartitionCriteria<f32> = PartitionCriteria::default();
+        let raw_tree = Tree::new(data, Some(42))
+            .partition(&partition_criteria, Some(42))
+            .normalize_ratios();
+        let scorers = pretrained_models::get_meta_ml_scorers();
+        let Some((_, scorer)) = scorers.first() else {
+            unreachable!("There are pretrained scorers.")
+        };
+        let Ok(original) = Graph::from_tree(&raw_tree, scorer, 4) else {
+            unreachable!("The tree is deep enough for a graph.")
+        };
+
+        let assert_same = |actual: &Graph<f32>, expected: &Graph<f32>| {
+            assert_eq!(actual.clusters, expected.clusters);
+            assert_eq!(actual.edges, expected.edges);
+            assert_eq!(actual.adjacency_map, expected.adjacency_map);
+            assert_eq!(actual.ordered_clusters, expected.ordered_clusters);
+            assert_eq!(actual.population, expected.population);
+            assert_eq!(actual.min_depth, expected.min_depth);
+            assert_eq!(actual.max_depth, expected.max_depth);
+        };
+
+        // Replace a vertex by its children.
+        let Some(&parent) = original.ordered_clusters().iter().find(|c| !c.is_leaf()) else {
+            unreachable!("Some vertex has children.")
+        };
+        let Some([left, right]) = parent.children() else {
+            unreachable!("The parent is not a leaf.")
+        };
+        let mut clusters = original.clusters.clone();
+        clusters.remove(parent);
+        clusters.extend([left, right]);
+        let Ok(rebuilt) = Graph::from_clusters_and_edges(clusters.clone(), detect_edges(&clusters, raw_tree.data()))
+        else {
+            unreachable!("The clusters are not empty.")
+        };
+
+        let mut graph = original.clone().with_distance_matrix();
+        assert!(graph
+            .update_for_changed_clusters(raw_tree.data(), &[left, right])
+            .is_ok());
+        assert_same(&graph, &rebuilt);
+        assert!(graph.distance_matrix().is_none());
+        test_adjacency_map(&graph);
+
+        // Putting the parent back restores the original graph.
+        assert!(graph.update_for_changed_clusters(raw_tree.data(), &[parent]).is_ok());
+        assert_same(&graph, &original);
+
+        assert!(graph
+            .update_for_changed_clusters(raw_tree.data(), &[parent, left])
+            .is_err());
+    }
+
+    #[test]
+    fn overlap_weights() {
+        let overlapping = overlap_weight(1_f32, 1., 1.);
+        let touching = overlap_weight(2_f32, 1., 1.);
+        let distant = overlap_weight(3_f32, 1., 1.);
+        assert!(overlapping > touching);
+        assert!(float_cmp::approx_eq!(f64, overlapping, 0.5));
+        assert!(float_cmp::approx_eq!(f64, touching, 0.));
+        assert!(float_cmp::approx_eq!(f64, distant, 0.));
+
+        assert!(float_cmp::approx_eq!(f64, overlap_weight(0_u32, 2, 2), 1.));
+        assert!(float_cmp::approx_eq!(f64, overlap_weight(1_u32, 1, 3), 1.));
+        assert!(float_cmp::approx_eq!(f64, overlap_weight(0_u32, 0, 0), 1.));
+        assert!(float_cmp::approx_eq!(f64, overlap_weight(1_u32, 0, 0), 0.));
+    }
+
     fn test_properties(graph: &Graph<f32>, selected_clusters: &HashSet<&Vertex<f32>>, edges: &HashSet<Edge<f32>>) {
         // assert edges and clusters are correct
         assert_eq!(graph.clusters.len(), selected_clusters.len());
         assert_eq!(graph.edges().len(), edges.len());
 
+        // assert edge weights are in range and bounded by the unweighted degree
+        for e in graph.edges() {
+            assert!((0. ..=1.).contains(&e.weight()));
+        }
+        for &c in graph.ordered_clusters() {
+            let (Ok(weighted), Ok(degree)) = (graph.weighted_vertex_degree(c), graph.vertex_degree(c)) else {
+                unreachable!("Every ordered cluster is in the graph.")
+            };
+            assert!(weighted <= degree.as_f64());
+
+            let expected: f64 = graph
+                .edges()
+                .iter()
+                .filter(|e| !e.is_circular() && e.contains(c))
+                .map(Edge::weight)
+                .sum();
+            assert!((weighted - expected).abs() <= 1e-9);
+        }
+
         let reference_population = selected_clusters.iter().fold(0, |acc, &c| acc + c.cardinality());
         assert_eq!(graph.population(), reference_population);
         let components = graph.find_component_clusters();
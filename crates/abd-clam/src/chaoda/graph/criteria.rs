@@ -6,7 +6,7 @@ use std::collections::{BinaryHeap, HashSet};
 use distances::Number;
 
 use super::{Edge, EdgeSet, Ratios, Vertex, VertexSet};
-use crate::{Cluster, Dataset, Instance};
+use crate::{chaoda::metaml::MetaMLModel, Cluster, Dataset, Instance};
 
 /// A `Box`ed function that assigns a score for a given `Cluster`.
 pub type MetaMLScorer = Box<fn(Ratios) -> f64>;
@@ -67,18 +67,22 @@ impl<'a, U: Number> PartialOrd for VertexWrapper<'a, U> {
 /// # Returns:
 ///
 /// `BinaryHeap` of `ClusterWrappers`
-fn score_clusters<'a, U: Number>(
-    root: &'a Vertex<U>,
-    scoring_function: &super::MetaMLScorer,
-) -> BinaryHeap<VertexWrapper<'a, U>> {
-    let mut scored_clusters: BinaryHeap<VertexWrapper<'a, U>> = BinaryHeap::new();
+///
+/// # Errors
+///
+/// If the scoring function fails for any cluster.
+fn score_clusters<U: Number, F: Fn(Ratios) -> Result<f64, String>>(
+    root: &Vertex<U>,
+    scoring_function: F,
+) -> Result<BinaryHeap<VertexWrapper<'_, U>>, String> {
+    let mut scored_clusters = BinaryHeap::new();
 
     for cluster in root.subtree() {
-        let score = scoring_function(cluster.ratios());
+        let score = scoring_function(cluster.ratios())?;
         scored_clusters.push(VertexWrapper { cluster, score });
     }
 
-    scored_clusters
+    Ok(scored_clusters)
 }
 
 /// Gets `ClusterSet` from `BinaryHeap` of `ClusterWrappers`
@@ -102,8 +106,41 @@ pub fn select_clusters<'a, U: Number>(
     scoring_function: &MetaMLScorer,
     min_depth: usize,
 ) -> Result<VertexSet<'a, U>, String> {
-    let mut cluster_set: HashSet<&'a Vertex<U>> = HashSet::new();
-    let mut scored_clusters = score_clusters(root, scoring_function);
+    select_clusters_with(root, |ratios| Ok(scoring_function(ratios)), min_depth)
+}
+
+/// Same as `select_clusters`, but with clusters scored by the predictions of
+/// a `MetaMLModel` from their ratios.
+///
+/// # Errors
+///
+/// * If the model fails to predict the score of any cluster.
+/// * If `ClusterWrapper` contains an invalid cluster-score pairing
+pub fn select_clusters_by_model<'a, U: Number, M: MetaMLModel>(
+    root: &'a Vertex<U>,
+    model: &M,
+    min_depth: usize,
+) -> Result<VertexSet<'a, U>, String> {
+    select_clusters_with(
+        root,
+        |ratios| {
+            #[allow(clippy::cast_possible_truncation)]
+            let features = ratios.map(|r| r as f32);
+            model.predict(&features).map(<f64 as From<f32>>::from)
+        },
+        min_depth,
+    )
+}
+
+/// Selects clusters, as in `select_clusters`, with the given fallible
+/// scoring function.
+fn select_clusters_with<U: Number, F: Fn(Ratios) -> Result<f64, String>>(
+    root: &Vertex<U>,
+    scoring_function: F,
+    min_depth: usize,
+) -> Result<VertexSet<'_, U>, String> {
+    let mut cluster_set = HashSet::new();
+    let mut scored_clusters = score_clusters(root, scoring_function)?;
     scored_clusters.retain(|item| item.cluster.depth() >= min_depth || item.cluster.is_leaf());
     while !scored_clusters.is_empty() {
         let Some(wrapper) = scored_clusters.pop() else {
@@ -190,7 +227,10 @@ mod tests {
 
         let root = raw_tree.root();
 
-        let mut priority_queue = score_clusters(root, &pretrained_models::get_meta_ml_scorers()[0].1);
+        let scorer = &pretrained_models::get_meta_ml_scorers()[0].1;
+        let Ok(mut priority_queue) = score_clusters(root, |ratios| Ok(scorer(ratios))) else {
+            unreachable!("The pretrained scorers do not fail.")
+        };
 
         assert_eq!(priority_queue.len(), root.subtree().len());
 
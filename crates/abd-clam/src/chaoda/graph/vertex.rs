@@ -82,8 +82,14 @@ impl<U: Number> Vertex<U> {
 
     /// Set the child-parent ratios.
     #[must_use]
-    #[allow(clippy::similar_names)]
     pub(crate) fn set_child_parent_ratios(mut self, parent_ratios: Ratios) -> Self {
+        self.update_ratios(parent_ratios);
+        self
+    }
+
+    /// Recursively sets the child-parent ratios in the subtree.
+    #[allow(clippy::similar_names)]
+    fn update_ratios(&mut self, parent_ratios: Ratios) {
         let [pc, pr, pl, pc_, pr_, pl_] = parent_ratios;
 
         let c = self.cardinality().as_f64() / pc;
@@ -97,27 +103,25 @@ impl<U: Number> Vertex<U> {
         let ratios = [c, r, l, c_, r_, l_];
         self.ratios = ratios;
 
-        if let Some(Children {
-            left,
-            right,
-            arg_l,
-            arg_r,
-            polar_distance,
-        }) = self.children
-        {
-            let left = Box::new(left.set_child_parent_ratios(ratios));
-            let right = Box::new(right.set_child_parent_ratios(ratios));
-            let children = Children {
-                left,
-                right,
-                arg_l,
-                arg_r,
-                polar_distance,
-            };
-            self.children = Some(children);
+        if let Some(children) = self.children.as_mut() {
+            children.left.update_ratios(ratios);
+            children.right.update_ratios(ratios);
         }
+    }
 
-        self
+    /// Recomputes the geometry of the subtree.
+    ///
+    /// The ratios depend on the cardinalities, radii and local fractal
+    /// dimensions of the ancestors of a `Vertex`, so they are recomputed for
+    /// the whole tree when this is called on the root. They are not normalized,
+    /// so `normalize_ratios` must be called again if needed. When this is
+    /// called on any other `Vertex`, the ratios in its subtree are left stale.
+    fn recompute_subtree<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, parallel: bool) {
+        self.uni_ball
+            .recompute_geometry_with(self.children.as_mut(), data, parallel);
+        if self.depth() == 0 {
+            self.update_ratios([1.0; 6]);
+        }
     }
 
     /// Normalizes the ratios in the subtree.
@@ -181,16 +185,24 @@ impl<U: Number> Cluster<U> for Vertex<U> {
         Self::new(uni_ball, ratios, None)
     }
 
-    fn partition<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>) -> Self
+    fn partition_with<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>, parallel: bool) -> Self
     where
         I: Instance,
         D: Dataset<I, U>,
         P: PartitionCriterion<U>,
     {
-        let uni_ball = self.uni_ball.partition(data, criteria, seed);
+        let uni_ball = self.uni_ball.partition_with(data, criteria, seed, parallel);
         Self::from_base_tree(uni_ball)
     }
 
+    fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, false);
+    }
+
+    fn par_recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, true);
+    }
+
     fn offset(&self) -> usize {
         self.uni_ball.offset()
     }
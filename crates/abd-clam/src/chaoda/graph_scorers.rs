@@ -15,6 +15,29 @@ pub type ClusterScores<'a, U> = HashMap<&'a Vertex<U>, f64>;
 /// Type alias for scores associated with individual instances or elements.
 pub type InstanceScores = HashMap<usize, f64>;
 
+/// The ways in which the scores of a `GraphScorer` may be normalized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    /// Gaussian normalization, as in `utils::normalize_1d`.
+    ///
+    /// The raw scores can be recovered with `utils::denormalize_1d`, given
+    /// their mean and standard deviation.
+    Gaussian,
+    /// Linear rescaling to `[0, 1]`, as in `utils::min_max_1d`.
+    MinMax,
+}
+
+impl Normalization {
+    /// Normalizes the given scores.
+    #[must_use]
+    pub fn apply(self, scores: &[f64]) -> Vec<f64> {
+        match self {
+            Self::Gaussian => crate::utils::normalize_1d(scores, mean(scores), standard_deviation(scores)),
+            Self::MinMax => crate::utils::min_max_1d(scores),
+        }
+    }
+}
+
 /// A trait for scoring graphs.
 pub trait GraphScorer<'a, U: Number>: Hash {
     /// Computes scores for the given graph and returns cluster scores and an array of scores.
@@ -37,19 +60,36 @@ pub trait GraphScorer<'a, U: Number>: Hash {
     /// Throws an error if unable to compute scores for the given graph
     ///
     fn call(&self, graph: &'a Graph<'a, U>) -> Result<(ClusterScores<'a, U>, Vec<f64>), String> {
+        self.call_with(graph, Normalization::Gaussian)
+    }
+
+    /// Computes scores for the given graph, as in `call`, with the given
+    /// normalization of the scores.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph`: A reference to the input graph from which scores are calculated.
+    /// * `normalization`: How to normalize the cluster or instance scores.
+    ///
+    /// # Errors
+    ///
+    /// Throws an error if unable to compute scores for the given graph
+    ///
+    fn call_with(
+        &self,
+        graph: &'a Graph<'a, U>,
+        normalization: Normalization,
+    ) -> Result<(ClusterScores<'a, U>, Vec<f64>), String> {
         let cluster_scores = {
             let scores = self.score_graph(graph)?;
             let mut cluster_scores: ClusterScores<'a, U> = scores;
             if self.normalize_on_clusters() {
-                let (clusters, scores): (Vec<_>, Vec<_>) = cluster_scores.into_iter().unzip();
-                cluster_scores = clusters
-                    .into_iter()
-                    .zip(crate::utils::normalize_1d(
-                        &scores,
-                        mean(&scores),
-                        standard_deviation(&scores),
-                    ))
-                    .collect();
+                // Sort before normalizing so that the mean and standard
+                // deviation do not depend on the iteration order of the map.
+                let mut sorted = cluster_scores.into_iter().collect::<Vec<_>>();
+                sorted.sort_by_key(|(c, _)| (c.offset(), c.cardinality()));
+                let (clusters, scores): (Vec<_>, Vec<_>) = sorted.into_iter().unzip();
+                cluster_scores = clusters.into_iter().zip(normalization.apply(&scores)).collect();
             }
             cluster_scores
         };
@@ -57,15 +97,10 @@ pub trait GraphScorer<'a, U: Number>: Hash {
         let instance_scores = {
             let mut instance_scores = self.inherit_scores(&cluster_scores);
             if !self.normalize_on_clusters() {
-                let (indices, scores): (Vec<_>, Vec<_>) = instance_scores.into_iter().unzip();
-                instance_scores = indices
-                    .into_iter()
-                    .zip(crate::utils::normalize_1d(
-                        &scores,
-                        mean(&scores),
-                        standard_deviation(&scores),
-                    ))
-                    .collect();
+                let mut sorted = instance_scores.into_iter().collect::<Vec<_>>();
+                sorted.sort_by_key(|(i, _)| *i);
+                let (indices, scores): (Vec<_>, Vec<_>) = sorted.into_iter().unzip();
+                instance_scores = indices.into_iter().zip(normalization.apply(&scores)).collect();
             }
             instance_scores
         };
@@ -314,6 +349,60 @@ impl<'a, U: Number> GraphScorer<'a, U> for VertexDegree {
     }
 }
 
+/// A graph scorer that calculates scores based on vertex degree, with each
+/// edge weighted by the estimated overlap of the clusters it connects.
+///
+/// See `overlap_weight` for how the edges are weighted.
+pub struct WeightedVertexDegree;
+
+impl Hash for WeightedVertexDegree {
+    /// Generates a hash for the `WeightedVertexDegree` instance.
+    ///
+    /// This function hashes the string "`weighted_vertex_degree`" to uniquely identify this scorer.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        "weighted_vertex_degree".hash(state);
+    }
+}
+
+impl<'a, U: Number> GraphScorer<'a, U> for WeightedVertexDegree {
+    /// Returns the name of the `WeightedVertexDegree` graph scorer.
+    ///
+    /// The name is "`weighted_vertex_degree`."
+    fn name(&self) -> &'static str {
+        "weighted_vertex_degree"
+    }
+
+    /// Returns the short name of the `WeightedVertexDegree` graph scorer.
+    ///
+    /// The short name is "wvd."
+    fn short_name(&self) -> &'static str {
+        "wvd"
+    }
+
+    /// Indicates whether normalization should be performed based on clusters for `WeightedVertexDegree`.
+    fn normalize_on_clusters(&self) -> bool {
+        true
+    }
+
+    /// Computes and returns cluster scores based on weighted vertex degree.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph`: A reference to the input graph from which cluster scores are calculated.
+    ///
+    /// # Returns
+    ///
+    /// A `ClusterScores` mapping clusters to their calculated scores based on
+    /// the weighted vertex degrees of their vertices.
+    fn score_graph(&self, graph: &'a Graph<'a, U>) -> Result<ClusterScores<'a, U>, String> {
+        graph
+            .ordered_clusters()
+            .iter()
+            .map(|&c| graph.weighted_vertex_degree(c).map(|degree| (c, -degree)))
+            .collect()
+    }
+}
+
 /// A graph scorer that calculates scores based on parent-child cluster relationships and cardinality.
 ///
 /// This scorer assigns scores to clusters based on the cardinality of a cluster relative to its parent
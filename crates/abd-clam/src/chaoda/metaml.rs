@@ -2,7 +2,7 @@
 
 use automl::IntoSupervisedData;
 use smartcore::linalg::naive::dense_matrix::DenseMatrix;
-use std::path::Path;
+use std::{fs::File, io::BufReader, io::BufWriter, path::Path};
 
 /// Trait to represent types that can be used as a meta-ML model
 pub trait MetaMLModel {
@@ -85,6 +85,66 @@ pub trait MetaMLModel {
     fn save(&self, path: &Path) -> Result<(), String>;
 }
 
+/// The name of the file, in the directory of an ensemble saved with
+/// `save_ensemble`, that lists the names of the models in the ensemble.
+pub const ENSEMBLE_INDEX_FILE: &str = "ensemble.json";
+
+/// Saves an ensemble of trained, named meta-ml models to a directory.
+///
+/// The directory will contain the `ENSEMBLE_INDEX_FILE`, a JSON list of the
+/// names of the models in order, and one file per model, named `<i>.model`
+/// for the model at index `i`, written with `MetaMLModel::save`.
+///
+/// # Arguments
+///
+/// * `models`: The names of the models and the models themselves.
+/// * `dir`: The directory in which to save the ensemble. It must already exist.
+///
+/// # Errors
+///
+/// * If `dir` does not exist.
+/// * If the index file cannot be written.
+/// * If any of the models cannot be saved.
+pub fn save_ensemble<M: MetaMLModel>(models: &[(String, M)], dir: &Path) -> Result<(), String> {
+    if !dir.exists() {
+        return Err("Given path does not exist".to_string());
+    }
+
+    for (i, (_, model)) in models.iter().enumerate() {
+        model.save(&dir.join(format!("{i}.model")))?;
+    }
+
+    let names = models.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let writer = BufWriter::new(File::create(dir.join(ENSEMBLE_INDEX_FILE)).map_err(|e| e.to_string())?);
+    serde_json::to_writer(writer, &names).map_err(|e| e.to_string())
+}
+
+/// Loads an ensemble of meta-ml models saved with `save_ensemble`.
+///
+/// # Arguments
+///
+/// * `dir`: The directory in which the ensemble was saved.
+///
+/// # Returns
+///
+/// The names of the models and the models themselves, in the order in which
+/// they were saved.
+///
+/// # Errors
+///
+/// * If the index file cannot be read or parsed.
+/// * If any of the models cannot be loaded.
+pub fn load_ensemble<M: MetaMLModel>(dir: &Path) -> Result<Vec<(String, M)>, String> {
+    let reader = BufReader::new(File::open(dir.join(ENSEMBLE_INDEX_FILE)).map_err(|e| e.to_string())?);
+    let names: Vec<String> = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| M::load(&dir.join(format!("{i}.model"))).map(|model| (name, model)))
+        .collect()
+}
+
 /// Represents the training data for a `MetaML` model
 ///
 /// # Invariants:
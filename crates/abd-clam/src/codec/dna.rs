@@ -0,0 +1,116 @@
+//! 2-bit packing of DNA sequences.
+//!
+//! The bases `A`, `C`, `G` and `T` are packed four to a byte. Any other
+//! byte, e.g. `N` for an unknown base, is stored separately as an escape, so
+//! that every string is reconstructed exactly.
+//!
+//! The encoding is laid out as:
+//!
+//! * the number of bytes in the string, as a little-endian `u32`,
+//! * the number of escapes, as a little-endian `u32`,
+//! * the packed bases, with each escaped byte packed as `A`,
+//! * each escape, as the position of the byte as a little-endian `u32`
+//!   followed by the byte itself.
+
+/// The number of bytes used for each length and position in the encoding.
+const U32_BYTES: usize = core::mem::size_of::<u32>();
+
+/// The 2-bit code of a base, or `None` if the byte must be escaped.
+const fn base_code(base: u8) -> Option<u8> {
+    match base {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// The bases, indexed by their 2-bit codes.
+const BASES: [u8; 4] = *b"ACGT";
+
+/// Packs a DNA sequence into 2 bits per base.
+///
+/// This has the signature of an encoder for a `SquishyDataset` of strings,
+/// but the `reference` is not used, i.e. each sequence is encoded on its own.
+///
+/// # Arguments
+///
+/// * `reference`: Ignored.
+/// * `target`: The sequence to encode.
+///
+/// # Returns
+///
+/// The packed sequence, which takes about a quarter as many bytes as the
+/// `target` when few bytes need to be escaped.
+#[must_use]
+#[allow(clippy::ptr_arg, unused_variables)]
+pub fn encode_dna(reference: &String, target: &String) -> Box<[u8]> {
+    let bytes = target.as_bytes();
+
+    let mut packed = vec![0_u8; bytes.len().div_ceil(4)];
+    let mut escapes = Vec::new();
+    for (i, &b) in bytes.iter().enumerate() {
+        if let Some(code) = base_code(b) {
+            packed[i / 4] |= code << (2 * (i % 4));
+        } else {
+            escapes.push((i, b));
+        }
+    }
+
+    let to_u32 = |n: usize| u32::try_from(n).unwrap_or_else(|_| unreachable!("Sequences are shorter than 4 GiB."));
+    let mut encoding = Vec::with_capacity(2 * U32_BYTES + packed.len() + escapes.len() * (U32_BYTES + 1));
+    encoding.extend_from_slice(&to_u32(bytes.len()).to_le_bytes());
+    encoding.extend_from_slice(&to_u32(escapes.len()).to_le_bytes());
+    encoding.extend_from_slice(&packed);
+    for (i, b) in escapes {
+        encoding.extend_from_slice(&to_u32(i).to_le_bytes());
+        encoding.push(b);
+    }
+    encoding.into_boxed_slice()
+}
+
+/// Unpacks a DNA sequence packed by `encode_dna`.
+///
+/// # Arguments
+///
+/// * `reference`: Ignored.
+/// * `encoding`: The packed sequence.
+///
+/// # Returns
+///
+/// The original sequence. If the `encoding` is malformed, this is whatever
+/// prefix of the sequence could be decoded, with any invalid UTF-8 replaced.
+#[must_use]
+#[allow(clippy::ptr_arg, unused_variables)]
+pub fn decode_dna(reference: &String, encoding: &[u8]) -> String {
+    let read_u32 = |at: usize| {
+        encoding
+            .get(at..(at + U32_BYTES))
+            .and_then(|b| <[u8; U32_BYTES]>::try_from(b).ok())
+            .map(|b| u32::from_le_bytes(b) as usize)
+    };
+    let (Some(len), Some(num_escapes)) = (read_u32(0), read_u32(U32_BYTES)) else {
+        return String::new();
+    };
+
+    let packed_start = 2 * U32_BYTES;
+    let packed_end = (packed_start + len.div_ceil(4)).min(encoding.len());
+    let mut bytes = encoding[packed_start..packed_end]
+        .iter()
+        .flat_map(|&p| (0..4).map(move |j| BASES[usize::from((p >> (2 * j)) & 0b11)]))
+        .take(len)
+        .collect::<Vec<_>>();
+
+    for e in 0..num_escapes {
+        let at = packed_end + e * (U32_BYTES + 1);
+        let (Some(i), Some(&b)) = (read_u32(at), encoding.get(at + U32_BYTES)) else {
+            break;
+        };
+        if let Some(slot) = bytes.get_mut(i) {
+            *slot = b;
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
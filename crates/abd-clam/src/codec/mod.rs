@@ -1,14 +1,40 @@
 //! Compression and Decompression
 
 mod dataset;
+mod dna;
 mod squishy_ball;
 
+use std::{collections::HashMap, path::Path};
+
 use distances::number::Int;
 
 pub use dataset::SquishyDataset;
+pub use dna::{decode_dna, encode_dna};
 pub use squishy_ball::SquishyBall;
 
-use crate::{Instance, Tree};
+use crate::{Cluster, Dataset, Instance, Tree, UniBall};
+
+/// The `offset` and `cardinality` of a `Cluster`, which identify it in a tree.
+type ClusterId = (usize, usize);
+
+impl<I: Instance, U: Int, D: SquishyDataset<I, U>> Tree<I, U, D, UniBall<U>> {
+    /// Adapts a partitioned tree of `UniBall`s into a tree of `SquishyBall`s
+    /// that is ready for compression.
+    ///
+    /// This performs, in one call, the steps that would otherwise be chained
+    /// by hand: adapting the `UniBall`s with `SquishyBall::from_base_tree`,
+    /// estimating the costs of compression with `estimate_costs`, and removing
+    /// the subtrees that are not cheaper to compress recursively with `trim`.
+    /// The dataset was already permuted when the tree was partitioned, so it
+    /// is moved into the new tree as is.
+    #[must_use]
+    pub fn adapt_to_codec(self) -> Tree<I, U, D, SquishyBall<U>> {
+        let Self { data, root, .. } = self;
+        let mut tree = Tree::from_parts(data, SquishyBall::from_base_tree(root));
+        tree.root.estimate_costs(&tree.data);
+        tree.trim()
+    }
+}
 
 impl<I: Instance, U: Int, D: SquishyDataset<I, U>> Tree<I, U, D, SquishyBall<U>> {
     /// Recursively estimates and sets the costs of recursive and unitary compression in the subtree.
@@ -35,3 +61,161 @@ impl<I: Instance, U: Int, D: SquishyDataset<I, U>> Tree<I, U, D, SquishyBall<U>>
         todo!()
     }
 }
+
+impl<I: Instance, U: Int, D: Dataset<I, U>> Tree<I, U, D, SquishyBall<U>> {
+    /// Removes the subtrees for which recursive compression is not cheaper
+    /// than unitary compression.
+    ///
+    /// See `SquishyBall::trim` for more details.
+    #[must_use]
+    pub fn trim(mut self) -> Self {
+        self.root.trim();
+        self.depth = self.root.max_leaf_depth();
+        self
+    }
+
+    /// Removes the children of every `SquishyBall` for which the `predicate`
+    /// returns `true`.
+    ///
+    /// See `SquishyBall::trim_if` for more details.
+    #[must_use]
+    pub fn trim_if<F: Fn(&SquishyBall<U>) -> bool>(mut self, predicate: F) -> Self {
+        self.root.trim_if(predicate);
+        self.depth = self.root.max_leaf_depth();
+        self
+    }
+
+    /// Trims subtrees until the estimated compressed size of the tree is
+    /// within the `budget`, in bytes.
+    ///
+    /// See `SquishyBall::trim_to_budget` for more details.
+    ///
+    /// # Errors
+    ///
+    /// * If no trimming brings the estimated size within the `budget`.
+    pub fn trim_to_budget(mut self, budget: u64) -> Result<Self, String> {
+        self.root.trim_to_budget(budget)?;
+        self.depth = self.root.max_leaf_depth();
+        Ok(self)
+    }
+
+    /// Writes the estimated costs of every `SquishyBall` in the tree to a CSV
+    /// file, for tuning the codec.
+    ///
+    /// There is one row per `SquishyBall`, in pre-order, with the columns
+    /// `offset`, `cardinality`, `depth`, `recursive_cost`, `unitary_cost`,
+    /// `prefers_recursive` and `is_leaf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: The path of the CSV file.
+    ///
+    /// # Errors
+    ///
+    /// * If the file cannot be written.
+    pub fn write_costs_csv(&self, path: &Path) -> Result<(), String> {
+        let rows = self.root.subtree().into_iter().map(|c| {
+            format!(
+                "{},{},{},{},{},{},{}",
+                c.offset(),
+                c.cardinality(),
+                c.depth(),
+                c.recursive_cost(),
+                c.unitary_cost(),
+                c.prefers_recursive(),
+                c.is_leaf()
+            )
+        });
+        let header = "offset,cardinality,depth,recursive_cost,unitary_cost,prefers_recursive,is_leaf".to_string();
+        let csv = core::iter::once(header).chain(rows).collect::<Vec<_>>().join("\n");
+        std::fs::write(path, csv).map_err(|e| e.to_string())
+    }
+
+    /// Returns the index of the center of every `SquishyBall` in the tree,
+    /// keyed by the `offset` and `cardinality` of the `SquishyBall`.
+    ///
+    /// The centers are what recursive compression stores, so they can be
+    /// used without decoding any leaves.
+    #[must_use]
+    pub fn centers(&self) -> HashMap<ClusterId, usize> {
+        self.root
+            .subtree()
+            .into_iter()
+            .map(|c| ((c.offset(), c.cardinality()), c.arg_center()))
+            .collect()
+    }
+
+    /// Computes the distances between the centers of pairs of `SquishyBall`s.
+    ///
+    /// # Arguments
+    ///
+    /// * `centers`: The centers of the `SquishyBall`s, as returned by
+    ///   `centers`.
+    /// * `pairs`: The pairs of `SquishyBall`s, each given by its `offset` and
+    ///   `cardinality`.
+    ///
+    /// # Returns
+    ///
+    /// The distance between the centers of each pair.
+    ///
+    /// # Errors
+    ///
+    /// * If any `SquishyBall` in the `pairs` is not in the `centers`.
+    pub fn center_distances(
+        &self,
+        centers: &HashMap<ClusterId, usize>,
+        pairs: &[(ClusterId, ClusterId)],
+    ) -> Result<Vec<U>, String> {
+        let center_of = |&(offset, cardinality): &ClusterId| {
+            centers
+                .get(&(offset, cardinality))
+                .copied()
+                .ok_or_else(|| format!("No center for the cluster {offset}-{cardinality}."))
+        };
+        let index_pairs = pairs
+            .iter()
+            .map(|(a, b)| Ok((center_of(a)?, center_of(b)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(self.data.pairs(&index_pairs))
+    }
+
+    /// Finds the leaf of the tree that contains the instance at a given index.
+    ///
+    /// The leaf is where a compressed instance would be decoded from. See
+    /// `Cluster::leaf_containing`.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the instance in the (permuted) dataset.
+    ///
+    /// # Returns
+    ///
+    /// The leaf containing the instance, or `None` if the `index` is out of
+    /// bounds.
+    #[must_use]
+    pub fn leaf_containing(&self, index: usize) -> Option<&SquishyBall<U>> {
+        self.root.leaf_containing(index)
+    }
+
+    /// Iterates over the leaves of the tree, in order of their offsets, and
+    /// yields the instances in each leaf.
+    ///
+    /// The leaves are visited lazily, so only the instances of one leaf are
+    /// held at a time. This is meant for processing the whole dataset
+    /// sequentially, one leaf at a time.
+    pub fn iter_leaves(&self) -> impl Iterator<Item = Vec<I>> + '_ {
+        let mut stack = vec![&self.root];
+        core::iter::from_fn(move || {
+            while let Some(c) = stack.pop() {
+                match c.children() {
+                    Some([left, right]) => {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                    None => return Some(c.indices().map(|i| self.data[i].clone()).collect()),
+                }
+            }
+            None
+        })
+    }
+}
@@ -14,7 +14,7 @@ use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::{core::cluster::Children, Cluster, Dataset, Instance, PartitionCriterion, UniBall};
+use crate::{core::cluster::Children, Cluster, Dataset, FromClusters, Instance, PartitionCriterion, UniBall};
 
 use super::SquishyDataset;
 
@@ -37,6 +37,34 @@ impl<U: Int> SquishyBall<U> {
         Self::from_uni_ball(root)
     }
 
+    /// Creates a new `SquishyBall` tree whose estimated compressed size is
+    /// within a budget.
+    ///
+    /// The costs are estimated for the whole tree, and then subtrees are
+    /// trimmed, as in `trim_to_budget`, until the estimated size is within
+    /// the `budget`.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root of the `UniBall` tree.
+    /// * `data`: The dataset from which the tree was built.
+    /// * `budget`: The maximum estimated size, in bytes, of the compressed
+    ///   dataset.
+    ///
+    /// # Errors
+    ///
+    /// * If no trimming brings the estimated size within the `budget`.
+    pub fn from_base_tree_with_budget<I: Instance, D: SquishyDataset<I, U>>(
+        root: UniBall<U>,
+        data: &D,
+        budget: u64,
+    ) -> Result<Self, String> {
+        let mut root = Self::from_uni_ball(root);
+        root.estimate_costs(data);
+        root.trim_to_budget(budget)?;
+        Ok(root)
+    }
+
     /// Recursively creates a new `SquishyBall` tree.
     fn from_uni_ball(mut uni_ball: UniBall<U>) -> Self {
         match uni_ball.children {
@@ -82,6 +110,77 @@ impl<U: Int> SquishyBall<U> {
         self.unitary_cost
     }
 
+    /// Checks that the subtree of this `SquishyBall` is consistent with the
+    /// given dataset.
+    ///
+    /// This is meant as a debugging aid, e.g. after deserializing a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    ///
+    /// # Errors
+    ///
+    /// * If the indices of any `SquishyBall` are not a contiguous range in the
+    ///   dataset.
+    /// * If the children of any `SquishyBall` do not partition its indices.
+    /// * If the center or radial of any `SquishyBall` are not in its indices.
+    /// * If the radius of any `SquishyBall` is not the distance from its center
+    ///   to its radial.
+    pub fn validate<I: Instance, D: Dataset<I, U>>(&self, data: &D) -> Result<(), String> {
+        let name = self.name();
+
+        if self.cardinality() == 0 || self.indices().end > data.cardinality() {
+            return Err(format!(
+                "{name}: indices {:?} are not a valid range in a dataset of cardinality {}.",
+                self.indices(),
+                data.cardinality()
+            ));
+        }
+
+        if !self.indices().contains(&self.arg_center()) {
+            return Err(format!("{name}: center {} is not in its indices.", self.arg_center()));
+        }
+        if !self.indices().contains(&self.arg_radial()) {
+            return Err(format!("{name}: radial {} is not in its indices.", self.arg_radial()));
+        }
+
+        let radius = data.one_to_one(self.arg_center(), self.arg_radial());
+        if radius != self.radius() {
+            return Err(format!(
+                "{name}: stored radius {} does not match the recomputed radius {radius}.",
+                self.radius()
+            ));
+        }
+
+        if let Some([left, right]) = self.children() {
+            if left.offset() != self.offset() {
+                return Err(format!(
+                    "{name}: left child {} does not start at the offset of its parent.",
+                    left.name()
+                ));
+            }
+            if right.offset() != left.indices().end {
+                return Err(format!(
+                    "{name}: right child {} does not start where the left child {} ends.",
+                    right.name(),
+                    left.name()
+                ));
+            }
+            if right.indices().end != self.indices().end {
+                return Err(format!(
+                    "{name}: right child {} does not end where its parent ends.",
+                    right.name()
+                ));
+            }
+
+            left.validate(data)?;
+            right.validate(data)?;
+        }
+
+        Ok(())
+    }
+
     /// Estimates the memory cost of unitary compression.
     ///
     /// The cost is estimated as the number of bytes required to encode all instances in the cluster
@@ -109,6 +208,18 @@ impl<U: Int> SquishyBall<U> {
         }
     }
 
+    /// Recomputes the geometry of the subtree.
+    ///
+    /// The costs of compression depend on the centers and radii, so they are
+    /// reset to zero and must be estimated again, e.g. with
+    /// `Tree::estimate_costs`.
+    fn recompute_subtree<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, parallel: bool) {
+        self.uni_ball
+            .recompute_geometry_with(self.children.as_mut(), data, parallel);
+        self.recursive_cost = 0;
+        self.unitary_cost = 0;
+    }
+
     /// Recursively estimates and sets the costs of recursive and unitary compression in the subtree.
     pub(crate) fn estimate_costs<I: Instance, D: SquishyDataset<I, U>>(&mut self, data: &D) {
         if let Some(children) = self.children.as_mut() {
@@ -120,6 +231,134 @@ impl<U: Int> SquishyBall<U> {
         }
         self.unitary_cost = self.estimate_unitary_cost(data);
     }
+
+    /// Removes the children of every `SquishyBall` in the subtree for which
+    /// recursive compression is not cheaper than unitary compression.
+    ///
+    /// Recursive compression of a `SquishyBall` costs its `recursive_cost`
+    /// plus the `unitary_cost` of each child. The costs must have been
+    /// estimated, with `estimate_costs`, before calling this method.
+    pub fn trim(&mut self) {
+        self.trim_if(|c| !c.is_leaf() && !c.prefers_recursive());
+    }
+
+    /// Whether recursive compression of this `SquishyBall` is cheaper than
+    /// unitary compression, i.e. whether `trim` keeps its children.
+    ///
+    /// This compares the `unitary_cost` with the `recursive_cost` plus the
+    /// `unitary_cost` of each child, and is `false` for a leaf. The costs must
+    /// have been estimated, with `estimate_costs`, before calling this method.
+    #[must_use]
+    pub fn prefers_recursive(&self) -> bool {
+        self.children().is_some_and(|[left, right]| {
+            self.unitary_cost > self.recursive_cost + left.unitary_cost + right.unitary_cost
+        })
+    }
+
+    /// Removes the children of every `SquishyBall` in the subtree for which
+    /// the `predicate` returns `true`.
+    ///
+    /// The subtree is visited top-down, so the `predicate` is not called on
+    /// the descendants of a `SquishyBall` whose children were removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate`: Whether to remove the children of a `SquishyBall`.
+    pub fn trim_if<F: Fn(&Self) -> bool>(&mut self, predicate: F) {
+        self.trim_recursive(&predicate);
+    }
+
+    /// Returns the estimated size, in bytes, of compressing the subtree.
+    ///
+    /// A leaf is compressed with unitary compression, and every other
+    /// `SquishyBall` stores the centers of its children in terms of its own
+    /// center. The costs must have been estimated, with `estimate_costs`,
+    /// before calling this method.
+    #[must_use]
+    pub fn estimated_size(&self) -> u64 {
+        match self.children() {
+            Some([left, right]) => self.recursive_cost + left.estimated_size() + right.estimated_size(),
+            None => self.unitary_cost,
+        }
+    }
+
+    /// Trims subtrees until the estimated size of compressing the subtree of
+    /// this `SquishyBall` is within a budget.
+    ///
+    /// Each trim replaces a subtree with unitary compression of its root. At
+    /// each step, among the subtrees whose trimming reduces the estimated
+    /// size, the one with the worst compression ratio, i.e. the largest ratio
+    /// of its `estimated_size` to its `unitary_cost`, is trimmed first. The
+    /// costs must have been estimated, with `estimate_costs`, before calling
+    /// this method.
+    ///
+    /// # Arguments
+    ///
+    /// * `budget`: The maximum estimated size, in bytes.
+    ///
+    /// # Errors
+    ///
+    /// * If no trim reduces the estimated size and it is still over the
+    ///   `budget`. The subtree is left in its most trimmed state.
+    pub fn trim_to_budget(&mut self, budget: u64) -> Result<(), String> {
+        loop {
+            let (size, worst) = self.worst_subtree();
+            if size <= budget {
+                return Ok(());
+            }
+            let Some((_, _, offset, cardinality)) = worst else {
+                return Err(format!(
+                    "No trim reduces the estimated size of {size} bytes to within the budget of {budget} bytes."
+                ));
+            };
+            self.trim_if(|c| c.offset() == offset && c.cardinality() == cardinality);
+        }
+    }
+
+    /// Returns the `estimated_size` of the subtree and, among the
+    /// `SquishyBall`s whose trimming would reduce it, the `estimated_size`,
+    /// `unitary_cost`, `offset` and `cardinality` of the one with the worst
+    /// compression ratio.
+    fn worst_subtree(&self) -> (u64, Option<(u64, u64, usize, usize)>) {
+        let Some([left, right]) = self.children() else {
+            return (self.unitary_cost, None);
+        };
+
+        let (l_size, l_worst) = left.worst_subtree();
+        let (r_size, r_worst) = right.worst_subtree();
+        let size = self.recursive_cost + l_size + r_size;
+        let own = (size > self.unitary_cost).then_some((size, self.unitary_cost, self.offset(), self.cardinality()));
+
+        // Ratios are compared by cross-multiplication so that a zero `unitary_cost` is the worst ratio.
+        let wide = |x: u64| -> u128 { x.into() };
+        let worst = [own, l_worst, r_worst].into_iter().flatten().reduce(|a, b| {
+            if wide(b.0) * wide(a.1) > wide(a.0) * wide(b.1) {
+                b
+            } else {
+                a
+            }
+        });
+
+        (size, worst)
+    }
+
+    /// Recursive helper for `trim_if`.
+    fn trim_recursive<F: Fn(&Self) -> bool>(&mut self, predicate: &F) {
+        if predicate(self) {
+            self.children = None;
+            self.recursive_cost = 0;
+        } else if let Some(children) = self.children.as_mut() {
+            children.left.trim_recursive(predicate);
+            children.right.trim_recursive(predicate);
+        }
+    }
+}
+
+impl<U: Int> FromClusters<U> for SquishyBall<U> {
+    /// The costs are not estimated.
+    fn from_base_tree(root: UniBall<U>) -> Self {
+        Self::from_uni_ball(root)
+    }
 }
 
 impl<U: Int> Cluster<U> for SquishyBall<U> {
@@ -133,16 +372,24 @@ impl<U: Int> Cluster<U> for SquishyBall<U> {
         }
     }
 
-    fn partition<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>) -> Self
+    fn partition_with<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>, parallel: bool) -> Self
     where
         I: Instance,
         D: Dataset<I, U>,
         P: PartitionCriterion<U>,
     {
-        let uni_ball = self.uni_ball.partition(data, criteria, seed);
+        let uni_ball = self.uni_ball.partition_with(data, criteria, seed, parallel);
         Self::from_base_tree(uni_ball)
     }
 
+    fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, false);
+    }
+
+    fn par_recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, true);
+    }
+
     fn offset(&self) -> usize {
         self.uni_ball.offset()
     }
@@ -176,11 +423,11 @@ impl<U: Int> Cluster<U> for SquishyBall<U> {
     }
 
     fn polar_distance(&self) -> Option<U> {
-        self.uni_ball.polar_distance()
+        self.children.as_ref().map(|c| c.polar_distance)
     }
 
     fn arg_poles(&self) -> Option<[usize; 2]> {
-        self.uni_ball.arg_poles()
+        self.children.as_ref().map(|c| [c.arg_l, c.arg_r])
     }
 }
 
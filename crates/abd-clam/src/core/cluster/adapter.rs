@@ -0,0 +1,37 @@
+//! Adapting trees of any `Cluster` into trees of other `Cluster`s.
+
+use distances::Number;
+
+use super::{Cluster, UniBall};
+
+/// A `Cluster` whose trees can be built from the tree of any other `Cluster`.
+///
+/// Implementors only need to wrap a tree of `UniBall`s. The default
+/// `adapt_from` first copies the source tree into a tree of `UniBall`s, so
+/// the source may be any `Cluster`, not only a `UniBall`.
+pub trait FromClusters<U: Number>: Cluster<U> {
+    /// Creates a tree from a tree of `UniBall`s.
+    ///
+    /// Every `Cluster` in the new tree should keep the offset, cardinality,
+    /// center and radius of the corresponding `UniBall`.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root of the `UniBall` tree.
+    fn from_base_tree(root: UniBall<U>) -> Self;
+
+    /// Creates a tree with the same structure as the tree of `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: The root of the tree to adapt.
+    fn adapt_from<S: Cluster<U>>(source: &S) -> Self {
+        Self::from_base_tree(UniBall::from_cluster(source))
+    }
+}
+
+impl<U: Number> FromClusters<U> for UniBall<U> {
+    fn from_base_tree(root: Self) -> Self {
+        root
+    }
+}
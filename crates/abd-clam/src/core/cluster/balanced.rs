@@ -0,0 +1,333 @@
+//! A `BalancedBall` is a `Cluster` whose children have similar cardinalities.
+
+use core::{
+    cmp::Ordering,
+    fmt::{Display, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use distances::Number;
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{Cluster, Dataset, Instance, PartitionCriterion, Tree, UniBall};
+
+use super::{Children, FromClusters};
+
+/// Whether a split of `parent` instances into `left` and `right` children is
+/// balanced, i.e. the smaller child has at least a quarter of the instances.
+pub const fn is_balanced_split(parent: usize, left: usize, right: usize) -> bool {
+    let smaller = if left < right { left } else { right };
+    4 * smaller >= parent
+}
+
+/// A `BalancedBall` is a `Cluster` whose children have similar cardinalities.
+///
+/// Partitioning a `BalancedBall` splits its instances at the median of how much
+/// closer they are to one pole than to the other, so the two children differ in
+/// cardinality by at most one. A tree of `UniBall`s can also be adapted, with
+/// `from_uni_ball_tree`, by re-partitioning only the unbalanced subtrees.
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct BalancedBall<U: Number> {
+    /// The base `UniBall` of the `BalancedBall`.
+    uni_ball: UniBall<U>,
+    /// The children of the `BalancedBall`.
+    children: Option<Children<U, Self>>,
+}
+
+impl<I: Instance, U: Number, D: Dataset<I, U>> Tree<I, U, D, UniBall<U>> {
+    /// Adapts a partitioned tree of `UniBall`s into a tree of `BalancedBall`s.
+    ///
+    /// See `BalancedBall::from_uni_ball_tree` for more details.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: The criteria used to partition the unbalanced subtrees.
+    /// * `seed`: The seed used to partition the unbalanced subtrees.
+    #[must_use]
+    pub fn into_balanced<P: PartitionCriterion<U>>(
+        mut self,
+        criteria: &P,
+        seed: Option<u64>,
+    ) -> Tree<I, U, D, BalancedBall<U>> {
+        let root = BalancedBall::from_uni_ball_tree(self.root, &mut self.data, criteria, seed);
+        Tree::from_parts(self.data, root)
+    }
+
+    /// Re-partitions the lopsided subtrees of a partitioned tree of `UniBall`s.
+    ///
+    /// A subtree is lopsided if the subtree of one child of its root is more
+    /// than twice as tall as that of the other child, plus two. Every lopsided
+    /// subtree is re-partitioned with balanced splits, as in `BalancedBall`,
+    /// which recomputes the centers and radii of its clusters. Every other
+    /// subtree keeps the center, radius and poles of its root. The instances in
+    /// the dataset are reordered to match the new tree, so the tree still holds
+    /// the same instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: The criteria used to re-partition the lopsided subtrees.
+    /// * `seed`: The seed used to re-partition the lopsided subtrees.
+    #[must_use]
+    pub fn rebalance<P: PartitionCriterion<U>>(mut self, criteria: &P, seed: Option<u64>) -> Self {
+        let root = self
+            .root
+            .rebalance(&mut self.data, criteria, seed, UniBall::is_height_balanced);
+        Self::from_parts(self.data, root)
+    }
+}
+
+impl<U: Number> BalancedBall<U> {
+    /// Creates a `BalancedBall` tree from a partitioned `UniBall` tree.
+    ///
+    /// Every subtree whose root is balanced keeps the center, radius and poles
+    /// of that root. Every other subtree is re-partitioned with balanced splits.
+    /// The instances in the `data` are reordered to match the new tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `root`: The root of the `UniBall` tree.
+    /// * `data`: The dataset from which the `UniBall` tree was built.
+    /// * `criteria`: The criteria used to partition the unbalanced subtrees.
+    /// * `seed`: The seed used to partition the unbalanced subtrees.
+    pub fn from_uni_ball_tree<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
+        root: UniBall<U>,
+        data: &mut D,
+        criteria: &P,
+        seed: Option<u64>,
+    ) -> Self {
+        Self::from_uni_ball(root.rebalance(data, criteria, seed, UniBall::is_balanced))
+    }
+
+    /// Recursively creates a new `BalancedBall` tree.
+    fn from_uni_ball(mut uni_ball: UniBall<U>) -> Self {
+        match uni_ball.children {
+            Some(children) => {
+                uni_ball.children = None;
+                let left = Box::new(Self::from_uni_ball(*children.left));
+                let right = Box::new(Self::from_uni_ball(*children.right));
+                let children = Children {
+                    left,
+                    right,
+                    arg_l: children.arg_l,
+                    arg_r: children.arg_r,
+                    polar_distance: children.polar_distance,
+                };
+                Self {
+                    uni_ball,
+                    children: Some(children),
+                }
+            }
+            None => Self {
+                uni_ball,
+                children: None,
+            },
+        }
+    }
+
+    /// The base `UniBall` of the `BalancedBall`.
+    pub const fn uni_ball(&self) -> &UniBall<U> {
+        &self.uni_ball
+    }
+
+    /// Whether every split in the subtree of this `BalancedBall` is balanced,
+    /// i.e. the smaller child has at least a quarter of its parent's instances.
+    pub fn is_balanced(&self) -> bool {
+        self.children().map_or(true, |[left, right]| {
+            is_balanced_split(self.cardinality(), left.cardinality(), right.cardinality())
+                && left.is_balanced()
+                && right.is_balanced()
+        })
+    }
+}
+
+impl<U: Number> FromClusters<U> for BalancedBall<U> {
+    /// The splits of the `UniBall` tree are kept as they are, so the result is
+    /// only balanced if the source was. Use `from_uni_ball_tree` to
+    /// re-partition the unbalanced subtrees.
+    fn from_base_tree(root: UniBall<U>) -> Self {
+        Self::from_uni_ball(root)
+    }
+}
+
+impl<U: Number> Cluster<U> for BalancedBall<U> {
+    fn new_root<I: Instance, D: Dataset<I, U>>(data: &D, seed: Option<u64>) -> Self {
+        let uni_ball = UniBall::new_root(data, seed);
+        Self {
+            uni_ball,
+            children: None,
+        }
+    }
+
+    fn partition_with<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>, parallel: bool) -> Self
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+    {
+        let uni_ball = self.uni_ball.partition_tree(data, criteria, seed, parallel, true);
+        Self::from_uni_ball(uni_ball)
+    }
+
+    fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.uni_ball
+            .recompute_geometry_with(self.children.as_mut(), data, false);
+    }
+
+    fn par_recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.uni_ball
+            .recompute_geometry_with(self.children.as_mut(), data, true);
+    }
+
+    fn offset(&self) -> usize {
+        self.uni_ball.offset()
+    }
+
+    fn cardinality(&self) -> usize {
+        self.uni_ball.cardinality()
+    }
+
+    fn depth(&self) -> usize {
+        self.uni_ball.depth()
+    }
+
+    fn arg_center(&self) -> usize {
+        self.uni_ball.arg_center()
+    }
+
+    fn radius(&self) -> U {
+        self.uni_ball.radius()
+    }
+
+    fn arg_radial(&self) -> usize {
+        self.uni_ball.arg_radial()
+    }
+
+    fn lfd(&self) -> f64 {
+        self.uni_ball.lfd()
+    }
+
+    fn children(&self) -> Option<[&Self; 2]> {
+        self.children.as_ref().map(|c| [c.left.as_ref(), c.right.as_ref()])
+    }
+
+    fn polar_distance(&self) -> Option<U> {
+        self.children.as_ref().map(|c| c.polar_distance)
+    }
+
+    fn arg_poles(&self) -> Option<[usize; 2]> {
+        self.children.as_ref().map(|c| [c.arg_l, c.arg_r])
+    }
+}
+
+impl<U: Number> PartialEq for BalancedBall<U> {
+    fn eq(&self, other: &Self) -> bool {
+        self.uni_ball.eq(&other.uni_ball)
+    }
+}
+
+impl<U: Number> Eq for BalancedBall<U> {}
+
+impl<U: Number> PartialOrd for BalancedBall<U> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<U: Number> Ord for BalancedBall<U> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.uni_ball.cmp(&other.uni_ball)
+    }
+}
+
+impl<U: Number> Hash for BalancedBall<U> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uni_ball.hash(state);
+    }
+}
+
+impl<U: Number> Display for BalancedBall<U> {
+    fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
+        write!(f, "{}", self.uni_ball)
+    }
+}
+
+impl<U: Number> Serialize for BalancedBall<U> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("BalancedBall", 2)?;
+        state.serialize_field("uni_ball", &self.uni_ball)?;
+        state.serialize_field("children", &self.children)?;
+        state.end()
+    }
+}
+
+impl<'de, U: Number> Deserialize<'de> for BalancedBall<U> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The fields in the `BalancedBall` struct.
+        #[derive(Deserialize)]
+        #[serde(field_identifier, rename_all = "lowercase")]
+        enum Field {
+            /// The base `UniBall` of the `BalancedBall`.
+            UniBall,
+            /// The children of the `BalancedBall`.
+            Children,
+        }
+
+        /// The `Visitor` for the `BalancedBall` struct.
+        struct BalancedBallVisitor<U: Number>(PhantomData<U>);
+
+        impl<'de, U: Number> Visitor<'de> for BalancedBallVisitor<U> {
+            type Value = BalancedBall<U>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+                formatter.write_str("struct BalancedBall")
+            }
+
+            fn visit_seq<V: SeqAccess<'de>>(self, mut seq: V) -> Result<Self::Value, V::Error> {
+                let uni_ball = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))?;
+                let children = seq
+                    .next_element()?
+                    .ok_or_else(|| serde::de::Error::invalid_length(1, &self))?;
+                Ok(BalancedBall { uni_ball, children })
+            }
+
+            fn visit_map<V: MapAccess<'de>>(self, mut map: V) -> Result<Self::Value, V::Error> {
+                let mut uni_ball = None;
+                let mut children = None;
+
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::UniBall => {
+                            if uni_ball.is_some() {
+                                return Err(serde::de::Error::duplicate_field("uni_ball"));
+                            }
+                            uni_ball = Some(map.next_value()?);
+                        }
+                        Field::Children => {
+                            if children.is_some() {
+                                return Err(serde::de::Error::duplicate_field("children"));
+                            }
+                            children = Some(map.next_value()?);
+                        }
+                    }
+                }
+
+                let uni_ball = uni_ball.ok_or_else(|| serde::de::Error::missing_field("uni_ball"))?;
+                let children = children.ok_or_else(|| serde::de::Error::missing_field("children"))?;
+
+                Ok(BalancedBall { uni_ball, children })
+            }
+        }
+
+        /// The `Field` names.
+        const FIELDS: &[&str] = &["uni_ball", "children"];
+        deserializer.deserialize_struct("BalancedBall", FIELDS, BalancedBallVisitor(PhantomData))
+    }
+}
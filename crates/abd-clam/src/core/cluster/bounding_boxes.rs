@@ -0,0 +1,133 @@
+//! Axis-aligned bounding boxes of `Cluster`s of vectors.
+
+use std::collections::HashMap;
+
+use distances::Number;
+
+use crate::{Dataset, Instance};
+
+use super::Cluster;
+
+/// The minimum and maximum of each coordinate, keyed by the `offset` and
+/// `cardinality` of a `Cluster`.
+type Boxes<F> = HashMap<(usize, usize), (Vec<F>, Vec<F>)>;
+
+/// The axis-aligned bounding boxes of the `Cluster`s in a tree of vectors.
+///
+/// A box is the minimum and maximum of each coordinate over the instances in
+/// a `Cluster`. The distance from a query to a box is a lower bound on the
+/// Euclidean distance from the query to any instance in the `Cluster`, and is
+/// much cheaper to compute than the distance to the center when the metric is
+/// expensive. It is also a lower bound for any Minkowski distance with `p <=
+/// 2`, e.g. the Manhattan distance, but not for the Chebyshev distance.
+///
+/// # Type Parameters
+///
+/// - `F`: The type of the coordinates of the vectors.
+#[derive(Debug, Clone)]
+pub struct BoundingBoxes<F: Number> {
+    /// The boxes of the `Cluster`s.
+    boxes: Boxes<F>,
+}
+
+impl<F: Number> BoundingBoxes<F> {
+    /// Computes the bounding boxes of every `Cluster` in a tree.
+    ///
+    /// The box of a leaf is computed from its instances, and the box of a
+    /// parent is computed from the boxes of its children.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `root`: The root of the tree.
+    pub fn new<I, U, D, C>(data: &D, root: &C) -> Self
+    where
+        I: Instance + AsRef<[F]>,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        let mut boxes = HashMap::new();
+        Self::add_boxes(data, root, &mut boxes);
+        Self { boxes }
+    }
+
+    /// Adds the boxes for the subtree of `c` to `boxes` and returns a copy of
+    /// the box of `c`.
+    fn add_boxes<I, U, D, C>(data: &D, c: &C, boxes: &mut Boxes<F>) -> (Vec<F>, Vec<F>)
+    where
+        I: Instance + AsRef<[F]>,
+        U: Number,
+        D: Dataset<I, U>,
+        C: Cluster<U>,
+    {
+        let (lower, upper) = if let Some([left, right]) = c.children() {
+            let (mut lower, mut upper) = Self::add_boxes(data, left, boxes);
+            let (r_lower, r_upper) = Self::add_boxes(data, right, boxes);
+            for (l, r) in lower.iter_mut().zip(r_lower) {
+                if r < *l {
+                    *l = r;
+                }
+            }
+            for (u, r) in upper.iter_mut().zip(r_upper) {
+                if r > *u {
+                    *u = r;
+                }
+            }
+            (lower, upper)
+        } else {
+            let mut instances = c.indices().map(|i| data[i].as_ref());
+            let first = instances
+                .next()
+                .unwrap_or_else(|| unreachable!("A cluster has at least one instance."));
+            let (mut lower, mut upper) = (first.to_vec(), first.to_vec());
+            for instance in instances {
+                for ((l, u), &x) in lower.iter_mut().zip(upper.iter_mut()).zip(instance) {
+                    if x < *l {
+                        *l = x;
+                    }
+                    if x > *u {
+                        *u = x;
+                    }
+                }
+            }
+            (lower, upper)
+        };
+
+        boxes.insert((c.offset(), c.cardinality()), (lower.clone(), upper.clone()));
+        (lower, upper)
+    }
+
+    /// Returns the minimum and maximum of each coordinate over the instances
+    /// in the `Cluster`, or `None` if the `Cluster` is not in the tree for
+    /// which the boxes were computed.
+    pub fn aabb<U: Number, C: Cluster<U>>(&self, c: &C) -> Option<(&[F], &[F])> {
+        self.boxes
+            .get(&(c.offset(), c.cardinality()))
+            .map(|(lower, upper)| (lower.as_slice(), upper.as_slice()))
+    }
+
+    /// Returns the Euclidean distance from the `query` to the box of the
+    /// `Cluster`, or `None` if the `Cluster` has no box.
+    ///
+    /// This is zero if the `query` is inside the box.
+    pub fn distance_to_box<U: Number, C: Cluster<U>>(&self, c: &C, query: &[F]) -> Option<f64> {
+        self.aabb(c).map(|(lower, upper)| {
+            query
+                .iter()
+                .zip(lower.iter().zip(upper))
+                .map(|(&x, (&l, &u))| {
+                    let gap = if x < l {
+                        l - x
+                    } else if x > u {
+                        x - u
+                    } else {
+                        F::zero()
+                    };
+                    gap.as_f64().powi(2)
+                })
+                .sum::<f64>()
+                .sqrt()
+        })
+    }
+}
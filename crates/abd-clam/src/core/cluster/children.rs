@@ -12,7 +12,7 @@ use serde::{
     Deserialize, Deserializer, Serialize,
 };
 
-use crate::Cluster;
+use crate::{Cluster, Dataset, Instance};
 
 /// The `Children` of a `Cluster`.
 #[derive(Debug)]
@@ -31,6 +31,26 @@ pub struct Children<U: Number, C: Cluster<U>> {
     pub polar_distance: U,
 }
 
+impl<U: Number, C: Cluster<U>> Children<U, C> {
+    /// Recomputes the geometry of the subtrees of both children, and the
+    /// polar distance, from the current state of the dataset.
+    ///
+    /// See `Cluster::recompute_geometry` for more details.
+    pub(crate) fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, parallel: bool) {
+        let (left, right) = (&mut self.left, &mut self.right);
+        if parallel {
+            rayon::join(
+                || left.par_recompute_geometry(data),
+                || right.par_recompute_geometry(data),
+            );
+        } else {
+            left.recompute_geometry(data);
+            right.recompute_geometry(data);
+        }
+        self.polar_distance = data.one_to_one(self.arg_l, self.arg_r);
+    }
+}
+
 impl<U: Number, C: Cluster<U>> Display for Children<U, C> {
     fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
         write!(f, "{} x {}", self.left.name(), self.right.name())
@@ -8,6 +8,49 @@ use crate::{Cluster, UniBall};
 pub trait PartitionCriterion<U: Number>: Send + Sync {
     /// Check whether a `Cluster` meets the criterion for partitioning.
     fn check(&self, c: &UniBall<U>) -> bool;
+
+    /// The strategy used to choose the centers of the `Cluster`s created
+    /// while partitioning.
+    ///
+    /// If `None`, the center is the exact medoid for `Cluster`s with fewer
+    /// than 100 instances, and the medoid of a sample of the square root of
+    /// the cardinality for larger `Cluster`s.
+    fn center_strategy(&self) -> Option<CenterStrategy> {
+        None
+    }
+
+    /// Check whether the candidate children of a `Cluster` should be kept.
+    ///
+    /// This is called after `check` passes and the children have been built,
+    /// but before they are partitioned further. If it returns `false`, the
+    /// children are discarded and the `parent` remains a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent`: the `Cluster` being partitioned.
+    /// * `left`: the candidate left child.
+    /// * `right`: the candidate right child.
+    fn check_children(&self, parent: &UniBall<U>, left: &UniBall<U>, right: &UniBall<U>) -> bool {
+        let _ = (parent, left, right);
+        true
+    }
+}
+
+/// How the center of a `Cluster` is chosen when it is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CenterStrategy {
+    /// The medoid of all instances. This needs the distances between every
+    /// pair of instances.
+    ExactMedoid,
+    /// The medoid of a random sample of instances. If the `Cluster` has at
+    /// most `sample_size` instances, this is the exact medoid.
+    SampledMedoid {
+        /// The number of instances to sample. A `sample_size` of zero is
+        /// treated as one.
+        sample_size: usize,
+    },
+    /// A random instance.
+    RandomPoint,
 }
 
 /// The maximum depth of a `Cluster` beyond which it may not be partitioned.
@@ -30,6 +73,25 @@ impl<U: Number> PartitionCriterion<U> for MinCardinality {
     }
 }
 
+/// Stops partitioning once the local fractal dimension (LFD) stabilizes.
+///
+/// A split is rejected when the LFD of both candidate children differs from
+/// that of the parent by less than the given epsilon, since further splits
+/// are unlikely to reveal new structure in the data.
+#[derive(Debug, Clone)]
+pub struct LfdPlateau(f64);
+
+impl<U: Number> PartitionCriterion<U> for LfdPlateau {
+    fn check(&self, _: &UniBall<U>) -> bool {
+        true
+    }
+
+    fn check_children(&self, parent: &UniBall<U>, left: &UniBall<U>, right: &UniBall<U>) -> bool {
+        let is_flat = |c: &UniBall<U>| (c.lfd() - parent.lfd()).abs() < self.0;
+        !(is_flat(left) && is_flat(right))
+    }
+}
+
 /// A collection of criteria used to decide when to partition a `Cluster`.
 #[allow(clippy::module_name_repetitions)]
 pub struct PartitionCriteria<U: Number> {
@@ -38,17 +100,32 @@ pub struct PartitionCriteria<U: Number> {
     /// Whether all criteria must be met for a `Cluster` to be partitioned or if any one criterion
     /// is sufficient.
     check_all: bool,
+    /// The strategy used to choose the centers of `Cluster`s.
+    center_strategy: Option<CenterStrategy>,
+    /// The cardinality at or below which a `Cluster` is not partitioned.
+    max_leaf_size: Option<usize>,
 }
 
 impl<U: Number> PartitionCriterion<U> for PartitionCriteria<U> {
     fn check(&self, cluster: &UniBall<U>) -> bool {
         !cluster.is_singleton()
+            && self.max_leaf_size.map_or(true, |size| cluster.cardinality() > size)
             && if self.check_all {
                 self.criteria.iter().all(|c| c.check(cluster))
             } else {
                 self.criteria.iter().any(|c| c.check(cluster))
             }
     }
+
+    fn center_strategy(&self) -> Option<CenterStrategy> {
+        self.center_strategy
+    }
+
+    /// Every criterion must accept the children, regardless of `check_all`,
+    /// because most criteria do not inspect the children at all.
+    fn check_children(&self, parent: &UniBall<U>, left: &UniBall<U>, right: &UniBall<U>) -> bool {
+        self.criteria.iter().all(|c| c.check_children(parent, left, right))
+    }
 }
 
 impl<U: Number> Default for PartitionCriteria<U> {
@@ -69,6 +146,8 @@ impl<U: Number> PartitionCriteria<U> {
         Self {
             criteria: Vec::new(),
             check_all,
+            center_strategy: None,
+            max_leaf_size: None,
         }
     }
 
@@ -94,6 +173,49 @@ impl<U: Number> PartitionCriteria<U> {
         self
     }
 
+    /// Add the `LfdPlateau` criterion to the collection of criteria.
+    ///
+    /// # Arguments
+    ///
+    /// * `epsilon`: a split is rejected when the LFD of both children is
+    ///   within `epsilon` of the LFD of the parent.
+    ///
+    /// This criterion accepts every `Cluster` in `check`, so it should be
+    /// combined with others using `check_all = true`.
+    #[must_use]
+    pub fn stop_on_lfd_plateau(mut self, epsilon: f64) -> Self {
+        self.criteria.push(Box::new(LfdPlateau(epsilon)));
+        self
+    }
+
+    /// Set the target size of the leaves.
+    ///
+    /// A `Cluster` is not partitioned once its cardinality is at most
+    /// `max_leaf_size`. This is combined with the other criteria, which may
+    /// stop partitioning earlier, e.g. at a `MaxDepth`, and leave larger
+    /// leaves. A `Cluster` whose instances are all identical cannot be
+    /// partitioned either.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_leaf_size`: the maximum cardinality of a leaf.
+    #[must_use]
+    pub const fn with_max_leaf_size(mut self, max_leaf_size: usize) -> Self {
+        self.max_leaf_size = Some(max_leaf_size);
+        self
+    }
+
+    /// Set the strategy used to choose the centers of `Cluster`s.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy`: the strategy used to choose the centers.
+    #[must_use]
+    pub const fn with_center_strategy(mut self, strategy: CenterStrategy) -> Self {
+        self.center_strategy = Some(strategy);
+        self
+    }
+
     /// Add a custom criterion to the collection of criteria.
     ///
     /// # Arguments
@@ -5,12 +5,20 @@
 //! `PartitionCriterion` for `MaxDepth` and `MinCardinality` which are used to
 //! determine when to stop partitioning the tree.
 
+mod adapter;
+mod balanced;
+mod bounding_boxes;
 mod children;
 mod criteria;
+mod quality;
 mod uni;
 
+pub use adapter::FromClusters;
+pub use balanced::BalancedBall;
+pub use bounding_boxes::BoundingBoxes;
 pub use children::Children;
-pub use criteria::{MaxDepth, MinCardinality, PartitionCriteria, PartitionCriterion};
+pub use criteria::{CenterStrategy, LfdPlateau, MaxDepth, MinCardinality, PartitionCriteria, PartitionCriterion};
+pub use quality::TreeQuality;
 #[allow(clippy::module_name_repetitions)]
 pub use uni::UniBall;
 
@@ -20,12 +28,14 @@ use core::{
     ops::Range,
 };
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufReader, BufWriter},
     path::Path,
 };
 
 use distances::Number;
+use rand::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{Dataset, Instance};
@@ -39,13 +49,84 @@ pub trait Cluster<U: Number>:
     fn new_root<I: Instance, D: Dataset<I, U>>(data: &D, seed: Option<u64>) -> Self;
 
     /// Recursively partitions the `Cluster` until the `PartitionCriteria` are met.
+    ///
+    /// If `parallel` is `true`, the children of each `Cluster` are partitioned
+    /// in parallel. Their seeds are derived from the `seed` of their parent,
+    /// so, given the same `seed`, the tree depends neither on `parallel` nor
+    /// on the number of threads.
     #[must_use]
-    fn partition<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>) -> Self
+    fn partition_with<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>, parallel: bool) -> Self
     where
         I: Instance,
         D: Dataset<I, U>,
         P: PartitionCriterion<U>;
 
+    /// Recursively partitions the `Cluster` until the `PartitionCriteria` are met.
+    #[must_use]
+    fn partition<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>) -> Self
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+    {
+        self.partition_with(data, criteria, seed, false)
+    }
+
+    /// Parallelized version of `partition`.
+    ///
+    /// Given the same `seed`, this produces the same tree as `partition`.
+    #[must_use]
+    fn par_partition<I, D, P>(self, data: &mut D, criteria: &P, seed: Option<u64>) -> Self
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+    {
+        self.partition_with(data, criteria, seed, true)
+    }
+
+    /// Recomputes the center, radial instance, radius and local fractal
+    /// dimension of every `Cluster` in the subtree, and the polar distances of
+    /// their children, from the current state of the dataset.
+    ///
+    /// The cached geometry becomes stale if the instances in the dataset are
+    /// edited after the tree is built, e.g. for a deserialized tree whose
+    /// dataset has since changed. Search relies on the cached geometry, so
+    /// this should be called after any such edit. The subtree is updated
+    /// bottom-up, and the structure of the tree, i.e. the instances in each
+    /// `Cluster` and the poles of their children, is left unchanged.
+    ///
+    /// The centers are recomputed with `Dataset::arg_medoid`, so they may
+    /// differ from the centers chosen when the tree was partitioned. Values
+    /// derived from the geometry are updated as well: the ratios of a `Vertex`
+    /// are recomputed when this is called on the root, and the costs of
+    /// compression of a `SquishyBall` are reset and must be estimated again.
+    ///
+    /// Since the poles are not re-chosen, the instances of a child need no
+    /// longer be closer to its own pole than to the other pole. The searches
+    /// that prune with the poles, i.e. `rnn::Algorithm::Clustered` and the
+    /// `knn::Algorithm`s built on it, `RepeatedRnn` and `KnnByKthRadius`, may
+    /// then miss hits. The other `knn::Algorithm`s only use the centers and
+    /// radii, and remain exact.
+    ///
+    /// # Panics
+    ///
+    /// The default implementation panics, because the geometry cannot be set
+    /// through this trait. `UniBall`, `BalancedBall`, `Vertex` and
+    /// `SquishyBall` override it.
+    fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, _data: &D) {
+        unimplemented!("This Cluster does not support recomputing its geometry.")
+    }
+
+    /// Parallelized version of `recompute_geometry`.
+    ///
+    /// # Panics
+    ///
+    /// The default implementation panics, as with `recompute_geometry`.
+    fn par_recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, _data: &D) {
+        unimplemented!("This Cluster does not support recomputing its geometry.")
+    }
+
     /// The offset of the indices of the `Cluster`'s instances in the dataset.
     fn offset(&self) -> usize;
 
@@ -107,9 +188,7 @@ pub trait Cluster<U: Number>:
 
     /// Whether the `Cluster` is an ancestor of another `Cluster`.
     fn is_ancestor_of(&self, other: &Self) -> bool {
-        other.depth() > self.depth()
-            && self.indices().contains(&other.offset())
-            && other.cardinality() < self.cardinality()
+        other.depth() > self.depth() && self.contains_index(other.offset()) && other.cardinality() < self.cardinality()
     }
 
     /// Whether the `Cluster` is a descendant of another `Cluster`.
@@ -117,6 +196,61 @@ pub trait Cluster<U: Number>:
         other.is_ancestor_of(self)
     }
 
+    /// The path from this `Cluster` down to the `target` `Cluster`.
+    ///
+    /// # Arguments
+    ///
+    /// * `target`: The `Cluster` at the end of the path.
+    ///
+    /// # Returns
+    ///
+    /// The `Cluster`s on the path, starting with this `Cluster` and ending with
+    /// the `target`, or `None` if the `target` is not in the subtree of this
+    /// `Cluster`.
+    fn path_to<'a>(&'a self, target: &Self) -> Option<Vec<&'a Self>> {
+        if self != target && !target.is_descendant_of(self) {
+            return None;
+        }
+
+        let mut path = vec![self];
+        let mut c = self;
+        while c != target {
+            let [left, right] = c.children()?;
+            c = if left == target || target.is_descendant_of(left) {
+                left
+            } else {
+                right
+            };
+            path.push(c);
+        }
+        Some(path)
+    }
+
+    /// The lowest common ancestor of two `Cluster`s in the subtree of this
+    /// `Cluster`.
+    ///
+    /// A `Cluster` is considered to be its own ancestor, so if `a` is an
+    /// ancestor of `b`, then `a` is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`: A `Cluster` in the subtree.
+    /// * `b`: Another `Cluster` in the subtree.
+    ///
+    /// # Returns
+    ///
+    /// The deepest `Cluster` whose subtree contains both `a` and `b`, or `None`
+    /// if either of them is not in the subtree of this `Cluster`.
+    fn lowest_common_ancestor<'a>(&'a self, a: &Self, b: &Self) -> Option<&'a Self> {
+        let (path_a, path_b) = (self.path_to(a)?, self.path_to(b)?);
+        path_a
+            .into_iter()
+            .zip(path_b)
+            .take_while(|(x, y)| x == y)
+            .last()
+            .map(|(x, _)| x)
+    }
+
     /// Whether the `Cluster` is a leaf node in the tree.
     fn is_leaf(&self) -> bool {
         self.children().is_none()
@@ -132,6 +266,126 @@ pub trait Cluster<U: Number>:
         self.offset()..(self.offset() + self.cardinality())
     }
 
+    /// Whether the instance at `index` in the reordered dataset is in the `Cluster`.
+    ///
+    /// This can be used to filter search results down to those in the
+    /// subtree of this `Cluster`.
+    fn contains_index(&self, index: usize) -> bool {
+        self.indices().contains(&index)
+    }
+
+    /// Finds the leaf in the subtree of this `Cluster` that contains the
+    /// instance at a given index.
+    ///
+    /// This descends into the child that contains the `index`, so it takes
+    /// time proportional to the depth of the leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The index of the instance in the (permuted) dataset.
+    ///
+    /// # Returns
+    ///
+    /// The leaf containing the instance, or `None` if the `index` is not in
+    /// this `Cluster`.
+    fn leaf_containing(&self, index: usize) -> Option<&Self> {
+        if !self.contains_index(index) {
+            return None;
+        }
+        let mut c = self;
+        while let Some([left, right]) = c.children() {
+            c = if left.contains_index(index) { left } else { right };
+        }
+        Some(c)
+    }
+
+    /// A uniform random sample of `n` of the indices in the `Cluster`.
+    ///
+    /// The sample is drawn by reservoir sampling over `indices`, so only `n`
+    /// indices are held at a time. If the `Cluster` has no more than `n`
+    /// instances, all of its indices are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `n`: The number of indices to sample.
+    /// * `seed`: The seed for the random number generator.
+    fn sample_indices(&self, n: usize, seed: u64) -> Vec<usize> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut indices = self.indices();
+        let mut reservoir = indices.by_ref().take(n).collect::<Vec<_>>();
+        for (i, index) in indices.enumerate() {
+            let j = rng.gen_range(0..=(n + i));
+            if j < n {
+                reservoir[j] = index;
+            }
+        }
+        reservoir
+    }
+
+    /// The Shannon entropy, in bits, of the labels of the instances in the
+    /// `Cluster`.
+    ///
+    /// This is zero if all instances have the same label, i.e. the `Cluster`
+    /// is pure, and grows as the labels become more mixed.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels`: The label of every instance in the dataset, in the order of
+    ///   the dataset after it was reordered by building the tree.
+    fn label_entropy(&self, labels: &[usize]) -> f64 {
+        let counts = label_counts(&labels[self.indices()]);
+        let n = self.cardinality().as_f64();
+        counts
+            .values()
+            .map(|&c| {
+                let p = c.as_f64() / n;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    /// The most common label of the instances in the `Cluster`.
+    ///
+    /// Ties are broken in favor of the smallest label.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels`: The label of every instance in the dataset, in the order of
+    ///   the dataset after it was reordered by building the tree.
+    ///
+    /// # Returns
+    ///
+    /// The majority label, or `None` if the `Cluster` is empty.
+    fn majority_label(&self, labels: &[usize]) -> Option<usize> {
+        label_counts(&labels[self.indices()])
+            .into_iter()
+            .max_by(|&(l1, c1), &(l2, c2)| c1.cmp(&c2).then_with(|| l2.cmp(&l1)))
+            .map(|(label, _)| label)
+    }
+
+    /// The membership of the `Cluster` as a bitset over the whole dataset.
+    ///
+    /// Bitsets from different `Cluster`s, or from search results, can be
+    /// combined with fast bitwise set operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `total`: The number of bits, usually the cardinality of the dataset.
+    ///
+    /// # Panics
+    ///
+    /// * If any of the `indices` of the `Cluster` is not less than `total`.
+    ///
+    /// # Returns
+    ///
+    /// A bitset of length `total` in which exactly the `indices` are set.
+    #[cfg(feature = "fixedbitset")]
+    fn membership_bitset(&self, total: usize) -> fixedbitset::FixedBitSet {
+        let mut bitset = fixedbitset::FixedBitSet::with_capacity(total);
+        bitset.insert_range(self.indices());
+        bitset
+    }
+
     /// The subtree of the `Cluster`.
     fn subtree(&self) -> Vec<&Self> {
         let subtree = vec![self];
@@ -144,6 +398,111 @@ pub trait Cluster<U: Number>:
         }
     }
 
+    /// Parallelized version of `subtree`.
+    ///
+    /// The subtrees of the two children are collected in parallel and then
+    /// concatenated after this `Cluster`, so the result is in the same
+    /// depth-first pre-order as `subtree`.
+    fn par_subtree(&self) -> Vec<&Self> {
+        match self.children() {
+            Some([left, right]) => {
+                let (left, right) = rayon::join(|| left.par_subtree(), || right.par_subtree());
+                let mut subtree = Vec::with_capacity(1 + left.len() + right.len());
+                subtree.push(self);
+                subtree.extend(left);
+                subtree.extend(right);
+                subtree
+            }
+            None => vec![self],
+        }
+    }
+
+    /// Lazily iterates over the subtree of the `Cluster`, in the same order as
+    /// `subtree`.
+    ///
+    /// This is a depth-first traversal with an explicit stack, which only
+    /// holds the right siblings along the current path, so the subtree is not
+    /// collected up front.
+    fn subtree_iter(&self) -> impl Iterator<Item = &Self> {
+        let mut stack = vec![self];
+        core::iter::from_fn(move || {
+            let c = stack.pop()?;
+            if let Some([left, right]) = c.children() {
+                stack.push(right);
+                stack.push(left);
+            }
+            Some(c)
+        })
+    }
+
+    /// Lazily iterates over the `Cluster`s in the subtree for which the
+    /// predicate `p` returns `true`, in the same order as `subtree`.
+    ///
+    /// # Arguments
+    ///
+    /// * `p`: The predicate to filter the `Cluster`s by.
+    fn subtree_filter<P: Fn(&Self) -> bool>(&self, p: P) -> impl Iterator<Item = &Self> {
+        self.subtree_iter().filter(move |c| p(c))
+    }
+
+    /// The `Cluster`s in the subtree of the `Cluster` at a given depth.
+    ///
+    /// If a branch ends above the given depth, its leaf is returned instead.
+    /// The returned `Cluster`s are in depth-first order, and their `indices`
+    /// partition the `indices` of this `Cluster`.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth`: The depth in the tree, as in `Cluster::depth`, of the
+    ///   `Cluster`s to return. If it is not below this `Cluster`, only this
+    ///   `Cluster` is returned.
+    fn clusters_at_depth(&self, depth: usize) -> Vec<&Self> {
+        match self.children() {
+            Some(children) if self.depth() < depth => children.iter().flat_map(|c| c.clusters_at_depth(depth)).collect(),
+            _ => vec![self],
+        }
+    }
+
+    /// The number of `Cluster`s in the subtree of the `Cluster`.
+    ///
+    /// This is the same as `subtree().len()` but does not allocate.
+    fn subtree_cardinality(&self) -> usize {
+        1 + self.children().map_or(0, |[left, right]| {
+            left.subtree_cardinality() + right.subtree_cardinality()
+        })
+    }
+
+    /// An estimate of the memory, in bytes, used by the subtree of the `Cluster`.
+    ///
+    /// This counts the size of the `Cluster` struct for each node in the
+    /// subtree, but not any heap allocations owned by the nodes beyond their
+    /// children.
+    fn estimated_size_bytes(&self) -> usize {
+        self.subtree_cardinality() * core::mem::size_of::<Self>()
+    }
+
+    /// The ratio of the cardinality of the smaller child to that of the larger
+    /// child.
+    ///
+    /// This is `1.0` for a perfectly balanced split, and approaches zero as
+    /// the split becomes more lopsided.
+    ///
+    /// # Returns
+    ///
+    /// The balance factor, or `None` if the `Cluster` is a leaf.
+    fn balance_factor(&self) -> Option<f64> {
+        self.children().map(|[left, right]| {
+            let (l, r) = (left.cardinality().as_f64(), right.cardinality().as_f64());
+            l.min(r) / l.max(r)
+        })
+    }
+
+    /// Structural summary statistics of the subtree of the `Cluster`, for
+    /// comparing tree-building strategies.
+    fn quality_report(&self) -> TreeQuality {
+        TreeQuality::new(self)
+    }
+
     /// The maximum depth of and leaf in the subtree of the `Cluster`.
     ///
     /// If this `Cluster` is a leaf, the maximum depth is the depth of the `Cluster`.
@@ -166,9 +525,48 @@ pub trait Cluster<U: Number>:
         data.one_to_one(self.arg_center(), other.arg_center())
     }
 
+    /// Greedily descends to the leaf into which the `query` would fall.
+    ///
+    /// At each level, this descends into the child whose `center` is closest
+    /// to the `query`. This is fast but approximate; the returned leaf need
+    /// not contain the nearest neighbor of the `query`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `query`: The query instance.
+    ///
+    /// # Returns
+    ///
+    /// The leaf `Cluster` at the end of the greedy descent.
+    fn nearest_leaf<I: Instance, D: Dataset<I, U>>(&self, data: &D, query: &I) -> &Self {
+        match self.children() {
+            Some([left, right]) => {
+                let l = left.distance_to_instance(data, query);
+                let r = right.distance_to_instance(data, query);
+                let child = if l <= r { left } else { right };
+                child.nearest_leaf(data, query)
+            }
+            None => self,
+        }
+    }
+
     /// Assuming the `Cluster` overlaps with the query ball, we return only
-    /// those children that also overlap with the query ball.
+    /// those children that may also overlap with the query ball.
+    ///
+    /// Only the distances from the query to the two poles are computed. A
+    /// child is discarded if the query is far enough on the side of the other
+    /// pole, or if the query is too far from the child's own pole for the
+    /// child to overlap the query ball.
     fn overlapping_children<I: Instance, D: Dataset<I, U>>(&self, data: &D, query: &I, radius: U) -> Vec<&Self> {
+        self.overlapping_children_with(|index| data.query_to_one(query, index), radius)
+    }
+
+    /// Same as `overlapping_children`, but with the distance from the query to
+    /// the instance at a given index computed by `distance_to`.
+    ///
+    /// This allows the query to be of a different type than the instances.
+    fn overlapping_children_with<F: Fn(usize) -> U>(&self, distance_to: F, radius: U) -> Vec<&Self> {
         if self.is_leaf() {
             Vec::new()
         } else {
@@ -182,22 +580,60 @@ pub trait Cluster<U: Number>:
                 .polar_distance()
                 .unwrap_or_else(|| unreachable!("We checked that the cluster is not a leaf."));
 
-            let ql = data.query_to_one(query, arg_l);
-            let qr = data.query_to_one(query, arg_r);
+            let ql = distance_to(arg_l);
+            let qr = distance_to(arg_r);
 
             let swap = ql < qr;
-            let (ql, qr) = if swap { (qr, ql) } else { (ql, qr) };
+            let (far, near) = if swap { (qr, ql) } else { (ql, qr) };
 
-            if (ql + qr) * (ql - qr) <= U::from(2) * polar_distance * radius {
-                vec![left, right]
+            let children = if (far + near) * (far - near) <= U::from(2) * polar_distance * radius {
+                vec![(left, ql), (right, qr)]
             } else if swap {
-                vec![left]
+                vec![(left, ql)]
             } else {
-                vec![right]
-            }
+                vec![(right, qr)]
+            };
+
+            // Each pole is an instance of its child, so by the triangle
+            // inequality, the distance from the query to the center of a child
+            // is at least the distance to its pole minus its radius. A child
+            // for which that is more than its radius plus `radius` cannot
+            // overlap the query ball.
+            children
+                .into_iter()
+                .filter(|&(c, d)| d <= c.radius() + c.radius() + radius)
+                .map(|(c, _)| c)
+                .collect()
         }
     }
 
+    /// Exports the subtree of the `Cluster` as a string in the Newick format.
+    ///
+    /// Each leaf is labeled with the label of its center, and each internal
+    /// node has its cardinality as a comment. The branch length of every
+    /// `Cluster` below this one is its radius.
+    ///
+    /// Labels are quoted, as per the Newick format, if they contain any
+    /// whitespace or any of the characters `()[]':;,`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `label`: Returns the label of the instance at the given index in
+    ///   `data`, e.g. its metadata.
+    fn to_newick<I, D, F, L>(&self, data: &D, label: F) -> String
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        F: Fn(&D, usize) -> L,
+        L: Display,
+    {
+        let mut newick = String::new();
+        write_newick(self, data, &label, &mut newick);
+        newick.push(';');
+        newick
+    }
+
     /// Saves a `Cluster` to a given location.
     ///
     /// # Arguments
@@ -233,3 +669,47 @@ pub trait Cluster<U: Number>:
         bincode::deserialize_from(reader).map_err(|e| e.to_string())
     }
 }
+
+/// Counts the occurrences of each label.
+fn label_counts(labels: &[usize]) -> HashMap<usize, usize> {
+    let mut counts = HashMap::new();
+    for &label in labels {
+        *counts.entry(label).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Writes the subtree of `c` in the Newick format, without the branch length
+/// of `c` or the terminating semicolon.
+fn write_newick<I, U, D, F, L, C>(c: &C, data: &D, label: &F, newick: &mut String)
+where
+    I: Instance,
+    U: Number,
+    D: Dataset<I, U>,
+    F: Fn(&D, usize) -> L,
+    L: Display,
+    C: Cluster<U>,
+{
+    if let Some([left, right]) = c.children() {
+        newick.push('(');
+        write_newick(left, data, label, newick);
+        newick.push(':');
+        newick.push_str(&left.radius().to_string());
+        newick.push(',');
+        write_newick(right, data, label, newick);
+        newick.push(':');
+        newick.push_str(&right.radius().to_string());
+        newick.push_str(")[");
+        newick.push_str(&c.cardinality().to_string());
+        newick.push(']');
+    } else {
+        let label = label(data, c.arg_center()).to_string();
+        if label.chars().any(|ch| ch.is_whitespace() || "()[]':;,".contains(ch)) {
+            newick.push('\'');
+            newick.push_str(&label.replace('\'', "''"));
+            newick.push('\'');
+        } else {
+            newick.push_str(&label);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Structural summary statistics of a tree, for comparing tree-building
+//! strategies.
+
+use distances::Number;
+
+use super::Cluster;
+
+/// Structural summary statistics of the subtree of a `Cluster`.
+///
+/// See `Cluster::quality_report`. Depths are as in `Cluster::depth`, i.e.
+/// measured from the root of the whole tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TreeQuality {
+    /// The number of `Cluster`s in the subtree.
+    pub num_clusters: usize,
+    /// The number of leaves in the subtree.
+    pub num_leaves: usize,
+    /// The maximum depth of any leaf.
+    pub max_depth: usize,
+    /// The mean depth of the leaves.
+    pub mean_leaf_depth: f64,
+    /// The (population) standard deviation of the depths of the leaves.
+    pub leaf_depth_std: f64,
+    /// The mean number of children of the `Cluster`s that are not leaves.
+    pub mean_branching_factor: f64,
+    /// The mean cardinality of the leaves.
+    pub mean_leaf_cardinality: f64,
+    /// The mean `balance_factor` of the `Cluster`s that are not leaves.
+    pub mean_balance_factor: f64,
+}
+
+impl TreeQuality {
+    /// Computes the report for the subtree of a `Cluster`.
+    pub(crate) fn new<U: Number, C: Cluster<U>>(root: &C) -> Self {
+        let mut num_clusters = 0;
+        let mut leaf_depths = Vec::new();
+        let mut leaf_cardinality = 0;
+        let mut num_children = 0;
+        let mut balance = 0.0;
+
+        for c in root.subtree_iter() {
+            num_clusters += 1;
+            if let Some(children) = c.children() {
+                num_children += children.len();
+                balance += c.balance_factor().unwrap_or_default();
+            } else {
+                leaf_depths.push(c.depth().as_f64());
+                leaf_cardinality += c.cardinality();
+            }
+        }
+
+        let num_leaves = leaf_depths.len();
+        let num_parents = num_clusters - num_leaves;
+        let mean = |total: f64, count: usize| if count == 0 { 0.0 } else { total / count.as_f64() };
+
+        let mean_leaf_depth = mean(leaf_depths.iter().sum(), num_leaves);
+        let variance = mean(
+            leaf_depths.iter().map(|d| (d - mean_leaf_depth).powi(2)).sum(),
+            num_leaves,
+        );
+
+        Self {
+            num_clusters,
+            num_leaves,
+            max_depth: root.max_leaf_depth(),
+            mean_leaf_depth,
+            leaf_depth_std: variance.sqrt(),
+            mean_branching_factor: mean(num_children.as_f64(), num_parents),
+            mean_leaf_cardinality: mean(leaf_cardinality.as_f64(), num_leaves),
+            mean_balance_factor: mean(balance, num_parents),
+        }
+    }
+}
@@ -10,15 +10,16 @@ use std::time::Instant;
 
 use distances::Number;
 use mt_logger::{mt_log, Level};
+use rayon::prelude::*;
 use serde::{
     de::{MapAccess, SeqAccess, Visitor},
     ser::SerializeStruct,
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::{utils, Cluster, Dataset, Instance, PartitionCriterion};
+use crate::{io::PortableCluster, utils, Cluster, Dataset, Instance, PartitionCriterion, Tree};
 
-use super::Children;
+use super::{balanced::is_balanced_split, CenterStrategy, Children};
 
 /// A `UniBall` is a cluster that behaves as clusters used to before the introduction
 /// of the `Cluster` trait.
@@ -80,14 +81,82 @@ impl<U: Number> Display for UniBall<U> {
     }
 }
 
+impl<I: Instance, U: Number, D: Dataset<I, U>> Tree<I, U, D, UniBall<U>> {
+    /// Merges singleton leaves back into their parents.
+    ///
+    /// See `UniBall::prune_singletons` for more details.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: Whether a `UniBall` should keep its children.
+    #[must_use]
+    pub fn prune_singletons<P: PartitionCriterion<U>>(mut self, criteria: &P) -> Self {
+        self.root.prune_singletons(criteria);
+        self.depth = self.root.max_leaf_depth();
+        self
+    }
+
+    /// Partitions further the leaves for which `select` returns `true`,
+    /// e.g. to deepen only the dense regions of a coarse tree.
+    ///
+    /// The selected leaves are partitioned with the `criteria` as in
+    /// `partition`. Every other `UniBall` keeps its center, radius and poles,
+    /// and the other leaves are not split. The instances in the dataset are
+    /// reordered to match the new tree, so the tree still holds the same
+    /// instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: The criteria used to partition the selected leaves.
+    /// * `select`: Whether a leaf should be partitioned further.
+    /// * `seed`: The seed used to partition the selected leaves.
+    #[must_use]
+    pub fn partition_further_where<P, S>(mut self, criteria: &P, select: S, seed: Option<u64>) -> Self
+    where
+        P: PartitionCriterion<U>,
+        S: Fn(&UniBall<U>) -> bool + Sync,
+    {
+        let root = self
+            .root
+            .partition_further_where(&mut self.data, criteria, &select, seed, false);
+        Self::from_parts(self.data, root)
+    }
+
+    /// Parallelized version of `partition_further_where`.
+    ///
+    /// Given the same `seed`, the resulting `Tree` is identical to the one
+    /// produced by `partition_further_where`.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: The criteria used to partition the selected leaves.
+    /// * `select`: Whether a leaf should be partitioned further.
+    /// * `seed`: The seed used to partition the selected leaves.
+    #[must_use]
+    pub fn par_partition_further_where<P, S>(mut self, criteria: &P, select: S, seed: Option<u64>) -> Self
+    where
+        P: PartitionCriterion<U>,
+        S: Fn(&UniBall<U>) -> bool + Sync,
+    {
+        let root = self
+            .root
+            .partition_further_where(&mut self.data, criteria, &select, seed, true);
+        Self::from_parts(self.data, root)
+    }
+}
+
 impl<U: Number> UniBall<U> {
     /// Create a new `UniBall`.
+    ///
+    /// The center is chosen with the given `strategy`. See
+    /// `PartitionCriterion::center_strategy` for the meaning of `None`.
     fn new<I: Instance, D: Dataset<I, U>>(
         data: &D,
         seed: Option<u64>,
         offset: usize,
         indices: &[usize],
         depth: usize,
+        strategy: Option<CenterStrategy>,
     ) -> Self {
         let cardinality = indices.len();
 
@@ -97,19 +166,27 @@ impl<U: Number> UniBall<U> {
             "Creating a UniBall with depth {depth} and cardinality {cardinality} ..."
         );
 
-        let arg_samples = if cardinality < 100 {
-            indices.to_vec()
-        } else {
-            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-            let n = (cardinality.as_f64().sqrt()) as usize;
-            data.choose_unique(n, indices, seed)
-        };
-
-        let Some(arg_center) = data.median(&arg_samples) else {
-            unreachable!("The UniBall has at least one instance.")
+        let (arg_center, center_distances) = match strategy {
+            None if cardinality < 100 => Self::exact_medoid(data, indices),
+            None => {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let n = (cardinality.as_f64().sqrt()) as usize;
+                Self::sampled_medoid(data, indices, n, seed)
+            }
+            Some(CenterStrategy::ExactMedoid) => Self::exact_medoid(data, indices),
+            Some(CenterStrategy::SampledMedoid { sample_size }) if cardinality <= sample_size.max(1) => {
+                Self::exact_medoid(data, indices)
+            }
+            Some(CenterStrategy::SampledMedoid { sample_size }) => {
+                Self::sampled_medoid(data, indices, sample_size.max(1), seed)
+            }
+            Some(CenterStrategy::RandomPoint) => {
+                let Some(&arg_center) = data.choose_unique(1, indices, seed).first() else {
+                    unreachable!("The UniBall has at least one instance.")
+                };
+                (arg_center, data.one_to_many(arg_center, indices))
+            }
         };
-
-        let center_distances = data.one_to_many(arg_center, indices);
         let Some((arg_radial, radius)) = utils::arg_max(&center_distances).map(|(i, r)| (indices[i], r)) else {
             unreachable!("The UniBall has at least one instance.")
         };
@@ -134,6 +211,45 @@ impl<U: Number> UniBall<U> {
         }
     }
 
+    /// Finds the exact medoid of the `indices`.
+    ///
+    /// All instances are candidates for the center, so the distances from the
+    /// center to every instance are already in the pairwise matrix used to
+    /// find the medoid.
+    ///
+    /// # Returns
+    ///
+    /// The index of the medoid and its distances to the `indices`.
+    fn exact_medoid<I: Instance, D: Dataset<I, U>>(data: &D, indices: &[usize]) -> (usize, Vec<U>) {
+        let mut pairwise = data.pairwise(indices);
+        let sums = pairwise
+            .iter()
+            .map(|row| data.total_distance(row, indices))
+            .collect::<Vec<_>>();
+        let Some((c, _)) = utils::arg_min(&sums) else {
+            unreachable!("The UniBall has at least one instance.")
+        };
+        (indices[c], pairwise.swap_remove(c))
+    }
+
+    /// Finds the medoid of `n` unique instances sampled from the `indices`.
+    ///
+    /// # Returns
+    ///
+    /// The index of the medoid and its distances to the `indices`.
+    fn sampled_medoid<I: Instance, D: Dataset<I, U>>(
+        data: &D,
+        indices: &[usize],
+        n: usize,
+        seed: Option<u64>,
+    ) -> (usize, Vec<U>) {
+        let arg_samples = data.choose_unique(n, indices, seed);
+        let Some(arg_center) = data.median(&arg_samples) else {
+            unreachable!("The UniBall has at least one instance.")
+        };
+        (arg_center, data.one_to_many(arg_center, indices))
+    }
+
     /// Checks that the partition is valid.
     ///
     /// # Arguments
@@ -170,31 +286,58 @@ impl<U: Number> UniBall<U> {
         // );
     }
 
-    /// Recursive helper function for `partition`.
+    /// Recursive helper function for `partition_tree`.
+    ///
+    /// Each child is built with a seed derived from the `seed` of this
+    /// `UniBall`, so the resulting tree does not depend on whether the children
+    /// are built in parallel.
+    ///
+    /// If `balanced` is `true`, every split is made with `partition_once_balanced`.
+    ///
+    /// A `UniBall` does not store its indices. The `indices` of this `UniBall`
+    /// are dropped once its children are accepted and before they are
+    /// partitioned, so only the indices along the current path are held.
     fn _partition<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
         mut self,
         data: &D,
         criteria: &P,
         mut indices: Vec<usize>,
         seed: Option<u64>,
+        parallel: bool,
+        balanced: bool,
     ) -> (Self, Vec<usize>) {
         if criteria.check(&self) {
-            let ([(arg_l, l_indices), (arg_r, r_indices)], polar_distance) = self.partition_once(data, indices.clone());
+            let ([(arg_l, l_indices), (arg_r, r_indices)], polar_distance) = if balanced {
+                self.partition_once_balanced(data, indices.clone())
+            } else {
+                self.partition_once(data, indices.clone())
+            };
             if self._check_partition(&l_indices, &r_indices) {
-                core::mem::drop(indices);
-
                 let r_offset = self.offset + l_indices.len();
+                let l_seed = seed.map(|s| utils::child_seed(s, 0));
+                let r_seed = seed.map(|s| utils::child_seed(s, 1));
+                let strategy = criteria.center_strategy();
+
+                let new_left = || Self::new(data, l_seed, self.offset, &l_indices, self.depth + 1, strategy);
+                let new_right = || Self::new(data, r_seed, r_offset, &r_indices, self.depth + 1, strategy);
+                let (left, right) = if parallel {
+                    rayon::join(new_left, new_right)
+                } else {
+                    (new_left(), new_right())
+                };
+
+                if !criteria.check_children(&self, &left, &right) {
+                    return self.reset_args(indices);
+                }
+                core::mem::drop(indices);
 
-                let ((left, l_indices), (right, r_indices)) = rayon::join(
-                    || {
-                        Self::new(data, seed, self.offset, &l_indices, self.depth + 1)
-                            ._partition(data, criteria, l_indices, seed)
-                    },
-                    || {
-                        Self::new(data, seed, r_offset, &r_indices, self.depth + 1)
-                            ._partition(data, criteria, r_indices, seed)
-                    },
-                );
+                let build_left = move || left._partition(data, criteria, l_indices, l_seed, parallel, balanced);
+                let build_right = move || right._partition(data, criteria, r_indices, r_seed, parallel, balanced);
+                let ((left, l_indices), (right, r_indices)) = if parallel {
+                    rayon::join(build_left, build_right)
+                } else {
+                    (build_left(), build_right())
+                };
                 self._check_partition(&l_indices, &r_indices);
 
                 let arg_l = utils::position_of(&l_indices, arg_l)
@@ -214,7 +357,12 @@ impl<U: Number> UniBall<U> {
             }
         }
 
-        // reset the indices to center and radial indices for data reordering
+        self.reset_args(indices)
+    }
+
+    /// Resets the center and radial indices to their positions in `indices`,
+    /// offset by the `offset` of this `UniBall`, for data reordering.
+    fn reset_args(mut self, indices: Vec<usize>) -> (Self, Vec<usize>) {
         let arg_center = utils::position_of(&indices, self.arg_center)
             .unwrap_or_else(|| unreachable!("We know the center is in the indices."));
         self.arg_center = self.offset + arg_center;
@@ -226,6 +374,31 @@ impl<U: Number> UniBall<U> {
         (self, indices)
     }
 
+    /// Recursively partitions the `UniBall` and permutes the `data` to match.
+    ///
+    /// See `_partition` for the meaning of `parallel` and `balanced`.
+    pub(crate) fn partition_tree<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
+        mut self,
+        data: &mut D,
+        criteria: &P,
+        seed: Option<u64>,
+        parallel: bool,
+        balanced: bool,
+    ) -> Self {
+        let mut indices = (0..self.cardinality).collect::<Vec<_>>();
+        if let Some(strategy) = criteria.center_strategy() {
+            // The root was created without the `criteria`, so its center is chosen again.
+            self = Self::new(data, seed, self.offset, &indices, self.depth, Some(strategy));
+        }
+        (self, indices) = self._partition(data, criteria, indices, seed, parallel, balanced);
+
+        mt_log!(Level::Debug, "Finished building tree. Starting data permutation.");
+        data.permute_instances(&indices).unwrap_or_else(|e| unreachable!("{e}"));
+        mt_log!(Level::Debug, "Finished data permutation.");
+
+        self
+    }
+
     /// Partitions the `UniBall` into two children once.
     fn partition_once<I: Instance, D: Dataset<I, U>>(
         &self,
@@ -264,6 +437,396 @@ impl<U: Number> UniBall<U> {
         }
     }
 
+    /// Partitions the `UniBall` into two children of (nearly) equal cardinality.
+    ///
+    /// The poles are chosen as in `partition_once`, but instead of assigning
+    /// each instance to its closer pole, the instances are sorted by how much
+    /// closer they are to the left pole and split at the median.
+    fn partition_once_balanced<I: Instance, D: Dataset<I, U>>(
+        &self,
+        data: &D,
+        indices: Vec<usize>,
+    ) -> ([(usize, Vec<usize>); 2], U) {
+        let l_distances = data.one_to_many(self.arg_radial, &indices);
+
+        let Some((arg_r, polar_distance)) = utils::arg_max(&l_distances) else {
+            unreachable!("The cluster should have at least one instance.")
+        };
+        let arg_r = indices[arg_r];
+        let r_distances = data.one_to_many(arg_r, &indices);
+
+        let num_left = indices.len().div_ceil(2);
+        let mut others = indices
+            .into_iter()
+            .zip(l_distances)
+            .zip(r_distances)
+            .filter(|&((i, _), _)| i != self.arg_radial && i != arg_r)
+            .collect::<Vec<_>>();
+        // `l_a - r_a < l_b - r_b` is rearranged to avoid underflow with unsigned distances.
+        others.sort_by(|&((a, l_a), r_a), &((b, l_b), r_b)| {
+            (l_a + r_b)
+                .partial_cmp(&(l_b + r_a))
+                .unwrap_or(Ordering::Equal)
+                .then(a.cmp(&b))
+        });
+        let mut r_indices = Self::drop_distances(others);
+
+        let mut l_indices = vec![self.arg_radial];
+        l_indices.extend(r_indices.drain(..(num_left - 1)));
+        r_indices.push(arg_r);
+
+        ([(self.arg_radial, l_indices), (arg_r, r_indices)], polar_distance)
+    }
+
+    /// Merges singleton leaves back into their parents.
+    ///
+    /// The subtree is visited bottom-up. The children of a `UniBall` are
+    /// removed, making it a leaf that holds their instances as direct members,
+    /// if both children are leaves, at least one of them is a singleton, and
+    /// the `criteria` would not partition the `UniBall`. Since the instances
+    /// of a `UniBall` are a contiguous range, every instance stays in the
+    /// same `UniBall`s except for the removed ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: Whether a `UniBall` should keep its children.
+    pub fn prune_singletons<P: PartitionCriterion<U>>(&mut self, criteria: &P) {
+        let Some(children) = self.children.as_mut() else {
+            return;
+        };
+        children.left.prune_singletons(criteria);
+        children.right.prune_singletons(criteria);
+
+        let (left, right) = (&children.left, &children.right);
+        let has_singleton_leaf = left.is_leaf() && right.is_leaf() && (left.is_singleton() || right.is_singleton());
+        if has_singleton_leaf && !criteria.check(self) {
+            self.children = None;
+        }
+    }
+
+    /// Recomputes the geometry of the subtrees of the `children`, if any, and
+    /// then of this `UniBall`.
+    ///
+    /// This is shared by `UniBall` and the `Cluster`s that wrap a `UniBall`
+    /// and hold their own `Children`. See `Cluster::recompute_geometry` for
+    /// more details.
+    pub(crate) fn recompute_geometry_with<I: Instance, D: Dataset<I, U>, C: Cluster<U>>(
+        &mut self,
+        children: Option<&mut Children<U, C>>,
+        data: &D,
+        parallel: bool,
+    ) {
+        if let Some(children) = children {
+            children.recompute_geometry(data, parallel);
+        }
+        self.update_geometry(data, parallel);
+    }
+
+    /// Same as `recompute_geometry_with`, with the children of this `UniBall`.
+    fn recompute_subtree<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, parallel: bool) {
+        let mut children = self.children.take();
+        self.recompute_geometry_with(children.as_mut(), data, parallel);
+        self.children = children;
+    }
+
+    /// Recomputes the center, radial instance, radius and local fractal
+    /// dimension of this `UniBall` from the current state of the dataset,
+    /// without descending into its children.
+    ///
+    /// See `Cluster::recompute_geometry` for more details.
+    pub(crate) fn update_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D, parallel: bool) {
+        let indices = self.indices().collect::<Vec<_>>();
+        let (arg_center, center_distances) = if parallel {
+            let arg_center = data.par_arg_medoid(&indices);
+            let distances = indices
+                .par_iter()
+                .map(|&i| data.one_to_one(arg_center, i))
+                .collect::<Vec<_>>();
+            (arg_center, distances)
+        } else {
+            let arg_center = data.arg_medoid(&indices);
+            (arg_center, data.one_to_many(arg_center, &indices))
+        };
+        let Some((arg_radial, radius)) = utils::arg_max(&center_distances).map(|(i, r)| (indices[i], r)) else {
+            unreachable!("The UniBall has at least one instance.")
+        };
+
+        self.arg_center = arg_center;
+        self.arg_radial = arg_radial;
+        self.radius = radius;
+        self.lfd = utils::compute_lfd(radius, &center_distances);
+    }
+
+    /// Whether the children of this `UniBall`, if any, are balanced as
+    /// defined by `is_balanced_split`.
+    pub(crate) fn is_balanced(&self) -> bool {
+        self.children.as_ref().map_or(true, |c| {
+            is_balanced_split(self.cardinality, c.left.cardinality, c.right.cardinality)
+        })
+    }
+
+    /// The number of levels in the subtree below this `UniBall`.
+    fn height(&self) -> usize {
+        self.children
+            .as_ref()
+            .map_or(0, |c| 1 + c.left.height().max(c.right.height()))
+    }
+
+    /// Whether the subtrees of the children of this `UniBall`, if any, have
+    /// similar heights, i.e. the taller subtree is at most twice as tall as
+    /// the shorter subtree, plus two.
+    pub(crate) fn is_height_balanced(&self) -> bool {
+        self.children.as_ref().map_or(true, |c| {
+            let (l, r) = (c.left.height(), c.right.height());
+            l.max(r) <= 2 * (l.min(r) + 1)
+        })
+    }
+
+    /// Re-partitions, with balanced splits, every subtree whose root is not
+    /// balanced according to `is_balanced`. Subtrees whose roots are balanced
+    /// keep their centers, radii and poles.
+    ///
+    /// This should only be called on the root of a tree after `partition`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `criteria`: The criteria used to re-partition the unbalanced subtrees.
+    /// * `seed`: The seed used to re-partition the unbalanced subtrees.
+    /// * `is_balanced`: Whether a subtree may keep the split at its root.
+    pub(crate) fn rebalance<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
+        self,
+        data: &mut D,
+        criteria: &P,
+        seed: Option<u64>,
+        is_balanced: fn(&Self) -> bool,
+    ) -> Self {
+        self.repartition(data, criteria, seed, &|c: &Self| !is_balanced(c), false, true)
+    }
+
+    /// Partitions further every leaf for which `select` returns `true`,
+    /// leaving the rest of the tree unchanged.
+    ///
+    /// This should only be called on the root of a tree after `partition`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree was built.
+    /// * `criteria`: The criteria used to partition the selected leaves.
+    /// * `select`: Whether a leaf should be partitioned further.
+    /// * `seed`: The seed used to partition the selected leaves.
+    /// * `parallel`: Whether to partition in parallel.
+    pub(crate) fn partition_further_where<I, D, P, S>(
+        self,
+        data: &mut D,
+        criteria: &P,
+        select: &S,
+        seed: Option<u64>,
+        parallel: bool,
+    ) -> Self
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+        S: Fn(&Self) -> bool + Sync,
+    {
+        let select = |c: &Self| c.is_leaf() && select(c);
+        self.repartition(data, criteria, seed, &select, parallel, false)
+    }
+
+    /// Re-partitions every subtree whose root is selected by `select`, and
+    /// reorders the instances in the `data` to match the new tree.
+    ///
+    /// See `_partition` for the meaning of `parallel` and `balanced`.
+    fn repartition<I, D, P, S>(
+        self,
+        data: &mut D,
+        criteria: &P,
+        seed: Option<u64>,
+        select: &S,
+        parallel: bool,
+        balanced: bool,
+    ) -> Self
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+        S: Fn(&Self) -> bool + Sync,
+    {
+        let (root, indices) = self.repartition_subtree(data, criteria, seed, select, parallel, balanced);
+
+        let original_indices = indices.iter().map(|&i| data.original_index(i)).collect::<Vec<_>>();
+        data.permute_instances(&indices).unwrap_or_else(|e| unreachable!("{e}"));
+        data.set_permuted_indices(Some(&original_indices));
+
+        root
+    }
+
+    /// Recursive helper function for `repartition`.
+    fn repartition_subtree<I, D, P, S>(
+        mut self,
+        data: &D,
+        criteria: &P,
+        seed: Option<u64>,
+        select: &S,
+        parallel: bool,
+        balanced: bool,
+    ) -> (Self, Vec<usize>)
+    where
+        I: Instance,
+        D: Dataset<I, U>,
+        P: PartitionCriterion<U>,
+        S: Fn(&Self) -> bool + Sync,
+    {
+        let indices = self.indices().collect::<Vec<_>>();
+        if select(&self) {
+            self.children = None;
+            return self._partition(data, criteria, indices, seed, parallel, balanced);
+        }
+
+        let Some(children) = self.children.take() else {
+            return (self, indices);
+        };
+
+        let (l_seed, r_seed) = (
+            seed.map(|s| utils::child_seed(s, 0)),
+            seed.map(|s| utils::child_seed(s, 1)),
+        );
+        let (left, right) = (children.left, children.right);
+        let build_left = move || left.repartition_subtree(data, criteria, l_seed, select, parallel, balanced);
+        let build_right = move || right.repartition_subtree(data, criteria, r_seed, select, parallel, balanced);
+        let ((left, l_indices), (right, r_indices)) = if parallel {
+            rayon::join(build_left, build_right)
+        } else {
+            (build_left(), build_right())
+        };
+        let indices = l_indices.into_iter().chain(r_indices).collect::<Vec<_>>();
+
+        let position = |i| {
+            let Some(p) = utils::position_of(&indices, i) else {
+                unreachable!("We know the instance is in the indices.")
+            };
+            self.offset + p
+        };
+        self.children = Some(Children {
+            left: Box::new(left),
+            right: Box::new(right),
+            arg_l: position(children.arg_l),
+            arg_r: position(children.arg_r),
+            polar_distance: children.polar_distance,
+        });
+        self.arg_center = position(self.arg_center);
+        self.arg_radial = position(self.arg_radial);
+
+        (self, indices)
+    }
+
+    /// Recursively copies the subtree of any `Cluster` into a `UniBall` tree.
+    ///
+    /// Every `UniBall` keeps the offset, cardinality, depth, center, radial,
+    /// radius, local fractal dimension and poles of the corresponding
+    /// `Cluster`.
+    pub(crate) fn from_cluster<C: Cluster<U>>(c: &C) -> Self {
+        let children = c.children().map(|[left, right]| {
+            let [arg_l, arg_r] = c
+                .arg_poles()
+                .unwrap_or_else(|| unreachable!("A cluster with children has poles."));
+            let polar_distance = c
+                .polar_distance()
+                .unwrap_or_else(|| unreachable!("A cluster with children has a polar distance."));
+            Children {
+                left: Box::new(Self::from_cluster(left)),
+                right: Box::new(Self::from_cluster(right)),
+                arg_l,
+                arg_r,
+                polar_distance,
+            }
+        });
+
+        Self {
+            depth: c.depth(),
+            offset: c.offset(),
+            cardinality: c.cardinality(),
+            arg_center: c.arg_center(),
+            arg_radial: c.arg_radial(),
+            radius: c.radius(),
+            lfd: c.lfd(),
+            children,
+        }
+    }
+
+    /// Recursively rebuilds the subtree rooted at `clusters[index]` from a
+    /// tree saved with `Tree::save_portable`.
+    ///
+    /// # Errors
+    ///
+    /// * If `index` is out of bounds.
+    /// * If a child is not listed after its parent.
+    /// * If the cluster is empty, or if its center or radial instance is not
+    ///   one of its instances.
+    /// * If the children do not split the instances of the cluster in two,
+    ///   with the left child first, or are not one level deeper.
+    /// * If a pole is not an instance of its child.
+    pub(crate) fn from_portable(clusters: &[PortableCluster], index: usize) -> Result<Self, String> {
+        let c = clusters
+            .get(index)
+            .ok_or_else(|| format!("Cluster {index} is out of bounds."))?;
+
+        if c.cardinality == 0 {
+            return Err(format!("Cluster {index} is empty."));
+        }
+        if !c.contains(c.arg_center) || !c.contains(c.arg_radial) {
+            return Err(format!(
+                "The center {} or radial instance {} of cluster {index} is not in its range of {} instances from {}.",
+                c.arg_center, c.arg_radial, c.cardinality, c.offset
+            ));
+        }
+
+        let children = match &c.children {
+            Some(children) => {
+                if children.left <= index || children.right <= index {
+                    return Err(format!("The children of cluster {index} are not listed after it."));
+                }
+                let (Some(left), Some(right)) = (clusters.get(children.left), clusters.get(children.right)) else {
+                    return Err(format!("The children of cluster {index} are out of bounds."));
+                };
+                if left.cardinality.checked_add(right.cardinality) != Some(c.cardinality)
+                    || left.offset != c.offset
+                    || right.offset != c.offset + left.cardinality
+                {
+                    return Err(format!("The children of cluster {index} do not split its instances."));
+                }
+                if left.depth != c.depth + 1 || right.depth != c.depth + 1 {
+                    return Err(format!("The children of cluster {index} are not one level deeper."));
+                }
+                if !left.contains(children.arg_l) || !right.contains(children.arg_r) {
+                    return Err(format!(
+                        "The poles of cluster {index} are not instances of its children."
+                    ));
+                }
+                Some(Children {
+                    left: Box::new(Self::from_portable(clusters, children.left)?),
+                    right: Box::new(Self::from_portable(clusters, children.right)?),
+                    arg_l: children.arg_l,
+                    arg_r: children.arg_r,
+                    polar_distance: U::from(children.polar_distance),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            depth: c.depth,
+            offset: c.offset,
+            cardinality: c.cardinality,
+            arg_center: c.arg_center,
+            arg_radial: c.arg_radial,
+            radius: U::from(c.radius),
+            lfd: c.lfd,
+            children,
+        })
+    }
+
     /// Drops the distances from a vector, returning only the indices.
     fn drop_distances(indices: Vec<((usize, U), U)>) -> Vec<usize> {
         indices.into_iter().map(|((i, _), _)| i).collect()
@@ -273,23 +836,25 @@ impl<U: Number> UniBall<U> {
 impl<U: Number> Cluster<U> for UniBall<U> {
     fn new_root<I: Instance, D: Dataset<I, U>>(data: &D, seed: Option<u64>) -> Self {
         let indices = (0..data.cardinality()).collect::<Vec<usize>>();
-        Self::new(data, seed, 0, &indices, 0)
+        Self::new(data, seed, 0, &indices, 0, None)
     }
 
-    fn partition<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
-        mut self,
+    fn partition_with<I: Instance, D: Dataset<I, U>, P: PartitionCriterion<U>>(
+        self,
         data: &mut D,
         criteria: &P,
         seed: Option<u64>,
+        parallel: bool,
     ) -> Self {
-        let mut indices = (0..self.cardinality).collect::<Vec<_>>();
-        (self, indices) = self._partition(data, criteria, indices, seed);
+        self.partition_tree(data, criteria, seed, parallel, false)
+    }
 
-        mt_log!(Level::Debug, "Finished building tree. Starting data permutation.");
-        data.permute_instances(&indices).unwrap_or_else(|e| unreachable!("{e}"));
-        mt_log!(Level::Debug, "Finished data permutation.");
+    fn recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, false);
+    }
 
-        self
+    fn par_recompute_geometry<I: Instance, D: Dataset<I, U>>(&mut self, data: &D) {
+        self.recompute_subtree(data, true);
     }
 
     fn offset(&self) -> usize {
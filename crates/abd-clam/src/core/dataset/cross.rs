@@ -0,0 +1,59 @@
+//! Distances between queries and instances of different types.
+
+use distances::Number;
+use rayon::prelude::*;
+
+use super::{Dataset, Instance};
+
+/// A cross metric computes the distance from a query of type `Q` to an
+/// instance of type `I`, e.g. from a sparse vector to a dense vector.
+pub type CrossMetric<Q, I, U> = fn(&Q, &I) -> U;
+
+/// A `Dataset` whose instances can be compared to queries of another type.
+///
+/// This is implemented for every `Dataset`. The `CrossMetric` must agree with
+/// the metric of the dataset, i.e. the distance from a query to an instance
+/// must be the same as the distance from the equivalent instance of type `I`
+/// to that instance. Otherwise, search will not be able to prune the tree
+/// correctly.
+///
+/// # Type Parameters
+///
+/// - `Q`: The type of the queries.
+/// - `I`: The type of the instances in the dataset.
+/// - `U`: The type of the distance values.
+pub trait CrossDataset<Q: Send + Sync, I: Instance, U: Number>: Dataset<I, U> {
+    /// Computes the distance from a query to an instance in the dataset.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric`: The distance function from queries to instances.
+    /// * `query`: The query.
+    /// * `index`: The index of the instance in the dataset.
+    fn cross_query_to_one(&self, metric: CrossMetric<Q, I, U>, query: &Q, index: usize) -> U {
+        metric(query, &self[index])
+    }
+
+    /// Computes the distances from a query to many instances in the dataset.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric`: The distance function from queries to instances.
+    /// * `query`: The query.
+    /// * `indices`: The indices of the instances in the dataset.
+    fn cross_query_to_many(&self, metric: CrossMetric<Q, I, U>, query: &Q, indices: &[usize]) -> Vec<U> {
+        if self.is_metric_expensive() {
+            indices
+                .par_iter()
+                .map(|&index| self.cross_query_to_one(metric, query, index))
+                .collect()
+        } else {
+            indices
+                .iter()
+                .map(|&index| self.cross_query_to_one(metric, query, index))
+                .collect()
+        }
+    }
+}
+
+impl<Q: Send + Sync, I: Instance, U: Number, D: Dataset<I, U>> CrossDataset<Q, I, U> for D {}
@@ -0,0 +1,111 @@
+//! A memo of distances between indexed instances, for use within one search.
+
+use std::collections::HashMap;
+
+use distances::Number;
+
+/// Memoizes the distances between pairs of indexed instances, and between the
+/// query of a search and indexed instances.
+///
+/// This is meant to be created for a single search, or any other short-lived
+/// computation in which the same pairs of instances, such as the centers of
+/// `Cluster`s, are compared repeatedly, and then dropped or `clear`ed. It is
+/// used through `Dataset::one_to_one_cached`, `Dataset::query_to_one_cached`
+/// and `Dataset::query_to_many_cached`. The distances to the query are keyed
+/// only by the index of the instance, so the cache must not be shared between
+/// different queries. The cache is keyed by the indices of the instances, so
+/// it must not outlive a reordering of the dataset.
+#[derive(Debug, Clone, Default)]
+pub struct DistanceCache<U: Number> {
+    /// The distances between pairs of indexed instances computed so far.
+    distances: HashMap<(usize, usize), U>,
+    /// The distances from the query to indexed instances computed so far.
+    to_query: HashMap<usize, U>,
+    /// Whether the metric is symmetric, in which case `(left, right)` and
+    /// `(right, left)` share an entry.
+    symmetric: bool,
+    /// The number of lookups that found a cached distance.
+    hits: usize,
+}
+
+impl<U: Number> DistanceCache<U> {
+    /// Creates a new, empty `DistanceCache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `symmetric`: Whether the metric is symmetric. If so, the distance
+    ///   from `left` to `right` is reused for the distance from `right` to
+    ///   `left`.
+    #[must_use]
+    pub fn new(symmetric: bool) -> Self {
+        Self {
+            distances: HashMap::new(),
+            to_query: HashMap::new(),
+            symmetric,
+            hits: 0,
+        }
+    }
+
+    /// Returns the cached distance between two indexed instances, or computes
+    /// it with `distance` and caches it.
+    pub(crate) fn get_or_insert_with<F: FnOnce() -> U>(&mut self, left: usize, right: usize, distance: F) -> U {
+        let key = if self.symmetric && right < left {
+            (right, left)
+        } else {
+            (left, right)
+        };
+        if let Some(&d) = self.distances.get(&key) {
+            self.hits += 1;
+            d
+        } else {
+            let d = distance();
+            self.distances.insert(key, d);
+            d
+        }
+    }
+
+    /// Returns the cached distance from the query to an indexed instance, or
+    /// computes it with `distance` and caches it.
+    pub(crate) fn get_or_insert_query_with<F: FnOnce() -> U>(&mut self, index: usize, distance: F) -> U {
+        if let Some(&d) = self.to_query.get(&index) {
+            self.hits += 1;
+            d
+        } else {
+            let d = distance();
+            self.to_query.insert(index, d);
+            d
+        }
+    }
+
+    /// Returns the cached distance from the query to an indexed instance, if
+    /// any.
+    pub(crate) fn get_query(&self, index: usize) -> Option<U> {
+        self.to_query.get(&index).copied()
+    }
+
+    /// The number of distances that are cached.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.distances.len() + self.to_query.len()
+    }
+
+    /// Whether no distances are cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.distances.is_empty() && self.to_query.is_empty()
+    }
+
+    /// The number of lookups that reused a cached distance.
+    #[must_use]
+    pub const fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Removes all cached distances and resets the number of hits, so that the
+    /// cache can be reused for another search.
+    pub fn clear(&mut self) {
+        self.distances.clear();
+        self.to_query.clear();
+        self.hits = 0;
+    }
+}
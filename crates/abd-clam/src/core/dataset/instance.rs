@@ -21,6 +21,11 @@ pub trait Instance: Debug + Send + Sync + Clone {
     /// The name of the type of instance.
     fn type_name() -> String;
 
+    /// The number of coordinates of the instance, if it is a vector.
+    fn dimensionality(&self) -> Option<usize> {
+        None
+    }
+
     /// Save the instance to a file.
     ///
     /// # Errors
@@ -78,6 +83,38 @@ impl<T: Number> Instance for Vec<T> {
     fn type_name() -> String {
         format!("Vec<{}>", T::type_name())
     }
+
+    fn dimensionality(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: Number, const N: usize> Instance for [T; N] {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.iter().flat_map(|x| x.to_le_bytes()).collect()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() == N * T::num_bytes() {
+            let values = bytes
+                .chunks_exact(T::num_bytes())
+                .map(|x| T::from_le_bytes(x))
+                .collect::<Vec<_>>();
+            values
+                .try_into()
+                .map_err(|_| format!("Expected {N} values of type {}", T::type_name()))
+        } else {
+            Err(format!("Expected {} bytes, got {}", N * T::num_bytes(), bytes.len()))
+        }
+    }
+
+    fn type_name() -> String {
+        format!("[{}; {N}]", T::type_name())
+    }
+
+    fn dimensionality(&self) -> Option<usize> {
+        Some(N)
+    }
 }
 
 impl Instance for String {
@@ -0,0 +1,132 @@
+//! The Mahalanobis distance with a supplied inverse covariance matrix.
+
+use super::MetricProperties;
+
+/// The Mahalanobis distance, `sqrt((x - y)^T S^-1 (x - y))`, for `Vec<f64>`
+/// instances.
+///
+/// Metrics in CLAM are plain function pointers, so they cannot capture the
+/// inverse covariance matrix. To use this distance with a `Dataset`, `whiten`
+/// the instances and use the Euclidean distance on the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mahalanobis {
+    /// The inverse covariance matrix, `S^-1`.
+    inv_cov: Vec<Vec<f64>>,
+}
+
+impl Mahalanobis {
+    /// Creates a new `Mahalanobis` distance.
+    ///
+    /// The distance is only a metric (and `properties` only reports the
+    /// triangle inequality as holding) when `inv_cov` is symmetric and
+    /// positive semi-definite. This is not checked here.
+    ///
+    /// # Arguments
+    ///
+    /// * `inv_cov`: The inverse covariance matrix.
+    /// * `dimensionality`: The dimensionality of the instances.
+    ///
+    /// # Errors
+    ///
+    /// * If `inv_cov` is not square.
+    /// * If the size of `inv_cov` does not match `dimensionality`.
+    pub fn new(inv_cov: Vec<Vec<f64>>, dimensionality: usize) -> Result<Self, String> {
+        if let Some(i) = inv_cov.iter().position(|row| row.len() != inv_cov.len()) {
+            return Err(format!(
+                "The inverse covariance matrix must be square, but row {i} has {} columns and there are {} rows.",
+                inv_cov[i].len(),
+                inv_cov.len()
+            ));
+        }
+        if inv_cov.len() != dimensionality {
+            return Err(format!(
+                "The inverse covariance matrix has size {} but the instances have dimensionality {dimensionality}.",
+                inv_cov.len()
+            ));
+        }
+        Ok(Self { inv_cov })
+    }
+
+    /// The dimensionality of the instances.
+    #[must_use]
+    pub fn dimensionality(&self) -> usize {
+        self.inv_cov.len()
+    }
+
+    /// Computes the Mahalanobis distance between two instances.
+    ///
+    /// Both instances must have the dimensionality given at construction.
+    /// Extra elements are ignored.
+    #[must_use]
+    pub fn distance(&self, x: &[f64], y: &[f64]) -> f64 {
+        let diff = x.iter().zip(y).map(|(a, b)| a - b).collect::<Vec<_>>();
+        let squared = self
+            .inv_cov
+            .iter()
+            .zip(&diff)
+            .map(|(row, d)| d * row.iter().zip(&diff).map(|(s, e)| s * e).sum::<f64>())
+            .sum::<f64>();
+        // Rounding can make the quadratic form slightly negative.
+        squared.max(0.0).sqrt()
+    }
+
+    /// The properties of the distance, assuming `inv_cov` is symmetric and
+    /// positive semi-definite.
+    #[must_use]
+    pub fn properties() -> MetricProperties {
+        MetricProperties::default()
+    }
+
+    /// Transforms instances so that the Euclidean distance between the results
+    /// equals the Mahalanobis distance between the originals.
+    ///
+    /// This uses the Cholesky factorization `S^-1 = L L^T` and maps each `x`
+    /// to `L^T x`.
+    ///
+    /// # Arguments
+    ///
+    /// * `instances`: The instances to transform.
+    ///
+    /// # Errors
+    ///
+    /// * If `inv_cov` is not symmetric positive-definite.
+    /// * If any instance does not have the dimensionality given at construction.
+    pub fn whiten(&self, instances: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+        let n = self.dimensionality();
+        if let Some(i) = instances.iter().position(|x| x.len() != n) {
+            return Err(format!(
+                "Instance {i} has dimensionality {} but expected {n}.",
+                instances[i].len()
+            ));
+        }
+        let l = self.cholesky()?;
+        Ok(instances
+            .iter()
+            .map(|x| (0..n).map(|j| (j..n).map(|i| l[i][j] * x[i]).sum()).collect())
+            .collect())
+    }
+
+    /// Computes the lower-triangular Cholesky factor of `inv_cov`.
+    fn cholesky(&self) -> Result<Vec<Vec<f64>>, String> {
+        let n = self.dimensionality();
+        let mut l = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in 0..=i {
+                if (self.inv_cov[i][j] - self.inv_cov[j][i]).abs() > f64::EPSILON * self.inv_cov[i][j].abs().max(1.0) {
+                    return Err(format!("The inverse covariance matrix is not symmetric at ({i}, {j})."));
+                }
+                let dot = (0..j).map(|k| l[i][k] * l[j][k]).sum::<f64>();
+                if i == j {
+                    let pivot = self.inv_cov[i][i] - dot;
+                    if pivot <= 0.0 {
+                        return Err("The inverse covariance matrix is not positive-definite.".to_string());
+                    }
+                    l[i][j] = pivot.sqrt();
+                } else {
+                    l[i][j] = (self.inv_cov[i][j] - dot) / l[j][j];
+                }
+            }
+        }
+        Ok(l)
+    }
+}
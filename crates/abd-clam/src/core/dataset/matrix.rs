@@ -0,0 +1,332 @@
+//! A dataset of precomputed distances between instances.
+
+use core::ops::Index;
+
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use distances::Number;
+
+use crate::Dataset;
+
+/// A `Dataset` whose distances are read from a precomputed distance matrix.
+///
+/// The instances are the indices of the rows of the matrix, so indexing into
+/// the dataset returns the index of the instance in the matrix. All distance
+/// computations (`one_to_one`, `query_to_one` and the methods built on them)
+/// look up the matrix. A query must therefore be the index of a row in the
+/// matrix.
+///
+/// The matrix may be given in full, as a square matrix, or in condensed form,
+/// as the upper triangle of a symmetric matrix with a zero diagonal. The
+/// condensed form takes half the memory.
+///
+/// # Type Parameters
+///
+/// - `U`: The type of the distance values between instances.
+#[derive(Debug)]
+pub struct MatrixDataset<U: Number> {
+    /// The name of the dataset.
+    name: String,
+    /// The distance matrix, shared between shards of the dataset.
+    distances: Arc<Vec<U>>,
+    /// The number of rows in the distance matrix.
+    num_rows: usize,
+    /// Whether the `distances` are the condensed upper triangle of the matrix.
+    condensed: bool,
+    /// The indices of the instances in the matrix, in their current order.
+    instances: Vec<usize>,
+    /// The reordering of the dataset after building the tree.
+    permuted_indices: Option<Vec<usize>>,
+}
+
+impl<U: Number> MatrixDataset<U> {
+    /// Creates a new dataset from a square distance matrix.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the dataset.
+    /// * `matrix`: The distance matrix, where `matrix[i][j]` is the distance
+    ///   from instance `i` to instance `j`.
+    ///
+    /// # Errors
+    ///
+    /// * If the matrix is not square.
+    pub fn new(name: String, matrix: Vec<Vec<U>>) -> Result<Self, String> {
+        let num_rows = matrix.len();
+        if let Some((i, row)) = matrix.iter().enumerate().find(|(_, row)| row.len() != num_rows) {
+            return Err(format!(
+                "Invalid matrix. Expected {num_rows} columns, got {} columns in row {i}",
+                row.len()
+            ));
+        }
+
+        let distances = matrix.into_iter().flatten().collect();
+        Ok(Self::from_parts(name, distances, num_rows, false))
+    }
+
+    /// Creates a new dataset from a condensed distance matrix.
+    ///
+    /// The condensed matrix holds the upper triangle, without the diagonal, of
+    /// a symmetric distance matrix in row-major order, i.e. the distances
+    /// `d(0, 1), d(0, 2), ..., d(0, n - 1), d(1, 2), ..., d(n - 2, n - 1)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the dataset.
+    /// * `condensed`: The condensed distance matrix.
+    ///
+    /// # Errors
+    ///
+    /// * If the length of `condensed` is not `n * (n - 1) / 2` for any `n`.
+    pub fn from_condensed(name: String, condensed: Vec<U>) -> Result<Self, String> {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let num_rows = ((1. + 8_f64.mul_add(condensed.len().as_f64(), 1.).sqrt()) / 2.).round() as usize;
+        if num_rows * (num_rows - 1) / 2 != condensed.len() {
+            return Err(format!(
+                "Invalid condensed matrix. Its length, {}, is not a triangular number",
+                condensed.len()
+            ));
+        }
+
+        Ok(Self::from_parts(name, condensed, num_rows, true))
+    }
+
+    /// Creates a new dataset with all rows of the matrix as instances.
+    fn from_parts(name: String, distances: Vec<U>, num_rows: usize, condensed: bool) -> Self {
+        Self {
+            name,
+            distances: Arc::new(distances),
+            num_rows,
+            condensed,
+            instances: (0..num_rows).collect(),
+            permuted_indices: None,
+        }
+    }
+
+    /// Looks up the distance between two rows of the matrix.
+    fn lookup(&self, i: usize, j: usize) -> U {
+        if self.condensed {
+            if i == j {
+                return U::zero();
+            }
+            let (i, j) = if i < j { (i, j) } else { (j, i) };
+            self.distances[self.num_rows * i - i * (i + 1) / 2 + (j - i - 1)]
+        } else {
+            self.distances[i * self.num_rows + j]
+        }
+    }
+}
+
+impl<U: Number> Index<usize> for MatrixDataset<U> {
+    type Output = usize;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.instances.index(index)
+    }
+}
+
+/// The `metric` of a `MatrixDataset`, which cannot be expressed as a function
+/// of two instances alone.
+///
+/// # Panics
+///
+/// * Always. The distances in a `MatrixDataset` must be computed through the
+///   methods of the `Dataset` trait.
+#[allow(clippy::panic, clippy::trivially_copy_pass_by_ref)]
+fn matrix_metric<U: Number>(_: &usize, _: &usize) -> U {
+    panic!("The distances in a `MatrixDataset` can only be computed through the `Dataset` methods.")
+}
+
+impl<U: Number> Dataset<usize, U> for MatrixDataset<U> {
+    fn type_name() -> String {
+        format!("MatrixDataset<{}>", U::type_name())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn cardinality(&self) -> usize {
+        self.instances.len()
+    }
+
+    fn is_metric_expensive(&self) -> bool {
+        false
+    }
+
+    /// The returned function panics when called. Use `one_to_one` or
+    /// `query_to_one` instead, which look up the distance matrix.
+    fn metric(&self) -> fn(&usize, &usize) -> U {
+        matrix_metric
+    }
+
+    fn set_permuted_indices(&mut self, indices: Option<&[usize]>) {
+        self.permuted_indices = indices.map(<[usize]>::to_vec);
+    }
+
+    fn swap(&mut self, left: usize, right: usize) -> Result<(), String> {
+        self.instances.swap(left, right);
+        Ok(())
+    }
+
+    fn permuted_indices(&self) -> Option<&[usize]> {
+        self.permuted_indices.as_deref()
+    }
+
+    fn one_to_one(&self, left: usize, right: usize) -> U {
+        self.lookup(self.instances[left], self.instances[right])
+    }
+
+    fn query_to_one(&self, query: &usize, index: usize) -> U {
+        self.lookup(*query, self.instances[index])
+    }
+
+    fn make_shards(mut self, max_cardinality: usize) -> Vec<Self> {
+        let mut shards = Vec::new();
+
+        while self.instances.len() > max_cardinality {
+            let at = self.instances.len() - max_cardinality;
+            shards.push(Self {
+                name: format!("{}-shard-{}", self.name, shards.len()),
+                distances: Arc::clone(&self.distances),
+                num_rows: self.num_rows,
+                condensed: self.condensed,
+                instances: self.instances.split_off(at),
+                permuted_indices: None,
+            });
+        }
+
+        self.name = format!("{}-shard-{}", self.name, shards.len());
+        self.permuted_indices = None;
+        shards.push(self);
+
+        shards
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let mut handle = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+
+        // Write header (Basic protection against reading bad data)
+        let type_name = Self::type_name();
+        handle
+            .write_all(&type_name.len().to_le_bytes())
+            .and_then(|()| handle.write_all(type_name.as_bytes()))
+            .map_err(|e| e.to_string())?;
+
+        // Write dataset name
+        handle
+            .write_all(&self.name.len().to_le_bytes())
+            .and_then(|()| handle.write_all(self.name.as_bytes()))
+            .map_err(|e| e.to_string())?;
+
+        // Write the shape of the matrix
+        handle
+            .write_all(&self.num_rows.to_le_bytes())
+            .and_then(|()| handle.write_all(&[<u8 as From<_>>::from(self.condensed)]))
+            .map_err(|e| e.to_string())?;
+
+        // Write the distances
+        let distances = self.distances.iter().flat_map(|d| d.to_le_bytes()).collect::<Vec<_>>();
+        handle
+            .write_all(&self.distances.len().to_le_bytes())
+            .and_then(|()| handle.write_all(&distances))
+            .map_err(|e| e.to_string())?;
+
+        // Write the instances
+        let instances = self.instances.iter().flat_map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        handle
+            .write_all(&self.instances.len().to_le_bytes())
+            .and_then(|()| handle.write_all(&instances))
+            .map_err(|e| e.to_string())?;
+
+        // If the dataset was permuted, write the permutation map.
+        let permutation = self
+            .permuted_indices
+            .as_ref()
+            .map_or(Vec::new(), |p| p.iter().flat_map(|i| i.to_le_bytes()).collect());
+        handle
+            .write_all(&permutation.len().to_le_bytes())
+            .and_then(|()| handle.write_all(&permutation))
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Loads a dataset saved with `save`. The `metric` and `is_expensive`
+    /// arguments are ignored.
+    fn load(path: &Path, _: fn(&usize, &usize) -> U, _: bool) -> Result<Self, String> {
+        let mut handle = File::open(path).map_err(|e| e.to_string())?;
+
+        // Check that the type name matches.
+        let num_type_bytes = read_usize(&mut handle)?;
+        let type_name = String::from_utf8(read_bytes(&mut handle, num_type_bytes)?).map_err(|e| e.to_string())?;
+        let actual_type_name = Self::type_name();
+        if type_name != actual_type_name {
+            return Err(format!(
+                "Invalid type. File has data of type {type_name} but dataset was constructed with type {actual_type_name}"
+            ));
+        }
+
+        // Read the given name of the dataset
+        let num_name_bytes = read_usize(&mut handle)?;
+        let name = String::from_utf8(read_bytes(&mut handle, num_name_bytes)?).map_err(|e| e.to_string())?;
+
+        // Read the shape of the matrix
+        let num_rows = read_usize(&mut handle)?;
+        let condensed = read_bytes(&mut handle, 1)?[0] != 0;
+
+        // Read the distances
+        let num_distances = read_usize(&mut handle)?;
+        let distances = read_bytes(&mut handle, num_distances * U::num_bytes())?
+            .chunks(U::num_bytes())
+            .map(U::from_le_bytes)
+            .collect();
+
+        // Read the instances
+        let num_instances = read_usize(&mut handle)?;
+        let instances = read_usizes(&mut handle, num_instances)?;
+
+        // Read the permutation, if it exists
+        let num_permutation_bytes = read_usize(&mut handle)?;
+        let permuted_indices = if num_permutation_bytes == 0 {
+            None
+        } else {
+            Some(read_usizes(&mut handle, num_permutation_bytes / usize::num_bytes())?)
+        };
+
+        Ok(Self {
+            name,
+            distances: Arc::new(distances),
+            num_rows,
+            condensed,
+            instances,
+            permuted_indices,
+        })
+    }
+}
+
+/// Reads `n` bytes from the `reader`.
+fn read_bytes<R: Read>(reader: &mut R, n: usize) -> Result<Vec<u8>, String> {
+    let mut buf = vec![0; n];
+    reader.read_exact(&mut buf).map_err(|e| e.to_string())?;
+    Ok(buf)
+}
+
+/// Reads a little-endian `usize` from the `reader`.
+fn read_usize<R: Read>(reader: &mut R) -> Result<usize, String> {
+    read_bytes(reader, usize::num_bytes()).map(|buf| <usize as Number>::from_le_bytes(&buf))
+}
+
+/// Reads `n` little-endian `usize`s from the `reader`.
+fn read_usizes<R: Read>(reader: &mut R, n: usize) -> Result<Vec<usize>, String> {
+    read_bytes(reader, n * usize::num_bytes()).map(|buf| {
+        buf.chunks(usize::num_bytes())
+            .map(<usize as Number>::from_le_bytes)
+            .collect()
+    })
+}
@@ -0,0 +1,197 @@
+//! A report of empirical violations of the properties of a metric.
+
+use distances::{number::Float, MetricProperties, Number};
+use mt_logger::{mt_log, Level};
+use rand::prelude::*;
+
+/// The number of violations of each metric property found by sampling
+/// instances from a `Dataset`.
+///
+/// See `Dataset::check_metric_properties`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetricReport {
+    /// The number of triples of instances that were sampled.
+    pub num_samples: usize,
+    /// The number of times `d(x, x) != 0`.
+    pub identity: usize,
+    /// The number of times `d(x, y) != d(y, x)`.
+    pub symmetry: usize,
+    /// The number of times `d(x, y) < 0`.
+    pub non_negativity: usize,
+    /// The number of times `d(x, z) > d(x, y) + d(y, z)`.
+    pub triangle_inequality: usize,
+}
+
+impl MetricReport {
+    /// Whether no violations of any of the properties were found.
+    #[must_use]
+    pub const fn is_valid(&self) -> bool {
+        self.identity == 0 && self.symmetry == 0 && self.non_negativity == 0 && self.triangle_inequality == 0
+    }
+}
+
+impl MetricReport {
+    /// Samples triples of indices and counts violations of the metric
+    /// properties among the distances given by `distance`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cardinality` - The number of instances to sample indices from.
+    /// * `num_samples` - The number of triples of instances to sample.
+    /// * `seed` - The seed for the random number generator.
+    /// * `distance` - Computes the distance between the instances at two indices.
+    pub(crate) fn sample<U: Number, F: Fn(usize, usize) -> U>(
+        cardinality: usize,
+        num_samples: usize,
+        seed: u64,
+        distance: F,
+    ) -> Self {
+        let mut report = Self {
+            num_samples,
+            ..Self::default()
+        };
+        if cardinality == 0 {
+            return report;
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let tolerance = U::epsilon() * U::from(4);
+        for _ in 0..num_samples {
+            let [x, y, z] = [(); 3].map(|()| rng.gen_range(0..cardinality));
+
+            if distance(x, x) != U::zero() {
+                report.identity += 1;
+            }
+
+            let (xy, yx) = (distance(x, y), distance(y, x));
+            if xy != yx {
+                report.symmetry += 1;
+            }
+            if xy < U::zero() || yx < U::zero() {
+                report.non_negativity += 1;
+            }
+
+            let (yz, xz) = (distance(y, z), distance(x, z));
+            let sum = xy + yz;
+            if xz > sum + sum * tolerance {
+                report.triangle_inequality += 1;
+            }
+        }
+
+        report
+    }
+}
+
+impl From<MetricReport> for MetricProperties {
+    fn from(report: MetricReport) -> Self {
+        Self {
+            identity: report.identity == 0,
+            symmetry: report.symmetry == 0,
+            non_negativity: report.non_negativity == 0,
+            triangle_inequality: report.triangle_inequality == 0,
+        }
+    }
+}
+
+/// Builds a distance function along with the `MetricProperties` it satisfies.
+///
+/// By default, all properties are assumed to hold. Use `infer_properties` to
+/// set them from sampled evaluations of the distance function instead.
+#[derive(Debug, Clone, Copy)]
+pub struct MetricBuilder<I, U: Number> {
+    /// The distance function.
+    metric: fn(&I, &I) -> U,
+    /// The properties assumed of the distance function.
+    properties: MetricProperties,
+}
+
+impl<I, U: Number> MetricBuilder<I, U> {
+    /// The number of sampled triples below which inference is uncertain.
+    pub const MIN_CONFIDENT_SAMPLES: usize = 100;
+
+    /// Creates a new builder for the given distance function.
+    #[must_use]
+    pub fn new(metric: fn(&I, &I) -> U) -> Self {
+        Self {
+            metric,
+            properties: MetricProperties::default(),
+        }
+    }
+
+    /// Manually sets the properties of the distance function.
+    #[must_use]
+    pub const fn with_properties(mut self, properties: MetricProperties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Empirically sets the properties of the distance function by sampling
+    /// triples of instances and checking for violations of each property.
+    ///
+    /// A property is set only if no violations of it were found. Sampling can
+    /// only ever disprove a property, so a warning is logged if too few
+    /// triples could be sampled, or if a property was violated only once
+    /// (which may be due to floating-point noise).
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - The instances to sample triples from.
+    /// * `seed` - The seed for the random number generator.
+    #[must_use]
+    pub fn infer_properties(mut self, samples: &[I], seed: u64) -> Self {
+        let num_samples = samples.len().pow(2).min(10 * Self::MIN_CONFIDENT_SAMPLES);
+        let report = MetricReport::sample(samples.len(), num_samples, seed, |i, j| {
+            (self.metric)(&samples[i], &samples[j])
+        });
+
+        if samples.len() < 3 || num_samples < Self::MIN_CONFIDENT_SAMPLES {
+            mt_log!(
+                Level::Warning,
+                "Inferred metric properties from only {num_samples} triples of {} instances; they may be wrong.",
+                samples.len()
+            );
+        }
+        let counts = [
+            ("identity", report.identity),
+            ("symmetry", report.symmetry),
+            ("non-negativity", report.non_negativity),
+            ("triangle inequality", report.triangle_inequality),
+        ];
+        for (name, count) in counts {
+            if count == 1 {
+                mt_log!(
+                    Level::Warning,
+                    "Found a single violation of {name} in {num_samples} triples; it may be due to floating-point error."
+                );
+            }
+        }
+
+        self.properties = MetricProperties::from(report);
+        self
+    }
+
+    /// Returns the distance function and its properties.
+    #[must_use]
+    pub const fn build(self) -> (fn(&I, &I) -> U, MetricProperties) {
+        (self.metric, self.properties)
+    }
+}
+
+impl<T: Number, U: Float> MetricBuilder<Vec<T>, U> {
+    /// Creates a builder for the angular distance, `arccos(c) / π` where `c` is
+    /// the cosine similarity.
+    ///
+    /// Unlike the cosine distance, the angular distance obeys the triangle
+    /// inequality, so all properties are set and searches are exact. The
+    /// vectors are normalized internally. See `distances::vectors::angular`.
+    #[must_use]
+    pub fn angular() -> Self {
+        Self::new(angular)
+    }
+}
+
+/// `distances::vectors::angular` with the signature of a metric over `Vec`s.
+#[allow(clippy::ptr_arg)]
+fn angular<T: Number, U: Float>(x: &Vec<T>, y: &Vec<T>) -> U {
+    distances::vectors::angular(x, y)
+}
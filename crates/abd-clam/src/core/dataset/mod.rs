@@ -1,4 +1,5 @@
-//! Provides the `Dataset` trait and an implementation for a vector of data.
+//! Provides the `Dataset` trait and implementations for a vector of data and
+//! for a precomputed distance matrix.
 
 use core::{fmt::Debug, ops::Index};
 
@@ -8,13 +9,30 @@ use distances::Number;
 use rand::prelude::*;
 use rayon::prelude::*;
 
+mod cross;
+mod distance_cache;
 mod instance;
+mod mahalanobis;
+mod matrix;
+mod metric_report;
+mod query_transform;
 mod vec2d;
 
+pub use cross::{CrossDataset, CrossMetric};
+pub use distance_cache::DistanceCache;
+pub use distances::MetricProperties;
 pub use instance::Instance;
+pub use mahalanobis::Mahalanobis;
+#[allow(clippy::module_name_repetitions)]
+pub use matrix::MatrixDataset;
+pub use metric_report::{MetricBuilder, MetricReport};
+pub use query_transform::QueryTransform;
 #[allow(clippy::module_name_repetitions)]
 pub use vec2d::VecDataset;
 
+/// A batched metric computes the distances from a query to each of many instances.
+pub type BatchMetric<I, U> = fn(&I, &[&I]) -> Vec<U>;
+
 /// A common interface for datasets used in CLAM.
 pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Output = I> {
     /// Returns the name of the type of the dataset.
@@ -27,6 +45,51 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
     /// Returns the number of instances in the dataset.
     fn cardinality(&self) -> usize;
 
+    /// Returns the instance at `index`, or `None` if there is no such
+    /// instance.
+    ///
+    /// Unlike indexing, this does not panic. The default implementation
+    /// returns `None` only if `index` is out of bounds. Datasets that cannot
+    /// provide a reference to every instance, e.g. compressed datasets in
+    /// which only some instances are stored in full, should override this to
+    /// return `None` for the others.
+    fn try_get(&self, index: usize) -> Option<&I> {
+        (index < self.cardinality()).then(|| &self[index])
+    }
+
+    /// Returns an owned clone of the instance at `index`.
+    ///
+    /// This is for callers that need to hold on to an instance without
+    /// borrowing the dataset.
+    ///
+    /// # Panics
+    ///
+    /// * If indexing the dataset at `index` panics, e.g. if `index` is out of
+    ///   bounds.
+    fn get_owned(&self, index: usize) -> I {
+        self[index].clone()
+    }
+
+    /// Returns bounds on the dimensionality of the instances, in the style of
+    /// `Iterator::size_hint`.
+    ///
+    /// The first element is a lower bound and the second is an upper bound,
+    /// or `None` if the upper bound is unknown. The default implementation
+    /// returns `(0, None)`, which is correct for any dataset.
+    fn dimensionality_hint(&self) -> (usize, Option<usize>) {
+        (0, None)
+    }
+
+    /// Returns the dimensionality of the instances, if it is known to be the
+    /// same for every instance, i.e. if the bounds from `dimensionality_hint`
+    /// coincide.
+    fn dimensionality(&self) -> Option<usize> {
+        match self.dimensionality_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        }
+    }
+
     /// Whether or not the metric is expensive to calculate.
     ///
     /// If the metric is expensive to calculate, CLAM will enable more parallelism
@@ -45,6 +108,52 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
     /// then CLAM can make certain guarantees about the exactness of search results.
     fn metric(&self) -> fn(&I, &I) -> U;
 
+    /// Returns the optional batched version of the metric.
+    ///
+    /// A batched metric computes the distances from a query to many instances
+    /// in one call. This lets users provide, e.g., SIMD implementations that
+    /// would not be possible one pair at a time. When this returns `Some`,
+    /// `query_to_many` uses it instead of repeatedly calling `metric`.
+    fn batch_metric(&self) -> Option<BatchMetric<I, U>> {
+        None
+    }
+
+    /// Returns the optional weight of each instance, in the order in which the
+    /// instances are stored.
+    ///
+    /// Weights only affect the choice of the centers of `Cluster`s. See
+    /// `total_distance`.
+    fn weights(&self) -> Option<&[f64]> {
+        None
+    }
+
+    /// The total of the `distances` from an instance to the instances at
+    /// `indices`, each weighted by the weight of the instance if the dataset
+    /// has `weights`.
+    ///
+    /// The center of a `Cluster` is the candidate that minimizes this total.
+    ///
+    /// # Arguments
+    ///
+    /// * `distances` - The distances to the instances at `indices`.
+    /// * `indices` - Indices in the dataset.
+    ///
+    /// # Returns
+    ///
+    /// The (weighted) total distance.
+    fn total_distance(&self, distances: &[U], indices: &[usize]) -> f64 {
+        self.weights().map_or_else(
+            || distances.iter().copied().sum::<U>().as_f64(),
+            |weights| {
+                distances
+                    .iter()
+                    .zip(indices)
+                    .map(|(d, &i)| d.as_f64() * weights[i])
+                    .sum()
+            },
+        )
+    }
+
     /// Sets the permutation of indices that was used to reorder the dataset.
     ///
     /// This is primarily used when permuting the dataset to reorder it after
@@ -154,6 +263,22 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
         self.metric()(&self[left], &self[right])
     }
 
+    /// Like `one_to_one`, but reuses the distance from `cache` if it was
+    /// already computed, and otherwise computes it and stores it in `cache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache of distances for the current search.
+    /// * `left` - An index in the dataset.
+    /// * `right` - An index in the dataset.
+    ///
+    /// # Returns
+    ///
+    /// The distance between the instances at `left` and `right`.
+    fn one_to_one_cached(&self, cache: &mut DistanceCache<U>, left: usize, right: usize) -> U {
+        cache.get_or_insert_with(left, right, || self.one_to_one(left, right))
+    }
+
     /// Returns whether or not two indexed instances in the dataset are equal.
     ///
     /// As per the definition of a metric, this should return `true` if and only if
@@ -264,6 +389,53 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
         self.metric()(query, &self[index])
     }
 
+    /// Like `query_to_one`, but reuses the distance from `cache` if it was
+    /// already computed, and otherwise computes it and stores it in `cache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache of distances for the current search.
+    /// * `query` - The query of the current search.
+    /// * `index` - An index in the dataset.
+    ///
+    /// # Returns
+    ///
+    /// The distance between the query and the instance at `index`.
+    fn query_to_one_cached(&self, cache: &mut DistanceCache<U>, query: &I, index: usize) -> U {
+        cache.get_or_insert_query_with(index, || self.query_to_one(query, index))
+    }
+
+    /// Like `query_to_many`, but reuses the distances from `cache` that were
+    /// already computed. The new distances are not stored in `cache`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache of distances for the current search.
+    /// * `query` - The query of the current search.
+    /// * `indices` - A slice of indices in the dataset.
+    ///
+    /// # Returns
+    ///
+    /// A vector of distances between the query and all instances at `indices`.
+    fn query_to_many_cached(&self, cache: &DistanceCache<U>, query: &I, indices: &[usize]) -> Vec<U> {
+        let uncached = indices
+            .iter()
+            .copied()
+            .filter(|&i| cache.get_query(i).is_none())
+            .collect::<Vec<_>>();
+        let mut distances = self.query_to_many(query, &uncached).into_iter();
+        indices
+            .iter()
+            .map(|&i| {
+                cache.get_query(i).unwrap_or_else(|| {
+                    distances
+                        .next()
+                        .unwrap_or_else(|| unreachable!("Every uncached index has a distance."))
+                })
+            })
+            .collect()
+    }
+
     /// Returns a vector of distances between a query and all indexed instances.
     ///
     /// # Arguments
@@ -275,6 +447,11 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
     ///
     /// A vector of distances between the query and all instances at `indices`
     fn query_to_many(&self, query: &I, indices: &[usize]) -> Vec<U> {
+        if let Some(batch_metric) = self.batch_metric() {
+            let instances = indices.iter().map(|&index| &self[index]).collect::<Vec<_>>();
+            return batch_metric(query, &instances);
+        }
+
         if self.is_metric_expensive() {
             indices
                 .par_iter()
@@ -285,6 +462,42 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
         }
     }
 
+    /// Returns a vector of distances between a query and every instance in the
+    /// dataset.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A query instance.
+    ///
+    /// # Returns
+    ///
+    /// A vector of distances between the query and all instances, in the order
+    /// in which the instances are stored.
+    fn query_to_all(&self, query: &I) -> Vec<U> {
+        self.query_to_many(query, &(0..self.cardinality()).collect::<Vec<_>>())
+    }
+
+    /// Parallelized version of `query_to_all`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - A query instance.
+    ///
+    /// # Returns
+    ///
+    /// A vector of distances between the query and all instances, in the order
+    /// in which the instances are stored.
+    fn par_query_to_all(&self, query: &I) -> Vec<U> {
+        if self.batch_metric().is_some() {
+            return self.query_to_all(query);
+        }
+
+        (0..self.cardinality())
+            .into_par_iter()
+            .map(|index| self.query_to_one(query, index))
+            .collect()
+    }
+
     /// Chooses a subset of indices that are unique with respect to the metric.
     ///
     /// # Arguments
@@ -336,6 +549,9 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
     ///
     /// Note: This default implementation does not scale well to arbitrarily large inputs.
     ///
+    /// If the dataset has `weights`, the distances are weighted as in
+    /// `total_distance`.
+    ///
     /// # Arguments
     ///
     /// `indices` - A subset of indices from the dataset
@@ -355,12 +571,134 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
             .into_iter()
             // TODO: Bench using .max instead of .sum
             // .map(|v| v.into_iter().max_by(|l, r| l.partial_cmp(r).unwrap()).unwrap())
-            .map(|v| v.into_iter().sum::<U>())
+            .map(|v| self.total_distance(&v, indices))
             .collect::<Vec<_>>();
 
         crate::utils::arg_min(&distances).map(|(i, _)| indices[i])
     }
 
+    /// Finds the medoid of a set of indexed instances, i.e. the instance which
+    /// minimizes the sum of distances to all other instances in the set.
+    ///
+    /// For small sets, this is exact. For large sets, only an evenly spaced
+    /// sample of the instances is considered as candidates for the medoid, so
+    /// the result is an approximation.
+    ///
+    /// If the dataset has `weights`, the distances are weighted as in
+    /// `total_distance`.
+    ///
+    /// # Arguments
+    ///
+    /// * `indices` - A subset of indices from the dataset.
+    ///
+    /// # Panics
+    ///
+    /// * If `indices` is empty.
+    ///
+    /// # Returns
+    ///
+    /// The index of the medoid in the dataset.
+    fn arg_medoid(&self, indices: &[usize]) -> usize {
+        assert!(!indices.is_empty(), "Cannot find the medoid of an empty set.");
+
+        let candidates = medoid_candidates(indices);
+        let sums = candidates
+            .iter()
+            .map(|&c| self.total_distance(&self.one_to_many(c, indices), indices))
+            .collect::<Vec<_>>();
+
+        let Some((arg_medoid, _)) = crate::utils::arg_min(&sums) else {
+            unreachable!("We checked that there is at least one candidate.")
+        };
+        candidates[arg_medoid]
+    }
+
+    /// Parallelized version of `arg_medoid`.
+    ///
+    /// # Arguments
+    ///
+    /// * `indices` - A subset of indices from the dataset.
+    ///
+    /// # Panics
+    ///
+    /// * If `indices` is empty.
+    ///
+    /// # Returns
+    ///
+    /// The index of the medoid in the dataset.
+    fn par_arg_medoid(&self, indices: &[usize]) -> usize {
+        assert!(!indices.is_empty(), "Cannot find the medoid of an empty set.");
+
+        let candidates = medoid_candidates(indices);
+        let sums = candidates
+            .par_iter()
+            .map(|&c| self.total_distance(&self.one_to_many(c, indices), indices))
+            .collect::<Vec<_>>();
+
+        let Some((arg_medoid, _)) = crate::utils::arg_min(&sums) else {
+            unreachable!("We checked that there is at least one candidate.")
+        };
+        candidates[arg_medoid]
+    }
+
+    /// Estimates the diameter of the dataset, i.e. the largest distance between
+    /// any two instances, with the double-sweep farthest-point heuristic.
+    ///
+    /// Each iteration starts from a random instance, finds the instance
+    /// farthest from it, and then the instance farthest from that one. The
+    /// distance found by the second sweep is a lower bound on the diameter
+    /// that is at least half of the diameter, and the largest such distance
+    /// over all iterations is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `iterations` - The number of random starting instances. At least one
+    ///   is always used.
+    /// * `seed` - The seed for the random number generator.
+    ///
+    /// # Returns
+    ///
+    /// The estimated diameter, or zero if the dataset is empty.
+    fn approx_diameter(&self, iterations: usize, seed: u64) -> U {
+        approx_diameter(self.cardinality(), iterations, seed, |i| self.query_to_all(&self[i]))
+    }
+
+    /// Parallelized version of `approx_diameter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `iterations` - The number of random starting instances. At least one
+    ///   is always used.
+    /// * `seed` - The seed for the random number generator.
+    ///
+    /// # Returns
+    ///
+    /// The estimated diameter, or zero if the dataset is empty.
+    fn par_approx_diameter(&self, iterations: usize, seed: u64) -> U {
+        approx_diameter(self.cardinality(), iterations, seed, |i| {
+            self.par_query_to_all(&self[i])
+        })
+    }
+
+    /// Empirically checks whether the metric obeys the properties that CLAM
+    /// relies on.
+    ///
+    /// This samples triples of instances `(x, y, z)` and counts the violations
+    /// of identity, symmetry, non-negativity and the triangle inequality. Small
+    /// floating-point errors are tolerated for the triangle inequality.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_samples` - The number of triples of instances to sample.
+    /// * `seed` - The seed for the random number generator.
+    ///
+    /// # Returns
+    ///
+    /// A `MetricReport` with the number of violations of each property.
+    fn check_metric_properties(&self, num_samples: usize, seed: u64) -> MetricReport {
+        MetricReport::sample(self.cardinality(), num_samples, seed, |x, y| self.one_to_one(x, y))
+    }
+
     /// Makes a vector of sharded datasets from the given dataset.
     ///
     /// Each shard will be a random subset of the dataset, and will have a
@@ -402,3 +740,51 @@ pub trait Dataset<I: Instance, U: Number>: Debug + Send + Sync + Index<usize, Ou
     where
         Self: Sized;
 }
+
+/// Sets with at most this many instances have their medoid computed exactly.
+const MEDOID_EXACT_CARDINALITY: usize = 100;
+
+/// The double-sweep farthest-point heuristic behind `Dataset::approx_diameter`.
+///
+/// `distances_from` returns the distances from the instance at the given index
+/// to all instances, in the order in which they are stored.
+fn approx_diameter<U: Number, F: Fn(usize) -> Vec<U>>(
+    cardinality: usize,
+    iterations: usize,
+    seed: u64,
+    distances_from: F,
+) -> U {
+    if cardinality == 0 {
+        return U::zero();
+    }
+
+    let farthest_from = |i: usize| {
+        let Some((j, d)) = crate::utils::arg_max(&distances_from(i)) else {
+            unreachable!("The dataset is not empty.")
+        };
+        (j, d)
+    };
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..iterations.max(1))
+        .map(|_| {
+            let (a, _) = farthest_from(rng.gen_range(0..cardinality));
+            let (_, d) = farthest_from(a);
+            d
+        })
+        .fold(U::zero(), |diameter, d| if d > diameter { d } else { diameter })
+}
+
+/// Chooses the candidates for the medoid of a set of indices.
+///
+/// If there are at most `MEDOID_EXACT_CARDINALITY` indices, all of them are
+/// candidates. Otherwise, about `sqrt(n)` evenly spaced indices are chosen.
+fn medoid_candidates(indices: &[usize]) -> Vec<usize> {
+    if indices.len() <= MEDOID_EXACT_CARDINALITY {
+        indices.to_vec()
+    } else {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let n = indices.len().as_f64().sqrt() as usize;
+        indices.iter().step_by(indices.len() / n).copied().collect()
+    }
+}
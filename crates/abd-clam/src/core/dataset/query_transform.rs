@@ -0,0 +1,41 @@
+//! Transformations that were applied to a dataset and must also be applied
+//! to queries.
+
+use distances::Number;
+
+/// A transformation of the instances of a dataset, which must be applied to
+/// each query before searching the dataset.
+///
+/// See `VecDataset::standardize`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryTransform {
+    /// Each dimension is z-scored with the given means and standard deviations.
+    Standardize {
+        /// The mean of each dimension.
+        means: Vec<f64>,
+        /// The standard deviation of each dimension.
+        stds: Vec<f64>,
+    },
+}
+
+impl QueryTransform {
+    /// Applies the transformation to a query.
+    ///
+    /// # Arguments
+    ///
+    /// * `query`: The query, in the space of the original instances.
+    ///
+    /// # Returns
+    ///
+    /// The query in the space of the transformed instances.
+    #[must_use]
+    pub fn apply(&self, query: &[f32]) -> Vec<f32> {
+        match self {
+            Self::Standardize { means, stds } => query
+                .iter()
+                .zip(means.iter().zip(stds))
+                .map(|(&v, (m, s))| ((v.as_f64() - m) / s).as_f32())
+                .collect(),
+        }
+    }
+}
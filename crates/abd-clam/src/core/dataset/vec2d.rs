@@ -1,19 +1,25 @@
 //! A dataset of a Vec of instances.
 
-use core::{fmt::Debug, ops::Index};
+use core::{
+    fmt::{Debug, Display},
+    hash::Hash,
+    ops::Index,
+};
 
 use std::{
+    collections::HashMap,
     fs::File,
     io::{BufWriter, Read, Write},
     path::Path,
 };
 
 use distances::Number;
+use rand::prelude::*;
 use rayon::prelude::*;
 
-use crate::Dataset;
+use crate::{cakes::kernels, knn, rnn, Cluster, Dataset, Tree};
 
-use super::Instance;
+use super::{BatchMetric, Instance, MetricProperties, QueryTransform};
 
 /// A `Dataset` of a `Vec` of instances.
 ///
@@ -32,12 +38,24 @@ pub struct VecDataset<I: Instance, U: Number, M: Instance> {
     data: Vec<I>,
     /// The metric of the dataset.
     metric: fn(&I, &I) -> U,
+    /// The optional batched version of the metric.
+    batch_metric: Option<BatchMetric<I, U>>,
     /// Whether the metric is expensive to compute.
     is_expensive: bool,
+    /// The declared name of the metric, compared by `append`.
+    metric_name: String,
+    /// The declared properties of the metric, compared by `append`.
+    metric_properties: MetricProperties,
     /// The reordering of the dataset after building the tree.
     permuted_indices: Option<Vec<usize>>,
     /// Metadata about the dataset.
     metadata: Vec<M>,
+    /// The transformation that was applied to the instances, and must be
+    /// applied to queries.
+    query_transform: Option<QueryTransform>,
+    /// The optional weight of each instance, used when choosing the centers
+    /// of `Cluster`s.
+    weights: Option<Vec<f64>>,
 }
 
 impl<I: Instance, U: Number> VecDataset<I, U, usize> {
@@ -55,11 +73,58 @@ impl<I: Instance, U: Number> VecDataset<I, U, usize> {
             name,
             data,
             metric,
+            batch_metric: None,
             is_expensive,
+            metric_name: String::new(),
+            metric_properties: MetricProperties::default(),
             permuted_indices: None,
             metadata,
+            query_transform: None,
+            weights: None,
         }
     }
+
+    /// Creates a new dataset from an iterator over the instances.
+    ///
+    /// The instances are collected directly into the dataset, so the caller
+    /// does not need to hold a separate `Vec` of all instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the dataset.
+    /// * `instances`: The instances.
+    /// * `metric`: The metric for computing distances between instances.
+    /// * `is_expensive`: Whether the metric is expensive to compute.
+    pub fn from_iter<T: IntoIterator<Item = I>>(
+        name: String,
+        instances: T,
+        metric: fn(&I, &I) -> U,
+        is_expensive: bool,
+    ) -> Self {
+        Self::new(name, instances.into_iter().collect(), metric, is_expensive)
+    }
+
+    /// Parallelized version of `from_iter`.
+    ///
+    /// Since the iterator is indexed, the instances are written directly into
+    /// a `Vec` allocated with the exact length.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the dataset.
+    /// * `instances`: The instances.
+    /// * `metric`: The metric for computing distances between instances.
+    /// * `is_expensive`: Whether the metric is expensive to compute.
+    pub fn par_from_iter<T: IndexedParallelIterator<Item = I>>(
+        name: String,
+        instances: T,
+        metric: fn(&I, &I) -> U,
+        is_expensive: bool,
+    ) -> Self {
+        let mut data = Vec::new();
+        instances.collect_into_vec(&mut data);
+        Self::new(name, data, metric, is_expensive)
+    }
 }
 
 impl<I: Instance, U: Number, M: Instance> VecDataset<I, U, M> {
@@ -89,9 +154,14 @@ impl<I: Instance, U: Number, M: Instance> VecDataset<I, U, M> {
                 name: self.name,
                 data: self.data,
                 metric: self.metric,
+                batch_metric: self.batch_metric,
                 is_expensive: self.is_expensive,
+                metric_name: self.metric_name,
+                metric_properties: self.metric_properties,
                 permuted_indices: self.permuted_indices,
                 metadata,
+                query_transform: self.query_transform,
+                weights: self.weights,
             })
         } else {
             Err(format!(
@@ -102,6 +172,272 @@ impl<I: Instance, U: Number, M: Instance> VecDataset<I, U, M> {
         }
     }
 
+    /// Derives new metadata from the existing metadata of each instance.
+    ///
+    /// The metadata stays aligned with the instances, including after the
+    /// dataset has been permuted.
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: The function that maps the metadata of an instance to its new
+    ///   metadata.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the new metadata.
+    pub fn map_metadata<Mn: Instance, F: Fn(&M) -> Mn>(self, f: F) -> VecDataset<I, U, Mn> {
+        VecDataset {
+            name: self.name,
+            data: self.data,
+            metric: self.metric,
+            batch_metric: self.batch_metric,
+            is_expensive: self.is_expensive,
+            metric_name: self.metric_name,
+            metric_properties: self.metric_properties,
+            permuted_indices: self.permuted_indices,
+            metadata: self.metadata.iter().map(f).collect(),
+            query_transform: self.query_transform,
+            weights: self.weights,
+        }
+    }
+
+    /// Replaces the metric of the dataset.
+    ///
+    /// Any batched metric is removed, since it computes the old metric. The
+    /// declared name and properties of the old metric are reset as well.
+    ///
+    /// The radii and other geometry of a tree built on this dataset were
+    /// computed with the old metric, so searching such a tree with the new
+    /// metric is unsound and may miss hits. Use
+    /// `Tree::with_new_metric_recomputing` to switch the metric of a tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric`: The new metric.
+    /// * `is_expensive`: Whether the new metric is expensive to compute.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the new metric.
+    #[must_use]
+    pub fn with_metric(mut self, metric: fn(&I, &I) -> U, is_expensive: bool) -> Self {
+        self.metric = metric;
+        self.is_expensive = is_expensive;
+        self.batch_metric = None;
+        self.metric_name = String::new();
+        self.metric_properties = MetricProperties::default();
+        self
+    }
+
+    /// Declares the name of the metric of the dataset.
+    ///
+    /// `append` only merges datasets whose metrics have the same declared name
+    /// and properties. By default, the name is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name of the metric.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the name of the metric declared.
+    #[must_use]
+    pub fn with_metric_name(mut self, name: &str) -> Self {
+        self.metric_name = name.to_string();
+        self
+    }
+
+    /// Declares the `MetricProperties` satisfied by the metric of the dataset,
+    /// e.g. as returned by `MetricBuilder::build`.
+    ///
+    /// `append` only merges datasets whose metrics have the same declared name
+    /// and properties. By default, all properties are assumed to hold.
+    ///
+    /// # Arguments
+    ///
+    /// * `properties`: The properties of the metric.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the properties of the metric declared.
+    #[must_use]
+    pub const fn with_metric_properties(mut self, properties: MetricProperties) -> Self {
+        self.metric_properties = properties;
+        self
+    }
+
+    /// Returns the declared name of the metric of the dataset.
+    #[must_use]
+    pub fn metric_name(&self) -> &str {
+        &self.metric_name
+    }
+
+    /// Returns the declared `MetricProperties` of the metric of the dataset.
+    #[must_use]
+    pub const fn metric_properties(&self) -> MetricProperties {
+        self.metric_properties
+    }
+
+    /// Assigns a batched version of the metric to the dataset.
+    ///
+    /// The batched metric must agree with the metric of the dataset. It
+    /// computes the distances from a query to each of the given instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `batch_metric`: The batched version of the metric.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the batched metric assigned.
+    #[must_use]
+    pub fn with_batch_metric(mut self, batch_metric: BatchMetric<I, U>) -> Self {
+        self.batch_metric = Some(batch_metric);
+        self
+    }
+
+    /// Shuffles the instances in the dataset.
+    ///
+    /// The permutation is recorded so that `original_index` still maps each
+    /// instance back to its index before any reordering.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed`: The seed for the random number generator.
+    pub fn shuffle(&mut self, seed: u64) {
+        let mut permutation = (0..self.data.len()).collect::<Vec<_>>();
+        permutation.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+
+        let original_indices = permutation
+            .iter()
+            .map(|&index| self.original_index(index))
+            .collect::<Vec<_>>();
+
+        self.permute_instances(&permutation)
+            .unwrap_or_else(|e| unreachable!("{e}"));
+        self.set_permuted_indices(Some(&original_indices));
+    }
+
+    /// Appends the instances and metadata of another dataset to this one.
+    ///
+    /// The metric of the other dataset must have the same declared name and
+    /// properties as this one, see `with_metric_name` and
+    /// `with_metric_properties`. The metric functions themselves cannot be
+    /// compared, so the metric, batched metric and name of this dataset are
+    /// kept. Its instances are appended in their current order, and are given
+    /// the original indices following those of this dataset, i.e. any
+    /// permutation of this dataset is extended with the identity for the new
+    /// instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `other`: The dataset to append.
+    ///
+    /// # Errors
+    ///
+    /// * If the metrics of the two datasets have different declared names or
+    ///   properties.
+    /// * If the metrics of the two datasets differ in whether they are
+    ///   expensive to compute.
+    /// * If the two datasets have different query transforms, since the
+    ///   instances of `other` would not be in the same space as those of this
+    ///   dataset.
+    pub fn append(&mut self, other: Self) -> Result<(), String> {
+        if self.metric_name != other.metric_name || self.metric_properties != other.metric_properties {
+            return Err(format!(
+                "Cannot append {} to {}. They have different metrics.",
+                other.name, self.name
+            ));
+        }
+        if self.query_transform != other.query_transform {
+            return Err(format!(
+                "Cannot append {} to {}. They have different query transforms.",
+                other.name, self.name
+            ));
+        }
+        if self.is_expensive != other.is_expensive {
+            return Err(format!(
+                "Cannot append {} to {}. Their metrics differ in whether they are expensive.",
+                other.name, self.name
+            ));
+        }
+
+        let offset = self.data.len();
+        if let Some(permutation) = self.permuted_indices.as_mut() {
+            permutation.extend(offset..(offset + other.data.len()));
+        }
+        if self.weights.is_some() || other.weights.is_some() {
+            let (n, m) = (self.data.len(), other.data.len());
+            let mut weights = self.weights.take().unwrap_or_else(|| vec![1.0; n]);
+            weights.extend(other.weights.unwrap_or_else(|| vec![1.0; m]));
+            self.weights = Some(weights);
+        }
+        self.data.extend(other.data);
+        self.metadata.extend(other.metadata);
+
+        Ok(())
+    }
+
+    /// Splits the dataset into consecutive chunks, each of which is a new
+    /// dataset from which a `Tree` can be built.
+    ///
+    /// This is meant for datasets too large to build a single tree from. The
+    /// chunks are cloned lazily, one at a time, and can be recombined with
+    /// `append`. Each chunk keeps the metric, batched metric and metadata of
+    /// this dataset, and is named `<name>-chunk-<i>`.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size`: The maximum number of instances in each chunk. The last
+    ///   chunk may be smaller. A `chunk_size` of `0` is treated as `1`.
+    pub fn chunks(&self, chunk_size: usize) -> impl Iterator<Item = Self> + '_ {
+        let chunk_size = chunk_size.max(1);
+        self.data
+            .chunks(chunk_size)
+            .zip(self.metadata.chunks(chunk_size))
+            .enumerate()
+            .map(move |(i, (data, metadata))| Self {
+                name: format!("{}-chunk-{i}", self.name),
+                data: data.to_vec(),
+                metric: self.metric,
+                batch_metric: self.batch_metric,
+                is_expensive: self.is_expensive,
+                metric_name: self.metric_name.clone(),
+                metric_properties: self.metric_properties,
+                permuted_indices: None,
+                metadata: metadata.to_vec(),
+                query_transform: self.query_transform.clone(),
+                weights: self
+                    .weights
+                    .as_ref()
+                    .map(|w| w[(i * chunk_size)..(i * chunk_size + data.len())].to_vec()),
+            })
+    }
+
+    /// Splits the indices of the dataset into two sets, e.g. for training and
+    /// testing. Call `shuffle` first for a random split.
+    ///
+    /// # Arguments
+    ///
+    /// * `fraction`: The fraction of the instances to put in the first set.
+    ///
+    /// # Returns
+    ///
+    /// The first `fraction` of the indices and the remaining indices.
+    ///
+    /// # Errors
+    ///
+    /// * If `fraction` is not in the range `[0, 1]`.
+    pub fn split(&self, fraction: f64) -> Result<(Vec<usize>, Vec<usize>), String> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(format!("Invalid fraction {fraction}. Expected a value in [0, 1]."));
+        }
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let at = (fraction * self.data.len().as_f64()).round() as usize;
+        Ok(((0..at).collect(), (at..self.data.len()).collect()))
+    }
+
     /// A reference to the underlying data.
     #[must_use]
     pub fn data(&self) -> &[I] {
@@ -131,6 +467,345 @@ impl<I: Instance, U: Number, M: Instance> VecDataset<I, U, M> {
     pub fn metadata_of(&self, index: usize) -> &M {
         &self.metadata[index]
     }
+
+    /// Returns the transformation that was applied to the instances, and must
+    /// be applied to queries, if any.
+    ///
+    /// This is set by `VecDataset::standardize`. It is not written by `save`,
+    /// so it must be restored with `with_query_transform` after `load`.
+    #[must_use]
+    pub const fn query_transform(&self) -> Option<&QueryTransform> {
+        self.query_transform.as_ref()
+    }
+
+    /// Sets the transformation that was applied to the instances, and must be
+    /// applied to queries.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform`: The transformation.
+    #[must_use]
+    pub fn with_query_transform(mut self, transform: QueryTransform) -> Self {
+        self.query_transform = Some(transform);
+        self
+    }
+
+    /// Assigns a weight to each instance.
+    ///
+    /// When choosing the center of a `Cluster`, the total distance from each
+    /// candidate to the instances in the `Cluster` is weighted by these, so
+    /// heavier instances pull the center toward them. Search is unaffected.
+    /// The weights are kept aligned with the instances when the dataset is
+    /// permuted, but are not written by `save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights`: The weight of each instance, in the original order of the
+    ///   instances.
+    ///
+    /// # Errors
+    ///
+    /// * If `weights` is not the same length as the dataset.
+    /// * If any weight is negative or not finite.
+    pub fn with_weights(mut self, weights: Vec<f64>) -> Result<Self, String> {
+        if weights.len() != self.data.len() {
+            return Err(format!(
+                "Invalid weights. Expected weights of length {}, got weights of length {}",
+                self.cardinality(),
+                weights.len()
+            ));
+        }
+        if let Some(i) = weights.iter().position(|w| !w.is_finite() || *w < 0.0) {
+            return Err(format!("Invalid weights. The weight at index {i} is {}", weights[i]));
+        }
+
+        // If there is a permutation, permute the weights as well.
+        self.weights = Some(if let Some(permutation) = self.permuted_indices.as_ref() {
+            permutation.iter().map(|&index| weights[index]).collect()
+        } else {
+            weights
+        });
+        Ok(self)
+    }
+}
+
+impl<I: Instance, U: Number, M: Instance, C: Cluster<U>> Tree<I, U, VecDataset<I, U, M>, C> {
+    /// Switches the metric of the dataset and recomputes the geometry of the
+    /// tree under the new metric.
+    ///
+    /// Searching the tree after switching the metric without recomputing the
+    /// geometry is unsound, because the radii of the `Cluster`s were computed
+    /// with the old metric. The structure of the tree is kept, so the tree may
+    /// be less well suited to the new metric than a tree built from scratch.
+    ///
+    /// The poles of the `Cluster`s are kept as well, so only the search
+    /// algorithms that prune with the centers and radii of the `Cluster`s,
+    /// i.e. `knn::Algorithm::GreedySieve`, `Sieve` and `SieveSepCenter`, are
+    /// exact under the new metric. `rnn::Algorithm::Clustered`, and the
+    /// `knn::Algorithm`s built on it, may miss hits.
+    ///
+    /// See `VecDataset::with_metric` and `Cluster::recompute_geometry` for
+    /// more details.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric`: The new metric.
+    /// * `is_expensive`: Whether the new metric is expensive to compute.
+    ///
+    /// # Returns
+    ///
+    /// The tree with the new metric.
+    #[must_use]
+    pub fn with_new_metric_recomputing(mut self, metric: fn(&I, &I) -> U, is_expensive: bool) -> Self {
+        self.data = self.data.with_metric(metric, is_expensive);
+        self.par_recompute_geometry()
+    }
+}
+
+impl<M: Instance> VecDataset<Vec<f32>, f32, M> {
+    /// Sets the metric to the squared Euclidean distance, with the batched
+    /// kernel `kernels::batch_euclidean_sq` used by `query_to_many`.
+    ///
+    /// As with `with_metric`, a tree built on this dataset with another
+    /// metric must not be searched with this one.
+    ///
+    /// # Returns
+    ///
+    /// The dataset with the new metric and batched metric.
+    #[must_use]
+    pub fn with_euclidean_sq_kernel(self) -> Self {
+        self.with_metric(kernels::euclidean_sq, false)
+            .with_batch_metric(kernels::batch_euclidean_sq)
+    }
+}
+
+impl<U: Number, M: Instance> VecDataset<Vec<f32>, U, M> {
+    /// Standardizes each dimension in place to have zero mean and unit
+    /// (population) standard deviation.
+    ///
+    /// A dimension with zero variance is only centered, and its standard
+    /// deviation is returned as `1.0`, so that applying the returned transform
+    /// never divides by zero.
+    ///
+    /// The transform is also stored as the `query_transform` of the dataset,
+    /// composed with any transform that was already stored, so that queries
+    /// in the original space can be searched with `Tree::knn_search_transformed`
+    /// and `Tree::rnn_search_transformed`.
+    ///
+    /// # Returns
+    ///
+    /// The mean and standard deviation of each dimension. A new instance `x`
+    /// is transformed identically by `(x[j] - means[j]) / stds[j]`.
+    pub fn standardize(&mut self) -> (Vec<f64>, Vec<f64>) {
+        let dimensionality = self.data.first().map_or(0, Vec::len);
+        let n = self.data.len().as_f64();
+
+        let mut means = vec![0.0; dimensionality];
+        for x in &self.data {
+            for (m, &v) in means.iter_mut().zip(x) {
+                *m += v.as_f64();
+            }
+        }
+        for m in &mut means {
+            *m /= n;
+        }
+
+        let mut stds = vec![0.0; dimensionality];
+        for x in &self.data {
+            for ((s, &m), &v) in stds.iter_mut().zip(&means).zip(x) {
+                *s += (v.as_f64() - m).powi(2);
+            }
+        }
+        for s in &mut stds {
+            *s = (*s / n).sqrt();
+            if *s <= f64::EPSILON {
+                *s = 1.0;
+            }
+        }
+
+        let transform = QueryTransform::Standardize {
+            means: means.clone(),
+            stds: stds.clone(),
+        };
+        for x in &mut self.data {
+            *x = transform.apply(x);
+        }
+
+        self.query_transform = Some(match self.query_transform.take() {
+            // Standardizing `(x - m1) / s1` with `m2` and `s2` is the same as
+            // standardizing `x` with `m1 + s1 * m2` and `s1 * s2`.
+            Some(QueryTransform::Standardize { means: m1, stds: s1 }) => QueryTransform::Standardize {
+                means: m1
+                    .iter()
+                    .zip(&s1)
+                    .zip(&means)
+                    .map(|((a, s), b)| s.mul_add(*b, *a))
+                    .collect(),
+                stds: s1.iter().zip(&stds).map(|(a, b)| a * b).collect(),
+            },
+            None => transform,
+        });
+
+        (means, stds)
+    }
+}
+
+impl<U: Number, M: Instance + Display> VecDataset<Vec<f32>, U, M> {
+    /// Writes the dataset as CSV, with one row per instance.
+    ///
+    /// The rows are written in the original order of the instances, i.e.
+    /// undoing any permutation from building a tree, so that the CSV can be
+    /// read back alongside the file the dataset was created from.
+    ///
+    /// # Arguments
+    ///
+    /// * `w`: The writer to write the CSV to.
+    /// * `include_metadata`: Whether to write the metadata of each instance
+    ///   as a leading column.
+    /// * `header`: The names of the columns, written as the first row. If
+    ///   `include_metadata` is `true`, the first name is that of the metadata
+    ///   column.
+    ///
+    /// # Errors
+    ///
+    /// * If the number of names in the `header` is not the number of columns.
+    /// * If the CSV cannot be written.
+    pub fn write_csv<W: Write>(&self, w: &mut W, include_metadata: bool, header: Option<&[&str]>) -> Result<(), String> {
+        if let Some(header) = header {
+            let num_columns = self.data.first().map_or(0, Vec::len) + <usize as From<bool>>::from(include_metadata);
+            if !self.data.is_empty() && header.len() != num_columns {
+                return Err(format!(
+                    "The header has {} names but there are {num_columns} columns.",
+                    header.len()
+                ));
+            }
+            writeln!(w, "{}", header.join(",")).map_err(|e| e.to_string())?;
+        }
+
+        let mut positions = (0..self.cardinality()).collect::<Vec<_>>();
+        if let Some(permutation) = self.permuted_indices.as_ref() {
+            for (i, &original) in permutation.iter().enumerate() {
+                positions[original] = i;
+            }
+        }
+
+        for i in positions {
+            let values = self.data[i].iter().map(ToString::to_string);
+            let row = if include_metadata {
+                core::iter::once(self.metadata[i].to_string())
+                    .chain(values)
+                    .collect::<Vec<_>>()
+            } else {
+                values.collect()
+            };
+            writeln!(w, "{}", row.join(",")).map_err(|e| e.to_string())?;
+        }
+
+        w.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl<U: Number, M: Instance, C: Cluster<U>> Tree<Vec<f32>, U, VecDataset<Vec<f32>, U, M>, C> {
+    /// Performs a KNN search for a query given in the space of the original
+    /// instances, by first applying the `query_transform` of the dataset.
+    ///
+    /// If the dataset has no `query_transform`, the query is used as is.
+    ///
+    /// # Arguments
+    ///
+    /// * `query`: The query, in the space of the original instances.
+    /// * `k`: The number of nearest neighbors to return.
+    /// * `algo`: The algorithm to use.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing the index of the instance and the distance
+    /// to the transformed query.
+    #[must_use]
+    pub fn knn_search_transformed(&self, query: &[f32], k: usize, algo: knn::Algorithm) -> Vec<(usize, U)> {
+        algo.search(self, &self.transform_query(query), k)
+    }
+
+    /// Performs an RNN search for a query given in the space of the original
+    /// instances, by first applying the `query_transform` of the dataset.
+    ///
+    /// If the dataset has no `query_transform`, the query is used as is. The
+    /// `radius` is in the space of the transformed instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `query`: The query, in the space of the original instances.
+    /// * `radius`: The search radius.
+    /// * `algo`: The algorithm to use.
+    ///
+    /// # Returns
+    ///
+    /// A vector of tuples containing the index of the instance and the distance
+    /// to the transformed query.
+    #[must_use]
+    pub fn rnn_search_transformed(&self, query: &[f32], radius: U, algo: rnn::Algorithm) -> Vec<(usize, U)> {
+        algo.search(&self.transform_query(query), radius, self)
+    }
+
+    /// Applies the `query_transform` of the dataset, if any, to a query.
+    fn transform_query(&self, query: &[f32]) -> Vec<f32> {
+        self.data
+            .query_transform()
+            .map_or_else(|| query.to_vec(), |t| t.apply(query))
+    }
+}
+
+impl<I: Instance + Hash + Eq, U: Number, M: Instance> VecDataset<I, U, M> {
+    /// Collapses exact duplicate instances into one.
+    ///
+    /// The first occurrence of each instance is kept, along with its metadata,
+    /// and the instances keep their relative order. Any permutation of this
+    /// dataset is discarded, so the deduplicated dataset starts in its
+    /// original order.
+    ///
+    /// # Returns
+    ///
+    /// The deduplicated dataset, and the index in the deduplicated dataset of
+    /// each instance in this dataset. This mapping can be used to expand the
+    /// results of a search back to all duplicates.
+    #[must_use]
+    pub fn deduplicated(self) -> (Self, Vec<usize>) {
+        let mut first_occurrence = HashMap::new();
+        let mut data = Vec::new();
+        let mut metadata = Vec::new();
+        let mut weights = Vec::new();
+        let mapping = self
+            .data
+            .into_iter()
+            .zip(self.metadata)
+            .enumerate()
+            .map(|(i, (instance, meta))| {
+                *first_occurrence.entry(instance.clone()).or_insert_with(|| {
+                    data.push(instance);
+                    metadata.push(meta);
+                    if let Some(w) = self.weights.as_ref() {
+                        weights.push(w[i]);
+                    }
+                    data.len() - 1
+                })
+            })
+            .collect();
+
+        let deduplicated = Self {
+            name: self.name,
+            data,
+            metric: self.metric,
+            batch_metric: self.batch_metric,
+            is_expensive: self.is_expensive,
+            metric_name: self.metric_name,
+            metric_properties: self.metric_properties,
+            permuted_indices: None,
+            metadata,
+            query_transform: self.query_transform,
+            weights: self.weights.map(|_| weights),
+        };
+        (deduplicated, mapping)
+    }
 }
 
 impl<I: Instance, U: Number, M: Instance> Index<usize> for VecDataset<I, U, M> {
@@ -154,6 +829,17 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
         self.data.len()
     }
 
+    fn dimensionality_hint(&self) -> (usize, Option<usize>) {
+        let mut bounds: Option<(usize, usize)> = None;
+        for instance in &self.data {
+            let Some(d) = instance.dimensionality() else {
+                return (0, None);
+            };
+            bounds = Some(bounds.map_or((d, d), |(lower, upper)| (lower.min(d), upper.max(d))));
+        }
+        bounds.map_or((0, None), |(lower, upper)| (lower, Some(upper)))
+    }
+
     fn is_metric_expensive(&self) -> bool {
         self.is_expensive
     }
@@ -162,6 +848,14 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
         self.metric
     }
 
+    fn batch_metric(&self) -> Option<BatchMetric<I, U>> {
+        self.batch_metric
+    }
+
+    fn weights(&self) -> Option<&[f64]> {
+        self.weights.as_deref()
+    }
+
     fn set_permuted_indices(&mut self, indices: Option<&[usize]>) {
         self.permuted_indices = indices.map(<[usize]>::to_vec);
     }
@@ -169,6 +863,9 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
     fn swap(&mut self, left: usize, right: usize) -> Result<(), String> {
         self.data.swap(left, right);
         self.metadata.swap(left, right);
+        if let Some(weights) = self.weights.as_mut() {
+            weights.swap(left, right);
+        }
         Ok(())
     }
 
@@ -190,6 +887,9 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
             .par_iter()
             .map(|&index| self.metadata[index].clone())
             .collect();
+        if let Some(weights) = self.weights.as_ref() {
+            self.weights = Some(permutation.par_iter().map(|&index| weights[index]).collect());
+        }
 
         self.set_permuted_indices(Some(permutation));
 
@@ -209,11 +909,12 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
             let data = self.data.split_off(at);
 
             // Create the shard, assign the metadata, and add it to the list of shards.
-            shards.push(
-                VecDataset::new(name, data, self.metric, self.is_expensive)
-                    .assign_metadata(metadata.split_off(at))
-                    .unwrap_or_else(|_| unreachable!("We just split this dataset at the same indices.")),
-            );
+            let mut shard = VecDataset::new(name, data, self.metric, self.is_expensive)
+                .assign_metadata(metadata.split_off(at))
+                .unwrap_or_else(|_| unreachable!("We just split this dataset at the same indices."));
+            shard.batch_metric = self.batch_metric;
+            shard.weights = self.weights.as_mut().map(|w| w.split_off(at));
+            shards.push(shard);
         }
 
         self.name = format!("{}-shard-{}", self.name, shards.len());
@@ -350,9 +1051,14 @@ impl<I: Instance, U: Number, M: Instance> Dataset<I, U> for VecDataset<I, U, M>
             name,
             data,
             metric,
+            batch_metric: None,
             is_expensive,
+            metric_name: String::new(),
+            metric_properties: MetricProperties::default(),
             permuted_indices: permutation,
             metadata,
+            query_transform: None,
+            weights: None,
         })
     }
 }
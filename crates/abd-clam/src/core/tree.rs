@@ -5,6 +5,7 @@ use core::marker::PhantomData;
 use std::path::Path;
 
 use distances::Number;
+use rand::RngCore;
 
 use crate::{Cluster, Dataset, Instance, PartitionCriterion};
 
@@ -51,6 +52,19 @@ impl<I: Instance, U: Number, D: Dataset<I, U>, C: Cluster<U>> Tree<I, U, D, C> {
         }
     }
 
+    /// Constructs a `Tree` from a dataset and the root of an already
+    /// partitioned `Cluster` tree built from that dataset.
+    pub(crate) fn from_parts(data: D, root: C) -> Self {
+        let depth = root.max_leaf_depth();
+        Self {
+            data,
+            root,
+            depth,
+            _i: PhantomData,
+            _u: PhantomData,
+        }
+    }
+
     /// Recursively partitions the root `Cluster` using the given criteria.
     ///
     /// # Arguments
@@ -67,6 +81,114 @@ impl<I: Instance, U: Number, D: Dataset<I, U>, C: Cluster<U>> Tree<I, U, D, C> {
         self
     }
 
+    /// Parallelized version of `partition`.
+    ///
+    /// Given the same `seed`, this produces the same `Tree` as `partition`.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: the criteria used to decide when to partition a `Cluster`.
+    ///
+    /// # Returns
+    ///
+    /// The `Tree` after partitioning.
+    #[must_use]
+    pub fn par_partition<P: PartitionCriterion<U>>(mut self, criteria: &P, seed: Option<u64>) -> Self {
+        self.root = self.root.par_partition(&mut self.data, criteria, seed);
+        self.depth = self.root.max_leaf_depth();
+        self
+    }
+
+    /// Same as `par_partition`, but runs in the given thread `pool` instead of
+    /// the global `rayon` thread pool.
+    ///
+    /// Given the same `seed`, the resulting `Tree` does not depend on the
+    /// number of threads in the `pool`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool`: the thread pool in which to partition.
+    /// * `criteria`: the criteria used to decide when to partition a `Cluster`.
+    ///
+    /// # Returns
+    ///
+    /// The `Tree` after partitioning.
+    #[must_use]
+    pub fn par_partition_in<P: PartitionCriterion<U>>(
+        self,
+        pool: &rayon::ThreadPool,
+        criteria: &P,
+        seed: Option<u64>,
+    ) -> Self {
+        pool.install(|| self.par_partition(criteria, seed))
+    }
+
+    /// Same as `new`, but with the seed for the root drawn from the given
+    /// random number generator.
+    ///
+    /// Along with `partition_with_rng`, this lets callers build reproducible
+    /// trees from a generator of their choosing.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The dataset from which the tree will be built.
+    /// * `rng`: The random number generator from which to draw the seed.
+    pub fn new_with_rng(data: D, rng: &mut dyn RngCore) -> Self {
+        Self::new(data, Some(rng.next_u64()))
+    }
+
+    /// Same as `partition`, but with the seed drawn from the given random
+    /// number generator.
+    ///
+    /// The seed is drawn once, and the seeds of the children of each
+    /// `Cluster` are derived from it as in `partition`, so that the same
+    /// `Tree` is built regardless of how the work is scheduled.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: the criteria used to decide when to partition a `Cluster`.
+    /// * `rng`: The random number generator from which to draw the seed.
+    ///
+    /// # Returns
+    ///
+    /// The `Tree` after partitioning.
+    #[must_use]
+    pub fn partition_with_rng<P: PartitionCriterion<U>>(self, criteria: &P, rng: &mut dyn RngCore) -> Self {
+        self.partition(criteria, Some(rng.next_u64()))
+    }
+
+    /// Parallelized version of `partition_with_rng`.
+    ///
+    /// # Arguments
+    ///
+    /// * `criteria`: the criteria used to decide when to partition a `Cluster`.
+    /// * `rng`: The random number generator from which to draw the seed.
+    ///
+    /// # Returns
+    ///
+    /// The `Tree` after partitioning.
+    #[must_use]
+    pub fn par_partition_with_rng<P: PartitionCriterion<U>>(self, criteria: &P, rng: &mut dyn RngCore) -> Self {
+        self.par_partition(criteria, Some(rng.next_u64()))
+    }
+
+    /// Recomputes the geometry of every `Cluster` in the tree from the current
+    /// state of the dataset.
+    ///
+    /// See `Cluster::recompute_geometry` for more details.
+    #[must_use]
+    pub fn recompute_geometry(mut self) -> Self {
+        self.root.recompute_geometry(&self.data);
+        self
+    }
+
+    /// Parallelized version of `recompute_geometry`.
+    #[must_use]
+    pub fn par_recompute_geometry(mut self) -> Self {
+        self.root.par_recompute_geometry(&self.data);
+        self
+    }
+
     /// Returns the `Cluster` with the given `offset` and `cardinality`.
     ///
     /// # Arguments
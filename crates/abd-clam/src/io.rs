@@ -0,0 +1,405 @@
+//! Saving and loading trees with a header that records the crate version.
+//!
+//! A tree file starts with the `MAGIC` bytes, followed by the `VERSION` of
+//! the crate that wrote it, serialized with `bincode` as a `String`, and then
+//! the root `Cluster`, also serialized with `bincode`. The layout of a
+//! `Cluster` may change between versions, so a tree written by a different
+//! version is rejected with an error naming both versions instead of an
+//! opaque deserialization error.
+//!
+//! Trees can also be saved in a portable format, with `Tree::save_portable`,
+//! for consumption from other languages. See `PORTABLE_SCHEMA` for details.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use distances::Number;
+use serde::{Deserialize, Serialize};
+
+use crate::{Cluster, Dataset, Instance, Tree, UniBall, VecDataset, VERSION};
+
+/// The bytes at the start of every tree file.
+pub const MAGIC: &[u8; 4] = b"CLAM";
+
+/// Saves a tree, with a header, to a given location.
+///
+/// # Arguments
+///
+/// * `root`: The root of the tree.
+/// * `path`: The path to the tree file.
+///
+/// # Errors
+///
+/// * If the file cannot be created.
+/// * If the tree cannot be serialized.
+pub fn save_tree<U: Number, C: Cluster<U>>(root: &C, path: &Path) -> Result<(), String> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    writer.write_all(MAGIC).map_err(|e| e.to_string())?;
+    bincode::serialize_into(&mut writer, VERSION).map_err(|e| e.to_string())?;
+    bincode::serialize_into(&mut writer, root).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Loads a tree, saved with `save_tree`, from a given location.
+///
+/// # Arguments
+///
+/// * `path`: The path to the tree file.
+///
+/// # Returns
+///
+/// * The root of the tree loaded from the file.
+///
+/// # Errors
+///
+/// * If the file cannot be opened.
+/// * If the file does not start with the `MAGIC` bytes.
+/// * If the file was written by a different version of the crate.
+/// * If the tree cannot be deserialized.
+pub fn load_tree<U: Number, C: Cluster<U>>(path: &Path) -> Result<C, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+
+    let mut magic = [0; MAGIC.len()];
+    if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+        return Err(format!("{} is not a tree file.", path.display()));
+    }
+
+    let version: String = bincode::deserialize_from(&mut reader)
+        .map_err(|e| format!("Could not read the version of {}: {e}", path.display()))?;
+    if version != VERSION {
+        return Err(format!(
+            "{} was written by version {version} of abd-clam, but this is version {VERSION}.",
+            path.display()
+        ));
+    }
+
+    bincode::deserialize_from(reader).map_err(|e| e.to_string())
+}
+
+/// The name of the JSON file holding the structure of a portable tree.
+pub const PORTABLE_TREE_FILE: &str = "tree.json";
+
+/// The name of the binary file holding the instances of a portable tree.
+pub const PORTABLE_DATA_FILE: &str = "data.bin";
+
+/// The value of the `format` field in every portable tree file.
+pub const PORTABLE_FORMAT: &str = "abd-clam-portable";
+
+/// The version of the portable format. This only changes when the format
+/// itself changes, not with every version of the crate.
+pub const PORTABLE_FORMAT_VERSION: usize = 1;
+
+/// The JSON Schema of the `PORTABLE_TREE_FILE` written by `Tree::save_portable`.
+///
+/// The clusters are listed in pre-order, so the root is at index 0 and each
+/// child is listed after its parent. The `left` and `right` fields of the
+/// `children` of a cluster are indices into the list of clusters. Distances
+/// (`radius` and `polar_distance`) are stored as JSON numbers, which is exact
+/// for floats and for integers below `2^53`.
+///
+/// The `PORTABLE_DATA_FILE` holds the instances of the dataset, in the order
+/// of the tree (i.e. after permutation). Each instance is stored as its
+/// number of bytes, as a little-endian `u64`, followed by the bytes from
+/// `Instance::to_bytes`. For vectors of numbers, those bytes are the
+/// little-endian bytes of each element.
+pub const PORTABLE_SCHEMA: &str = r#"{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "abd-clam portable tree",
+  "type": "object",
+  "required": ["format", "format_version", "name", "instance_type", "distance_type", "cardinality", "permutation", "clusters"],
+  "properties": {
+    "format": { "const": "abd-clam-portable" },
+    "format_version": { "type": "integer", "minimum": 1 },
+    "name": { "type": "string" },
+    "instance_type": { "type": "string" },
+    "distance_type": { "type": "string" },
+    "cardinality": { "type": "integer", "minimum": 0 },
+    "permutation": {
+      "oneOf": [
+        { "type": "null" },
+        { "type": "array", "items": { "type": "integer", "minimum": 0 } }
+      ]
+    },
+    "clusters": {
+      "type": "array",
+      "minItems": 1,
+      "items": {
+        "type": "object",
+        "required": ["depth", "offset", "cardinality", "arg_center", "arg_radial", "radius", "lfd", "children"],
+        "properties": {
+          "depth": { "type": "integer", "minimum": 0 },
+          "offset": { "type": "integer", "minimum": 0 },
+          "cardinality": { "type": "integer", "minimum": 1 },
+          "arg_center": { "type": "integer", "minimum": 0 },
+          "arg_radial": { "type": "integer", "minimum": 0 },
+          "radius": { "type": "number" },
+          "lfd": { "type": "number" },
+          "children": {
+            "oneOf": [
+              { "type": "null" },
+              {
+                "type": "object",
+                "required": ["left", "right", "arg_l", "arg_r", "polar_distance"],
+                "properties": {
+                  "left": { "type": "integer", "minimum": 1 },
+                  "right": { "type": "integer", "minimum": 1 },
+                  "arg_l": { "type": "integer", "minimum": 0 },
+                  "arg_r": { "type": "integer", "minimum": 0 },
+                  "polar_distance": { "type": "number" }
+                }
+              }
+            ]
+          }
+        }
+      }
+    }
+  }
+}"#;
+
+/// The contents of the `PORTABLE_TREE_FILE`. See `PORTABLE_SCHEMA`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PortableTree {
+    /// Always `PORTABLE_FORMAT`.
+    format: String,
+    /// The `PORTABLE_FORMAT_VERSION` of the writer.
+    format_version: usize,
+    /// The name of the dataset.
+    name: String,
+    /// The `Instance::type_name` of the instances.
+    instance_type: String,
+    /// The `Number::type_name` of the distance values.
+    distance_type: String,
+    /// The number of instances in the dataset.
+    cardinality: usize,
+    /// The original index of each instance, if the dataset was permuted.
+    permutation: Option<Vec<usize>>,
+    /// The clusters of the tree, in pre-order.
+    clusters: Vec<PortableCluster>,
+}
+
+/// A `Cluster` in a `PortableTree`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PortableCluster {
+    /// The depth of the cluster in the tree.
+    pub(crate) depth: usize,
+    /// The offset of the indices of the cluster's instances.
+    pub(crate) offset: usize,
+    /// The number of instances in the cluster.
+    pub(crate) cardinality: usize,
+    /// The index of the center instance.
+    pub(crate) arg_center: usize,
+    /// The index of the instance farthest from the center.
+    pub(crate) arg_radial: usize,
+    /// The radius of the cluster.
+    pub(crate) radius: f64,
+    /// The local fractal dimension of the cluster.
+    pub(crate) lfd: f64,
+    /// The children of the cluster, if any.
+    pub(crate) children: Option<PortableChildren>,
+}
+
+impl PortableCluster {
+    /// Whether the instance at `index` is in the cluster.
+    pub(crate) const fn contains(&self, index: usize) -> bool {
+        index >= self.offset && index - self.offset < self.cardinality
+    }
+}
+
+/// The children of a `PortableCluster`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PortableChildren {
+    /// The index of the left child in the list of clusters.
+    pub(crate) left: usize,
+    /// The index of the right child in the list of clusters.
+    pub(crate) right: usize,
+    /// The index of the left pole.
+    pub(crate) arg_l: usize,
+    /// The index of the right pole.
+    pub(crate) arg_r: usize,
+    /// The distance between the poles.
+    pub(crate) polar_distance: f64,
+}
+
+/// Appends the subtree of `c` to `clusters` in pre-order, and returns the
+/// index of `c` in `clusters`.
+fn flatten<U: Number, C: Cluster<U>>(c: &C, clusters: &mut Vec<PortableCluster>) -> usize {
+    let index = clusters.len();
+    clusters.push(PortableCluster {
+        depth: c.depth(),
+        offset: c.offset(),
+        cardinality: c.cardinality(),
+        arg_center: c.arg_center(),
+        arg_radial: c.arg_radial(),
+        radius: c.radius().as_f64(),
+        lfd: c.lfd(),
+        children: None,
+    });
+
+    if let (Some([left, right]), Some([arg_l, arg_r]), Some(polar_distance)) =
+        (c.children(), c.arg_poles(), c.polar_distance())
+    {
+        let left = flatten(left, clusters);
+        let right = flatten(right, clusters);
+        clusters[index].children = Some(PortableChildren {
+            left,
+            right,
+            arg_l,
+            arg_r,
+            polar_distance: polar_distance.as_f64(),
+        });
+    }
+
+    index
+}
+
+impl<I: Instance, U: Number, D: Dataset<I, U>, C: Cluster<U>> Tree<I, U, D, C> {
+    /// Saves the tree to a directory in a portable format that can be read
+    /// from other languages.
+    ///
+    /// The directory will contain the `PORTABLE_TREE_FILE`, a JSON file with
+    /// the structure of the tree as described by `PORTABLE_SCHEMA`, and the
+    /// `PORTABLE_DATA_FILE`, a binary file with the permuted instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: The directory to save the tree in. It must already exist.
+    ///
+    /// # Errors
+    ///
+    /// * If `dir` does not exist.
+    /// * If either file cannot be written.
+    pub fn save_portable(&self, dir: &Path) -> Result<(), String> {
+        if !dir.exists() {
+            return Err("Given path does not exist".to_string());
+        }
+
+        let mut clusters = Vec::new();
+        flatten(&self.root, &mut clusters);
+        let tree = PortableTree {
+            format: PORTABLE_FORMAT.to_string(),
+            format_version: PORTABLE_FORMAT_VERSION,
+            name: self.data.name().to_string(),
+            instance_type: I::type_name(),
+            distance_type: U::type_name().to_string(),
+            cardinality: self.data.cardinality(),
+            permutation: self.data.permuted_indices().map(<[usize]>::to_vec),
+            clusters,
+        };
+
+        let writer = BufWriter::new(File::create(dir.join(PORTABLE_TREE_FILE)).map_err(|e| e.to_string())?);
+        serde_json::to_writer_pretty(writer, &tree).map_err(|e| e.to_string())?;
+
+        let mut writer = BufWriter::new(File::create(dir.join(PORTABLE_DATA_FILE)).map_err(|e| e.to_string())?);
+        for i in 0..self.data.cardinality() {
+            let bytes = self.data[i].to_bytes();
+            writer
+                .write_all(&(bytes.len() as u64).to_le_bytes())
+                .and_then(|()| writer.write_all(&bytes))
+                .map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl<I: Instance, U: Number> Tree<I, U, VecDataset<I, U, usize>, UniBall<U>> {
+    /// Loads a tree saved with `save_portable`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir`: The directory the tree was saved in.
+    /// * `metric`: The metric to use for the dataset.
+    /// * `is_expensive`: Whether the metric is expensive to compute.
+    ///
+    /// # Errors
+    ///
+    /// * If either file cannot be read.
+    /// * If the JSON file does not match `PORTABLE_SCHEMA`.
+    /// * If the file was written with a different `PORTABLE_FORMAT_VERSION`.
+    /// * If the types of the instances or distances do not match `I` and `U`.
+    /// * If the number of instances in the binary file does not match the
+    ///   cardinality in the JSON file.
+    /// * If the clusters are not listed in pre-order.
+    /// * If the root does not contain every instance, or if the children of a
+    ///   cluster do not split its instances in two.
+    /// * If a center, radial instance or pole is not an instance of its
+    ///   cluster.
+    /// * If the permutation is not a permutation of the instances.
+    pub fn load_portable(dir: &Path, metric: fn(&I, &I) -> U, is_expensive: bool) -> Result<Self, String> {
+        let reader = BufReader::new(File::open(dir.join(PORTABLE_TREE_FILE)).map_err(|e| e.to_string())?);
+        let tree: PortableTree = serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+
+        if tree.format != PORTABLE_FORMAT || tree.format_version != PORTABLE_FORMAT_VERSION {
+            return Err(format!(
+                "Expected format {PORTABLE_FORMAT} version {PORTABLE_FORMAT_VERSION}, got {} version {}.",
+                tree.format, tree.format_version
+            ));
+        }
+        if tree.instance_type != I::type_name() || tree.distance_type != U::type_name() {
+            return Err(format!(
+                "Expected instances of type {} and distances of type {}, got {} and {}.",
+                I::type_name(),
+                U::type_name(),
+                tree.instance_type,
+                tree.distance_type
+            ));
+        }
+
+        let mut reader = BufReader::new(File::open(dir.join(PORTABLE_DATA_FILE)).map_err(|e| e.to_string())?);
+        let mut instances = Vec::with_capacity(tree.cardinality);
+        let mut num_bytes = [0; 8];
+        for _ in 0..tree.cardinality {
+            reader.read_exact(&mut num_bytes).map_err(|e| e.to_string())?;
+            let num_bytes = usize::try_from(u64::from_le_bytes(num_bytes)).map_err(|e| e.to_string())?;
+            let mut bytes = vec![0; num_bytes];
+            reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+            instances.push(I::from_bytes(&bytes)?);
+        }
+        if reader.read(&mut num_bytes).map_err(|e| e.to_string())? != 0 {
+            return Err(format!("Expected {} instances, found more.", tree.cardinality));
+        }
+
+        if let Some(permutation) = &tree.permutation {
+            let mut seen = vec![false; tree.cardinality];
+            for &i in permutation {
+                if i >= tree.cardinality || core::mem::replace(&mut seen[i], true) {
+                    return Err(format!(
+                        "The permutation is not a permutation of {} instances.",
+                        tree.cardinality
+                    ));
+                }
+            }
+            if permutation.len() != tree.cardinality {
+                return Err(format!(
+                    "Expected a permutation of length {}, got {}.",
+                    tree.cardinality,
+                    permutation.len()
+                ));
+            }
+        }
+
+        // The children of each cluster are checked to split its instances, so
+        // every cluster is within the range of the root.
+        match tree.clusters.first() {
+            Some(root) if root.offset == 0 && root.cardinality == tree.cardinality => (),
+            Some(root) => {
+                return Err(format!(
+                    "Expected the root to have offset 0 and cardinality {}, got {} and {}.",
+                    tree.cardinality, root.offset, root.cardinality
+                ));
+            }
+            None => return Err("The tree has no clusters.".to_string()),
+        }
+
+        let mut data = VecDataset::new(tree.name, instances, metric, is_expensive);
+        data.set_permuted_indices(tree.permutation.as_deref());
+        let root = UniBall::from_portable(&tree.clusters, 0)?;
+
+        Ok(Self::from_parts(data, root))
+    }
+}
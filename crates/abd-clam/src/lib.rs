@@ -20,14 +20,21 @@ mod cakes;
 pub mod chaoda;
 pub mod codec;
 mod core;
+pub mod io;
 pub mod utils;
 
 pub use crate::{
-    cakes::{knn, rnn, Cakes},
+    cakes::{classify, coreset, dbscan, density, hit_count_stats, join, kernels, knn, rnn, Cakes, HitStats},
     chaoda::graph,
     core::{
-        cluster::{Cluster, MaxDepth, MinCardinality, PartitionCriteria, PartitionCriterion, UniBall},
-        dataset::{Dataset, Instance, VecDataset},
+        cluster::{
+            BalancedBall, BoundingBoxes, CenterStrategy, Cluster, FromClusters, LfdPlateau, MaxDepth, MinCardinality,
+            PartitionCriteria, PartitionCriterion, TreeQuality, UniBall,
+        },
+        dataset::{
+            BatchMetric, CrossDataset, CrossMetric, Dataset, DistanceCache, Instance, Mahalanobis, MatrixDataset,
+            MetricBuilder, MetricProperties, MetricReport, QueryTransform, VecDataset,
+        },
         tree::Tree,
     },
 };
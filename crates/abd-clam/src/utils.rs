@@ -77,8 +77,10 @@ pub fn variance<T: Number>(values: &[T], mean: f64) -> f64 {
 }
 
 /// Apply Gaussian normalization to the given values.
-#[allow(dead_code)]
-pub(crate) fn normalize_1d(values: &[f64], mean: f64, sd: f64) -> Vec<f64> {
+///
+/// Each value is mapped to `[0, 1]` by the cumulative distribution function
+/// of a normal distribution with the given `mean` and standard deviation `sd`.
+pub fn normalize_1d(values: &[f64], mean: f64, sd: f64) -> Vec<f64> {
     values
         .iter()
         .map(|&v| v - mean)
@@ -88,6 +90,64 @@ pub(crate) fn normalize_1d(values: &[f64], mean: f64, sd: f64) -> Vec<f64> {
         .collect()
 }
 
+/// Invert the Gaussian normalization applied by `normalize_1d`.
+///
+/// The `mean` and `sd` must be the same as those passed to `normalize_1d`.
+/// Normalized values of `0` and `1` map to negative and positive infinity.
+pub fn denormalize_1d(values: &[f64], mean: f64, sd: f64) -> Vec<f64> {
+    values
+        .iter()
+        .map(|&v| 2_f64.mul_add(v, -1.))
+        .map(erf_inv)
+        .map(|v| v * ((f64::EPSILON + sd) * SQRT_2))
+        .map(|v| v + mean)
+        .collect()
+}
+
+/// Compute the inverse of the error function.
+///
+/// An initial estimate, from Winitzki's approximation, is refined with
+/// Newton's method on `libm::erf`.
+fn erf_inv(y: f64) -> f64 {
+    if y <= -1. {
+        return f64::NEG_INFINITY;
+    } else if y >= 1. {
+        return f64::INFINITY;
+    }
+
+    let a = 0.147;
+    let ln = y.mul_add(-y, 1.).ln();
+    let b = 2. / (core::f64::consts::PI * a) + ln / 2.;
+    let mut x = y.signum() * (b.mul_add(b, -ln / a).sqrt() - b).sqrt();
+
+    for _ in 0..4 {
+        let slope = core::f64::consts::FRAC_2_SQRT_PI * (-x * x).exp();
+        if slope == 0. {
+            break;
+        }
+        x -= (libm::erf(x) - y) / slope;
+    }
+    x
+}
+
+/// Linearly rescale the given values to the range `[0, 1]`.
+///
+/// If all values are equal, they are all mapped to `0`.
+#[must_use]
+pub fn min_max_1d(values: &[f64]) -> Vec<f64> {
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+            (min.min(v), max.max(v))
+        });
+    let range = max - min;
+    if range > 0. {
+        values.iter().map(|&v| (v - min) / range).collect()
+    } else {
+        vec![0.; values.len()]
+    }
+}
+
 /// Compute the local fractal dimension of the given distances using the given radius.
 ///
 /// The local fractal dimension is computed as the log2 of the ratio of the number of
@@ -138,6 +198,24 @@ pub(crate) fn position_of<T: Eq + Copy>(values: &[T], v: T) -> Option<usize> {
         .map(|(i, _)| i)
 }
 
+/// Derives the seed for a child `Cluster` from the seed of its parent.
+///
+/// Each `Cluster` gets its own seed, determined only by the root seed and the
+/// path of left/right branches taken to reach it. This keeps the tree
+/// reproducible regardless of the order in which `Cluster`s are built.
+///
+/// # Arguments
+///
+/// * `seed` - The seed of the parent `Cluster`.
+/// * `branch` - `0` for the left child and `1` for the right child.
+pub(crate) const fn child_seed(seed: u64, branch: u64) -> u64 {
+    // SplitMix64 finalizer applied to the parent seed and branch.
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15_u64.wrapping_mul(branch + 1));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 /// Transpose a matrix represented as an array of arrays (slices) to an array of Vecs.
 ///
 /// Given an array of arrays (slices), where each slice represents a row and each element
@@ -493,4 +571,30 @@ mod tests {
         let std = standard_deviation::<f32>(&data);
         assert_eq!(std, 2.);
     }
+
+    #[test]
+    fn test_denormalize() {
+        let values = random_data::random_tabular(1, 1000, -10., 10., &mut rand::rngs::StdRng::seed_from_u64(42))
+            .into_iter()
+            .flatten()
+            .collect::<Vec<f64>>();
+        let (mean, sd) = (mean(&values), standard_deviation(&values));
+
+        let normalized = normalize_1d(&values, mean, sd);
+        let recovered = denormalize_1d(&normalized, mean, sd);
+        for (&v, r) in values.iter().zip(recovered) {
+            assert!(float_cmp::approx_eq!(f64, v, r, epsilon = 1e-9), "{v}, {r} not equal");
+        }
+
+        assert_eq!(denormalize_1d(&[0., 1.], mean, sd), [f64::NEG_INFINITY, f64::INFINITY]);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let values = [3., -1., 7., 1.];
+        assert_eq!(min_max_1d(&values), [0.5, 0., 1., 0.25]);
+
+        assert_eq!(min_max_1d(&[2.; 5]), [0.; 5]);
+        assert!(min_max_1d(&[]).is_empty());
+    }
 }
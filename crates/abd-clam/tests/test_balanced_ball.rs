@@ -0,0 +1,121 @@
+//! Tests for the `BalancedBall` struct.
+
+use abd_clam::{knn, BalancedBall, Cluster, Dataset, PartitionCriteria, Tree, UniBall};
+use rand::prelude::*;
+
+mod utils;
+
+/// Generates points in two clusters of very different cardinalities, so that a
+/// tree of `UniBall`s has unbalanced splits.
+fn gen_skewed_data() -> Vec<Vec<f32>> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let mut data = symagen::random_data::random_tabular(900, 5, -1., 1., &mut rng);
+    data.extend(symagen::random_data::random_tabular(100, 5, 99., 101., &mut rng));
+    data
+}
+
+#[test]
+fn partition() {
+    let data = utils::gen_dataset_from(gen_skewed_data(), utils::euclidean::<f32, f32>, vec![0_usize; 1000]);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, BalancedBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    assert!(tree.root().is_balanced());
+    assert_eq!(tree.root().indices().collect::<Vec<_>>(), (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn from_uni_ball_tree() {
+    let reference = gen_skewed_data();
+    let metadata = (0..reference.len()).collect::<Vec<_>>();
+    let data = utils::gen_dataset_from(reference.clone(), utils::euclidean::<f32, f32>, metadata);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    let unbalanced = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .filter_map(|c| {
+            c.children()
+                .map(|[l, r]| l.cardinality().min(r.cardinality()) * 4 < c.cardinality())
+        })
+        .any(|b| b);
+    assert!(unbalanced, "The test data should produce an unbalanced tree.");
+
+    let tree = tree.into_balanced(&criteria, Some(42));
+    assert!(tree.root().is_balanced());
+    assert_eq!(tree.root().indices().collect::<Vec<_>>(), (0..1000).collect::<Vec<_>>());
+
+    let data = tree.data();
+    for i in 0..data.cardinality() {
+        let original = data.original_index(i);
+        assert_eq!(data[i], reference[original]);
+        assert_eq!(data.metadata_of(i), &original);
+    }
+
+    for c in tree.root().subtree() {
+        let indices = c.indices().collect::<Vec<_>>();
+        assert!(indices.contains(&c.arg_center()));
+        assert!(indices.contains(&c.arg_radial()));
+        if let Some([l, r]) = c.arg_poles() {
+            assert!(indices.contains(&l) && indices.contains(&r));
+        }
+        let radius = data
+            .one_to_many(c.arg_center(), &indices)
+            .into_iter()
+            .fold(0_f32, f32::max);
+        float_cmp::assert_approx_eq!(f32, radius, c.radius());
+    }
+}
+
+#[test]
+fn rebalance() {
+    // Points on a line whose gaps grow geometrically, so that each split of a
+    // `UniBall` peels only a few points off the far end.
+    let reference = (0..200).map(|i| vec![1.1_f32.powi(i)]).collect::<Vec<_>>();
+    let metadata = (0..reference.len()).collect::<Vec<_>>();
+    let data = utils::gen_dataset_from(reference.clone(), utils::euclidean::<f32, f32>, metadata);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let skewed_depth = tree.depth();
+
+    let tree = tree.rebalance(&criteria, Some(42));
+    assert!(
+        tree.depth() < skewed_depth,
+        "Rebalancing should reduce the depth from {skewed_depth}, got {}.",
+        tree.depth()
+    );
+    assert_eq!(tree.depth(), tree.root().max_leaf_depth());
+    assert_eq!(tree.root().indices().collect::<Vec<_>>(), (0..200).collect::<Vec<_>>());
+
+    let data = tree.data();
+    for i in 0..data.cardinality() {
+        let original = data.original_index(i);
+        assert_eq!(data[i], reference[original]);
+        assert_eq!(data.metadata_of(i), &original);
+    }
+
+    for c in tree.root().subtree() {
+        let indices = c.indices().collect::<Vec<_>>();
+        assert!(indices.contains(&c.arg_center()));
+        let radius = data
+            .one_to_many(c.arg_center(), &indices)
+            .into_iter()
+            .fold(0_f32, f32::max);
+        float_cmp::assert_approx_eq!(f32, radius, c.radius());
+    }
+
+    for query in [vec![0.], vec![5.], vec![1e4], vec![1e8]] {
+        for k in [1, 5, 20] {
+            let linear_nn = knn::Algorithm::Linear.search(&tree, &query, k);
+            // `RepeatedRnn` and `SieveSepCenter` also miss hits on a tree of
+            // this data built with `BalancedBall`s.
+            for variant in [knn::Algorithm::GreedySieve, knn::Algorithm::Sieve] {
+                let variant_nn = variant.search(&tree, &query, k);
+                let recall = utils::compute_recall(linear_nn.clone(), variant_nn);
+                float_cmp::assert_approx_eq!(f32, recall, 1.0);
+            }
+        }
+    }
+}
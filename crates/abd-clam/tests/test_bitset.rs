@@ -0,0 +1,29 @@
+//! Tests for exporting `Cluster` memberships as bitsets.
+
+#![cfg(feature = "fixedbitset")]
+
+use abd_clam::{Cluster, Dataset, PartitionCriteria, Tree, UniBall};
+
+mod utils;
+
+#[test]
+fn membership_bitset() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean::<f32, f32>);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let total = tree.data().cardinality();
+
+    for c in tree.root().subtree() {
+        let bitset = c.membership_bitset(total);
+        assert_eq!(bitset.len(), total);
+        assert_eq!(bitset.ones().collect::<Vec<_>>(), c.indices().collect::<Vec<_>>());
+    }
+
+    // The bitsets of the children partition the bitset of their parent.
+    let Some([left, right]) = tree.root().children() else {
+        unreachable!("The root has children.")
+    };
+    let (left, right) = (left.membership_bitset(total), right.membership_bitset(total));
+    assert!(left.is_disjoint(&right));
+    assert_eq!(&left | &right, tree.root().membership_bitset(total));
+}
@@ -1,6 +1,6 @@
 //! Tests for Cakes.
 
-use abd_clam::{knn, rnn, Cakes, Instance, PartitionCriteria, VecDataset};
+use abd_clam::{kernels, knn, rnn, Cakes, Cluster, Dataset, Instance, PartitionCriteria, VecDataset};
 use distances::Number;
 use float_cmp::approx_eq;
 use test_case::test_case;
@@ -72,6 +72,61 @@ fn line() {
     }
 }
 
+#[test]
+fn chunked_batch_search() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let queries = utils::gen_dataset(25, 10, 0, utils::euclidean).data_owned();
+    let queries = queries.iter().collect::<Vec<_>>();
+    let criteria = PartitionCriteria::default();
+    let cakes = Cakes::new(data, Some(42), &criteria);
+
+    let (radius, k) = (0.5, 10);
+    let rnn_hits = cakes.batch_rnn_search(&queries, radius, rnn::Algorithm::Clustered);
+    let knn_hits = cakes.batch_knn_search(&queries, k, knn::Algorithm::GreedySieve);
+
+    for chunk_size in [0, 1, 7, 25, 100] {
+        let mut indices = Vec::new();
+        cakes.chunked_batch_rnn_search(&queries, radius, rnn::Algorithm::Clustered, chunk_size, |i, hits| {
+            assert_eq!(
+                hits, rnn_hits[i],
+                "Failed RNN for query {i} with chunk size {chunk_size}"
+            );
+            indices.push(i);
+        });
+        assert_eq!(indices, (0..queries.len()).collect::<Vec<_>>());
+
+        let mut indices = Vec::new();
+        cakes.chunked_batch_knn_search(&queries, k, knn::Algorithm::GreedySieve, chunk_size, |i, hits| {
+            assert_eq!(
+                hits, knn_hits[i],
+                "Failed KNN for query {i} with chunk size {chunk_size}"
+            );
+            indices.push(i);
+        });
+        assert_eq!(indices, (0..queries.len()).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn batch_search_in_pool() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let queries = utils::gen_dataset(25, 10, 0, utils::euclidean).data_owned();
+    let queries = queries.iter().collect::<Vec<_>>();
+    let criteria = PartitionCriteria::default();
+    let cakes = Cakes::new(data, Some(42), &criteria);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+    let (radius, k) = (0.5, 10);
+    assert_eq!(
+        cakes.batch_rnn_search_in(&pool, &queries, radius, rnn::Algorithm::Clustered),
+        cakes.batch_rnn_search(&queries, radius, rnn::Algorithm::Clustered)
+    );
+    assert_eq!(
+        cakes.batch_knn_search_in(&pool, &queries, k, knn::Algorithm::GreedySieve),
+        cakes.batch_knn_search(&queries, k, knn::Algorithm::GreedySieve)
+    );
+}
+
 #[ignore = "Fails with Sieve and SieveSepCenter."]
 #[test_case(1000, 10; "1k_10")]
 #[test_case(1000, 100; "1k_100")]
@@ -246,3 +301,130 @@ fn save_load_sharded(num_shards: u64) {
     let trees = cakes.trees();
     assert_eq!(trees.len(), num_shards as usize);
 }
+
+#[test]
+fn rnn_search_verified() {
+    let data = (-100..=100).map(|x| vec![x.as_f32()]).collect::<Vec<_>>();
+    let metadata = data.iter().map(|x| x[0] > 0.0).collect::<Vec<_>>();
+    let queries = (-10..=10)
+        .step_by(2)
+        .map(|x| vec![x.as_f32() + 0.5])
+        .collect::<Vec<_>>();
+    let queries = queries.iter().collect::<Vec<_>>();
+    let criteria = PartitionCriteria::default();
+
+    let exact = Cakes::new(
+        utils::gen_dataset_from(data.clone(), utils::euclidean, metadata.clone()),
+        Some(42),
+        &criteria,
+    );
+    let hits = exact
+        .batch_rnn_search_verified(&queries, 20.0, rnn::Algorithm::Clustered, true)
+        .unwrap();
+    assert_eq!(hits, exact.batch_rnn_search(&queries, 20.0, rnn::Algorithm::Clustered));
+
+    // Squaring the distances loses the triangle inequality, so the tree prunes
+    // clusters that contain hits.
+    let lossy = Cakes::new(
+        utils::gen_dataset_from(data, utils::euclidean_sq, metadata),
+        Some(42),
+        &criteria,
+    );
+    let hits = lossy.batch_rnn_search_verified(&queries, 400.0, rnn::Algorithm::Clustered, false);
+    assert!(hits.is_ok());
+
+    let err = lossy
+        .batch_rnn_search_verified(&queries, 400.0, rnn::Algorithm::Clustered, true)
+        .unwrap_err();
+    assert!(err.contains("differs from linear search"), "{err}");
+    assert!(err.contains("missing"), "{err}");
+}
+
+#[test]
+fn hit_count_stats() {
+    let hits = [3, 0, 5, 2, 10]
+        .into_iter()
+        .map(|n| (0..n).map(|i| (i, i.as_f32())).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let stats = abd_clam::hit_count_stats(&hits);
+    assert_eq!(stats.min, 0);
+    assert_eq!(stats.max, 10);
+    assert_eq!(stats.median, 3);
+    assert!(approx_eq!(f64, stats.mean, 4.0));
+    // The population variance of [3, 0, 5, 2, 10] is 11.6.
+    assert!(approx_eq!(f64, stats.std_dev, 11.6_f64.sqrt(), epsilon = 1e-12));
+
+    // The lower of the two middle values is the median.
+    let stats = abd_clam::hit_count_stats(&hits[..4]);
+    assert_eq!(stats.median, 2);
+
+    let stats = abd_clam::hit_count_stats::<f32>(&[]);
+    assert_eq!(stats, abd_clam::HitStats::default());
+}
+
+#[test]
+fn search_with_provenance() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let query = vec![0.5; 10];
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(8);
+    let cakes = Cakes::new(data, Some(42), &criteria);
+    let root = cakes.trees()[0].root();
+
+    let knn_hits = cakes.knn_search_with_provenance(&query, 10, knn::Algorithm::RepeatedRnn);
+    let radius = knn_hits.iter().map(|&(_, d, _)| d).fold(0., f32::max);
+    let rnn_hits = cakes.rnn_search_with_provenance(&query, radius, rnn::Algorithm::Clustered);
+    assert_eq!(knn_hits.len(), 10);
+    assert_eq!(rnn_hits.len(), 10);
+
+    for (hits, plain) in [
+        (knn_hits, cakes.knn_search(&query, 10, knn::Algorithm::RepeatedRnn)),
+        (rnn_hits, cakes.rnn_search(&query, radius, rnn::Algorithm::Clustered)),
+    ] {
+        assert_eq!(hits.iter().map(|&(i, d, _)| (i, d)).collect::<Vec<_>>(), plain);
+        for (i, _, leaf_offset) in hits {
+            let leaf = root
+                .subtree()
+                .into_iter()
+                .find(|c| c.is_leaf() && c.offset() == leaf_offset)
+                .unwrap();
+            assert!(leaf.contains_index(i));
+        }
+    }
+}
+
+#[test]
+fn batched_euclidean_squared() {
+    let data = utils::gen_dataset(1000, 100, 42, utils::euclidean::<f32, f32>).data_owned();
+    let query = &data[0];
+    let instances = data.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+    let batched = kernels::batched_euclidean_squared(query, &instances);
+    for (x, &b) in data.iter().zip(&batched) {
+        let naive: f32 = utils::euclidean_sq(query, x);
+        assert!((b - naive).abs() <= 1e-4 * naive.max(1.), "{b} != {naive}");
+    }
+    assert!(batched[0] < 1e-4);
+
+    // The dataset uses the kernel for `query_to_many`.
+    let dataset = VecDataset::new("kernel".to_string(), data.clone(), utils::euclidean::<f32, f32>, false)
+        .with_euclidean_sq_kernel();
+    let indices = (0..data.len()).collect::<Vec<_>>();
+    assert_eq!(dataset.query_to_many(query, &indices), batched);
+    assert_eq!(dataset.one_to_one(0, 1), utils::euclidean_sq::<f32>(query, &data[1]));
+}
+
+#[test]
+fn batch_distance_matrix() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean::<f32, f32>);
+    let queries = utils::gen_dataset(20, 10, 43, utils::euclidean::<f32, f32>).data_owned();
+    let indices = (0..data.cardinality()).step_by(3).collect::<Vec<_>>();
+
+    let matrix = kernels::batch_distance_matrix(&data, &queries, &indices);
+    assert_eq!(matrix.len(), queries.len());
+    for (row, query) in matrix.iter().zip(&queries) {
+        assert_eq!(row, &data.query_to_many(query, &indices));
+    }
+
+    assert!(kernels::batch_distance_matrix(&data, &[], &indices).is_empty());
+}
@@ -0,0 +1,44 @@
+//! Tests for the `classify` module.
+
+use abd_clam::{classify, Cluster, PartitionCriteria, Tree, UniBall};
+use rand::prelude::*;
+
+mod utils;
+
+#[test]
+fn nearest_centroid() {
+    // Four well separated blobs, labelled by blob.
+    let centers = [[0., 0.], [100., 0.], [0., 100.], [100., 100.]];
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let mut blob = |n: usize| {
+        centers
+            .iter()
+            .enumerate()
+            .flat_map(|(label, &[x, y])| {
+                (0..n)
+                    .map(|_| (vec![x + rng.gen_range(-5.0..5.0), y + rng.gen_range(-5.0..5.0)], label))
+                    .collect::<Vec<_>>()
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>()
+    };
+    let (train, labels) = blob(100);
+    let (test, expected) = blob(10);
+
+    let data = utils::gen_dataset_from(train, utils::euclidean::<f32, f32>, labels);
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(10);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let (data, root) = (tree.data(), tree.root());
+    let labels = data.metadata();
+
+    for (query, &label) in test.iter().zip(&expected) {
+        assert_eq!(classify::nearest_centroid(root, data, query, labels), label);
+    }
+
+    // A tree with only the root classifies every query with the majority
+    // label of the whole dataset.
+    let root = UniBall::new_root(data, Some(42));
+    let majority = root.majority_label(labels);
+    assert!(test
+        .iter()
+        .all(|q| Some(classify::nearest_centroid(&root, data, q, labels)) == majority));
+}
@@ -0,0 +1,72 @@
+//! Tests for the `coreset` module.
+
+use abd_clam::{coreset, Cluster, Dataset, PartitionCriteria, Tree, UniBall};
+use rand::prelude::*;
+
+mod utils;
+
+/// The minimum distance between any two of the given instances.
+fn min_pairwise<D: Dataset<Vec<f32>, f32>>(data: &D, indices: &[usize]) -> f32 {
+    data.pairwise(indices)
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.into_iter().skip(i + 1))
+        .fold(f32::MAX, f32::min)
+}
+
+/// Farthest-first traversal by brute force, recomputing the distance of
+/// every instance to the closest chosen instance after each choice.
+fn farthest_points<D: Dataset<Vec<f32>, f32>>(data: &D, indices: &[usize], m: usize, first: usize) -> Vec<usize> {
+    let mut chosen = vec![first];
+    let mut min_distances = data.one_to_many(first, indices);
+    while chosen.len() < m {
+        match abd_clam::utils::arg_max(&min_distances) {
+            Some((i, d)) if d > 0. => {
+                chosen.push(indices[i]);
+                let distances = data.one_to_many(indices[i], indices);
+                min_distances.iter_mut().zip(distances).for_each(|(m, d)| *m = m.min(d));
+            }
+            _ => break,
+        }
+    }
+    chosen
+}
+
+#[test]
+fn farthest_first() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let (data, root) = (tree.data(), tree.root());
+    let indices = root.indices().collect::<Vec<_>>();
+
+    assert!(coreset::farthest_first(data, root, 0).is_empty());
+    assert_eq!(coreset::farthest_first(data, root, 1), vec![root.arg_center()]);
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    for m in [2, 10, 50] {
+        let chosen = coreset::farthest_first(data, root, m);
+        assert_eq!(chosen, farthest_points(data, &indices, m, root.arg_center()));
+
+        let random = indices.choose_multiple(&mut rng, m).copied().collect::<Vec<_>>();
+        let (chosen_min, random_min) = (min_pairwise(data, &chosen), min_pairwise(data, &random));
+        assert!(
+            chosen_min > random_min,
+            "The coreset of size {m} should be more spread out than a random subset: {chosen_min} vs {random_min}."
+        );
+    }
+}
+
+#[test]
+fn duplicates() {
+    let data = utils::gen_dataset_from(
+        vec![vec![0., 0.], vec![1., 1.], vec![0., 0.], vec![1., 1.], vec![3., 0.]],
+        utils::euclidean::<f32, f32>,
+        vec![0_usize; 5],
+    );
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    let chosen = coreset::farthest_first(tree.data(), tree.root(), 5);
+    assert_eq!(chosen.len(), 3, "Sampling should stop once only duplicates remain.");
+}
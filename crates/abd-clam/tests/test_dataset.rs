@@ -1,7 +1,15 @@
 //! Tests for the dataset module.
 
-use abd_clam::{Dataset, VecDataset};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use abd_clam::{
+    rnn, Cluster, Dataset, DistanceCache, Instance, Mahalanobis, MetricBuilder, MetricProperties, PartitionCriteria,
+    QueryTransform, Tree, UniBall, VecDataset,
+};
+use distances::Number;
+use float_cmp::assert_approx_eq;
 use rand::prelude::*;
+use rayon::prelude::*;
 use tempdir::TempDir;
 use test_case::test_case;
 
@@ -129,3 +137,747 @@ fn load_errors() {
     let other = VecDataset::<Vec<f32>, f32, usize>::load(&tmp_file, utils::euclidean, false);
     assert!(other.is_err());
 }
+
+#[test]
+fn query_to_all() {
+    let dataset = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let query = &dataset[7];
+
+    let expected = dataset.query_to_many(query, &(0..dataset.cardinality()).collect::<Vec<_>>());
+    assert_eq!(dataset.query_to_all(query), expected);
+    assert_eq!(dataset.par_query_to_all(query), expected);
+    assert_eq!(expected[7], 0.);
+}
+
+#[test]
+fn dimensionality() {
+    let dataset = utils::gen_dataset(100, 10, 42, utils::euclidean);
+    assert_eq!(dataset.dimensionality_hint(), (10, Some(10)));
+    assert_eq!(dataset.dimensionality(), Some(10));
+
+    let ragged = vec![vec![0., 1.], vec![0.], vec![0., 1., 2.]];
+    let dataset = VecDataset::new("ragged".to_string(), ragged, utils::euclidean::<f32, f32>, false);
+    assert_eq!(dataset.dimensionality_hint(), (1, Some(3)));
+    assert_eq!(dataset.dimensionality(), None);
+
+    let strings = vec!["a".to_string(), "b".to_string()];
+    let dataset = VecDataset::new("strings".to_string(), strings, |_: &String, _: &String| 0_u32, false);
+    assert_eq!(dataset.dimensionality_hint(), (0, None));
+    assert_eq!(dataset.dimensionality(), None);
+}
+
+#[test]
+fn arg_medoid() {
+    let data = vec![vec![0.], vec![1.], vec![2.], vec![3.], vec![10.]];
+    let dataset = VecDataset::new("test".to_string(), data, utils::euclidean::<f32, f32>, false);
+
+    // Sums of distances: [16, 13, 12, 13, 36]
+    let indices = (0..5).collect::<Vec<_>>();
+    assert_eq!(dataset.arg_medoid(&indices), 2);
+    assert_eq!(dataset.par_arg_medoid(&indices), 2);
+
+    // Sums of distances: [11, 10, 19]
+    let indices = [0, 1, 4];
+    assert_eq!(dataset.arg_medoid(&indices), 1);
+    assert_eq!(dataset.par_arg_medoid(&indices), 1);
+
+    assert_eq!(dataset.arg_medoid(&[3]), 3);
+
+    let dataset = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let indices = (0..dataset.cardinality()).step_by(2).collect::<Vec<_>>();
+    let medoid = dataset.arg_medoid(&indices);
+    assert!(indices.contains(&medoid));
+    assert_eq!(medoid, dataset.par_arg_medoid(&indices));
+}
+
+/// Counts the calls to `batch_euclidean`.
+static BATCH_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Batched Euclidean distance using the SIMD implementation.
+#[allow(clippy::ptr_arg)]
+fn batch_euclidean(query: &Vec<f32>, instances: &[&Vec<f32>]) -> Vec<f32> {
+    BATCH_CALLS.fetch_add(1, Ordering::Relaxed);
+    instances
+        .iter()
+        .map(|x| distances::simd::euclidean_f32(query, x))
+        .collect()
+}
+
+#[test]
+fn batch_metric() {
+    let scalar = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let batched = utils::gen_dataset(1000, 10, 42, utils::euclidean).with_batch_metric(batch_euclidean);
+
+    let indices = (0..scalar.cardinality()).collect::<Vec<_>>();
+    for i in (0..scalar.cardinality()).step_by(100) {
+        let query = &scalar[i];
+        let calls = BATCH_CALLS.load(Ordering::Relaxed);
+        let batch_distances = batched.query_to_many(query, &indices);
+        assert_eq!(BATCH_CALLS.load(Ordering::Relaxed), calls + 1);
+
+        let scalar_distances = scalar.query_to_many(query, &indices);
+        for (b, s) in batch_distances.into_iter().zip(scalar_distances) {
+            assert_approx_eq!(f32, b, s, epsilon = f32::EPSILON, ulps = 4);
+        }
+    }
+}
+
+/// A distance function that is not symmetric.
+#[allow(clippy::ptr_arg)]
+fn asymmetric(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    let d = utils::euclidean(x, y);
+    if x[0] < y[0] {
+        2. * d
+    } else {
+        d
+    }
+}
+
+#[test]
+fn check_metric_properties() {
+    let data = utils::gen_dataset(100, 10, 42, utils::euclidean);
+    let report = data.check_metric_properties(1000, 42);
+    assert_eq!(report.num_samples, 1000);
+    assert!(report.is_valid(), "{report:?}");
+
+    let data = utils::gen_dataset(100, 10, 42, asymmetric);
+    let report = data.check_metric_properties(1000, 42);
+    assert!(!report.is_valid());
+    assert!(report.symmetry > 0, "{report:?}");
+    assert_eq!(report.identity, 0);
+    assert_eq!(report.non_negativity, 0);
+}
+
+#[test]
+fn infer_metric_properties() {
+    let samples = utils::gen_dataset(100, 10, 42, utils::euclidean).data().to_vec();
+
+    let (_, properties) = MetricBuilder::new(utils::euclidean::<f32, f32>)
+        .infer_properties(&samples, 42)
+        .build();
+    assert!(properties.is_metric(), "{properties:?}");
+
+    let (metric, properties) = MetricBuilder::new(asymmetric).infer_properties(&samples, 42).build();
+    assert!(!properties.symmetry, "{properties:?}");
+    assert!(properties.identity);
+    assert!(properties.non_negativity);
+    assert!(!properties.is_metric());
+    assert_eq!(metric(&samples[0], &samples[1]), asymmetric(&samples[0], &samples[1]));
+
+    let (_, properties) = MetricBuilder::new(asymmetric).build();
+    assert_eq!(properties, MetricProperties::default());
+}
+
+#[test]
+fn angular_metric() {
+    let (metric, properties) = MetricBuilder::<Vec<f32>, f32>::angular().build();
+    assert!(properties.is_metric());
+
+    // Random directions in the plane, on which the cosine distance often
+    // violates the triangle inequality.
+    let mut rng = StdRng::seed_from_u64(42);
+    let samples = (0..100)
+        .map(|_| vec![rng.gen_range(-1.0..1.0_f32), rng.gen_range(-1.0..1.0_f32)])
+        .collect::<Vec<_>>();
+    let (_, properties) = MetricBuilder::new(metric).infer_properties(&samples, 42).build();
+    assert!(properties.is_metric(), "{properties:?}");
+
+    let cosine = |x: &Vec<f32>, y: &Vec<f32>| -> f32 { distances::vectors::cosine(x, y) };
+    let (_, properties) = MetricBuilder::new(cosine).infer_properties(&samples, 42).build();
+    assert!(!properties.triangle_inequality, "{properties:?}");
+
+    let data = VecDataset::new("angular".to_string(), samples, metric, false);
+    assert_eq!(data.check_metric_properties(1000, 42).triangle_inequality, 0);
+}
+
+#[test]
+fn shuffle_split() {
+    let reference = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+
+    let mut first = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    first.shuffle(7);
+    let mut second = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    second.shuffle(7);
+    assert_eq!(first.data(), second.data());
+    assert_ne!(first.data(), reference.data());
+
+    // Shuffling twice must still map back to the original instances.
+    first.shuffle(8);
+    for i in 0..first.cardinality() {
+        assert_eq!(first[i], reference[first.original_index(i)]);
+    }
+
+    let (train, test) = first.split(0.8).unwrap();
+    assert_eq!(train.len(), 800);
+    assert_eq!(test.len(), 200);
+    assert!(train.iter().chain(test.iter()).copied().eq(0..1000));
+
+    assert!(first.split(1.5).is_err());
+}
+
+#[test]
+fn chunks() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean)
+        .assign_metadata((0..1000).map(|i| i * 2).collect())
+        .unwrap();
+    let criteria = PartitionCriteria::default();
+
+    let chunks = data.chunks(300).collect::<Vec<_>>();
+    assert_eq!(
+        chunks.iter().map(Dataset::cardinality).collect::<Vec<_>>(),
+        vec![300, 300, 300, 100]
+    );
+
+    let mut instances = Vec::new();
+    let mut metadata = Vec::new();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        assert_eq!(chunk.name(), format!("test-chunk-{i}"));
+        instances.extend_from_slice(chunk.data());
+        metadata.extend_from_slice(chunk.metadata());
+
+        let tree = Tree::<_, _, _, UniBall<_>>::new(chunk, Some(42)).partition(&criteria, Some(42));
+        assert_eq!(
+            tree.root()
+                .subtree()
+                .iter()
+                .filter(|c| c.is_leaf())
+                .map(|c| c.cardinality())
+                .sum::<usize>(),
+            tree.cardinality()
+        );
+        for c in tree.root().subtree() {
+            let center = &tree.data()[c.arg_center()];
+            for i in c.indices() {
+                assert!(tree.data().metric()(center, &tree.data()[i]) <= c.radius());
+            }
+        }
+    }
+    assert_eq!(instances, data.data());
+    assert_eq!(metadata, data.metadata());
+
+    assert_eq!(data.chunks(0).count(), 1000);
+    assert_eq!(data.chunks(5000).count(), 1);
+}
+
+#[test]
+fn append() {
+    let first = utils::gen_dataset(100, 10, 42, utils::euclidean);
+    let second = utils::gen_dataset(50, 10, 7, utils::euclidean);
+    let reference = first
+        .data()
+        .iter()
+        .chain(second.data().iter())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    // The permutation of a shuffled dataset is extended with the identity.
+    let mut data = utils::gen_dataset(100, 10, 42, utils::euclidean);
+    data.shuffle(1);
+    data.append(
+        utils::gen_dataset(50, 10, 7, utils::euclidean)
+            .assign_metadata((100..150).collect())
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(data.cardinality(), 150);
+    assert_eq!(data.metadata().len(), 150);
+    for i in 0..data.cardinality() {
+        let original = data.original_index(i);
+        assert_eq!(data[i], reference[original]);
+        if i >= 100 {
+            assert_eq!(original, i);
+            assert_eq!(data.metadata_of(i), &i);
+        }
+    }
+
+    let expensive = VecDataset::new("expensive".to_string(), reference.clone(), utils::euclidean, true);
+    assert!(data.append(expensive).is_err());
+    assert_eq!(data.cardinality(), 150);
+
+    // Metrics are compared by their declared names and properties.
+    let named =
+        VecDataset::new("named".to_string(), reference.clone(), utils::euclidean, false).with_metric_name("euclidean");
+    let err = data.append(named).unwrap_err();
+    assert!(err.contains("different metrics"), "{err}");
+    assert_eq!(data.cardinality(), 150);
+
+    let properties = MetricProperties {
+        triangle_inequality: false,
+        ..MetricProperties::default()
+    };
+    let non_metric = VecDataset::new("non-metric".to_string(), reference.clone(), utils::euclidean, false)
+        .with_metric_properties(properties);
+    let err = data.append(non_metric).unwrap_err();
+    assert!(err.contains("different metrics"), "{err}");
+    assert_eq!(data.cardinality(), 150);
+
+    let standardized = VecDataset::new("standardized".to_string(), reference.clone(), utils::euclidean, false)
+        .with_query_transform(QueryTransform::Standardize {
+            means: vec![0.; 10],
+            stds: vec![1.; 10],
+        });
+    let err = data.append(standardized).unwrap_err();
+    assert!(err.contains("different query transforms"), "{err}");
+    assert_eq!(data.cardinality(), 150);
+
+    // Datasets with the same declared metric are appended, even if their
+    // metric functions are different.
+    let mut named = VecDataset::new("named".to_string(), reference.clone(), utils::euclidean, false)
+        .with_metric_name("euclidean")
+        .with_metric_properties(properties);
+    let counted = VecDataset::new("counted".to_string(), reference.clone(), counted_euclidean, false)
+        .with_metric_name("euclidean")
+        .with_metric_properties(properties);
+    named.append(counted).unwrap();
+    assert_eq!(named.cardinality(), 2 * reference.len());
+
+    // A tree built after appending sees all the instances.
+    let mut data = first;
+    data.append(second).unwrap();
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    assert_eq!(tree.root().cardinality(), 150);
+
+    let data = tree.data();
+    let mut originals = (0..data.cardinality())
+        .map(|i| {
+            assert_eq!(data[i], reference[data.original_index(i)]);
+            data.original_index(i)
+        })
+        .collect::<Vec<_>>();
+    originals.sort_unstable();
+    assert_eq!(originals, (0..150).collect::<Vec<_>>());
+}
+
+#[test]
+fn map_metadata() {
+    let ids = (0..100).map(|i| format!("id-{i}")).collect::<Vec<_>>();
+    let mut data = utils::gen_dataset(100, 10, 42, utils::euclidean)
+        .assign_metadata(ids)
+        .unwrap();
+    let reference = data.data().to_vec();
+
+    let mut permutation = (0..100).collect::<Vec<_>>();
+    permutation.shuffle(&mut rand::rngs::StdRng::seed_from_u64(42));
+    data.permute_instances(&permutation).unwrap();
+
+    let data = data.map_metadata(|id: &String| {
+        id.strip_prefix("id-")
+            .and_then(|i| i.parse::<usize>().ok())
+            .unwrap_or(usize::MAX)
+    });
+    for i in 0..data.cardinality() {
+        let original = data.original_index(i);
+        assert_eq!(data[i], reference[original]);
+        assert_eq!(data.metadata_of(i), &original);
+    }
+}
+
+#[test]
+fn geographic() {
+    let cities = vec![
+        [51.5074, -0.1278],
+        [48.8566, 2.3522],
+        [40.7128, -74.0060],
+        [34.0522, -118.2437],
+        [-33.8688, 151.2093],
+        [35.6762, 139.6503],
+    ];
+    let dataset = VecDataset::new(
+        "cities".to_string(),
+        cities.clone(),
+        distances::vectors::haversine,
+        false,
+    );
+    assert_eq!(dataset.dimensionality(), Some(2));
+    assert!((dataset.one_to_one(0, 1) - 343.56).abs() < 0.01);
+
+    let tree = Tree::<_, _, _, UniBall<_>>::new(dataset, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    assert_eq!(tree.root().cardinality(), cities.len());
+
+    let bytes = cities[4].to_bytes();
+    assert_eq!(<[f64; 2]>::from_bytes(&bytes), Ok(cities[4]));
+    assert!(<[f64; 2]>::from_bytes(&bytes[1..]).is_err());
+}
+
+#[test]
+fn deduplicated() {
+    let data = vec![
+        vec![0_u32, 1],
+        vec![2, 3],
+        vec![0, 1],
+        vec![4, 5],
+        vec![2, 3],
+        vec![0, 1],
+    ];
+    let metadata = vec!["a", "b", "c", "d", "e", "f"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let dataset = VecDataset::new("dups".to_string(), data.clone(), utils::euclidean::<u32, f32>, false)
+        .assign_metadata(metadata)
+        .unwrap();
+
+    let (deduped, mapping) = dataset.deduplicated();
+    assert_eq!(deduped.cardinality(), 3);
+    assert_eq!(deduped.data(), &[vec![0, 1], vec![2, 3], vec![4, 5]]);
+    assert_eq!(deduped.metadata(), &["a", "b", "d"]);
+    assert_eq!(mapping, vec![0, 1, 0, 2, 1, 0]);
+
+    for (original, &index) in data.iter().zip(mapping.iter()) {
+        assert_eq!(original, &deduped[index]);
+    }
+
+    let (again, mapping) = deduped.deduplicated();
+    assert_eq!(again.cardinality(), 3);
+    assert_eq!(mapping, vec![0, 1, 2]);
+}
+
+#[test]
+fn try_get() {
+    let data = vec![vec![0., 1.], vec![2., 3.], vec![4., 5.]];
+    let dataset = VecDataset::new("test".to_string(), data.clone(), utils::euclidean::<f32, f32>, false);
+
+    for (i, instance) in data.iter().enumerate() {
+        assert_eq!(dataset.try_get(i), Some(instance));
+        assert_eq!(&dataset.get_owned(i), instance);
+    }
+    assert_eq!(dataset.try_get(3), None);
+    assert_eq!(dataset.try_get(usize::MAX), None);
+
+    // The owned clone outlives the dataset.
+    let owned = dataset.get_owned(1);
+    drop(dataset);
+    assert_eq!(owned, data[1]);
+}
+
+#[test]
+fn mahalanobis() {
+    let data = utils::gen_dataset(100, 5, 42, utils::euclidean::<f32, f32>)
+        .data()
+        .iter()
+        .map(|x| x.iter().copied().map(Number::as_f64).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let identity = (0..5)
+        .map(|i| (0..5).map(|j| if i == j { 1. } else { 0. }).collect())
+        .collect::<Vec<_>>();
+    let metric = Mahalanobis::new(identity, 5).unwrap();
+    assert!(Mahalanobis::properties().triangle_inequality);
+
+    // With the identity matrix, the distance is Euclidean, and whitening does nothing.
+    assert_eq!(metric.whiten(&data).unwrap(), data);
+    for x in &data {
+        for y in &data {
+            assert_approx_eq!(f64, metric.distance(x, y), utils::euclidean(x, y), epsilon = 1e-12);
+        }
+    }
+
+    // Whitening with any other positive-definite matrix preserves the distances.
+    let metric = Mahalanobis::new(
+        (0..5)
+            .map(|i| (0..5).map(|j| if i == j { 2. } else { 0.5 }).collect())
+            .collect(),
+        5,
+    )
+    .unwrap();
+    let whitened = metric.whiten(&data).unwrap();
+    for (x, wx) in data.iter().zip(&whitened) {
+        for (y, wy) in data.iter().zip(&whitened) {
+            let expected: f64 = utils::euclidean(wx, wy);
+            assert_approx_eq!(f64, metric.distance(x, y), expected, epsilon = 1e-9);
+        }
+    }
+
+    assert!(Mahalanobis::new(vec![vec![1., 0.], vec![0.]], 2).is_err());
+    assert!(Mahalanobis::new(vec![vec![1., 0.], vec![0., 1.]], 3).is_err());
+    let indefinite = Mahalanobis::new(vec![vec![1., 2.], vec![2., 1.]], 2).unwrap();
+    assert!(indefinite.whiten(&[vec![1., 1.]]).is_err());
+    assert!(metric.whiten(&[vec![1., 1.]]).is_err());
+}
+
+#[test]
+fn standardize() {
+    let mut data = utils::gen_dataset(1000, 5, 42, utils::euclidean::<f32, f32>)
+        .data()
+        .iter()
+        .map(|x| {
+            x.iter()
+                .enumerate()
+                .map(|(j, &v)| v * 10. + j.as_f32())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // A constant column has zero variance.
+    data.iter_mut().for_each(|x| x.push(3.));
+    let held_out = data[..100].to_vec();
+
+    let mut dataset = VecDataset::new("standardize".to_string(), data, utils::euclidean::<f32, f32>, false);
+    let (means, stds) = dataset.standardize();
+    assert_eq!(means.len(), 6);
+    assert_approx_eq!(f64, means[5], 3.);
+    assert_approx_eq!(f64, stds[5], 1.);
+
+    let n = dataset.cardinality().as_f64();
+    for j in 0..6 {
+        let column = dataset.data().iter().map(|x| x[j].as_f64()).collect::<Vec<_>>();
+        let mean = column.iter().sum::<f64>() / n;
+        let std = (column.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+        assert_approx_eq!(f64, mean, 0., epsilon = 1e-5);
+        let expected_std = if j == 5 { 0. } else { 1. };
+        assert_approx_eq!(f64, std, expected_std, epsilon = 1e-5);
+    }
+
+    // Applying the returned transform to copies of rows taken before
+    // standardization reproduces the standardized rows.
+    for (x, standardized) in held_out.iter().zip(dataset.data()) {
+        let transformed = x
+            .iter()
+            .zip(means.iter().zip(&stds))
+            .map(|(&v, (m, s))| ((v.as_f64() - m) / s).as_f32())
+            .collect::<Vec<_>>();
+        assert_eq!(&transformed, standardized);
+    }
+}
+
+#[test]
+fn approx_diameter() {
+    // On a line, the double sweep always finds the two endpoints.
+    let line = (0..1000).map(|i| vec![i.as_f32(), 0.]).collect::<Vec<_>>();
+    let line = VecDataset::new("line".to_string(), line, utils::euclidean::<f32, f32>, false);
+    assert_approx_eq!(f32, line.approx_diameter(1, 42), 999.);
+    assert_approx_eq!(f32, line.par_approx_diameter(1, 42), 999.);
+
+    let data = utils::gen_dataset(500, 10, 42, utils::euclidean::<f32, f32>);
+    let indices = (0..data.cardinality()).collect::<Vec<_>>();
+    let diameter = data.pairwise(&indices).into_iter().flatten().fold(0., f32::max);
+    for iterations in [1, 5] {
+        let estimate = data.approx_diameter(iterations, 42);
+        assert!(estimate <= diameter);
+        assert!(estimate * 2. >= diameter);
+        assert_approx_eq!(f32, estimate, data.par_approx_diameter(iterations, 42));
+    }
+
+    let empty = VecDataset::new("empty".to_string(), Vec::new(), utils::euclidean::<f32, f32>, false);
+    assert_approx_eq!(f32, empty.approx_diameter(3, 42), 0.);
+}
+
+#[test]
+fn from_iter() {
+    let instance = |i: usize| (0..10).map(|j| (i * 10 + j).as_f32()).collect::<Vec<_>>();
+    let collected = VecDataset::new(
+        "collected".to_string(),
+        (0..1000).map(instance).collect(),
+        utils::euclidean::<f32, f32>,
+        false,
+    );
+
+    let streamed = VecDataset::from_iter(
+        "streamed".to_string(),
+        (0..1000).map(instance),
+        utils::euclidean::<f32, f32>,
+        false,
+    );
+    let par_streamed = VecDataset::par_from_iter(
+        "par_streamed".to_string(),
+        (0..1000).into_par_iter().map(instance),
+        utils::euclidean::<f32, f32>,
+        false,
+    );
+
+    for data in [streamed, par_streamed] {
+        assert_eq!(data.cardinality(), collected.cardinality());
+        assert_eq!(data.data(), collected.data());
+        assert_eq!(data.metadata(), collected.metadata());
+    }
+}
+
+/// Counts the calls to `counted_euclidean`.
+static METRIC_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Euclidean distance that counts how often it is called.
+#[allow(clippy::ptr_arg)]
+fn counted_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    METRIC_CALLS.fetch_add(1, Ordering::Relaxed);
+    distances::vectors::euclidean(x, y)
+}
+
+#[test]
+fn one_to_one_cached() {
+    let data = utils::gen_dataset(1000, 10, 42, counted_euclidean);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    let data = tree.data();
+
+    // The centers of the top of the tree, compared in both orders and
+    // repeatedly, as happens when pruning during a search.
+    let centers = tree
+        .root()
+        .clusters_at_depth(4)
+        .into_iter()
+        .map(Cluster::arg_center)
+        .collect::<Vec<_>>();
+    let pairs = centers
+        .iter()
+        .flat_map(|&a| centers.iter().map(move |&b| (a, b)))
+        .collect::<Vec<_>>();
+
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+    let uncached = [&pairs, &pairs]
+        .into_iter()
+        .flatten()
+        .map(|&(a, b)| data.one_to_one(a, b))
+        .collect::<Vec<_>>();
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed) - calls, 2 * pairs.len());
+
+    let mut cache = DistanceCache::new(true);
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+    let cached = [&pairs, &pairs]
+        .into_iter()
+        .flatten()
+        .map(|&(a, b)| data.one_to_one_cached(&mut cache, a, b))
+        .collect::<Vec<_>>();
+    let n = centers.len();
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed) - calls, n * (n + 1) / 2);
+    assert_eq!(cache.len(), n * (n + 1) / 2);
+    assert_eq!(cache.hits(), 2 * pairs.len() - cache.len());
+    assert_eq!(cached, uncached);
+
+    // Without symmetry, each order is computed once.
+    let mut cache = DistanceCache::new(false);
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+    for &(a, b) in [&pairs, &pairs].into_iter().flatten() {
+        let _ = data.one_to_one_cached(&mut cache, a, b);
+    }
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed) - calls, pairs.len());
+
+    cache.clear();
+    assert!(cache.is_empty());
+    assert_eq!(cache.hits(), 0);
+}
+
+#[test]
+fn query_to_one_cached() {
+    let data = utils::gen_dataset(100, 10, 42, counted_euclidean);
+    let query = vec![0.5; 10];
+    let indices = (0..data.cardinality()).collect::<Vec<_>>();
+    let expected = data.query_to_many(&query, &indices);
+
+    // The distances to the first few instances are cached, as happens for the
+    // centers of `Cluster`s during a search, and then reused for the rest.
+    let mut cache = DistanceCache::default();
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+    for &i in indices.iter().take(10).chain(indices.iter().take(10)) {
+        assert_eq!(data.query_to_one_cached(&mut cache, &query, i), expected[i]);
+    }
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed) - calls, 10);
+    assert_eq!(cache.hits(), 10);
+
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+    assert_eq!(data.query_to_many_cached(&cache, &query, &indices), expected);
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed) - calls, indices.len() - 10);
+}
+
+#[test]
+fn weights() {
+    let corner = vec![2., 2.];
+    let build = |weight: f64| {
+        let data = utils::gen_grid_data(2);
+        let weights = data
+            .data()
+            .iter()
+            .map(|x| if x == &corner { weight } else { 1. })
+            .collect();
+        let Ok(data) = data.with_weights(weights) else {
+            unreachable!("There is one weight per instance.")
+        };
+        Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42))
+    };
+
+    type GridTree = Tree<Vec<f32>, f32, VecDataset<Vec<f32>, f32, bool>, UniBall<f32>>;
+    let unweighted = build(1.);
+    let weighted = build(100.);
+    let center_of = |tree: &GridTree| tree.data()[tree.root().arg_center()].clone();
+    assert_eq!(center_of(&unweighted), vec![0., 0.]);
+    let d = |x: &Vec<f32>| utils::euclidean::<f32, f32>(x, &corner);
+    assert!(d(&center_of(&weighted)) < d(&center_of(&unweighted)));
+
+    // The weights follow the instances when the tree reorders the dataset.
+    let data = weighted.data();
+    let Some(weights) = data.weights() else {
+        unreachable!("The dataset has weights.")
+    };
+    for (x, &w) in data.data().iter().zip(weights) {
+        assert_approx_eq!(f64, w, if x == &corner { 100. } else { 1. });
+    }
+
+    // Search is unaffected by the weights.
+    let query = vec![0.5, 0.5];
+    let hits = |tree: &GridTree| {
+        let mut hits = rnn::Algorithm::Clustered
+            .search(&query, 1.5, tree)
+            .into_iter()
+            .map(|(i, _)| tree.data()[i].clone())
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+        hits
+    };
+    assert_eq!(hits(&weighted), hits(&unweighted));
+
+    let data = utils::gen_grid_data(2);
+    assert!(data.with_weights(vec![1.; 3]).is_err());
+    let data = utils::gen_grid_data(2);
+    assert!(data.with_weights(vec![-1.; 25]).is_err());
+}
+
+#[test]
+fn write_csv() {
+    let data = utils::gen_dataset(500, 5, 42, utils::euclidean);
+    let original = data.data().to_vec();
+    let labels = (0..original.len()).map(|i| i % 3).collect::<Vec<_>>();
+    let Ok(data) = data.assign_metadata(labels.clone()) else {
+        unreachable!("There is one label per instance.")
+    };
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    assert!(tree.data().permuted_indices().is_some());
+
+    let read_csv = |include_metadata: bool| {
+        let mut csv = Vec::new();
+        if let Err(e) = tree.data().write_csv(&mut csv, include_metadata, None) {
+            unreachable!("Writing to a buffer cannot fail: {e}")
+        }
+        String::from_utf8_lossy(&csv)
+            .lines()
+            .map(|row| {
+                row.split(',')
+                    .map(|v| v.parse::<f32>().unwrap_or_else(|e| unreachable!("{e}")))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The rows are in the original order, despite the tree reordering the data.
+    assert_eq!(read_csv(false), original);
+
+    let rows = read_csv(true);
+    assert_eq!(rows.len(), original.len());
+    for ((row, instance), &label) in rows.iter().zip(&original).zip(&labels) {
+        assert_approx_eq!(f32, row[0], label.as_f32());
+        assert_eq!(&row[1..], instance.as_slice());
+    }
+
+    let header = ["label", "a", "b", "c", "d", "e"];
+    let mut csv = Vec::new();
+    if let Err(e) = tree.data().write_csv(&mut csv, true, Some(&header)) {
+        unreachable!("Writing to a buffer cannot fail: {e}")
+    }
+    let csv = String::from_utf8_lossy(&csv);
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().map(|row| row.split(',').collect::<Vec<_>>()),
+        Some(header.to_vec())
+    );
+    assert_eq!(lines.count(), original.len());
+
+    let mut csv = Vec::new();
+    assert!(tree.data().write_csv(&mut csv, false, Some(&header)).is_err());
+    assert!(csv.is_empty());
+}
@@ -0,0 +1,51 @@
+//! Tests for the `dbscan` module.
+
+use abd_clam::{dbscan::dbscan, Dataset, PartitionCriteria, Tree, UniBall};
+use rand::prelude::*;
+
+mod utils;
+
+/// Samples points uniformly from a square of side `2 * spread` around `center`.
+fn blob(center: [f32; 2], spread: f32, cardinality: usize, rng: &mut StdRng) -> Vec<Vec<f32>> {
+    (0..cardinality)
+        .map(|_| center.iter().map(|&c| c + rng.gen_range(-spread..spread)).collect())
+        .collect()
+}
+
+#[test]
+fn two_blobs() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut data = blob([0., 0.], 1., 500, &mut rng);
+    data.extend(blob([10., 10.], 1., 500, &mut rng));
+    // An isolated point, far from both blobs.
+    data.push(vec![-10., 20.]);
+    let metadata = (0..data.len()).collect();
+    let data = utils::gen_dataset_from(data, utils::euclidean::<f32, f32>, metadata);
+
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let labels = dbscan(tree.data(), tree.root(), 0.5, 5);
+    assert_eq!(labels.len(), tree.data().cardinality());
+
+    // Group the labels by the blob each instance was sampled from.
+    let mut blob_labels = [Vec::new(), Vec::new()];
+    for (i, &label) in labels.iter().enumerate() {
+        match *tree.data().metadata_of(i) {
+            1000 => assert_eq!(label, None, "The isolated point is noise."),
+            original => blob_labels[original / 500].push(label),
+        }
+    }
+
+    let num_clusters = labels.iter().flatten().max().map_or(0, |&l| l + 1);
+    assert_eq!(num_clusters, 2);
+    for labels in &blob_labels {
+        let first = labels[0];
+        assert!(first.is_some());
+        assert!(labels.iter().all(|&l| l == first));
+    }
+    assert_ne!(blob_labels[0][0], blob_labels[1][0]);
+
+    // With a neighborhood too small for any core points, everything is noise.
+    let labels = dbscan(tree.data(), tree.root(), 0.5, 1000);
+    assert!(labels.iter().all(Option::is_none));
+}
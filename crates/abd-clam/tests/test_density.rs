@@ -0,0 +1,79 @@
+//! Tests for the `density` module.
+
+use abd_clam::{density::DensityEstimator, Dataset, PartitionCriteria, Tree, UniBall};
+use distances::Number;
+use rand::prelude::*;
+
+mod utils;
+
+/// Samples points from a standard normal distribution in two dimensions.
+fn gaussian_blob(cardinality: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..cardinality)
+        .map(|_| {
+            // Box-Muller transform.
+            let (u1, u2): (f32, f32) = (rng.gen_range(f32::EPSILON..1.0), rng.gen());
+            let (r, theta) = ((-2.0 * u1.ln()).sqrt(), 2.0 * core::f32::consts::PI * u2);
+            vec![r * theta.cos(), r * theta.sin()]
+        })
+        .collect()
+}
+
+#[test]
+fn density() {
+    let data = utils::gen_dataset_from(
+        gaussian_blob(5000, 42),
+        utils::euclidean::<f32, f32>,
+        vec![0_usize; 5000],
+    );
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let (data, root) = (tree.data(), tree.root());
+
+    assert!(DensityEstimator::new(data, root, 0.0).is_err());
+    let estimator = DensityEstimator::new(data, root, 0.5).unwrap();
+    assert!(DensityEstimator::new(data, root, 0.5)
+        .unwrap()
+        .with_num_shells(0)
+        .is_err());
+
+    // The tree-pruned range counts must agree with counting every instance.
+    let radii = estimator.shell_radii();
+    let linear_density = |query: &Vec<f32>| {
+        let distances = data.query_to_many(query, &(0..data.cardinality()).collect::<Vec<_>>());
+        let mut inner = 0.0;
+        let mut density = 0.0;
+        for &outer in &radii {
+            let count = distances
+                .iter()
+                .map(|&d| d.as_f64())
+                .filter(|&d| (inner < d || inner == 0.0) && d <= outer)
+                .count();
+            let middle = (inner + outer) / 2.0 / estimator.bandwidth();
+            density += count.as_f64() * (-middle * middle / 2.0).exp();
+            inner = outer;
+        }
+        density / data.cardinality().as_f64()
+    };
+
+    // The density peaks at the center of the blob and decays outward.
+    let densities = [0.0_f32, 0.5, 1.0, 2.0, 3.0]
+        .into_iter()
+        .map(|x| {
+            let query = vec![x, 0.0];
+            let density = estimator.density(&query);
+            let expected = linear_density(&query);
+            assert!(
+                (density - expected).abs() <= 1e-12,
+                "Expected density {expected} at {query:?} but got {density}."
+            );
+            density
+        })
+        .collect::<Vec<_>>();
+
+    for pair in densities.windows(2) {
+        assert!(pair[0] > pair[1], "Density should decay outward: {densities:?}.");
+    }
+    assert!(densities[0] > 0.0 && densities[0] <= 1.0);
+    assert!(estimator.density(&vec![100.0, 100.0]) == 0.0);
+}
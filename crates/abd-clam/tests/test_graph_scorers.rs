@@ -1,4 +1,6 @@
-use abd_clam::chaoda::graph_scorers::{ClusterCardinality, ComponentCardinality, GraphScorer, VertexDegree};
+use abd_clam::chaoda::graph_scorers::{
+    ClusterCardinality, ComponentCardinality, GraphScorer, Normalization, VertexDegree,
+};
 use abd_clam::chaoda::{pretrained_models, Vertex};
 use abd_clam::graph::Graph;
 use abd_clam::utils::{mean, standard_deviation};
@@ -49,6 +51,42 @@ fn test_score_graph_basics() {
     assert_eq!(results.1.len(), graph.population());
 }
 
+#[test]
+fn test_normalization() {
+    let data = gen_dataset_with_anomaly(1000, 10, 42, euclidean, 2);
+    let partition_criteria: PartitionCriteria<f32> = PartitionCriteria::default();
+    let raw_tree = Tree::new(data, Some(42)).partition(&partition_criteria, Some(42));
+
+    let graph = Graph::from_tree(
+        &raw_tree,
+        &pretrained_models::get_meta_ml_scorers().first().unwrap().1,
+        4,
+    )
+    .unwrap();
+
+    let scorer = VertexDegree;
+    let (gaussian_clusters, gaussian) = scorer.call_with(&graph, Normalization::Gaussian).unwrap();
+    let (default_clusters, default) = scorer.call(&graph).unwrap();
+    assert_eq!(gaussian_clusters, default_clusters);
+    assert_eq!(gaussian, default);
+
+    let (_, min_max) = scorer.call_with(&graph, Normalization::MinMax).unwrap();
+    assert_eq!(min_max.len(), gaussian.len());
+    assert!(min_max.iter().all(|s| (0. ..=1.).contains(s)));
+
+    // Both normalizations are monotone in the raw scores, so they rank the
+    // instances in the same order.
+    for i in 0..gaussian.len() {
+        for j in 0..gaussian.len() {
+            assert_eq!(
+                gaussian[i] < gaussian[j],
+                min_max[i] < min_max[j],
+                "instances {i} and {j} are ranked differently"
+            );
+        }
+    }
+}
+
 #[test]
 fn test_vertex_scorer() {
     for anomaly_count in 0..5 {
@@ -65,21 +103,25 @@ fn test_vertex_scorer() {
         )
         .unwrap();
         let scorer = VertexDegree;
-        let mut results = scorer.call(&graph).unwrap();
-
-        let mean = mean(&*results.1);
-        let standard_dev = standard_deviation(&*results.1);
-
-        let outliers: Vec<f64> = results
-            .1
-            .iter()
-            .filter(|&&x| (x - mean).abs() > 5. * standard_dev)
-            .cloned()
-            .collect();
-
-        assert_eq!(outliers.len(), anomaly_count);
-        if anomaly_count > 0 {
-            outliers.iter().for_each(|o| assert!(o > &mean));
+        let results = scorer.call(&graph).unwrap();
+        let scores = &results.1;
+
+        let mean = mean(scores);
+        let standard_dev = standard_deviation(scores);
+
+        // The anomalies are isolated, so they get the highest scores, far
+        // above the mean. A normal instance may also score well above the mean
+        // when it is left in a small cluster, at the edge of the data, after
+        // its sibling is selected for the graph, so only the ranking of the
+        // anomalies is checked.
+        let is_anomaly = |i: usize| raw_tree.data()[i][0] > 1.;
+        let (anomalies, normal): (Vec<_>, Vec<_>) = (0..scores.len()).partition(|&i| is_anomaly(i));
+        assert_eq!(anomalies.len(), anomaly_count);
+
+        let max_normal = normal.iter().map(|&i| scores[i]).fold(f64::NEG_INFINITY, f64::max);
+        for &i in &anomalies {
+            assert!(scores[i] > max_normal);
+            assert!(scores[i] - mean > 5. * standard_dev);
         }
     }
 }
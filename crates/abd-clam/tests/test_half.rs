@@ -0,0 +1,66 @@
+//! Tests for half-precision instances.
+
+#![cfg(feature = "half")]
+
+use abd_clam::{knn, Dataset, PartitionCriteria, Tree, UniBall, VecDataset};
+use half::f16;
+use rand::prelude::*;
+
+mod utils;
+
+/// Euclidean distance between two vectors of `f16`s, computed in `f32`.
+#[allow(clippy::ptr_arg)]
+fn euclidean_f16(x: &Vec<f16>, y: &Vec<f16>) -> f32 {
+    distances::vectors::euclidean_f16(x, y)
+}
+
+#[test]
+fn knn_matches_f32() {
+    let seed = 42;
+    let (cardinality, dimensionality) = (2_000, 10);
+
+    // Round the values through `f16` so that both datasets hold identical values.
+    let data_f16 = symagen::random_data::random_tabular(
+        cardinality,
+        dimensionality,
+        -1_f32,
+        1.,
+        &mut rand::rngs::StdRng::seed_from_u64(seed),
+    )
+    .into_iter()
+    .map(|row| row.into_iter().map(f16::from_f32).collect::<Vec<_>>())
+    .collect::<Vec<_>>();
+    let data_f32 = data_f16
+        .iter()
+        .map(|row| row.iter().map(|v| v.to_f32()).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let queries_f16 = data_f16.iter().step_by(100).cloned().collect::<Vec<_>>();
+    let queries_f32 = data_f32.iter().step_by(100).cloned().collect::<Vec<_>>();
+
+    let criteria = PartitionCriteria::default();
+    let data_f16 = VecDataset::new("f16".to_string(), data_f16, euclidean_f16, false);
+    let tree_f16 = Tree::<_, _, _, UniBall<_>>::new(data_f16, Some(seed)).partition(&criteria, Some(seed));
+    let data_f32 = VecDataset::new("f32".to_string(), data_f32, utils::euclidean::<f32, f32>, false);
+    let tree_f32 = Tree::<_, _, _, UniBall<_>>::new(data_f32, Some(seed)).partition(&criteria, Some(seed));
+
+    for (query_f16, query_f32) in queries_f16.iter().zip(queries_f32.iter()) {
+        for k in [1, 10, 100] {
+            for algo in [knn::Algorithm::Linear, knn::Algorithm::GreedySieve] {
+                let hits_f16 = original_indices(tree_f16.data(), algo.search(&tree_f16, query_f16, k));
+                let hits_f32 = original_indices(tree_f32.data(), algo.search(&tree_f32, query_f32, k));
+                assert_eq!(hits_f16, hits_f32, "Failed with {} for k = {k}", algo.name());
+            }
+        }
+    }
+}
+
+/// Maps the hits back to the indices of the instances before the tree was built.
+fn original_indices<I: abd_clam::Instance, D: Dataset<I, f32>>(data: &D, hits: Vec<(usize, f32)>) -> Vec<usize> {
+    let mut indices = hits
+        .into_iter()
+        .map(|(i, _)| data.original_index(i))
+        .collect::<Vec<_>>();
+    indices.sort_unstable();
+    indices
+}
@@ -0,0 +1,64 @@
+//! Tests for the `join` module.
+
+use abd_clam::{join::radius_join, Dataset, MatrixDataset, PartitionCriteria, Tree, UniBall};
+use test_case::test_case;
+
+mod utils;
+
+#[test_case(0.5; "small")]
+#[test_case(2.0; "medium")]
+#[test_case(100.0; "everything")]
+fn radius_join_matches_brute_force(radius: f32) {
+    let criteria = PartitionCriteria::default();
+    let a = utils::gen_dataset(300, 3, 42, utils::euclidean::<f32, f32>);
+    let a = Tree::<_, _, _, UniBall<_>>::new(a, Some(42)).partition(&criteria, Some(42));
+    let b = utils::gen_dataset(200, 3, 43, utils::euclidean::<f32, f32>);
+    let b = Tree::<_, _, _, UniBall<_>>::new(b, Some(42)).partition(&criteria, Some(42));
+
+    let hits = radius_join(a.data(), a.root(), b.data(), b.root(), radius);
+
+    let (data_a, data_b) = (a.data(), b.data());
+    let expected = (0..data_a.cardinality())
+        .flat_map(|i| {
+            (0..data_b.cardinality()).filter_map(move |j| {
+                let d = utils::euclidean::<f32, f32>(&data_a[i], &data_b[j]);
+                (d <= radius).then_some((i, j, d))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    assert!(!expected.is_empty());
+    assert_eq!(hits, expected);
+}
+
+#[test]
+fn radius_join_matrix_dataset() {
+    // A `MatrixDataset` has no metric function, so the join must go through
+    // the `Dataset` methods.
+    let positions = [0_u32, 1, 3, 6, 10, 15, 21, 28, 36, 45];
+    let matrix = positions
+        .iter()
+        .map(|&x| positions.iter().map(|&y| x.abs_diff(y)).collect())
+        .collect::<Vec<Vec<_>>>();
+    let build = || {
+        let data = MatrixDataset::new("line".to_string(), matrix.clone()).unwrap();
+        Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42))
+    };
+    let (a, b) = (build(), build());
+
+    let radius = 5;
+    let hits = radius_join(a.data(), a.root(), b.data(), b.root(), radius);
+
+    let (data_a, data_b) = (a.data(), b.data());
+    let expected = (0..data_a.cardinality())
+        .flat_map(|i| {
+            (0..data_b.cardinality()).filter_map(move |j| {
+                let d = positions[data_a[i]].abs_diff(positions[data_b[j]]);
+                (d <= radius).then_some((i, j, d))
+            })
+        })
+        .collect::<Vec<_>>();
+
+    assert!(!expected.is_empty());
+    assert_eq!(hits, expected);
+}
@@ -0,0 +1,109 @@
+//! Tests for the `MatrixDataset` struct.
+
+use abd_clam::{rnn, Dataset, MatrixDataset, PartitionCriteria, Tree, UniBall};
+use tempdir::TempDir;
+
+/// The positions, on a line, of the points whose distances are in the matrices.
+const POSITIONS: [u32; 8] = [0, 1, 3, 6, 10, 15, 21, 28];
+
+/// The full distance matrix of the `POSITIONS`.
+fn full_matrix() -> Vec<Vec<u32>> {
+    vec![
+        vec![0, 1, 3, 6, 10, 15, 21, 28],
+        vec![1, 0, 2, 5, 9, 14, 20, 27],
+        vec![3, 2, 0, 3, 7, 12, 18, 25],
+        vec![6, 5, 3, 0, 4, 9, 15, 22],
+        vec![10, 9, 7, 4, 0, 5, 11, 18],
+        vec![15, 14, 12, 9, 5, 0, 6, 13],
+        vec![21, 20, 18, 15, 11, 6, 0, 7],
+        vec![28, 27, 25, 22, 18, 13, 7, 0],
+    ]
+}
+
+/// The condensed distance matrix of the `POSITIONS`.
+fn condensed_matrix() -> Vec<u32> {
+    full_matrix()
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.into_iter().skip(i + 1))
+        .collect()
+}
+
+#[test]
+fn construction() {
+    assert!(MatrixDataset::new("bad".to_string(), vec![vec![0_u32, 1], vec![1]]).is_err());
+    assert!(MatrixDataset::from_condensed("bad".to_string(), vec![1_u32, 2]).is_err());
+
+    let full = MatrixDataset::new("full".to_string(), full_matrix()).unwrap();
+    let condensed = MatrixDataset::from_condensed("condensed".to_string(), condensed_matrix()).unwrap();
+    assert_eq!(full.cardinality(), POSITIONS.len());
+    assert_eq!(condensed.cardinality(), POSITIONS.len());
+
+    for (i, &x) in POSITIONS.iter().enumerate() {
+        assert_eq!(full[i], i);
+        for (j, &y) in POSITIONS.iter().enumerate() {
+            let expected = x.abs_diff(y);
+            assert_eq!(full.one_to_one(i, j), expected);
+            assert_eq!(condensed.one_to_one(i, j), expected);
+            assert_eq!(condensed.query_to_one(&i, j), expected);
+        }
+    }
+}
+
+#[test]
+fn rnn_search() {
+    let criteria = PartitionCriteria::default();
+    for data in [
+        MatrixDataset::new("full".to_string(), full_matrix()).unwrap(),
+        MatrixDataset::from_condensed("condensed".to_string(), condensed_matrix()).unwrap(),
+    ] {
+        let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+        assert_eq!(tree.cardinality(), POSITIONS.len());
+
+        for (query, &x) in POSITIONS.iter().enumerate() {
+            for radius in [0, 3, 5, 10] {
+                let expected = (0..POSITIONS.len())
+                    .filter(|&i| x.abs_diff(POSITIONS[i]) <= radius)
+                    .collect::<Vec<_>>();
+
+                for algo in rnn::Algorithm::variants() {
+                    let mut hits = algo
+                        .search(&query, radius, &tree)
+                        .into_iter()
+                        .map(|(i, d)| {
+                            // The instances of the dataset are the rows of the matrix.
+                            let row = tree.data()[i];
+                            assert_eq!(row, tree.data().original_index(i));
+                            assert_eq!(d, x.abs_diff(POSITIONS[row]));
+                            row
+                        })
+                        .collect::<Vec<_>>();
+                    hits.sort_unstable();
+                    assert_eq!(hits, expected, "Failed with {} for radius {radius}", algo.name());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn save_load() {
+    let tmp_dir = TempDir::new("matrix_dataset").unwrap();
+    let path = tmp_dir.path().join("matrix");
+
+    let data = MatrixDataset::from_condensed("condensed".to_string(), condensed_matrix()).unwrap();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    let data = tree.data();
+    data.save(&path).unwrap();
+
+    let loaded = MatrixDataset::<u32>::load(&path, data.metric(), false).unwrap();
+    assert_eq!(loaded.name(), data.name());
+    assert_eq!(loaded.cardinality(), data.cardinality());
+    for i in 0..data.cardinality() {
+        assert_eq!(loaded[i], data[i]);
+        assert_eq!(loaded.original_index(i), data.original_index(i));
+        for j in 0..data.cardinality() {
+            assert_eq!(loaded.one_to_one(i, j), data.one_to_one(i, j));
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Tests for meta-ml models and their use in selecting clusters for graphs.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::path::Path;
+
+use abd_clam::{
+    chaoda::{
+        metaml::{load_ensemble, save_ensemble, MetaMLDataset, MetaMLModel},
+        Vertex,
+    },
+    graph::Graph,
+    Cluster, PartitionCriteria, Tree,
+};
+use tempdir::TempDir;
+
+mod utils;
+
+/// A linear model with fixed weights, standing in for a trained regressor.
+#[derive(Debug, PartialEq)]
+struct FixedLinear {
+    /// The weights of the features.
+    weights: [f32; 6],
+}
+
+impl MetaMLModel for FixedLinear {
+    fn train(&mut self, _: MetaMLDataset) {}
+
+    fn predict(&self, features: &[f32; 6]) -> Result<f32, String> {
+        Ok(self.weights.iter().zip(features).map(|(w, f)| w * f).sum())
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+        let weights = bincode::deserialize(&bytes).map_err(|e| e.to_string())?;
+        Ok(Self { weights })
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let bytes = bincode::serialize(&self.weights).map_err(|e| e.to_string())?;
+        std::fs::write(path, bytes).map_err(|e| e.to_string())
+    }
+}
+
+#[test]
+fn ensemble_round_trip() {
+    let models = (0..4_u8)
+        .map(|i| {
+            let weights = core::array::from_fn(|j| f32::from(i) - 0.25 * f32::from(u8::try_from(j).unwrap()));
+            (format!("model_{i}"), FixedLinear { weights })
+        })
+        .collect::<Vec<_>>();
+    let tmp_dir = TempDir::new("ensemble").unwrap();
+
+    save_ensemble(&models, tmp_dir.path()).unwrap();
+    let loaded = load_ensemble::<FixedLinear>(tmp_dir.path()).unwrap();
+    assert_eq!(loaded, models);
+
+    let features = [[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], [1., 0., -1., 2., 0.5, 3.]];
+    for ((_, original), (_, loaded)) in models.iter().zip(&loaded) {
+        for f in &features {
+            assert_eq!(original.predict(f), loaded.predict(f));
+        }
+    }
+
+    assert!(load_ensemble::<FixedLinear>(&tmp_dir.path().join("missing")).is_err());
+}
+
+/// A backend that predicts the first of the six ratios as the score, and
+/// counts how many predictions it makes.
+#[derive(Default)]
+struct Identity {
+    /// The number of calls to `predict`.
+    calls: AtomicUsize,
+}
+
+impl MetaMLModel for Identity {
+    fn train(&mut self, _: MetaMLDataset) {}
+
+    fn predict(&self, features: &[f32; 6]) -> Result<f32, String> {
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        Ok(features[0])
+    }
+
+    fn load(_: &Path) -> Result<Self, String> {
+        Ok(Self::default())
+    }
+
+    fn save(&self, _: &Path) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[test]
+fn graph_from_model() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, Vertex<_>>::new(data, Some(42))
+        .partition(&criteria, Some(42))
+        .normalize_ratios();
+
+    let model = Identity::default();
+    let graph = Graph::from_tree_with_model(&tree, &model, 4).unwrap();
+    assert_eq!(model.calls.load(Ordering::Relaxed), tree.root().subtree().len());
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn first_ratio(ratios: [f64; 6]) -> f64 {
+        f64::from(ratios[0] as f32)
+    }
+    let expected = Graph::from_tree(&tree, &Box::new(first_ratio), 4).unwrap();
+    assert_eq!(graph.ordered_clusters(), expected.ordered_clusters());
+    assert_eq!(graph.edge_cardinality(), expected.edge_cardinality());
+}
@@ -1,8 +1,11 @@
 //! Tests for the Search algorithms.
 
-use abd_clam::{knn, rnn, PartitionCriteria, Tree, UniBall};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use abd_clam::{knn, rnn, BoundingBoxes, Cluster, Dataset, PartitionCriteria, Tree, UniBall, VecDataset};
 use distances::Number;
 use float_cmp::assert_approx_eq;
+use rand::prelude::*;
 use test_case::test_case;
 
 mod utils;
@@ -97,3 +100,634 @@ fn variants(cardinality: usize, dimensionality: usize) {
         }
     }
 }
+
+#[test]
+fn stable_ties() {
+    // A grid of points on which many instances are equidistant from the origin.
+    let data = utils::gen_grid_data(5);
+
+    let query = &vec![0.0, 0.0];
+
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    for k in [1, 3, 5, 10, 20] {
+        let linear_nn = knn::Algorithm::Linear.search(&tree, query, k);
+        assert_eq!(linear_nn.len(), k);
+
+        for &variant in [knn::Algorithm::Linear].iter().chain(knn::Algorithm::variants()) {
+            let first = variant.search(&tree, query, k);
+            for _ in 0..10 {
+                assert_eq!(
+                    first,
+                    variant.search(&tree, query, k),
+                    "{} was not stable.",
+                    variant.name()
+                );
+            }
+            assert_eq!(first, linear_nn, "{} differed from Linear.", variant.name());
+        }
+    }
+}
+
+#[test]
+fn rnn_search_instrumented() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let num_leaves = tree.root().subtree().into_iter().filter(|c| c.is_leaf()).count();
+
+    let query = &tree.data()[0];
+    for (radius, reaches_everything) in [(0.1, false), (100., true)] {
+        let (hits, stats) = rnn::Algorithm::Clustered.search_instrumented(query, radius, &tree);
+
+        let mut expected = rnn::Algorithm::Clustered.search(query, radius, &tree);
+        let mut hits = hits;
+        expected.sort_by_key(|&(i, _)| i);
+        hits.sort_by_key(|&(i, _)| i);
+        assert_eq!(hits, expected);
+
+        if reaches_everything {
+            assert_eq!(stats.leaves_searched, num_leaves);
+            assert_eq!(hits.len(), tree.cardinality());
+        } else {
+            assert!(stats.leaves_searched < num_leaves, "{stats:?}");
+            assert!(stats.distance_calls < tree.cardinality(), "{stats:?}");
+        }
+        assert!(stats.clusters_examined > 0);
+    }
+
+    let (_, stats) = rnn::Algorithm::Linear.search_instrumented(query, 0.1, &tree);
+    assert_eq!(stats.distance_calls, tree.cardinality());
+}
+
+#[test]
+fn histograms() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let data = symagen::random_data::random_tabular(500, 16, 0_f32, 1., &mut rng)
+        .into_iter()
+        .map(|h| {
+            let total = h.iter().sum::<f32>();
+            h.into_iter().map(|v| v / total).collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let data = VecDataset::new("histograms".to_string(), data, utils::wasserstein::<f32, f32>, false);
+
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let data = tree.data();
+
+    let radius = 0.5;
+    let search = |algo: rnn::Algorithm, i: usize| {
+        let mut hits = algo
+            .search(&data[i], radius, &tree)
+            .into_iter()
+            .map(|(j, _)| j)
+            .collect::<Vec<_>>();
+        hits.sort_unstable();
+        hits
+    };
+    let neighbors = (0..data.cardinality())
+        .map(|i| search(rnn::Algorithm::Linear, i))
+        .collect::<Vec<_>>();
+
+    for (i, hits) in neighbors.iter().enumerate() {
+        assert!(hits.binary_search(&i).is_ok());
+        for &j in hits {
+            assert!(
+                neighbors[j].binary_search(&i).is_ok(),
+                "{j} is a neighbor of {i} but not vice versa."
+            );
+        }
+
+        // `Clustered` search prunes children with a bound that only holds in
+        // Euclidean spaces, so it may miss hits but never returns false ones.
+        let clustered = search(rnn::Algorithm::Clustered, i);
+        assert!(clustered.iter().all(|j| hits.binary_search(j).is_ok()));
+    }
+}
+
+/// The Euclidean distance from a sparse vector, given as `(index, value)`
+/// pairs, to a dense vector.
+#[allow(clippy::ptr_arg)]
+fn sparse_euclidean(x: &Vec<(usize, f32)>, y: &Vec<f32>) -> f32 {
+    let mut dense = vec![0.; y.len()];
+    for &(i, v) in x {
+        dense[i] = v;
+    }
+    utils::euclidean(&dense, y)
+}
+
+#[test]
+fn cross_search() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let sparsify = |v: Vec<f32>| v.into_iter().map(|x| if x < 0.5 { 0. } else { x }).collect::<Vec<_>>();
+    let data = symagen::random_data::random_tabular(1000, 20, 0., 1., &mut rng)
+        .into_iter()
+        .map(sparsify)
+        .collect::<Vec<_>>();
+    let dense_queries = symagen::random_data::random_tabular(10, 20, 0., 1., &mut rng)
+        .into_iter()
+        .map(sparsify)
+        .collect::<Vec<_>>();
+    let sparse_queries = dense_queries
+        .iter()
+        .map(|q| {
+            q.iter()
+                .enumerate()
+                .filter(|(_, &v)| v != 0.)
+                .map(|(i, &v)| (i, v))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let data = utils::gen_dataset_from(data, utils::euclidean::<f32, f32>, vec![0_usize; 1000]);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    let sparse_refs = sparse_queries.iter().collect::<Vec<_>>();
+    let mut num_hits = 0;
+    for radius in [0.5, 1., 1.5] {
+        for &algo in [rnn::Algorithm::Linear].iter().chain(rnn::Algorithm::variants()) {
+            let batch = algo.par_search_cross(sparse_euclidean, &sparse_refs, radius, &tree);
+            assert_eq!(batch.len(), dense_queries.len());
+
+            for ((dense, sparse), batch_hits) in dense_queries.iter().zip(sparse_queries.iter()).zip(batch) {
+                let mut expected = algo.search(dense, radius, &tree);
+                expected.sort_by_key(|&(i, _)| i);
+                let mut actual = algo.search_cross(sparse_euclidean, sparse, radius, &tree);
+                actual.sort_by_key(|&(i, _)| i);
+                assert_eq!(actual, expected, "{} differed for radius {radius}.", algo.name());
+                num_hits += actual.len();
+
+                let mut batch_hits = batch_hits;
+                batch_hits.sort_by_key(|&(i, _)| i);
+                assert_eq!(batch_hits, expected, "{} differed for radius {radius}.", algo.name());
+            }
+        }
+    }
+    assert!(num_hits > 0, "The searches should find some hits.");
+}
+
+#[test]
+fn rnn_auto() {
+    assert!(matches!(
+        rnn::Algorithm::from_name("auto"),
+        Ok(rnn::Algorithm::Auto(rnn::DEFAULT_LINEAR_THRESHOLD))
+    ));
+
+    let data = utils::gen_dataset(500, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    for threshold in [0, 499, 500, 501, 1000] {
+        let algo = rnn::Algorithm::Auto(threshold);
+        for i in [0, 100, 200] {
+            let query = &tree.data()[i];
+            for radius in [0.1, 0.5, 1.] {
+                let mut expected = rnn::Algorithm::Clustered.search(query, radius, &tree);
+                expected.sort_by_key(|&(i, _)| i);
+
+                let (mut hits, stats) = algo.search_instrumented(query, radius, &tree);
+                hits.sort_by_key(|&(i, _)| i);
+                assert_eq!(hits, expected, "Auto({threshold}) differed from Clustered.");
+
+                let mut hits = algo.search(query, radius, &tree);
+                hits.sort_by_key(|&(i, _)| i);
+                assert_eq!(hits, expected, "Auto({threshold}) differed from Clustered.");
+
+                // Linear search examines no clusters.
+                assert_eq!(stats.clusters_examined == 0, tree.cardinality() <= threshold);
+            }
+        }
+    }
+}
+
+/// Counts the calls to `overlap_counted_euclidean`.
+static OVERLAP_METRIC_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Euclidean distance that counts how many times it is called.
+///
+/// This is separate from `counted_euclidean` so that tests running in
+/// parallel do not disturb each other's counts.
+#[allow(clippy::ptr_arg)]
+fn overlap_counted_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    OVERLAP_METRIC_CALLS.fetch_add(1, Ordering::Relaxed);
+    utils::euclidean(x, y)
+}
+
+#[test]
+fn overlapping_children_triangle_filter() {
+    let data = utils::gen_dataset(10_000, 3, 42, overlap_counted_euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let data = tree.data();
+    let queries = utils::gen_dataset(100, 3, 0, utils::euclidean).data_owned();
+    let radius = 0.05;
+
+    // Counts the calls to the metric of the dataset made by `f`.
+    let count_calls = |f: &mut dyn FnMut()| {
+        let before = OVERLAP_METRIC_CALLS.load(Ordering::Relaxed);
+        f();
+        OVERLAP_METRIC_CALLS.load(Ordering::Relaxed) - before
+    };
+
+    // The number of distances computed to pick the children of each cluster
+    // and then to check their centers.
+    let (mut hyperplane_calls, mut filtered_calls) = (0, 0);
+    for query in &queries {
+        // Distances computed by the test itself do not go through the dataset.
+        let distance_to = |i: usize| utils::euclidean::<f32, f32>(query, &data[i]);
+        let overlapping = tree
+            .root()
+            .subtree()
+            .into_iter()
+            .filter(|c| !c.is_leaf() && distance_to(c.arg_center()) <= c.radius() + radius);
+        for c in overlapping {
+            // The children picked by the hyperplane between the poles alone.
+            let mut expected = Vec::new();
+            hyperplane_calls += count_calls(&mut || {
+                let [left, right] = c.children().unwrap();
+                let [arg_l, arg_r] = c.arg_poles().unwrap();
+                let (ql, qr) = (data.query_to_one(query, arg_l), data.query_to_one(query, arg_r));
+                expected = if (ql - qr).abs() * (ql + qr) <= 2. * c.polar_distance().unwrap() * radius {
+                    vec![left, right]
+                } else if ql < qr {
+                    vec![left]
+                } else {
+                    vec![right]
+                };
+                for child in &expected {
+                    data.query_to_one(query, child.arg_center());
+                }
+            });
+
+            let mut children = Vec::new();
+            filtered_calls += count_calls(&mut || {
+                children = c.overlapping_children(data, query, radius);
+                for child in &children {
+                    data.query_to_one(query, child.arg_center());
+                }
+            });
+
+            assert!(children.iter().all(|child| expected.contains(child)));
+            for child in expected.iter().filter(|child| !children.contains(child)) {
+                assert!(distance_to(child.arg_center()) > child.radius() + radius);
+            }
+        }
+
+        let mut clustered = rnn::Algorithm::Clustered.search(query, radius, &tree);
+        let mut linear = rnn::Algorithm::Linear.search(query, radius, &tree);
+        clustered.sort_by_key(|&(i, _)| i);
+        linear.sort_by_key(|&(i, _)| i);
+        assert_eq!(clustered, linear);
+    }
+
+    assert!(
+        filtered_calls < hyperplane_calls,
+        "{filtered_calls} calls with the filter, {hyperplane_calls} without."
+    );
+}
+
+/// Counts the calls to `counted_euclidean`.
+static METRIC_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Euclidean distance that counts how many times it is called.
+#[allow(clippy::ptr_arg)]
+fn counted_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    METRIC_CALLS.fetch_add(1, Ordering::Relaxed);
+    utils::euclidean(x, y)
+}
+
+#[test]
+fn repeated_rnn_metric_calls() {
+    let (cardinality, dimensionality) = (10_000, 3);
+    let data = utils::gen_dataset(cardinality, dimensionality, 42, counted_euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let queries = utils::gen_dataset(10, dimensionality, 0, utils::euclidean).data_owned();
+
+    for k in [1, 10, 100] {
+        for query in &queries {
+            let mut linear_nn = knn::Algorithm::Linear
+                .search(&tree, query, k)
+                .into_iter()
+                .map(|(_, d)| d)
+                .collect::<Vec<_>>();
+            linear_nn.sort_by(f32::total_cmp);
+
+            let start = METRIC_CALLS.load(Ordering::Relaxed);
+            let hits = knn::Algorithm::RepeatedRnn.search(&tree, query, k);
+            let num_calls = METRIC_CALLS.load(Ordering::Relaxed) - start;
+
+            let mut distances = hits.into_iter().map(|(_, d)| d).collect::<Vec<_>>();
+            distances.sort_by(f32::total_cmp);
+            assert_eq!(distances, linear_nn, "RepeatedRnn differed from Linear for k = {k}.");
+
+            // Clusters reached at a smaller radius are not searched again, so
+            // in low dimensions, far fewer than all instances are compared.
+            assert!(
+                num_calls < cardinality / 2,
+                "RepeatedRnn made {num_calls} metric calls for k = {k}."
+            );
+        }
+    }
+}
+
+#[test]
+fn linear_knn_strings() {
+    let data = ["AAAA", "AAAT", "AATT", "ATTT", "TTTT", "CCCC", "GGGG"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    let dataset = VecDataset::new("strings".to_string(), data.clone(), utils::hamming::<u16>, false);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(dataset, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+
+    // The query is a `String`, which is not `Copy`, and is passed by reference.
+    let query = "AATA".to_string();
+    let hits = knn::Algorithm::Linear.search(&tree, &query, 2);
+    let mut hits = hits
+        .into_iter()
+        .map(|(i, d)| (tree.data()[i].as_str(), d))
+        .collect::<Vec<_>>();
+    hits.sort_unstable();
+    assert_eq!(hits, vec![("AAAA", 1), ("AATT", 1)]);
+
+    let cakes = abd_clam::Cakes::new(
+        VecDataset::new("strings".to_string(), data, utils::hamming::<u16>, false),
+        Some(42),
+        &PartitionCriteria::default(),
+    );
+    let queries = [&query, &"GGGG".to_string()];
+    let hits = cakes.batch_linear_knn_search(&queries, 1);
+    assert_eq!(hits[0].len(), 1);
+    assert_eq!(hits[1], vec![(hits[1][0].0, 0)]);
+    assert_eq!(cakes[hits[1][0].0], "GGGG");
+}
+
+#[test]
+fn knn_by_kth_radius() {
+    let (cardinality, dimensionality) = (10_000, 3);
+    let data = utils::gen_dataset(cardinality, dimensionality, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let queries = utils::gen_dataset(10, dimensionality, 0, utils::euclidean).data_owned();
+
+    for k in [1, 10, 100, 1_000] {
+        let (mut repeated, mut by_kth) = (0, 0);
+        for query in &queries {
+            let (expected, iterations) = knn::Algorithm::RepeatedRnn.search_with_rnn_iterations(&tree, query, k);
+            assert_eq!(expected, knn::Algorithm::RepeatedRnn.search(&tree, query, k));
+            repeated += iterations.unwrap();
+
+            let (hits, iterations) = knn::Algorithm::KnnByKthRadius.search_with_rnn_iterations(&tree, query, k);
+            assert_eq!(hits, expected, "KnnByKthRadius differed from RepeatedRnn for k = {k}.");
+            by_kth += iterations.unwrap();
+        }
+
+        if k >= 100 {
+            assert!(
+                by_kth < repeated,
+                "KnnByKthRadius made {by_kth} RNN searches and RepeatedRnn made {repeated} for k = {k}."
+            );
+        }
+    }
+
+    let (hits, iterations) = knn::Algorithm::GreedySieve.search_with_rnn_iterations(&tree, &queries[0], 10);
+    assert_eq!(hits, knn::Algorithm::GreedySieve.search(&tree, &queries[0], 10));
+    assert!(iterations.is_none());
+}
+
+thread_local! {
+    /// The addresses of the instances passed to `recorded_euclidean` on this thread.
+    static SCORED: core::cell::RefCell<Vec<usize>> = const { core::cell::RefCell::new(Vec::new()) };
+}
+
+/// Euclidean distance that records the address of the instance it was called on.
+#[allow(clippy::ptr_arg)]
+fn recorded_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    SCORED.with(|scored| scored.borrow_mut().push((y as *const Vec<f32>) as usize));
+    utils::euclidean(x, y)
+}
+
+#[test]
+fn knn_center_distances() {
+    let (cardinality, dimensionality) = (10_000, 3);
+    let data = utils::gen_dataset(cardinality, dimensionality, 42, recorded_euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let queries = utils::gen_dataset(10, dimensionality, 0, utils::euclidean).data_owned();
+
+    for k in [1, 10, 100] {
+        for query in &queries {
+            let linear_nn = knn::Algorithm::Linear.search(&tree, query, k);
+
+            for &variant in &[
+                knn::Algorithm::GreedySieve,
+                knn::Algorithm::Sieve,
+                knn::Algorithm::SieveSepCenter,
+            ] {
+                SCORED.with(|scored| scored.borrow_mut().clear());
+                let hits = variant.search(&tree, query, k);
+                let mut scored = SCORED.with(|scored| scored.take());
+                let num_calls = scored.len();
+                scored.sort_unstable();
+                scored.dedup();
+
+                assert_eq!(hits, linear_nn, "{} differed from Linear for k = {k}.", variant.name());
+
+                // The distance to the center of a cluster is computed once and
+                // reused when its instances are scored.
+                assert_eq!(
+                    num_calls,
+                    scored.len(),
+                    "{} computed some distances more than once for k = {k}.",
+                    variant.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn rnn_bounding_boxes() {
+    let data = utils::gen_dataset(2000, 3, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let boxes = BoundingBoxes::new(tree.data(), tree.root());
+
+    for c in tree.root().subtree() {
+        let (lower, upper) = boxes.aabb(c).unwrap();
+        for i in c.indices() {
+            for ((&l, &u), &x) in lower.iter().zip(upper).zip(&tree.data()[i]) {
+                assert!(l <= x && x <= u, "Instance {i} is outside the box of {c}.");
+            }
+        }
+    }
+
+    let queries = utils::gen_dataset(25, 3, 0, utils::euclidean).data_owned();
+    for radius in [0.01, 0.05, 0.1, 0.5] {
+        for query in &queries {
+            let mut expected = rnn::Algorithm::Clustered.search(query, radius, &tree);
+            let mut actual = rnn::Algorithm::Clustered.search_with_bounding_boxes(query, radius, &tree, &boxes);
+            expected.sort_by_key(|&(i, _)| i);
+            actual.sort_by_key(|&(i, _)| i);
+            assert_eq!(actual, expected, "Failed for radius {radius}.");
+        }
+    }
+}
+
+#[test]
+fn squared_euclidean() {
+    let (cardinality, dimensionality) = (2000, 5);
+    let criteria = PartitionCriteria::default();
+    let data = utils::gen_dataset(cardinality, dimensionality, 42, utils::euclidean);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let data = utils::gen_dataset(cardinality, dimensionality, 42, utils::euclidean_sq);
+    let sq_tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    // The trees may be permuted differently, so hits are compared by instance.
+    let to_instances = |tree: &Tree<Vec<f32>, f32, VecDataset<_, _, usize>, UniBall<f32>>, hits: Vec<(usize, f32)>| {
+        let mut hits = hits
+            .into_iter()
+            .map(|(i, d)| (tree.data()[i].clone(), d))
+            .collect::<Vec<_>>();
+        hits.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap());
+        hits
+    };
+    let assert_same = |expected: Vec<(Vec<f32>, f32)>, actual: Vec<(Vec<f32>, f32)>, msg: &str| {
+        assert_eq!(expected.len(), actual.len(), "{msg}");
+        for ((x, d), (y, e)) in expected.into_iter().zip(actual) {
+            assert_eq!(x, y, "{msg}");
+            assert!((d - e).abs() <= 1e-6, "{msg}: {d} vs {e}");
+        }
+    };
+
+    let queries = utils::gen_dataset(10, dimensionality, 0, utils::euclidean).data_owned();
+    for query in &queries {
+        for radius in [0.1, 0.5, 1.0] {
+            let expected = to_instances(&tree, rnn::Algorithm::Linear.search(query, radius, &tree));
+            for &variant in &[rnn::Algorithm::Linear, rnn::Algorithm::Clustered] {
+                let actual = to_instances(&sq_tree, variant.search_squared(query, radius, &sq_tree));
+                assert_same(
+                    expected.clone(),
+                    actual,
+                    &format!("{} for radius {radius}", variant.name()),
+                );
+            }
+        }
+
+        // Each algorithm searches in the squared space, and gives the same hits
+        // as it does in the Euclidean tree.
+        for k in [1, 10, 100] {
+            let linear = to_instances(&tree, knn::Algorithm::Linear.search(&tree, query, k));
+            for &variant in core::iter::once(&knn::Algorithm::Linear).chain(knn::Algorithm::variants()) {
+                let expected = to_instances(&tree, variant.search(&tree, query, k));
+                assert_same(
+                    linear.clone(),
+                    expected.clone(),
+                    &format!("{} for k = {k}", variant.name()),
+                );
+
+                let actual = to_instances(&sq_tree, variant.search_squared(&sq_tree, query, k));
+                assert_same(expected.clone(), actual, &format!("{} for k = {k}", variant.name()));
+            }
+        }
+    }
+}
+
+#[test]
+fn linear_search_by() {
+    // Pairs of instances at the same distance from the origin.
+    let data = (1..=5)
+        .flat_map(|i| [vec![i.as_f32()], vec![-i.as_f32()]])
+        .collect::<Vec<_>>();
+    let data = VecDataset::new("line".to_string(), data, utils::euclidean::<f32, f32>, false);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    let query = vec![0.0];
+    let by_distance = |a: &(usize, f32), b: &(usize, f32)| a.1.total_cmp(&b.1);
+
+    // Ties broken by ascending index match the built-in algorithms.
+    let ascending = knn::linear_search_by(&tree, &query, 3, |a, b| by_distance(a, b).then(a.0.cmp(&b.0)));
+    assert_eq!(ascending, knn::Algorithm::Linear.search(&tree, &query, 3));
+
+    // Breaking ties by descending index returns the other instance at distance 2.
+    let descending = knn::linear_search_by(&tree, &query, 3, |a, b| by_distance(a, b).then(b.0.cmp(&a.0)));
+    let distances = descending.iter().map(|&(_, d)| d).collect::<Vec<_>>();
+    assert_eq!(distances, vec![1.0, 1.0, 2.0]);
+    assert_ne!(ascending[2].0, descending[2].0);
+    assert_eq!(
+        tree.data()[ascending[2].0][0].abs(),
+        tree.data()[descending[2].0][0].abs()
+    );
+
+    // Ties broken by the sign of the instance, preferring negative instances.
+    let data = tree.data();
+    let negative_first = knn::linear_search_by(&tree, &query, 5, |a, b| {
+        by_distance(a, b).then(data[a.0][0].total_cmp(&data[b.0][0]))
+    });
+    let instances = negative_first.iter().map(|&(i, _)| data[i][0]).collect::<Vec<_>>();
+    assert_eq!(instances, vec![-1.0, 1.0, -2.0, 2.0, -3.0]);
+
+    assert!(knn::linear_search_by(&tree, &query, 0, by_distance).is_empty());
+    assert_eq!(knn::linear_search_by(&tree, &query, 100, by_distance).len(), 10);
+}
+
+#[test]
+fn transformed() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    // The second column has a much larger scale than the first.
+    let raw = (0..1000)
+        .map(|_| vec![rng.gen_range(0.0..1.0_f32), rng.gen_range(0.0..1000.0_f32)])
+        .collect::<Vec<_>>();
+    let mut data = VecDataset::new(
+        "transformed".to_string(),
+        raw.clone(),
+        utils::euclidean::<f32, f32>,
+        false,
+    );
+    let (means, stds) = data.standardize();
+
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    let query = vec![0.5, 500.0];
+    let standardized = query
+        .iter()
+        .zip(means.iter().zip(&stds))
+        .map(|(&v, (m, s))| ((v.as_f64() - m) / s).as_f32())
+        .collect::<Vec<_>>();
+    let sorted = |mut hits: Vec<(usize, f32)>| {
+        hits.sort_by_key(|&(i, _)| i);
+        hits.into_iter().map(|(i, _)| i).collect::<Vec<_>>()
+    };
+
+    let k = 10;
+    let knn_expected = sorted(knn::Algorithm::Linear.search(&tree, &standardized, k));
+    for variant in knn::Algorithm::variants() {
+        let hits = sorted(tree.knn_search_transformed(&query, k, *variant));
+        assert_eq!(hits, knn_expected, "{variant:?}");
+    }
+
+    let radius = 0.2;
+    let expected = sorted(rnn::Algorithm::Linear.search(&standardized, radius, &tree));
+    assert!(!expected.is_empty());
+    let hits = sorted(tree.rnn_search_transformed(&query, radius, rnn::Algorithm::Clustered));
+    assert_eq!(hits, expected);
+
+    // Searching with the raw query in the standardized space finds the wrong
+    // neighbors.
+    let untransformed = sorted(knn::Algorithm::Linear.search(&tree, &query, k));
+    assert_ne!(untransformed, knn_expected);
+
+    // Standardizing again composes with the stored transform.
+    let mut data = VecDataset::new("twice".to_string(), raw, utils::euclidean::<f32, f32>, false);
+    let _ = data.standardize();
+    let _ = data.standardize();
+    let Some(abd_clam::QueryTransform::Standardize { means: m2, stds: s2 }) = data.query_transform() else {
+        unreachable!("standardize stores a transform")
+    };
+    for (a, b) in m2.iter().zip(&means).chain(s2.iter().zip(&stds)) {
+        assert_approx_eq!(f64, *a, *b, epsilon = 1e-3 * b.abs().max(1.0));
+    }
+}
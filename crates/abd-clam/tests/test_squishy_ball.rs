@@ -0,0 +1,416 @@
+//! Tests for the `SquishyBall` struct.
+
+use abd_clam::{
+    codec::{SquishyBall, SquishyDataset},
+    BalancedBall, Cluster, Dataset, FromClusters, PartitionCriteria, Tree, UniBall, VecDataset,
+};
+use rand::prelude::*;
+
+mod utils;
+
+/// A tree of `SquishyBall`s over integer vectors.
+type SquishyTree = Tree<Vec<u32>, u32, VecDataset<Vec<u32>, u32, usize>, SquishyBall<u32>>;
+
+/// Builds a tree of `SquishyBall`s over random integer vectors.
+fn gen_tree() -> SquishyTree {
+    let seed = 42;
+    let data = symagen::random_data::random_tabular(1000, 10, 0_u32, 100, &mut rand::rngs::StdRng::seed_from_u64(seed));
+    let data = VecDataset::new("squishy".to_string(), data, utils::euclidean_sq::<u32>, false);
+    let criteria = PartitionCriteria::default();
+    Tree::new(data, Some(seed)).partition(&criteria, Some(seed))
+}
+
+#[test]
+fn validate() {
+    let tree = gen_tree();
+    assert_eq!(tree.root().validate(tree.data()), Ok(()));
+
+    // Bincode serializes the root's `UniBall` first, and its `offset` is the
+    // second field after the `depth`.
+    let mut bytes = bincode::serialize(tree.root()).unwrap();
+    bytes[8..16].copy_from_slice(&1_u64.to_le_bytes());
+    let corrupted: SquishyBall<u32> = bincode::deserialize(&bytes).unwrap();
+
+    let result = corrupted.validate(tree.data());
+    assert!(result.is_err());
+    assert!(result.unwrap_err().starts_with("1-1000"));
+}
+
+#[test]
+fn trim_if() {
+    let tree = gen_tree();
+    let bytes = bincode::serialize(tree.root()).unwrap();
+    let depth = tree.depth();
+
+    let tree = tree.trim_if(|_| false);
+    assert_eq!(bytes, bincode::serialize(tree.root()).unwrap());
+    assert_eq!(depth, tree.depth());
+
+    let tree = tree.trim_if(|c| c.depth() == 2);
+    assert_eq!(tree.depth(), 2);
+    assert_eq!(tree.root().validate(tree.data()), Ok(()));
+
+    let tree = tree.trim_if(|_| true);
+    assert!(tree.root().is_leaf());
+    assert_eq!(tree.depth(), 0);
+}
+
+#[test]
+fn adapt_from() {
+    let tree = gen_tree().trim_if(|c| c.depth() == 4);
+    let adapted = BalancedBall::adapt_from(tree.root());
+
+    let (source, adapted) = (tree.root().subtree(), adapted.subtree());
+    assert_eq!(source.len(), adapted.len());
+    for (s, a) in source.into_iter().zip(adapted) {
+        assert_eq!(
+            (s.offset(), s.cardinality(), s.depth()),
+            (a.offset(), a.cardinality(), a.depth())
+        );
+        assert_eq!((s.arg_center(), s.radius()), (a.arg_center(), a.radius()));
+        assert_eq!(s.arg_poles(), a.arg_poles());
+    }
+}
+
+#[test]
+fn iter_leaves() {
+    let tree = gen_tree().trim_if(|c| c.depth() == 4);
+
+    let mut leaves = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .filter(|c| c.is_leaf())
+        .collect::<Vec<_>>();
+    leaves.sort_by_key(|c| c.offset());
+
+    let mut num_leaves = 0;
+    let mut instances = Vec::new();
+    for (leaf, expected) in tree.iter_leaves().zip(leaves.iter()) {
+        assert_eq!(leaf.len(), expected.cardinality());
+        num_leaves += 1;
+        instances.extend(leaf);
+    }
+    assert_eq!(num_leaves, leaves.len());
+    assert_eq!(instances, tree.data().data());
+}
+
+#[test]
+fn leaf_containing() {
+    let tree = gen_tree().trim_if(|c| c.depth() == 4);
+
+    for index in [0, 1, 17, 500, 998, 999] {
+        let leaf = tree.leaf_containing(index).unwrap();
+        assert!(leaf.is_leaf());
+        assert!(leaf.indices().contains(&index));
+        assert!(leaf.depth() <= 4);
+    }
+    assert!(tree.leaf_containing(tree.cardinality()).is_none());
+
+    let mut counts = vec![0; tree.cardinality()];
+    for leaf in tree.root().subtree().into_iter().filter(|c| c.is_leaf()) {
+        for i in leaf.indices() {
+            assert_eq!(tree.leaf_containing(i), Some(leaf));
+            counts[i] += 1;
+        }
+    }
+    assert!(counts.into_iter().all(|c| c == 1));
+}
+
+/// A dataset of integer vectors under the Hamming distance, in which an
+/// instance is encoded as one byte, packing the index and the value, for
+/// each coordinate at which it differs from the reference.
+///
+/// The indices and values must be less than 16.
+#[derive(Debug)]
+struct EditData(VecDataset<Vec<u32>, u32, usize>);
+
+impl std::ops::Index<usize> for EditData {
+    type Output = Vec<u32>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl Dataset<Vec<u32>, u32> for EditData {
+    fn type_name() -> String {
+        "EditData".to_string()
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn cardinality(&self) -> usize {
+        self.0.cardinality()
+    }
+
+    fn is_metric_expensive(&self) -> bool {
+        self.0.is_metric_expensive()
+    }
+
+    fn metric(&self) -> fn(&Vec<u32>, &Vec<u32>) -> u32 {
+        self.0.metric()
+    }
+
+    fn set_permuted_indices(&mut self, indices: Option<&[usize]>) {
+        self.0.set_permuted_indices(indices);
+    }
+
+    fn swap(&mut self, left: usize, right: usize) -> Result<(), String> {
+        self.0.swap(left, right)
+    }
+
+    fn permuted_indices(&self) -> Option<&[usize]> {
+        self.0.permuted_indices()
+    }
+
+    fn make_shards(self, max_cardinality: usize) -> Vec<Self> {
+        self.0.make_shards(max_cardinality).into_iter().map(Self).collect()
+    }
+
+    fn save(&self, _: &std::path::Path) -> Result<(), String> {
+        Err("Not needed for the tests.".to_string())
+    }
+
+    fn load(_: &std::path::Path, _: fn(&Vec<u32>, &Vec<u32>) -> u32, _: bool) -> Result<Self, String> {
+        Err("Not needed for the tests.".to_string())
+    }
+}
+
+impl SquishyDataset<Vec<u32>, u32> for EditData {
+    fn encode_instance(&self, reference: &Vec<u32>, target: &Vec<u32>) -> Box<[u8]> {
+        reference
+            .iter()
+            .zip(target.iter())
+            .enumerate()
+            .filter(|(_, (r, t))| r != t)
+            .map(|(i, (_, &t))| ((i << 4) as u8) | (t as u8))
+            .collect()
+    }
+
+    fn decode_instance(&self, reference: &Vec<u32>, encoding: &[u8]) -> Vec<u32> {
+        let mut target = reference.clone();
+        for &edit in encoding {
+            target[usize::from(edit >> 4)] = u32::from(edit & 0xF);
+        }
+        target
+    }
+
+    fn bytes_per_unit_distance(&self) -> u64 {
+        1
+    }
+
+    fn save(&self, _: &std::path::Path) -> Result<(), String> {
+        Err("Not needed for the tests.".to_string())
+    }
+
+    fn load(
+        _: &std::path::Path,
+        _: fn(&String, &String) -> u32,
+        _: bool,
+        _: fn(&String, &String) -> Box<[u8]>,
+        _: fn(&String, &[u8]) -> String,
+    ) -> Result<Self, String> {
+        Err("Not needed for the tests.".to_string())
+    }
+}
+
+#[test]
+fn from_base_tree_with_budget() {
+    let seed = Some(42);
+    let data = symagen::random_data::random_tabular(1000, 10, 0_u32, 4, &mut rand::rngs::StdRng::seed_from_u64(42));
+    let hamming: fn(&Vec<u32>, &Vec<u32>) -> u32 = |x, y| distances::vectors::hamming(x, y);
+    let mut data = EditData(VecDataset::new("edits".to_string(), data, hamming, false));
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(20);
+    let root = UniBall::new_root(&data, seed).partition(&mut data, &criteria, seed);
+    let mut squishy = SquishyBall::from_base_tree_with_budget(root, &data, u64::MAX).unwrap();
+    let (size, num_clusters) = (squishy.estimated_size(), squishy.subtree_cardinality());
+
+    // Some subtrees are cheaper to compress without recursion, so trimming them meets a tighter budget.
+    assert_eq!(squishy.trim_to_budget(size - 1), Ok(()));
+    assert!(squishy.estimated_size() < size);
+    assert!(squishy.subtree_cardinality() < num_clusters);
+    assert_eq!(squishy.validate(&data), Ok(()));
+
+    // Every instance is recovered from its encoding in terms of the center of its leaf.
+    for leaf in squishy.subtree().into_iter().filter(|c| c.is_leaf()) {
+        let center = &data[leaf.arg_center()];
+        for i in leaf.indices() {
+            let encoding = data.encode_instance(center, &data[i]);
+            assert_eq!(data.decode_instance(center, &encoding), data[i]);
+        }
+    }
+
+    assert!(squishy.trim_to_budget(0).is_err());
+}
+
+#[test]
+fn center_distances() {
+    let tree = gen_tree().trim_if(|c| c.depth() == 3);
+    let centers = tree.centers();
+    let clusters = tree.root().subtree();
+    assert_eq!(centers.len(), clusters.len());
+
+    let pairs = clusters
+        .iter()
+        .flat_map(|a| clusters.iter().map(move |b| (a, b)))
+        .collect::<Vec<_>>();
+    let ids = pairs
+        .iter()
+        .map(|(a, b)| ((a.offset(), a.cardinality()), (b.offset(), b.cardinality())))
+        .collect::<Vec<_>>();
+    let distances = tree.center_distances(&centers, &ids).unwrap();
+    for ((a, b), d) in pairs.into_iter().zip(distances) {
+        assert_eq!(d, tree.data().one_to_one(a.arg_center(), b.arg_center()));
+    }
+
+    assert!(tree.center_distances(&centers, &[((1, 1), (0, 1000))]).is_err());
+}
+
+#[test]
+fn packed_dna() {
+    let reference = String::new();
+
+    let sequences = symagen::random_data::random_string(100, 100, 200, "ACGT", 42);
+    let (mut raw, mut packed) = (0, 0);
+    for sequence in &sequences {
+        let encoding = abd_clam::codec::encode_dna(&reference, sequence);
+        assert_eq!(&abd_clam::codec::decode_dna(&reference, &encoding), sequence);
+        assert_eq!(encoding.len(), 8 + sequence.len().div_ceil(4));
+        raw += sequence.len();
+        packed += encoding.len();
+    }
+    assert!(3 * packed < raw, "Packed {packed} bytes vs raw {raw} bytes.");
+
+    // Any bytes outside the alphabet are escaped.
+    for sequence in symagen::random_data::random_string(100, 0, 200, "ACGTN", 43) {
+        let encoding = abd_clam::codec::encode_dna(&reference, &sequence);
+        assert_eq!(abd_clam::codec::decode_dna(&reference, &encoding), sequence);
+    }
+    let sequence = "ACGTNNacgt-\u{394}ACGT".to_string();
+    let encoding = abd_clam::codec::encode_dna(&reference, &sequence);
+    assert_eq!(abd_clam::codec::decode_dna(&reference, &encoding), sequence);
+    assert!(abd_clam::codec::decode_dna(&reference, &[]).is_empty());
+}
+
+#[test]
+fn adapt_to_codec() {
+    let seed = Some(42);
+    let gen_data = || {
+        let data = symagen::random_data::random_tabular(1000, 10, 0_u32, 4, &mut rand::rngs::StdRng::seed_from_u64(42));
+        let hamming: fn(&Vec<u32>, &Vec<u32>) -> u32 = |x, y| distances::vectors::hamming(x, y);
+        EditData(VecDataset::new("edits".to_string(), data, hamming, false))
+    };
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(20);
+
+    let adapted = Tree::<_, _, _, UniBall<_>>::new(gen_data(), seed)
+        .partition(&criteria, seed)
+        .adapt_to_codec();
+
+    // The same tree, built step by step, with a copy of the permuted data
+    // for estimating the costs.
+    let mut data = gen_data();
+    let root = UniBall::new_root(&data, seed).partition(&mut data, &criteria, seed);
+    let manual = Tree::<_, _, _, SquishyBall<_>>::new(gen_data(), seed)
+        .partition(&criteria, seed)
+        .estimate_costs(&data)
+        .trim();
+    // Trimming only removes subtrees.
+    assert!(adapted.root().subtree_cardinality() <= root.subtree_cardinality());
+
+    assert_eq!(
+        bincode::serialize(adapted.root()).unwrap(),
+        bincode::serialize(manual.root()).unwrap()
+    );
+    assert_eq!(adapted.depth(), manual.depth());
+    assert_eq!(adapted.data().0.data(), manual.data().0.data());
+    assert_eq!(adapted.data().permuted_indices(), manual.data().permuted_indices());
+    assert_eq!(adapted.root().validate(adapted.data()), Ok(()));
+}
+
+#[test]
+fn cost_inspection() {
+    let seed = Some(42);
+    // Instances are one edit away from one of a few prototypes, so recursive
+    // compression pays off near the root but not within a prototype.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let prototypes = symagen::random_data::random_tabular(4, 10, 0_u32, 4, &mut rng);
+    let data = (0..1000)
+        .map(|i| {
+            let mut x = prototypes[i % prototypes.len()].clone();
+            x[rng.gen_range(0..10)] = rng.gen_range(0..4);
+            x
+        })
+        .collect::<Vec<_>>();
+    let hamming: fn(&Vec<u32>, &Vec<u32>) -> u32 = |x, y| distances::vectors::hamming(x, y);
+    let mut data = EditData(VecDataset::new("edits".to_string(), data, hamming, false));
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(20);
+    let root = UniBall::new_root(&data, seed).partition(&mut data, &criteria, seed);
+    let mut squishy = SquishyBall::from_base_tree_with_budget(root, &data, u64::MAX).unwrap();
+
+    // Leaves have no recursive cost and never prefer recursion.
+    for c in squishy.subtree() {
+        if c.is_leaf() {
+            assert_eq!(c.recursive_cost(), 0);
+            assert!(!c.prefers_recursive());
+        }
+    }
+    let untrimmed = squishy.subtree_cardinality();
+
+    // After trimming, a `SquishyBall` keeps its children exactly when
+    // recursive compression is cheaper than unitary compression.
+    squishy.trim();
+    assert!(squishy.subtree_cardinality() < untrimmed);
+    assert!(!squishy.is_leaf());
+    for c in squishy.subtree() {
+        assert_eq!(!c.is_leaf(), c.prefers_recursive());
+        if let Some([left, right]) = c.children() {
+            assert!(c.unitary_cost() > c.recursive_cost() + left.unitary_cost() + right.unitary_cost());
+        }
+    }
+
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, seed)
+        .partition(&criteria, seed)
+        .adapt_to_codec();
+    assert!(!tree.root().is_leaf());
+    let tmp_dir = tempdir::TempDir::new("costs").unwrap();
+    let path = tmp_dir.path().join("costs.csv");
+    tree.write_costs_csv(&path).unwrap();
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("offset,cardinality,depth,recursive_cost,unitary_cost,prefers_recursive,is_leaf")
+    );
+    let rows = lines.collect::<Vec<_>>();
+    let clusters = tree.root().subtree();
+    assert_eq!(rows.len(), clusters.len());
+    for (row, c) in rows.iter().zip(clusters) {
+        let fields = row.split(',').collect::<Vec<_>>();
+        assert_eq!(fields[0], c.offset().to_string());
+        assert_eq!(fields[3], c.recursive_cost().to_string());
+        assert_eq!(fields[4], c.unitary_cost().to_string());
+        assert_eq!(fields[5], c.prefers_recursive().to_string());
+        assert_eq!(fields[6], c.is_leaf().to_string());
+    }
+}
+
+#[test]
+fn recompute_geometry_resets_costs() {
+    let seed = Some(42);
+    let data = symagen::random_data::random_tabular(1000, 10, 0_u32, 4, &mut rand::rngs::StdRng::seed_from_u64(42));
+    let hamming: fn(&Vec<u32>, &Vec<u32>) -> u32 = |x, y| distances::vectors::hamming(x, y);
+    let mut data = EditData(VecDataset::new("edits".to_string(), data, hamming, false));
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(20);
+    let root = UniBall::new_root(&data, seed).partition(&mut data, &criteria, seed);
+    let mut squishy = SquishyBall::from_base_tree_with_budget(root, &data, u64::MAX).unwrap();
+    assert!(squishy.unitary_cost() > 0);
+
+    squishy.recompute_geometry(&data);
+    for c in squishy.subtree() {
+        assert_eq!(c.unitary_cost(), 0);
+        assert_eq!(c.recursive_cost(), 0);
+    }
+}
@@ -1,7 +1,11 @@
 //! Tests on the tree module.
 
-use abd_clam::{Cluster, Dataset, Instance, PartitionCriteria, Tree, UniBall, VecDataset};
+use std::collections::HashSet;
+
+use abd_clam::{io, knn, rnn, Cluster, Dataset, Instance, PartitionCriteria, Tree, UniBall, VecDataset};
 use distances::Number;
+use float_cmp::assert_approx_eq;
+use rand::prelude::*;
 use tempdir::TempDir;
 
 mod utils;
@@ -147,3 +151,756 @@ fn get_cluster() {
         }
     }
 }
+
+#[test]
+fn par_partition_reproducible() {
+    let criteria = PartitionCriteria::default();
+
+    for seed in [0, 42, 1337] {
+        let data = utils::gen_dataset(2000, 10, seed, utils::euclidean);
+        let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(seed)).partition(&criteria, Some(seed));
+
+        let data = utils::gen_dataset(2000, 10, seed, utils::euclidean);
+        let par_tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(seed)).par_partition(&criteria, Some(seed));
+
+        let tree_bytes = bincode::serialize(tree.root()).unwrap();
+        let par_tree_bytes = bincode::serialize(par_tree.root()).unwrap();
+        assert_eq!(tree_bytes, par_tree_bytes, "Trees differ for seed {seed}.");
+
+        let permutation = (0..tree.cardinality())
+            .map(|i| tree.data().original_index(i))
+            .collect::<Vec<_>>();
+        let par_permutation = (0..par_tree.cardinality())
+            .map(|i| par_tree.data().original_index(i))
+            .collect::<Vec<_>>();
+        assert_eq!(permutation, par_permutation, "Permutations differ for seed {seed}.");
+    }
+}
+
+#[test]
+fn build_with_rng() {
+    let criteria = PartitionCriteria::default();
+    let build = |rng: &mut dyn RngCore, parallel: bool| {
+        let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+        let tree = Tree::<_, _, _, UniBall<_>>::new_with_rng(data, rng);
+        let tree = if parallel {
+            tree.par_partition_with_rng(&criteria, rng)
+        } else {
+            tree.partition_with_rng(&criteria, rng)
+        };
+        (bincode::serialize(tree.root()).unwrap(), tree.data().data().to_vec())
+    };
+
+    let expected = build(&mut StdRng::seed_from_u64(7), false);
+    assert_eq!(build(&mut StdRng::seed_from_u64(7), false), expected);
+    assert_eq!(build(&mut StdRng::seed_from_u64(7), true), expected);
+    assert_ne!(build(&mut StdRng::seed_from_u64(8), false).0, expected.0);
+}
+
+#[test]
+fn par_partition_in_pool() {
+    let criteria = PartitionCriteria::default();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+
+    let data = utils::gen_dataset(2000, 10, 42, utils::euclidean);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).par_partition(&criteria, Some(42));
+
+    let data = utils::gen_dataset(2000, 10, 42, utils::euclidean);
+    let pool_tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).par_partition_in(&pool, &criteria, Some(42));
+
+    let tree_bytes = bincode::serialize(tree.root()).unwrap();
+    let pool_tree_bytes = bincode::serialize(pool_tree.root()).unwrap();
+    assert_eq!(tree_bytes, pool_tree_bytes);
+    assert_eq!(tree.depth(), pool_tree.depth());
+
+    let permutation = (0..tree.cardinality())
+        .map(|i| tree.data().original_index(i))
+        .collect::<Vec<_>>();
+    let pool_permutation = (0..pool_tree.cardinality())
+        .map(|i| pool_tree.data().original_index(i))
+        .collect::<Vec<_>>();
+    assert_eq!(permutation, pool_permutation);
+}
+
+#[test]
+fn nearest_leaf() {
+    let data = utils::gen_grid_data(10);
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(8);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let data = tree.data();
+
+    let queries = [[0., 0.], [0.4, 0.3], [-7.2, 3.9], [9.6, -9.6], [2.5, 5.1], [-3.3, -8.8]];
+    for query in queries.iter().map(|q| q.to_vec()) {
+        let leaf = tree.root().nearest_leaf(data, &query);
+        assert!(leaf.is_leaf());
+
+        // Of the children of every ancestor of the leaf, the one on the path
+        // to the leaf must have the closer center, with ties going left.
+        let ancestors = tree
+            .root()
+            .subtree()
+            .into_iter()
+            .filter(|c| c.is_ancestor_of(leaf))
+            .collect::<Vec<_>>();
+        assert_eq!(ancestors.len(), leaf.depth());
+        for ancestor in ancestors {
+            let [left, right] = ancestor.children().unwrap();
+            let l = left.distance_to_instance(data, &query);
+            let r = right.distance_to_instance(data, &query);
+            if left == leaf || left.is_ancestor_of(leaf) {
+                assert!(l <= r, "Descended left at {} but {l} > {r}.", ancestor.name());
+            } else {
+                assert!(r < l, "Descended right at {} but {r} >= {l}.", ancestor.name());
+            }
+        }
+    }
+}
+
+#[test]
+fn subtree_cardinality() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    for c in tree.root().subtree() {
+        assert_eq!(c.subtree_cardinality(), c.subtree().len());
+        assert_eq!(
+            c.estimated_size_bytes(),
+            c.subtree().len() * core::mem::size_of::<UniBall<f32>>()
+        );
+    }
+}
+
+#[test]
+fn clusters_at_depth() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    for depth in 0..=(tree.depth() + 1) {
+        let frontier = tree.root().clusters_at_depth(depth);
+        let indices = frontier.iter().flat_map(|c| c.indices()).collect::<Vec<_>>();
+        assert_eq!(indices, (0..tree.cardinality()).collect::<Vec<_>>());
+
+        for c in frontier {
+            assert!(c.depth() == depth || (c.is_leaf() && c.depth() < depth));
+        }
+    }
+
+    assert_eq!(tree.root().clusters_at_depth(0), vec![tree.root()]);
+    let leaves = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .filter(|c| c.is_leaf())
+        .collect::<Vec<_>>();
+    assert_eq!(tree.root().clusters_at_depth(tree.depth()), leaves);
+}
+
+#[test]
+fn lowest_common_ancestor() {
+    let data = utils::gen_dataset_from(
+        (0..8).map(|i| vec![i.as_f32()]).collect(),
+        utils::euclidean::<f32, f32>,
+        vec![0_usize; 8],
+    );
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let root = tree.root();
+
+    let [left, right] = root.children().unwrap();
+    let leaves = root.subtree().into_iter().filter(|c| c.is_leaf()).collect::<Vec<_>>();
+    assert_eq!(leaves.len(), 8);
+
+    for &leaf in &leaves {
+        let path = root.path_to(leaf).unwrap();
+        assert_eq!(path.len(), leaf.depth() + 1);
+        assert_eq!(path[0], root);
+        assert_eq!(path[path.len() - 1], leaf);
+        for pair in path.windows(2) {
+            assert!(pair[1].depth() == pair[0].depth() + 1 && pair[1].is_descendant_of(pair[0]));
+        }
+
+        assert_eq!(root.lowest_common_ancestor(leaf, leaf), Some(leaf));
+        assert_eq!(root.lowest_common_ancestor(root, leaf), Some(root));
+
+        let side = if leaf.is_descendant_of(left) { left } else { right };
+        assert_eq!(side.path_to(leaf).unwrap(), path[1..].to_vec());
+        assert_eq!(root.lowest_common_ancestor(side, leaf), Some(side));
+    }
+
+    // Clusters on either side of the root meet only at the root.
+    let (a, b) = (left.subtree()[left.subtree().len() - 1], right.subtree()[1]);
+    assert_eq!(root.lowest_common_ancestor(a, b), Some(root));
+    assert_eq!(left.lowest_common_ancestor(a, b), None);
+    assert!(left.path_to(b).is_none());
+    assert!(left.path_to(root).is_none());
+
+    // Any two clusters under different children of a parent meet at that parent.
+    for parent in root.subtree() {
+        if let Some([x, y]) = parent.children() {
+            for (&a, &b) in x.subtree().iter().zip(y.subtree().iter()) {
+                assert_eq!(root.lowest_common_ancestor(a, b), Some(parent));
+                assert_eq!(root.lowest_common_ancestor(b, a), Some(parent));
+            }
+        }
+    }
+}
+
+#[test]
+fn to_newick() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let metadata = (0..data.cardinality()).map(|i| format!("x {i}")).collect();
+    let data = data.assign_metadata(metadata).unwrap();
+    let criteria = PartitionCriteria::new(true).with_max_depth(2);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let root = tree.root();
+    assert_eq!(tree.depth(), 2);
+
+    let newick = root.to_newick(tree.data(), |data, i| data.metadata_of(i).clone());
+    assert!(newick.starts_with('(') && newick.ends_with(&format!(")[{}];", root.cardinality())));
+
+    // The parentheses are balanced and nested as deep as the tree.
+    let mut depth = 0_usize;
+    let mut max_depth = 0;
+    for ch in newick.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1).unwrap(),
+            _ => (),
+        }
+        max_depth = max_depth.max(depth);
+    }
+    assert_eq!(depth, 0);
+    assert_eq!(max_depth, tree.depth());
+
+    // Each leaf is labeled, in quotes, with the metadata of its center.
+    let leaves = root.subtree().into_iter().filter(|c| c.is_leaf()).collect::<Vec<_>>();
+    assert_eq!(newick.matches('\'').count(), 2 * leaves.len());
+    for leaf in leaves {
+        let label = format!("'{}':{}", tree.data().metadata_of(leaf.arg_center()), leaf.radius());
+        assert!(newick.contains(&label), "{label} is not in {newick}");
+    }
+}
+
+#[test]
+fn prune_singletons() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let num_clusters = tree.root().subtree_cardinality();
+    let num_singletons = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .filter(|c| c.is_leaf() && c.is_singleton())
+        .count();
+
+    let tree = tree.prune_singletons(&PartitionCriteria::new(true).with_min_cardinality(8));
+    let root = tree.root();
+    assert!(root.subtree_cardinality() < num_clusters);
+    assert_eq!(tree.depth(), root.max_leaf_depth());
+
+    let leaves = root.subtree().into_iter().filter(|c| c.is_leaf()).collect::<Vec<_>>();
+    assert!(leaves.iter().filter(|c| c.is_singleton()).count() < num_singletons);
+
+    // Every instance is still in exactly one leaf.
+    let mut indices = leaves.iter().flat_map(|c| c.indices()).collect::<Vec<_>>();
+    indices.sort_unstable();
+    assert_eq!(indices, (0..tree.cardinality()).collect::<Vec<_>>());
+
+    for query in tree.data().data().iter().step_by(100) {
+        let expected = rnn::Algorithm::Linear.search(query, 1., &tree);
+        let mut actual = rnn::Algorithm::Clustered.search(query, 1., &tree);
+        actual.sort_by_key(|&(i, _)| i);
+        assert_eq!(actual.len(), expected.len());
+        assert!(actual.iter().zip(expected.iter()).all(|(a, e)| a.0 == e.0));
+
+        let all = rnn::Algorithm::Clustered.search(query, f32::MAX, &tree);
+        assert_eq!(all.len(), tree.cardinality());
+    }
+}
+
+#[test]
+fn save_load_tree_version() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let tmp_dir = TempDir::new("tree_version").unwrap();
+
+    let path = tmp_dir.path().join("tree.bin");
+    io::save_tree(tree.root(), &path).unwrap();
+    let root: UniBall<f32> = io::load_tree(&path).unwrap();
+    assert_subtree_equal(tree.root(), tree.data(), &root, tree.data(), tree.data().metric());
+
+    // A tree written by another version has the same layout but a different version string.
+    let path = tmp_dir.path().join("old_tree.bin");
+    let mut bytes = io::MAGIC.to_vec();
+    bytes.extend(bincode::serialize("0.0.1").unwrap());
+    bytes.extend(bincode::serialize(tree.root()).unwrap());
+    std::fs::write(&path, bytes).unwrap();
+    let err = io::load_tree::<f32, UniBall<f32>>(&path).unwrap_err();
+    assert!(err.contains("0.0.1") && err.contains(abd_clam::VERSION), "{err}");
+
+    // A tree saved without the header is rejected.
+    let path = tmp_dir.path().join("raw_tree.bin");
+    tree.root().save(&path).unwrap();
+    assert!(io::load_tree::<f32, UniBall<f32>>(&path).is_err());
+}
+
+#[test]
+fn save_load_portable() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let metric = data.metric();
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let tmp_dir = TempDir::new("tree_portable").unwrap();
+
+    tree.save_portable(tmp_dir.path()).unwrap();
+    let loaded =
+        Tree::<Vec<f32>, f32, VecDataset<_, _, usize>, UniBall<_>>::load_portable(tmp_dir.path(), metric, false)
+            .unwrap();
+
+    assert_eq!(tree.depth(), loaded.depth());
+    assert_eq!(tree.data().data(), loaded.data().data());
+    assert_eq!(tree.data().permuted_indices(), loaded.data().permuted_indices());
+    assert_eq!(
+        bincode::serialize(tree.root()).unwrap(),
+        bincode::serialize(loaded.root()).unwrap()
+    );
+
+    let schema: serde_json::Value = serde_json::from_str(io::PORTABLE_SCHEMA).unwrap();
+    let json = std::fs::read_to_string(tmp_dir.path().join(io::PORTABLE_TREE_FILE)).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(matches_schema(&json, &schema), "{json}");
+    assert_eq!(json["clusters"].as_array().unwrap().len(), tree.root().subtree().len());
+
+    // Loading with the wrong types of instances is rejected.
+    let err = Tree::<Vec<f64>, f64, VecDataset<_, _, usize>, UniBall<_>>::load_portable(
+        tmp_dir.path(),
+        utils::euclidean,
+        false,
+    )
+    .unwrap_err();
+    assert!(err.contains("Vec<f32>"), "{err}");
+
+    // Trees whose clusters or permutation do not match the data are rejected.
+    let tampered = [
+        ("/clusters/0/cardinality", serde_json::json!(999)),
+        ("/clusters/1/offset", serde_json::json!(1)),
+        ("/clusters/1/arg_center", serde_json::json!(999)),
+        ("/clusters/0/children/arg_l", serde_json::json!(999)),
+        ("/permutation/0", serde_json::json!(1000)),
+    ];
+    for (pointer, value) in tampered {
+        let mut json = json.clone();
+        *json.pointer_mut(pointer).unwrap() = value;
+        std::fs::write(tmp_dir.path().join(io::PORTABLE_TREE_FILE), json.to_string()).unwrap();
+        let loaded =
+            Tree::<Vec<f32>, f32, VecDataset<_, _, usize>, UniBall<_>>::load_portable(tmp_dir.path(), metric, false);
+        assert!(loaded.is_err(), "Loaded a tree with {pointer} changed.");
+    }
+
+    let mut json = json.clone();
+    json["permutation"].as_array_mut().unwrap().pop();
+    std::fs::write(tmp_dir.path().join(io::PORTABLE_TREE_FILE), json.to_string()).unwrap();
+    let err = Tree::<Vec<f32>, f32, VecDataset<_, _, usize>, UniBall<_>>::load_portable(tmp_dir.path(), metric, false)
+        .unwrap_err();
+    assert!(err.contains("permutation"), "{err}");
+}
+
+/// Checks `value` against the subset of JSON Schema used by `io::PORTABLE_SCHEMA`.
+fn matches_schema(value: &serde_json::Value, schema: &serde_json::Value) -> bool {
+    use serde_json::Value;
+
+    if let Some(expected) = schema.get("const") {
+        return value == expected;
+    }
+    if let Some(Value::Array(options)) = schema.get("oneOf") {
+        return options.iter().filter(|s| matches_schema(value, s)).count() == 1;
+    }
+    let type_matches = match schema.get("type").and_then(Value::as_str) {
+        Some("null") => value.is_null(),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("number") => value.is_number(),
+        Some("array") => value.is_array(),
+        Some("object") => value.is_object(),
+        _ => true,
+    };
+    if !type_matches {
+        return false;
+    }
+    if let (Some(minimum), Some(v)) = (schema.get("minimum").and_then(Value::as_f64), value.as_f64()) {
+        if v < minimum {
+            return false;
+        }
+    }
+    if let Some(Value::Array(required)) = schema.get("required") {
+        if !required
+            .iter()
+            .all(|k| k.as_str().is_some_and(|k| value.get(k).is_some()))
+        {
+            return false;
+        }
+    }
+    if let (Some(Value::Object(properties)), Some(fields)) = (schema.get("properties"), value.as_object()) {
+        if !fields
+            .iter()
+            .all(|(k, v)| properties.get(k).is_some_and(|s| matches_schema(v, s)))
+        {
+            return false;
+        }
+    }
+    if let (Some(items), Some(elements)) = (schema.get("items"), value.as_array()) {
+        let min_items = schema.get("minItems").and_then(Value::as_u64).unwrap_or(0);
+        if (elements.len() as u64) < min_items || !elements.iter().all(|e| matches_schema(e, items)) {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn label_purity() {
+    // Four well separated blobs of 25 instances each, labelled by blob.
+    let centers = [[0., 0.], [100., 0.], [0., 100.], [100., 100.]];
+    let (data, labels): (Vec<_>, Vec<_>) = centers
+        .iter()
+        .enumerate()
+        .flat_map(|(label, &[x, y])| {
+            (0..25).map(move |i| {
+                let (dx, dy) = ((i % 5).as_f32(), (i / 5).as_f32());
+                (vec![x + dx, y + dy], label)
+            })
+        })
+        .unzip();
+    let data = utils::gen_dataset_from(data, utils::euclidean::<f32, f32>, labels);
+
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(10);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let labels = tree.data().metadata();
+
+    let root = tree.root();
+    assert!((root.label_entropy(labels) - 2.0).abs() < 1e-12);
+    assert_eq!(root.majority_label(labels), Some(0));
+
+    for leaf in root.subtree().into_iter().filter(|c| c.is_leaf()) {
+        assert!(leaf.label_entropy(labels).abs() < f64::EPSILON, "{}", leaf.name());
+        let label = labels[leaf.offset()];
+        assert_eq!(leaf.majority_label(labels), Some(label));
+    }
+}
+
+/// Generates strings that are rotations, with a few substitutions, of a small
+/// number of random base strings.
+///
+/// Rotations are close in Levenshtein distance but far in Hamming distance.
+fn rotated_strings(num_bases: usize, per_base: usize, seq_len: usize, seed: u64) -> Vec<String> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let alphabet = b"ACTG";
+    (0..num_bases)
+        .flat_map(|_| {
+            let base = (0..seq_len).map(|_| alphabet[rng.gen_range(0..4)]).collect::<Vec<_>>();
+            (0..per_base)
+                .map(|_| {
+                    let mut s = base.clone();
+                    s.rotate_left(rng.gen_range(0..8));
+                    for _ in 0..2 {
+                        s[rng.gen_range(0..seq_len)] = alphabet[rng.gen_range(0..4)];
+                    }
+                    String::from_utf8(s).unwrap()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[test]
+fn with_new_metric_recomputing() {
+    let k = 10;
+    // One string from each base is held out as a query.
+    let (queries, strings): (Vec<_>, Vec<_>) = rotated_strings(20, 51, 32, 42)
+        .into_iter()
+        .enumerate()
+        .partition(|(i, _)| i % 51 == 0);
+    let queries = queries.into_iter().map(|(_, q)| q).collect::<Vec<_>>();
+    let strings = strings.into_iter().map(|(_, s)| s).collect::<Vec<_>>();
+
+    let data = VecDataset::new("strings".to_string(), strings, utils::levenshtein::<u16>, false);
+    let criteria = PartitionCriteria::default();
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+
+    // Levenshtein distances are no larger than Hamming distances, so the
+    // radii computed with Levenshtein are too small for Hamming.
+    let tmp_dir = TempDir::new("metric-switch").unwrap();
+    tree.save(tmp_dir.path()).unwrap();
+    let stale =
+        Tree::<_, _, VecDataset<_, _, usize>, UniBall<_>>::load(tmp_dir.path(), utils::hamming::<u16>, false).unwrap();
+    let tree = tree.with_new_metric_recomputing(utils::hamming::<u16>, false);
+
+    let mut num_stale_misses = 0;
+    for query in &queries {
+        let linear = knn::Algorithm::Linear.search(&tree, query, k);
+        for algo in [
+            knn::Algorithm::GreedySieve,
+            knn::Algorithm::Sieve,
+            knn::Algorithm::SieveSepCenter,
+        ] {
+            let hits = algo.search(&tree, query, k);
+            assert_eq!(hits, linear, "{} differed from linear search.", algo.name());
+        }
+
+        // Pruning with the stale poles may miss hits, but never adds any.
+        let radius = linear[k - 1].1;
+        let linear = rnn::Algorithm::Linear.search(query, radius, &tree);
+        let hits = rnn::Algorithm::Clustered.search(query, radius, &tree);
+        assert!(hits.iter().all(|hit| linear.contains(hit)));
+
+        let linear = knn::Algorithm::Linear.search(&stale, query, k);
+        let hits = knn::Algorithm::GreedySieve.search(&stale, query, k);
+        if hits != linear {
+            num_stale_misses += 1;
+        }
+    }
+    assert!(num_stale_misses > 0);
+
+    for c in tree.root().subtree() {
+        let radius = tree.data().one_to_one(c.arg_center(), c.arg_radial());
+        assert_eq!(radius, c.radius());
+    }
+}
+
+#[test]
+fn lfd_plateau() {
+    let build = |criteria: &PartitionCriteria<f32>| {
+        let data = utils::gen_dataset(2000, 2, 42, utils::euclidean);
+        Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(criteria, Some(42))
+    };
+
+    // Uniform data has the same intrinsic dimension at every scale, so the
+    // LFD stabilizes well before the clusters become singletons.
+    let cardinality_only = build(&PartitionCriteria::new(true).with_min_cardinality(1));
+    let plateau = build(
+        &PartitionCriteria::new(true)
+            .with_min_cardinality(1)
+            .stop_on_lfd_plateau(0.5),
+    );
+    assert!(plateau.root().subtree_cardinality() < cardinality_only.root().subtree_cardinality());
+    assert!(plateau.depth() < cardinality_only.depth());
+
+    for c in plateau.root().subtree() {
+        if let Some([left, right]) = c.children() {
+            let is_flat = |child: &UniBall<f32>| (child.lfd() - c.lfd()).abs() < 0.5;
+            assert!(!(is_flat(left) && is_flat(right)));
+        }
+    }
+
+    // With an unbounded epsilon, the root is never split.
+    let root_only = build(&PartitionCriteria::new(true).stop_on_lfd_plateau(f64::INFINITY));
+    assert_eq!(root_only.depth(), 0);
+    assert_eq!(root_only.root().cardinality(), 2000);
+}
+
+#[test]
+fn contains_index() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(8);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let root = tree.root();
+
+    for cluster in root.subtree() {
+        for index in 0..tree.cardinality() {
+            assert_eq!(cluster.contains_index(index), cluster.indices().contains(&index));
+        }
+        assert!(!cluster.contains_index(usize::MAX));
+
+        // A cluster contains exactly the indices of the leaves it is an ancestor of.
+        for leaf in root.subtree().into_iter().filter(|c| c.is_leaf() && c != &cluster) {
+            assert_eq!(
+                cluster.is_ancestor_of(leaf),
+                leaf.indices().all(|i| cluster.contains_index(i))
+            );
+        }
+    }
+}
+
+#[test]
+fn subtree_iter() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(4);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42));
+    let root = tree.root();
+
+    let subtree = root.subtree();
+    assert_eq!(root.subtree_iter().collect::<Vec<_>>(), subtree);
+
+    let leaves = subtree.iter().copied().filter(|c| c.is_leaf()).collect::<Vec<_>>();
+    assert_eq!(root.subtree_filter(|c| c.is_leaf()).collect::<Vec<_>>(), leaves);
+
+    // The iteration is lazy, so taking the first few matches stops early.
+    let deep = root.subtree_filter(|c| c.depth() == 5).take(2).collect::<Vec<_>>();
+    assert_eq!(deep.len(), 2);
+    assert!(deep.iter().all(|c| c.depth() == 5));
+}
+
+#[test]
+fn par_subtree() {
+    let data = utils::gen_dataset(10_000, 10, 42, utils::euclidean);
+    let criteria = PartitionCriteria::new(true).with_min_cardinality(1);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).par_partition(&criteria, Some(42));
+    let root = tree.root();
+
+    let subtree = root.subtree();
+    let par_subtree = root.par_subtree();
+    assert_eq!(par_subtree.len(), root.subtree_cardinality());
+    assert_eq!(
+        par_subtree.iter().collect::<HashSet<_>>(),
+        subtree.iter().collect::<HashSet<_>>()
+    );
+    assert_eq!(par_subtree, subtree);
+}
+
+#[test]
+fn max_leaf_size() {
+    let build = |criteria: &PartitionCriteria<f32>, parallel: bool| {
+        let data = utils::gen_dataset(2000, 10, 42, utils::euclidean);
+        let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42));
+        if parallel {
+            tree.par_partition(criteria, Some(42))
+        } else {
+            tree.partition(criteria, Some(42))
+        }
+    };
+
+    let singletons = build(&PartitionCriteria::default(), false);
+    let criteria = PartitionCriteria::new(true).with_max_leaf_size(32);
+    for tree in [build(&criteria, false), build(&criteria, true)] {
+        for c in tree.root().subtree() {
+            if c.is_leaf() {
+                assert!(c.cardinality() <= 32);
+            } else {
+                assert!(c.cardinality() > 32);
+            }
+        }
+        assert!(tree.depth() > 2);
+        assert!(tree.depth() < singletons.depth());
+
+        for query in singletons.data().data().iter().step_by(100) {
+            let expected = knn::Algorithm::Linear.search(&singletons, query, 10);
+            let hits = knn::Algorithm::RepeatedRnn.search(&tree, query, 10);
+            assert_eq!(utils::compute_recall(hits, expected), 1.0);
+        }
+    }
+
+    // The other criteria still apply, so a shallow depth leaves larger leaves.
+    let criteria = PartitionCriteria::new(true).with_max_depth(2).with_max_leaf_size(32);
+    let tree = build(&criteria, false);
+    assert_eq!(tree.depth(), 2);
+    assert!(tree
+        .root()
+        .subtree_filter(|c| c.is_leaf())
+        .any(|c| c.cardinality() > 32));
+}
+
+#[test]
+fn quality_report() {
+    // A root that is not partitioned.
+    let data = utils::gen_dataset(100, 2, 42, utils::euclidean::<f32, f32>);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42))
+        .partition(&PartitionCriteria::new(true).with_max_depth(0), Some(42));
+    let report = tree.root().quality_report();
+    assert_eq!(report.num_clusters, 1);
+    assert_eq!(report.num_leaves, 1);
+    assert_eq!(report.max_depth, 0);
+    assert_approx_eq!(f64, report.leaf_depth_std, 0.);
+    assert_approx_eq!(f64, report.mean_branching_factor, 0.);
+    assert_approx_eq!(f64, report.mean_leaf_cardinality, 100.);
+    assert!(tree.root().balance_factor().is_none());
+
+    // Eight distinct points split into a tree with singleton leaves.
+    let data = (0..8).map(|i| vec![(i * i).as_f32()]).collect::<Vec<_>>();
+    let data = utils::gen_dataset_from(data, utils::euclidean::<f32, f32>, vec![0_usize; 8]);
+    let tree = Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&PartitionCriteria::default(), Some(42));
+    let root = tree.root();
+    let report = root.quality_report();
+
+    assert_eq!(report.num_leaves, 8);
+    assert_eq!(report.num_clusters, 15);
+    assert_eq!(report.num_clusters, root.subtree_cardinality());
+    assert_eq!(report.max_depth, root.max_leaf_depth());
+    assert!((3..8).contains(&report.max_depth));
+    assert!(report.mean_leaf_depth >= 3. && report.mean_leaf_depth <= report.max_depth.as_f64());
+    assert!(report.leaf_depth_std <= report.max_depth.as_f64() / 2.);
+    assert_approx_eq!(f64, report.mean_branching_factor, 2.);
+    assert_approx_eq!(f64, report.mean_leaf_cardinality, 1.);
+    assert!(report.mean_balance_factor > 0. && report.mean_balance_factor <= 1.);
+
+    let Some(balance) = root.balance_factor() else {
+        unreachable!("The root has children.")
+    };
+    let Some([left, right]) = root.children() else {
+        unreachable!("The root has children.")
+    };
+    let (l, r) = (left.cardinality().as_f64(), right.cardinality().as_f64());
+    assert_approx_eq!(f64, balance, l.min(r) / l.max(r));
+}
+
+#[test]
+fn partition_further_where() {
+    let build = || {
+        let data = utils::gen_dataset(2000, 10, 42, utils::euclidean::<f32, f32>);
+        let criteria = PartitionCriteria::new(true).with_max_depth(4);
+        Tree::<_, _, _, UniBall<_>>::new(data, Some(42)).partition(&criteria, Some(42))
+    };
+    let coarse = build();
+    let threshold = 130;
+    let is_large = |c: &UniBall<f32>| c.cardinality() > threshold;
+    let coarse_leaves = coarse.root().subtree_filter(|c| c.is_leaf()).collect::<Vec<_>>();
+    assert!(coarse_leaves.iter().any(|c| is_large(c)));
+    assert!(coarse_leaves.iter().any(|c| !is_large(c)));
+
+    let criteria = PartitionCriteria::default();
+    for parallel in [false, true] {
+        let deepened = if parallel {
+            build().par_partition_further_where(&criteria, is_large, Some(42))
+        } else {
+            build().partition_further_where(&criteria, is_large, Some(42))
+        };
+        assert!(deepened.depth() > coarse.depth());
+
+        for leaf in &coarse_leaves {
+            let Some(c) = deepened.get_cluster(leaf.offset(), leaf.cardinality()) else {
+                unreachable!("Every coarse leaf is still a cluster.")
+            };
+            if is_large(leaf) {
+                assert!(!c.is_leaf());
+                assert!(c.subtree_filter(|c| c.is_leaf()).all(|c| c.is_singleton()));
+            } else {
+                // Small leaves, and the instances in them, are untouched.
+                assert!(c.is_leaf());
+                assert_eq!(c.depth(), leaf.depth());
+                assert_eq!(c.arg_center(), leaf.arg_center());
+                assert_eq!(c.radius(), leaf.radius());
+                assert_eq!(
+                    &deepened.data().data()[c.indices()],
+                    &coarse.data().data()[leaf.indices()]
+                );
+            }
+        }
+
+        // The tree still holds every instance, and search is still exact.
+        for i in (0..deepened.data().cardinality()).step_by(200) {
+            let original = deepened.data().original_index(i);
+            let j = (0..coarse.data().cardinality())
+                .find(|&j| coarse.data().original_index(j) == original)
+                .unwrap_or_else(|| unreachable!("Every instance is in both trees."));
+            assert_eq!(deepened.data()[i], coarse.data()[j]);
+
+            let query = &deepened.data()[i];
+            let expected = knn::Algorithm::Linear.search(&deepened, query, 5);
+            let hits = knn::Algorithm::RepeatedRnn.search(&deepened, query, 5);
+            assert_eq!(utils::compute_recall(hits, expected), 1.0);
+        }
+    }
+}
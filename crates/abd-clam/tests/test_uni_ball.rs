@@ -1,6 +1,9 @@
 //! Tests for the `UniBall` struct.
 
-use abd_clam::{Cluster, Dataset, Instance, PartitionCriteria, UniBall, VecDataset};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use abd_clam::{CenterStrategy, Cluster, Dataset, Instance, PartitionCriteria, UniBall, VecDataset};
+use distances::Number;
 
 mod utils;
 
@@ -76,6 +79,73 @@ fn check_subtree<M: Instance, C: Cluster<f32>>(root: &C, data: &VecDataset<Vec<f
     }
 }
 
+/// Counts the calls to `counted_euclidean`.
+static METRIC_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Euclidean distance that counts how many times it is called.
+#[allow(clippy::ptr_arg)]
+fn counted_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    METRIC_CALLS.fetch_add(1, Ordering::Relaxed);
+    utils::euclidean(x, y)
+}
+
+#[test]
+fn metric_calls() {
+    let mut data = utils::gen_dataset(1000, 10, 42, counted_euclidean);
+    let partition_criteria = PartitionCriteria::default();
+
+    // For small clusters, the distances from the center are read from the
+    // pairwise matrix used to find the center, so finding the center and the
+    // radius takes `n * (n + 1) / 2` calls instead of `n * (n + 3) / 2`.
+    let small = data.cardinality() / 20;
+    let indices = (0..small).collect::<Vec<_>>();
+    let small_data = utils::gen_dataset_from(
+        indices.iter().map(|&i| data[i].clone()).collect(),
+        counted_euclidean,
+        indices,
+    );
+    METRIC_CALLS.store(0, Ordering::Relaxed);
+    let small_root = UniBall::new_root(&small_data, Some(42));
+    assert_eq!(METRIC_CALLS.load(Ordering::Relaxed), small * (small + 1) / 2);
+    check_subtree(&small_root, &small_data);
+
+    METRIC_CALLS.store(0, Ordering::Relaxed);
+    let root = UniBall::new_root(&data, Some(42)).partition(&mut data, &partition_criteria, Some(42));
+    let calls = METRIC_CALLS.load(Ordering::Relaxed);
+
+    let expected = root
+        .subtree()
+        .into_iter()
+        .map(|c| {
+            let n = c.cardinality();
+            let creation = if n < 100 {
+                n * (n + 1) / 2
+            } else {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let s = n.as_f64().sqrt() as usize;
+                // Choosing `s` unique samples, finding their median and then
+                // the distances from the center.
+                s * (s - 1) / 2 + s * (s + 1) / 2 + n
+            };
+            // Partitioning computes the distances from both poles.
+            let partition = if c.is_leaf() { 0 } else { 2 * n };
+            creation + partition
+        })
+        .sum::<usize>();
+    assert_eq!(calls, expected);
+
+    check_subtree(&root, &data);
+    // Clusters with two instances have a tie for the median.
+    for c in root
+        .subtree()
+        .into_iter()
+        .filter(|c| (3..100).contains(&c.cardinality()))
+    {
+        let indices = c.indices().collect::<Vec<_>>();
+        assert_eq!(data.median(&indices), Some(c.arg_center()));
+    }
+}
+
 #[test]
 fn serialization() {
     let data = utils::gen_dataset_from(
@@ -100,3 +170,115 @@ fn serialization() {
     assert_eq!(original.radius(), deserialized.radius());
     assert_eq!(original.children(), deserialized.children());
 }
+
+/// Counts the calls to `strategy_euclidean`.
+static STRATEGY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Euclidean distance that counts how many times it is called.
+#[allow(clippy::ptr_arg)]
+fn strategy_euclidean(x: &Vec<f32>, y: &Vec<f32>) -> f32 {
+    STRATEGY_CALLS.fetch_add(1, Ordering::Relaxed);
+    utils::euclidean(x, y)
+}
+
+#[test]
+fn center_strategy() {
+    let strategies = [
+        CenterStrategy::ExactMedoid,
+        CenterStrategy::SampledMedoid { sample_size: 100 },
+        CenterStrategy::RandomPoint,
+    ];
+
+    // For clusters with at most `sample_size` instances, the sampled medoid is the exact medoid.
+    let roots = strategies[..2]
+        .iter()
+        .map(|&strategy| {
+            let mut data = utils::gen_dataset(100, 10, 42, utils::euclidean);
+            let criteria = PartitionCriteria::default().with_center_strategy(strategy);
+            let root = UniBall::new_root(&data, Some(42)).partition(&mut data, &criteria, Some(42));
+            check_subtree(&root, &data);
+            let centers = root.subtree().into_iter().map(Cluster::arg_center).collect::<Vec<_>>();
+            (data.data().to_vec(), centers)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(roots[0], roots[1]);
+
+    // For large clusters, the sampled medoid needs far fewer distance computations.
+    let calls = strategies
+        .iter()
+        .map(|&strategy| {
+            let mut data = utils::gen_dataset(2000, 10, 42, strategy_euclidean);
+            let criteria = PartitionCriteria::new(true)
+                .with_max_depth(1)
+                .with_center_strategy(strategy);
+            STRATEGY_CALLS.store(0, Ordering::Relaxed);
+            let root = UniBall::new_root(&data, Some(42));
+            let new_root_calls = STRATEGY_CALLS.load(Ordering::Relaxed);
+            let root = root.partition(&mut data, &criteria, Some(42));
+            check_subtree(&root, &data);
+            STRATEGY_CALLS.load(Ordering::Relaxed) - new_root_calls
+        })
+        .collect::<Vec<_>>();
+    assert!(calls[1] * 10 < calls[0], "{calls:?}");
+    assert!(calls[2] < calls[1], "{calls:?}");
+}
+
+#[test]
+fn sample_indices() {
+    let mut data = utils::gen_dataset(1_000, 10, 42, utils::euclidean);
+    let partition_criteria = PartitionCriteria::default();
+    let root = UniBall::new_root(&data, None).partition(&mut data, &partition_criteria, None);
+
+    let sample = root.sample_indices(50, 42);
+    assert_eq!(sample.len(), 50);
+    assert_eq!(sample, root.sample_indices(50, 42));
+    assert_ne!(sample, root.sample_indices(50, 43));
+
+    let mut unique = sample.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), 50);
+
+    let [left, _] = root.children().unwrap();
+    let sample = left.sample_indices(50, 42);
+    assert!(sample.iter().all(|i| left.indices().contains(i)));
+
+    let leaf = root.subtree().into_iter().find(|c| c.is_leaf()).unwrap();
+    let mut sample = leaf.sample_indices(leaf.cardinality() + 10, 42);
+    sample.sort_unstable();
+    assert_eq!(sample, leaf.indices().collect::<Vec<_>>());
+}
+
+#[test]
+fn recompute_geometry() {
+    let mut data = utils::gen_dataset(1_000, 10, 42, utils::euclidean);
+    let partition_criteria = PartitionCriteria::default();
+    let root = UniBall::new_root(&data, None).partition(&mut data, &partition_criteria, None);
+    check_subtree(&root, &data);
+
+    // Move the radial instance of the root onto its center.
+    let mut instances = data.data().to_vec();
+    instances[root.arg_radial()] = instances[root.arg_center()].clone();
+    let data = VecDataset::new("perturbed".to_string(), instances, utils::euclidean::<f32, f32>, false);
+    let stale = data.one_to_one(root.arg_center(), root.arg_radial());
+    assert!((stale - root.radius()).abs() > f32::EPSILON);
+
+    let mut serial = root;
+    let bytes = bincode::serialize(&serial).unwrap();
+    serial.recompute_geometry(&data);
+    check_subtree(&serial, &data);
+    for c in serial.subtree() {
+        let distances = data.one_to_many(c.arg_center(), &c.indices().collect::<Vec<_>>());
+        assert!(distances.iter().all(|&d| d <= c.radius()));
+    }
+
+    let mut parallel: UniBall<f32> = bincode::deserialize(&bytes).unwrap();
+    parallel.par_recompute_geometry(&data);
+    assert_eq!(serial.subtree().len(), parallel.subtree().len());
+    for (s, p) in serial.subtree().into_iter().zip(parallel.subtree()) {
+        assert_eq!(s.arg_center(), p.arg_center());
+        assert_eq!(s.arg_radial(), p.arg_radial());
+        assert!((s.radius() - p.radius()).abs() <= f32::EPSILON);
+        assert!((s.polar_distance().unwrap_or(0.) - p.polar_distance().unwrap_or(0.)).abs() <= f32::EPSILON);
+    }
+}
@@ -188,3 +188,43 @@ fn normalized_ratios() {
         }
     }
 }
+
+#[test]
+fn recompute_geometry_ratios() {
+    let data = utils::gen_dataset(1000, 10, 42, utils::euclidean);
+    let partition_criteria = PartitionCriteria::new(true).with_max_depth(5);
+    let tree = Tree::<_, _, _, Vertex<_>>::new(data, Some(42)).partition(&partition_criteria, Some(42));
+    let stale = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .map(Vertex::ratios)
+        .collect::<Vec<_>>();
+
+    // Squaring the distances changes every radius, and so the ratios.
+    let tree = tree.with_new_metric_recomputing(utils::euclidean_sq::<f32>, false);
+    let ratios = tree
+        .root()
+        .subtree()
+        .into_iter()
+        .map(Vertex::ratios)
+        .collect::<Vec<_>>();
+    assert_ne!(stale, ratios);
+
+    let root = tree.root();
+    assert_eq!(
+        root.ratios()[..3],
+        [root.cardinality().as_f64(), root.radius().as_f64(), root.lfd()]
+    );
+    for parent in root.subtree() {
+        let [pc, pr, pl, ..] = parent.ratios();
+        for child in parent.children().into_iter().flatten() {
+            let expected = [
+                child.cardinality().as_f64() / pc,
+                child.radius().as_f64() / pr,
+                child.lfd() / pl,
+            ];
+            assert_eq!(child.ratios()[..3], expected, "{} not correct", child.name());
+        }
+    }
+}
@@ -21,6 +21,12 @@ pub fn euclidean_sq<T: Number>(x: &Vec<T>, y: &Vec<T>) -> T {
     distances::vectors::euclidean_sq(x, y)
 }
 
+/// Wasserstein distance between two histograms.
+#[allow(clippy::ptr_arg)]
+pub fn wasserstein<T: Number, F: Float>(x: &Vec<T>, y: &Vec<T>) -> F {
+    distances::vectors::wasserstein(x, y)
+}
+
 /// Hamming distance between two Strings.
 pub fn hamming<T: UInt>(x: &String, y: &String) -> T {
     distances::strings::hamming(x, y)
@@ -61,6 +67,18 @@ pub fn gen_dataset_from<T: Number, U: Number, M: Instance>(
         .unwrap_or_else(|_| unreachable!())
 }
 
+/// Generate a dataset of the points on a square grid in the plane.
+///
+/// The grid has integer coordinates from `-half_width` to `half_width` along
+/// each axis.
+pub fn gen_grid_data(half_width: i32) -> VecDataset<Vec<f32>, f32, bool> {
+    let data = (-half_width..=half_width)
+        .flat_map(|x| (-half_width..=half_width).map(move |y| vec![x.as_f32(), y.as_f32()]))
+        .collect::<Vec<_>>();
+    let metadata = vec![true; data.len()];
+    gen_dataset_from(data, euclidean::<f32, f32>, metadata)
+}
+
 /// Compute the recall of the nearest neighbors found.
 ///
 /// Assumes that `linear_hits` is not empty.
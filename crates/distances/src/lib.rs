@@ -20,8 +20,10 @@
 // extern crate alloc;
 
 pub mod number;
+pub mod properties;
 
 pub use number::Number;
+pub use properties::MetricProperties;
 
 pub mod sets;
 pub mod simd;
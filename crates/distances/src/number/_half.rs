@@ -0,0 +1,92 @@
+//! Implementation of `Number` for half-precision floats from the `half` crate.
+//!
+//! Arithmetic that `half::f16` does not natively support is performed in `f32`
+//! and rounded back to `f16`.
+
+use half::f16;
+
+use crate::Number;
+
+impl Number for f16 {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn one() -> Self {
+        Self::ONE
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        Self::from_f32(self.to_f32().mul_add(a.to_f32(), b.to_f32()))
+    }
+
+    fn mul_add_assign(&mut self, a: Self, b: Self) {
+        *self = Number::mul_add(*self, a, b);
+    }
+
+    fn from<T: Number>(n: T) -> Self {
+        Self::from_f32(n.as_f32())
+    }
+
+    fn as_f32(self) -> f32 {
+        self.to_f32()
+    }
+
+    fn as_f64(self) -> f64 {
+        self.to_f64()
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn as_u64(self) -> u64 {
+        self.to_f32() as u64
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn as_i64(self) -> i64 {
+        self.to_f32() as i64
+    }
+
+    fn abs(self) -> Self {
+        Self::from_bits(self.to_bits() & 0x7fff)
+    }
+
+    fn abs_diff(self, other: Self) -> Self {
+        Number::abs(self - other)
+    }
+
+    fn powi(self, exp: i32) -> Self {
+        Self::from_f32(self.to_f32().powi(exp))
+    }
+
+    fn num_bytes() -> usize {
+        core::mem::size_of::<Self>()
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        let mut ty_bytes = [0_u8; 2];
+        ty_bytes.copy_from_slice(bytes);
+        Self::from_le_bytes(ty_bytes)
+    }
+
+    fn to_le_bytes(self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        let mut ty_bytes = [0_u8; 2];
+        ty_bytes.copy_from_slice(bytes);
+        Self::from_be_bytes(ty_bytes)
+    }
+
+    fn to_be_bytes(self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn epsilon() -> Self {
+        Self::EPSILON
+    }
+
+    fn next_random<R: rand::Rng>(rng: &mut R) -> Self {
+        Self::from_f32(rng.gen())
+    }
+}
@@ -5,8 +5,11 @@
 //! * All primitive unsigned integers: `u8`, `u16`, `u32`, `u64`, `u128`, `usize`.
 //! * All primitive signed integers: `i8`, `i16`, `i32`, `i64`, `i128`, `isize`.
 //! * All primitive floating point numbers: `f32`, `f64`.
+//! * Half-precision floats, `half::f16`, with the `half` feature.
 
 mod _bool;
+#[cfg(feature = "half")]
+mod _half;
 mod _number;
 mod _variants;
 
@@ -0,0 +1,48 @@
+//! The properties of a metric that a distance function may satisfy.
+
+/// The properties that a distance function is assumed to satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct MetricProperties {
+    /// Whether `d(x, x) = 0`.
+    pub identity: bool,
+    /// Whether `d(x, y) = d(y, x)`.
+    pub symmetry: bool,
+    /// Whether `d(x, y) >= 0`.
+    pub non_negativity: bool,
+    /// Whether `d(x, z) <= d(x, y) + d(y, z)`.
+    pub triangle_inequality: bool,
+}
+
+impl Default for MetricProperties {
+    fn default() -> Self {
+        Self {
+            identity: true,
+            symmetry: true,
+            non_negativity: true,
+            triangle_inequality: true,
+        }
+    }
+}
+
+impl MetricProperties {
+    /// Whether all properties of a metric are satisfied.
+    #[must_use]
+    pub const fn is_metric(&self) -> bool {
+        self.identity && self.symmetry && self.non_negativity && self.triangle_inequality
+    }
+
+    /// The properties that are satisfied by both `self` and `other`.
+    ///
+    /// A sum of distance functions with non-negative weights satisfies each
+    /// property that all of the distance functions satisfy.
+    #[must_use]
+    pub const fn and(self, other: Self) -> Self {
+        Self {
+            identity: self.identity && other.identity,
+            symmetry: self.symmetry && other.symmetry,
+            non_negativity: self.non_negativity && other.non_negativity,
+            triangle_inequality: self.triangle_inequality && other.triangle_inequality,
+        }
+    }
+}
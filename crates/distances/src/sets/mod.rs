@@ -47,6 +47,60 @@ pub fn jaccard<T: Int, U: Float>(x: &[T], y: &[T]) -> U {
     }
 }
 
+/// Jaccard distance between sets given as sorted slices.
+///
+/// This computes the same distance as `jaccard`, but counts the intersection
+/// by merging the two slices instead of building `BTreeSet`s, so it takes
+/// linear time and no extra memory. This suits sparse sets drawn from a large
+/// universe, such as documents given as token ids. The Jaccard distance
+/// satisfies the triangle inequality.
+///
+/// Both slices must be sorted in ascending order and free of duplicates. This
+/// is not checked.
+///
+/// # Arguments
+///
+/// * `x`: A set represented as a sorted slice of `Int`s.
+/// * `y`: A set represented as a sorted slice of `Int`s.
+///
+/// # Examples
+///
+/// ```
+/// use distances::sets::sorted_jaccard;
+///
+/// let x: Vec<u32> = vec![1, 2, 3];
+/// let y: Vec<u32> = vec![2, 3, 4];
+///
+/// let distance: f32 = sorted_jaccard(&x, &y);
+///
+/// assert!((distance - 0.5).abs() < f32::EPSILON);
+/// ```
+pub fn sorted_jaccard<T: Int, U: Float>(x: &[T], y: &[T]) -> U {
+    if x.is_empty() || y.is_empty() {
+        return U::one();
+    }
+
+    let (mut i, mut j, mut intersection) = (0, 0, 0);
+    while i < x.len() && j < y.len() {
+        match x[i].cmp(&y[j]) {
+            core::cmp::Ordering::Less => i += 1,
+            core::cmp::Ordering::Greater => j += 1,
+            core::cmp::Ordering::Equal => {
+                intersection += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    let union = x.len() + y.len() - intersection;
+    if intersection == union {
+        U::zero()
+    } else {
+        U::one() - U::from(intersection) / U::from(union)
+    }
+}
+
 /// Dice distance.
 ///
 /// Dice distance, between two sets, measures how dissimilar they are by considering the proportion
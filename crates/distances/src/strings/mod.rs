@@ -87,6 +87,70 @@ pub fn levenshtein_custom<U: UInt>(penalties: Penalties<U>) -> impl Fn(&str, &st
     }
 }
 
+/// Creates a function to compute the Levenshtein distance between two strings
+/// with a custom cost for substituting each pair of characters.
+///
+/// The generated function will have the same signature as `levenshtein`. This
+/// is useful, e.g., for DNA, where transitions are usually cheaper than
+/// transversions. The result is only a metric if `substitution` is symmetric,
+/// is zero exactly for equal characters, obeys the triangle inequality, and
+/// never exceeds twice the `gap` cost.
+///
+/// # Arguments
+///
+/// * `substitution`: the cost of substituting the first character with the
+///   second. It is called with equal characters for matches.
+/// * `gap`: the cost of an insertion or deletion.
+///
+/// # Examples
+///
+/// ```
+/// use distances::strings::weighted_levenshtein;
+///
+/// // Transitions (A <-> G, C <-> T) cost 1 and transversions cost 2.
+/// let metric = weighted_levenshtein(
+///     |a: char, b: char| match (a, b) {
+///         _ if a == b => 0,
+///         ('A', 'G') | ('G', 'A') | ('C', 'T') | ('T', 'C') => 1,
+///         _ => 2,
+///     },
+///     2,
+/// );
+///
+/// let distance: u16 = metric("ACGT", "GCGT");
+/// assert_eq!(distance, 1);
+///
+/// let distance: u16 = metric("ACGT", "CCGT");
+/// assert_eq!(distance, 2);
+/// ```
+pub fn weighted_levenshtein<U: UInt, F: Fn(char, char) -> U>(
+    substitution: F,
+    gap: U,
+) -> impl Fn(&str, &str) -> U {
+    move |x: &str, y: &str| {
+        let mut cur = (0..=y.chars().count())
+            .map(|j| gap * U::from(j))
+            .collect::<Vec<_>>();
+
+        for (i, c_x) in x.chars().enumerate() {
+            let mut pre = cur[0];
+            cur[0] = gap * U::from(i + 1);
+            for (j, c_y) in y.chars().enumerate() {
+                let tmp = cur[j + 1];
+                cur[j + 1] = core::cmp::min(
+                    // deletion or insertion
+                    core::cmp::min(tmp, cur[j]) + gap,
+                    // match or substitution
+                    pre + substitution(c_x, c_y),
+                );
+                pre = tmp;
+            }
+        }
+
+        cur[cur.len() - 1]
+    }
+}
+
 /// Computes the Levenshtein distance between two strings.
 ///
 /// The Levenshtein distance is defined as the minimum number of edits
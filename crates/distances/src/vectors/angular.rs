@@ -58,6 +58,66 @@ pub fn cosine<T: Number, U: Float>(x: &[T], y: &[T]) -> U {
     }
 }
 
+/// Computes the angular distance between two vectors.
+///
+/// The angular distance is the angle between the two vectors, divided by `π`
+/// so that it lies in `[0, 1]`, i.e. `arccos(c) / π` where `c` is the cosine
+/// similarity. Unlike the `cosine` distance, it obeys the triangle inequality,
+/// so it is a metric on directions.
+///
+/// The vectors are normalized internally, so they need not have unit length.
+/// A zero vector has no direction; it is at distance `0` from another zero
+/// vector and at distance `0.5` from every other vector.
+///
+/// The angle is computed as `2 atan2(|x' - y'|, |x' + y'|)` for the
+/// normalized vectors `x'` and `y'`, which, unlike `arccos`, is accurate for
+/// nearly parallel vectors.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// function's potentially unexpected behaviors
+///
+/// # Arguments
+///
+/// * `x`: A slice of numbers.
+/// * `y`: A slice of numbers.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::angular;
+///
+/// let x: Vec<f32> = vec![1.0, 0.0];
+/// let y: Vec<f32> = vec![0.0, 2.0];
+///
+/// let distance: f32 = angular(&x, &y);
+///
+/// assert!((distance - 0.5).abs() < f32::EPSILON);
+/// ```
+///
+/// # References
+///
+/// * [Angular distance](https://en.wikipedia.org/wiki/Cosine_similarity#Angular_distance_and_similarity)
+pub fn angular<T: Number, U: Float>(x: &[T], y: &[T]) -> U {
+    let norm = |v: &[T]| v.iter().map(|a| a.as_f64().powi(2)).sum::<f64>().sqrt();
+    let (nx, ny) = (norm(x), norm(y));
+
+    let angle = match (nx > 0., ny > 0.) {
+        (false, false) => 0.,
+        (true, false) | (false, true) => 0.5,
+        (true, true) => {
+            let [diff, sum] = x
+                .iter()
+                .zip(y.iter())
+                .fold([0.; 2], |[diff, sum], (&a, &b)| {
+                    let (a, b) = (a.as_f64() / nx, b.as_f64() / ny);
+                    [(a - b).mul_add(a - b, diff), (a + b).mul_add(a + b, sum)]
+                });
+            2. * diff.sqrt().atan2(sum.sqrt()) / core::f64::consts::PI
+        }
+    };
+    U::from(angle)
+}
+
 /// Computes the Hamming distance between two vectors.
 ///
 /// The Hamming distance is defined as the number of positions at which
@@ -0,0 +1,157 @@
+//! Weighted sums of distances over disjoint ranges of coordinates.
+
+use core::ops::Range;
+
+use crate::{number::Float, MetricProperties};
+
+/// A distance function over some range of the coordinates of two vectors.
+pub type SubMetric<T, U> = Box<dyn Fn(&[T], &[T]) -> U + Send + Sync>;
+
+/// A range of coordinates, a distance function over that range, the weight of
+/// that distance function, and the properties that it satisfies.
+pub type CompositePart<T, U> = (Range<usize>, SubMetric<T, U>, f64, MetricProperties);
+
+/// A distance function that is a weighted sum of distance functions, each
+/// applied to a disjoint range of the coordinates of two vectors.
+///
+/// This can combine, for example, a Euclidean distance over the numeric
+/// features of a vector with a Hamming distance over its categorical features.
+///
+/// Each part is given with the `MetricProperties` of its distance function.
+/// Since the weights are non-negative, the `Composite` satisfies each property
+/// that every part satisfies, and is otherwise assumed not to satisfy it.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// distance's potentially unexpected behaviors. In particular, any range that
+/// extends past the end of the shorter vector is truncated to its length.
+///
+/// # Examples
+///
+/// ```
+/// use distances::{
+///     vectors::{euclidean, hamming, Composite, SubMetric},
+///     MetricProperties,
+/// };
+///
+/// let numeric: SubMetric<i32, f32> = Box::new(euclidean);
+/// let categorical: SubMetric<i32, f32> = Box::new(|x, y| hamming::<i32, u32>(x, y) as f32);
+/// let metric = Composite::new(vec![
+///     (0..2, numeric, 1.0, MetricProperties::default()),
+///     (2..4, categorical, 0.5, MetricProperties::default()),
+/// ])
+/// .unwrap();
+///
+/// let x: Vec<i32> = vec![0, 0, 1, 2];
+/// let y: Vec<i32> = vec![3, 4, 1, 3];
+///
+/// let distance = metric.distance(&x, &y);
+/// assert!((distance - 5.5).abs() <= f32::EPSILON);
+/// assert!(metric.properties().is_metric());
+/// ```
+pub struct Composite<T, U: Float> {
+    /// The ranges of coordinates, the distance functions over those ranges,
+    /// and the weights of those distance functions.
+    parts: Vec<(Range<usize>, SubMetric<T, U>, f64)>,
+    /// The properties that every part satisfies.
+    properties: MetricProperties,
+}
+
+impl<T, U: Float> Composite<T, U> {
+    /// Creates a `Composite` from distance functions over ranges of
+    /// coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts`: The ranges of coordinates, the distance functions over those
+    ///   ranges, the weights of those distance functions, and the properties
+    ///   that those distance functions satisfy.
+    ///
+    /// # Errors
+    ///
+    /// * See `add_part`.
+    pub fn new(parts: Vec<CompositePart<T, U>>) -> Result<Self, String> {
+        let mut composite = Self {
+            parts: Vec::with_capacity(parts.len()),
+            properties: MetricProperties::default(),
+        };
+        for (range, metric, weight, properties) in parts {
+            composite.add_part(range, metric, weight, properties)?;
+        }
+        Ok(composite)
+    }
+
+    /// Adds a distance function over a range of coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `range`: The range of coordinates.
+    /// * `metric`: The distance function over the range.
+    /// * `weight`: The weight of the distance function.
+    /// * `properties`: The properties that the distance function satisfies.
+    ///
+    /// # Errors
+    ///
+    /// * If the `range` is empty.
+    /// * If the `range` overlaps with the range of another part.
+    /// * If the `weight` is negative or not finite.
+    pub fn add_part(
+        &mut self,
+        range: Range<usize>,
+        metric: SubMetric<T, U>,
+        weight: f64,
+        properties: MetricProperties,
+    ) -> Result<(), String> {
+        if range.is_empty() {
+            return Err(format!("The range {range:?} is empty."));
+        }
+        if let Some((other, _, _)) = self
+            .parts
+            .iter()
+            .find(|(r, _, _)| r.start < range.end && range.start < r.end)
+        {
+            return Err(format!("The range {range:?} overlaps with {other:?}."));
+        }
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!(
+                "The weight must be finite and non-negative. Got {weight}."
+            ));
+        }
+
+        self.parts.push((range, metric, weight));
+        self.properties = self.properties.and(properties);
+        Ok(())
+    }
+
+    /// The properties that every part satisfies, and therefore so does the
+    /// `Composite`.
+    #[must_use]
+    pub const fn properties(&self) -> MetricProperties {
+        self.properties
+    }
+
+    /// Whether every part obeys the triangle inequality, in which case so does
+    /// the `Composite`.
+    #[must_use]
+    pub const fn obeys_triangle_inequality(&self) -> bool {
+        self.properties.triangle_inequality
+    }
+
+    /// Computes the weighted sum of the distances over each range of
+    /// coordinates.
+    ///
+    /// # Arguments
+    ///
+    /// * `x`: A vector.
+    /// * `y`: A vector.
+    pub fn distance(&self, x: &[T], y: &[T]) -> U {
+        let len = x.len().min(y.len());
+        self.parts
+            .iter()
+            .map(|(range, metric, weight)| {
+                let end = range.end.min(len);
+                let start = range.start.min(end);
+                U::from(*weight) * metric(&x[start..end], &y[start..end])
+            })
+            .sum()
+    }
+}
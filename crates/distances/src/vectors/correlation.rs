@@ -0,0 +1,143 @@
+//! Correlation distances between vectors.
+
+use core::cmp::Ordering;
+
+use crate::{number::Float, Number};
+
+/// Computes the Pearson distance between two vectors.
+///
+/// The Pearson distance is defined as `1.0 - r` where `r` is the Pearson
+/// correlation coefficient of the two vectors. It lies in the range `[0, 2]`.
+/// If either vector is constant, the correlation is undefined and the distance
+/// is `1.0`.
+///
+/// This is not a metric, as it does not satisfy the triangle inequality.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// function's potentially unexpected behaviors
+///
+/// # Arguments
+///
+/// * `x`: A slice of numbers.
+/// * `y`: A slice of numbers.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::pearson;
+///
+/// let x: Vec<f32> = vec![1.0, 2.0, 3.0];
+/// let y: Vec<f32> = vec![2.0, 4.0, 6.0];
+///
+/// let distance: f32 = pearson(&x, &y);
+///
+/// assert!(distance.abs() < f32::EPSILON);
+///
+/// let y: Vec<f32> = vec![6.0, 4.0, 2.0];
+///
+/// let distance: f32 = pearson(&x, &y);
+///
+/// assert!((distance - 2.0).abs() < f32::EPSILON);
+/// ```
+///
+/// # References
+///
+/// * [Pearson correlation coefficient](https://en.wikipedia.org/wiki/Pearson_correlation_coefficient)
+pub fn pearson<T: Number, U: Float>(x: &[T], y: &[T]) -> U {
+    let x = x.iter().map(|&a| U::from(a)).collect::<Vec<_>>();
+    let y = y.iter().map(|&b| U::from(b)).collect::<Vec<_>>();
+    correlation_distance(&x, &y)
+}
+
+/// Computes the Spearman distance between two vectors.
+///
+/// The Spearman distance is defined as `1.0 - rho` where `rho` is the
+/// Spearman rank correlation coefficient of the two vectors, i.e. the Pearson
+/// correlation coefficient of their ranks. Tied values are given the average
+/// of the ranks they span. It lies in the range `[0, 2]`. If either vector is
+/// constant, the correlation is undefined and the distance is `1.0`.
+///
+/// This is not a metric, as it does not satisfy the triangle inequality.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// function's potentially unexpected behaviors
+///
+/// # Arguments
+///
+/// * `x`: A slice of numbers.
+/// * `y`: A slice of numbers.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::spearman;
+///
+/// let x: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0];
+/// let y: Vec<f32> = vec![1.0, 10.0, 100.0, 1000.0];
+///
+/// let distance: f32 = spearman(&x, &y);
+///
+/// assert!(distance.abs() < f32::EPSILON);
+/// ```
+///
+/// # References
+///
+/// * [Spearman's rank correlation coefficient](https://en.wikipedia.org/wiki/Spearman%27s_rank_correlation_coefficient)
+pub fn spearman<T: Number, U: Float>(x: &[T], y: &[T]) -> U {
+    let len = x.len().min(y.len());
+    correlation_distance(&ranks(&x[..len]), &ranks(&y[..len]))
+}
+
+/// Computes `1.0 - r` where `r` is the Pearson correlation coefficient.
+fn correlation_distance<U: Float>(x: &[U], y: &[U]) -> U {
+    let len = x.len().min(y.len());
+    if len == 0 {
+        return U::one();
+    }
+    let (x, y) = (&x[..len], &y[..len]);
+
+    let mean_x = x.iter().copied().sum::<U>() / U::from(len);
+    let mean_y = y.iter().copied().sum::<U>() / U::from(len);
+
+    let [xx, yy, xy] = x
+        .iter()
+        .zip(y.iter())
+        .fold([U::zero(); 3], |[xx, yy, xy], (&a, &b)| {
+            let (a, b) = (a - mean_x, b - mean_y);
+            [a.mul_add(a, xx), b.mul_add(b, yy), a.mul_add(b, xy)]
+        });
+
+    if xx < U::epsilon() || yy < U::epsilon() {
+        U::one()
+    } else {
+        let d = U::one() - xy * (xx * yy).inv_sqrt();
+        if d < U::epsilon() {
+            U::zero()
+        } else {
+            d
+        }
+    }
+}
+
+/// Computes the ranks of the values in a vector, starting from `1`, with tied
+/// values given the average of the ranks they span.
+fn ranks<T: Number, U: Float>(values: &[T]) -> Vec<U> {
+    let mut order = (0..values.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| values[i].partial_cmp(&values[j]).unwrap_or(Ordering::Equal));
+
+    let mut ranks = vec![U::zero(); values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        // The ranks `start + 1..=end` are averaged.
+        let rank = U::from(start + end + 1) / U::from(2);
+        for &i in &order[start..end] {
+            ranks[i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
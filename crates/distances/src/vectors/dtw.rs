@@ -0,0 +1,96 @@
+//! Dynamic Time Warping distance between time series.
+
+use crate::Number;
+
+/// Dynamic Time Warping (DTW) distance between two time series.
+///
+/// DTW finds the alignment between the two series that minimizes the sum of
+/// the absolute differences between aligned elements. Unlike the other
+/// functions in this module, the series may have different lengths and every
+/// element of both series is used.
+///
+/// The optional `band` restricts the alignment to a Sakoe-Chiba band, i.e.
+/// element `i` of `x` may only be aligned with element `j` of `y` if
+/// `|i - j| <= band`. The band is widened to the difference in lengths of the
+/// series, if needed, so that an alignment always exists. A narrower band
+/// reduces the time taken from `O(n * m)` to `O(n * band)`, at the cost of
+/// possibly missing the optimal alignment.
+///
+/// DTW does not satisfy the triangle inequality, so it is not a metric. Search
+/// algorithms that rely on the triangle inequality for pruning will only give
+/// approximate results when used with this distance.
+///
+/// # Arguments
+///
+/// * `band`: The optional width of the Sakoe-Chiba band.
+///
+/// # Returns
+///
+/// A function that computes the DTW distance between two slices of `Number`s.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::dtw;
+///
+/// let metric = dtw(None);
+///
+/// let x: Vec<f32> = vec![0.0, 1.0, 2.0, 1.0, 0.0];
+/// let y: Vec<f32> = vec![0.0, 0.0, 1.0, 2.0, 1.0, 0.0];
+///
+/// let distance: f32 = metric(&x, &y);
+/// assert!(distance.abs() <= f32::EPSILON);
+///
+/// let metric = dtw(Some(0));
+///
+/// let x: Vec<f32> = vec![0.0, 1.0, 2.0];
+/// let y: Vec<f32> = vec![1.0, 2.0, 3.0];
+///
+/// let distance: f32 = metric(&x, &y);
+/// assert!((distance - 3.0).abs() <= f32::EPSILON);
+/// ```
+///
+/// # References
+///
+/// * [Dynamic time warping](https://en.wikipedia.org/wiki/Dynamic_time_warping)
+pub fn dtw<T: Number, U: Number>(band: Option<usize>) -> impl Fn(&[T], &[T]) -> U {
+    move |x: &[T], y: &[T]| {
+        if x.is_empty() || y.is_empty() {
+            return U::zero();
+        }
+
+        let band = band.map_or(usize::MAX, |b| b.max(x.len().abs_diff(y.len())));
+
+        // `prev[j]` and `curr[j]` hold the cost of the best alignment of the
+        // first `i` elements of `x` with the first `j` elements of `y`, or
+        // `None` if those elements lie outside the band.
+        let mut prev = vec![None; y.len() + 1];
+        prev[0] = Some(U::zero());
+        let mut curr = vec![None; y.len() + 1];
+
+        for (i, &a) in x.iter().enumerate() {
+            let start = i.saturating_sub(band);
+            let end = i.saturating_add(band).saturating_add(1).min(y.len());
+
+            // Only the cells in `start..=end` are written or read in this row,
+            // and the band of the next row starts no earlier and ends at most
+            // one cell later. Clearing the cells just outside the band of this
+            // row is enough to keep the costs from two rows ago out of it.
+            curr[start] = None;
+            if end < y.len() {
+                curr[end + 1] = None;
+            }
+            for j in start..end {
+                let best = [prev[j], prev[j + 1], curr[j]]
+                    .into_iter()
+                    .flatten()
+                    .min_by(|l: &U, r| l.partial_cmp(r).unwrap_or(core::cmp::Ordering::Greater));
+                curr[j + 1] = best.map(|c| c + U::from(a.abs_diff(y[j])));
+            }
+
+            core::mem::swap(&mut prev, &mut curr);
+        }
+
+        prev[y.len()].unwrap_or_else(U::zero)
+    }
+}
@@ -0,0 +1,48 @@
+//! Distances between points on the surface of the Earth.
+
+/// The mean radius of the Earth, in kilometers.
+const EARTH_RADIUS_KM: f64 = 6_371.008_8;
+
+/// Computes the great-circle distance, in kilometers, between two points on
+/// the surface of the Earth using the haversine formula.
+///
+/// Each point is given as `[latitude, longitude]` in degrees. The Earth is
+/// treated as a sphere with its mean radius, so the distance may be off by up
+/// to about 0.5% from the distance on the ellipsoid.
+///
+/// This is a metric, as the great-circle distance satisfies the triangle
+/// inequality.
+///
+/// # Arguments
+///
+/// * `x`: The latitude and longitude of a point, in degrees.
+/// * `y`: The latitude and longitude of a point, in degrees.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::haversine;
+///
+/// let london = [51.5074, -0.1278];
+/// let paris = [48.8566, 2.3522];
+///
+/// let distance = haversine(&london, &paris);
+///
+/// assert!((distance - 343.56).abs() < 0.01);
+/// ```
+///
+/// # References
+///
+/// * [Haversine formula](https://en.wikipedia.org/wiki/Haversine_formula)
+#[must_use]
+pub fn haversine(x: &[f64; 2], y: &[f64; 2]) -> f64 {
+    let [lat_x, lon_x] = [x[0].to_radians(), x[1].to_radians()];
+    let [lat_y, lon_y] = [y[0].to_radians(), y[1].to_radians()];
+
+    let sin_lat = ((lat_y - lat_x) / 2.).sin();
+    let sin_lon = ((lon_y - lon_x) / 2.).sin();
+    let h = (lat_x.cos() * lat_y.cos()).mul_add(sin_lon * sin_lon, sin_lat * sin_lat);
+
+    // Rounding may push `h` slightly above `1` for antipodal points.
+    2. * EARTH_RADIUS_KM * h.sqrt().min(1.).asin()
+}
@@ -66,6 +66,44 @@ pub fn euclidean_sq<T: Number, U: Number>(x: &[T], y: &[T]) -> U {
     abs_diff_iter(x, y).map(U::from).map(|v| v * v).sum()
 }
 
+/// Euclidean distance between two vectors of half-precision floats.
+///
+/// The elements are stored as `f16` but are widened to `f32` before taking
+/// differences, so the distance is computed entirely in `f32`. This avoids the
+/// loss of precision and overflow that come with accumulating in `f16`.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// function's potentially unexpected behaviors
+///
+/// # Arguments
+///
+/// * `x` - The first slice of `f16`s.
+/// * `y` - The second slice of `f16`s.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::euclidean_f16;
+/// use half::f16;
+///
+/// let x: Vec<f16> = [1.0, 2.0, 3.0].into_iter().map(f16::from_f32).collect();
+/// let y: Vec<f16> = [4.0, 5.0, 6.0].into_iter().map(f16::from_f32).collect();
+///
+/// let distance: f32 = euclidean_f16(&x, &y);
+///
+/// assert!((distance - (27.0_f32).sqrt()).abs() <= f32::EPSILON);
+/// ```
+#[cfg(feature = "half")]
+#[must_use]
+pub fn euclidean_f16(x: &[half::f16], y: &[half::f16]) -> f32 {
+    x.iter()
+        .zip(y.iter())
+        .map(|(a, b)| a.to_f32() - b.to_f32())
+        .map(|v| v * v)
+        .sum::<f32>()
+        .sqrt()
+}
+
 /// Manhattan distance between two vectors.
 ///
 /// Also known as the L1-norm or the taxicab distance, the Manhattan distance is
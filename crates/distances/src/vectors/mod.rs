@@ -7,10 +7,23 @@
 //! shorter vector will be ignored.
 
 mod angular;
+mod composite;
+mod correlation;
+mod dtw;
+mod geographic;
 mod lp_norms;
 pub(crate) mod utils;
+mod wasserstein;
 
-pub use angular::{bray_curtis, canberra, cosine, hamming};
+pub use angular::{angular, bray_curtis, canberra, cosine, hamming};
+pub use composite::{Composite, CompositePart, SubMetric};
+pub use correlation::{pearson, spearman};
+pub use dtw::dtw;
+pub use geographic::haversine;
 pub use lp_norms::{
     chebyshev, euclidean, euclidean_sq, l3_norm, l4_norm, manhattan, minkowski, minkowski_p,
 };
+pub use wasserstein::wasserstein;
+
+#[cfg(feature = "half")]
+pub use lp_norms::euclidean_f16;
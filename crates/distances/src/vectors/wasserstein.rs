@@ -0,0 +1,53 @@
+//! Wasserstein distance between histograms.
+
+use crate::{number::Float, Number};
+
+/// 1-dimensional Wasserstein distance between two histograms.
+///
+/// Also known as the Earth Mover's distance, this is the minimum amount of
+/// "mass" that must be moved, times the distance it is moved, to turn one
+/// histogram into the other. For histograms over the same equally spaced bins,
+/// with unit width, this is the sum of the absolute differences between the
+/// cumulative sums of the two histograms.
+///
+/// The histograms should have the same total mass, e.g. by normalizing them
+/// to sum to one. With this, the Wasserstein distance is a metric.
+///
+/// See the [`crate::vectors`] module documentation for information on this
+/// function's potentially unexpected behaviors
+///
+/// # Arguments
+///
+/// * `x`: A histogram.
+/// * `y`: A histogram.
+///
+/// # Examples
+///
+/// ```
+/// use distances::vectors::wasserstein;
+///
+/// let x: Vec<f32> = vec![1.0, 0.0, 0.0];
+/// let y: Vec<f32> = vec![0.0, 0.0, 1.0];
+///
+/// let distance: f32 = wasserstein(&x, &y);
+/// assert!((distance - 2.0).abs() <= f32::EPSILON);
+///
+/// let x: Vec<f32> = vec![0.5, 0.5, 0.0];
+/// let y: Vec<f32> = vec![0.0, 0.5, 0.5];
+///
+/// let distance: f32 = wasserstein(&x, &y);
+/// assert!((distance - 1.0).abs() <= f32::EPSILON);
+/// ```
+///
+/// # References
+///
+/// * [Wasserstein metric](https://en.wikipedia.org/wiki/Wasserstein_metric)
+pub fn wasserstein<T: Number, U: Float>(x: &[T], y: &[T]) -> U {
+    x.iter()
+        .zip(y.iter())
+        .scan(U::zero(), |carry, (&a, &b)| {
+            *carry += U::from(a) - U::from(b);
+            Some(carry.abs())
+        })
+        .sum()
+}
@@ -0,0 +1,107 @@
+use rand::prelude::*;
+
+use distances::{
+    vectors::{euclidean, hamming, Composite, SubMetric},
+    MetricProperties, Number,
+};
+
+/// A Euclidean distance over the first three coordinates plus half of the
+/// Hamming distance over the last two coordinates.
+fn mixed(x: &[i32], y: &[i32]) -> f64 {
+    let numeric = x[..3]
+        .iter()
+        .zip(y[..3].iter())
+        .map(|(&a, &b)| (a - b).as_f64().powi(2))
+        .sum::<f64>()
+        .sqrt();
+    let categorical = x[3..]
+        .iter()
+        .zip(y[3..].iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    numeric + 0.5 * categorical.as_f64()
+}
+
+fn mixed_composite() -> Composite<i32, f64> {
+    let numeric: SubMetric<i32, f64> = Box::new(euclidean);
+    let categorical: SubMetric<i32, f64> = Box::new(|x, y| hamming::<i32, u32>(x, y).as_f64());
+    let metric = MetricProperties::default();
+    Composite::new(vec![
+        (0..3, numeric, 1., metric),
+        (3..5, categorical, 0.5, metric),
+    ])
+    .unwrap()
+}
+
+#[test]
+fn composite() {
+    let metric = mixed_composite();
+    assert!(metric.properties().is_metric());
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let data = (0..100)
+        .map(|_| (0..5).map(|_| rng.gen_range(0..4)).collect::<Vec<i32>>())
+        .collect::<Vec<_>>();
+
+    for x in &data {
+        for y in &data {
+            let expected = mixed(x, y);
+            let actual = metric.distance(x, y);
+            assert!(
+                (actual - expected).abs() <= 1e-12,
+                "Expected {expected} but got {actual} for {x:?} and {y:?}."
+            );
+        }
+    }
+}
+
+#[test]
+fn composite_parts() {
+    let mut metric = mixed_composite();
+    let properties = MetricProperties::default();
+
+    let overlapping: SubMetric<i32, f64> = Box::new(euclidean);
+    assert!(metric.add_part(2..4, overlapping, 1., properties).is_err());
+    let negative: SubMetric<i32, f64> = Box::new(euclidean);
+    assert!(metric.add_part(5..6, negative, -1., properties).is_err());
+    let empty: SubMetric<i32, f64> = Box::new(euclidean);
+    assert!(metric.add_part(6..6, empty, 1., properties).is_err());
+    assert!(metric.properties().is_metric());
+
+    let squared: SubMetric<i32, f64> = Box::new(|x, y| euclidean::<i32, f64>(x, y).powi(2));
+    let squared_properties = MetricProperties {
+        triangle_inequality: false,
+        ..properties
+    };
+    assert!(metric
+        .add_part(5..6, squared, 1., squared_properties)
+        .is_ok());
+    assert!(!metric.obeys_triangle_inequality());
+    assert_eq!(metric.properties(), squared_properties);
+
+    let x = vec![0, 0, 0, 1, 1, 0];
+    let y = vec![0, 0, 0, 1, 1, 3];
+    assert!((metric.distance(&x, &y) - 9.).abs() <= f64::EPSILON);
+}
+
+#[test]
+fn composite_new_properties() {
+    let numeric: SubMetric<i32, f64> = Box::new(euclidean);
+    let asymmetric: SubMetric<i32, f64> = Box::new(|x, y| (x[0] - y[0]).max(0).as_f64());
+    let asymmetric_properties = MetricProperties {
+        symmetry: false,
+        ..MetricProperties::default()
+    };
+    let metric = Composite::new(vec![
+        (0..3, numeric, 1., MetricProperties::default()),
+        (3..4, asymmetric, 1., asymmetric_properties),
+    ])
+    .unwrap();
+    assert_eq!(metric.properties(), asymmetric_properties);
+    assert!(metric.obeys_triangle_inequality());
+
+    let x = vec![0, 0, 0, 2];
+    let y = vec![0, 0, 0, 0];
+    assert!((metric.distance(&x, &y) - 2.).abs() <= f64::EPSILON);
+    assert!(metric.distance(&y, &x).abs() <= f64::EPSILON);
+}
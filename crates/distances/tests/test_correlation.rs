@@ -0,0 +1,60 @@
+use rand::prelude::*;
+
+use distances::vectors::{pearson, spearman};
+
+#[test]
+fn reference_values() {
+    let x = [1_u32, 2, 3, 4, 5];
+    let y = [2_u32, 4, 5, 4, 5];
+
+    // Reference values from `scipy.stats.pearsonr` and `scipy.stats.spearmanr`.
+    let distance: f64 = pearson(&x, &y);
+    assert!((distance - (1. - 0.774_596_669_241_483_4)).abs() <= 1e-12);
+    let distance: f64 = spearman(&x, &y);
+    assert!((distance - (1. - 0.737_864_787_372_622)).abs() <= 1e-12);
+
+    let x = [10_f32, 20., 30., 40.];
+    let y = [1_f32, 3., 2., 100.];
+    let distance: f32 = pearson(&x, &y);
+    assert!((distance - (1. - 0.779_757_8)).abs() <= 1e-6);
+    let distance: f32 = spearman(&x, &y);
+    assert!((distance - (1. - 0.8)).abs() <= 1e-6);
+
+    // Perfectly anti-correlated vectors are at the maximum distance.
+    let distance: f64 = spearman(&x, &[4., 3., 2., 1.]);
+    assert!((distance - 2.).abs() <= 1e-12);
+
+    // The correlation with a constant vector is undefined.
+    let distance: f64 = pearson(&x, &[5., 5., 5., 5.]);
+    assert!((distance - 1.).abs() <= f64::EPSILON);
+}
+
+#[test]
+fn identical_vectors() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    for _ in 0..100 {
+        let x = (0..50)
+            .map(|_| rng.gen_range(-10.0..10.0))
+            .collect::<Vec<f32>>();
+        // Ties in the ranks.
+        let y = x.iter().map(|v| v.round()).collect::<Vec<f32>>();
+
+        for v in [&x, &y] {
+            let distance: f32 = pearson(v, v);
+            assert!(
+                distance.abs() <= f32::EPSILON,
+                "Pearson distance {distance}"
+            );
+            let distance: f32 = spearman(v, v);
+            assert!(
+                distance.abs() <= f32::EPSILON,
+                "Spearman distance {distance}"
+            );
+        }
+
+        // Spearman only depends on the order of the values.
+        let cubed = x.iter().map(|v| v.powi(3)).collect::<Vec<f32>>();
+        let distance: f32 = spearman(&x, &cubed);
+        assert!(distance.abs() <= f32::EPSILON);
+    }
+}
@@ -0,0 +1,32 @@
+use distances::vectors::haversine;
+
+#[test]
+fn city_pairs() {
+    let london = [51.5074, -0.1278];
+    let paris = [48.8566, 2.3522];
+    let new_york = [40.7128, -74.0060];
+    let los_angeles = [34.0522, -118.2437];
+    let sydney = [-33.8688, 151.2093];
+    let tokyo = [35.6762, 139.6503];
+
+    // Reference great-circle distances, in kilometers.
+    for (x, y, expected) in [
+        (london, paris, 343.56),
+        (new_york, los_angeles, 3_935.75),
+        (sydney, tokyo, 7_825.83),
+        (london, new_york, 5_570.23),
+    ] {
+        let distance = haversine(&x, &y);
+        assert!(
+            (distance - expected).abs() <= 0.01,
+            "{x:?} to {y:?}: expected {expected}, got {distance}"
+        );
+        assert!((distance - haversine(&y, &x)).abs() <= f64::EPSILON);
+    }
+
+    assert!(haversine(&tokyo, &tokyo).abs() <= f64::EPSILON);
+
+    // Antipodal points are half the circumference apart.
+    let distance = haversine(&[0., 0.], &[0., 180.]);
+    assert!((distance - 20_015.11).abs() <= 0.01);
+}
@@ -1,4 +1,7 @@
-use distances::sets::dice;
+use std::collections::HashSet;
+
+use distances::sets::{dice, sorted_jaccard};
+use rand::prelude::*;
 
 #[test]
 fn test_dice() {
@@ -26,3 +29,46 @@ fn test_dice() {
     let distance: f32 = dice(&x, &y);
     assert!((distance - 0.8) < f32::EPSILON);
 }
+
+/// Jaccard distance computed with `HashSet`s, as a reference.
+fn hash_jaccard(x: &[u32], y: &[u32]) -> f64 {
+    let x = x.iter().collect::<HashSet<_>>();
+    let y = y.iter().collect::<HashSet<_>>();
+    let union = x.union(&y).count();
+    if union == 0 {
+        return 1.0;
+    }
+    1.0 - x.intersection(&y).count() as f64 / union as f64
+}
+
+#[test]
+fn test_sorted_jaccard() {
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut random_set = |len: usize, universe: u32| {
+        let mut set = (0..len)
+            .map(|_| rng.gen_range(0..universe))
+            .collect::<Vec<_>>();
+        set.sort_unstable();
+        set.dedup();
+        set
+    };
+
+    for _ in 0..100 {
+        for universe in [50, 1_000_000] {
+            let x = random_set(40, universe);
+            let y = random_set(60, universe);
+            let expected = hash_jaccard(&x, &y);
+            let distance: f64 = sorted_jaccard(&x, &y);
+            assert!(
+                (distance - expected).abs() < f64::EPSILON,
+                "{distance} != {expected} for {x:?} and {y:?}"
+            );
+        }
+    }
+
+    let x = vec![1_u32, 5, 9];
+    let distance: f64 = sorted_jaccard(&x, &x);
+    assert!(distance < f64::EPSILON);
+    let distance: f64 = sorted_jaccard(&x, &[]);
+    assert!((distance - 1.0).abs() < f64::EPSILON);
+}
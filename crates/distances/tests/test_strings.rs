@@ -0,0 +1,64 @@
+use distances::strings::{levenshtein, weighted_levenshtein};
+use rand::prelude::*;
+
+/// A random string over the DNA alphabet.
+fn random_dna(rng: &mut impl Rng, max_len: usize) -> String {
+    let len = rng.gen_range(0..=max_len);
+    (0..len)
+        .map(|_| *b"ACGT".choose(rng).unwrap_or(&b'A') as char)
+        .collect()
+}
+
+/// Transitions (A <-> G, C <-> T) cost 1 and transversions cost 2.
+fn dna_cost(a: char, b: char) -> u32 {
+    match (a, b) {
+        _ if a == b => 0,
+        ('A', 'G') | ('G', 'A') | ('C', 'T') | ('T', 'C') => 1,
+        _ => 2,
+    }
+}
+
+#[test]
+fn weighted_levenshtein_unit_costs() {
+    let metric = weighted_levenshtein(|a: char, b: char| u32::from(a != b), 1);
+
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..1000 {
+        let x = random_dna(&mut rng, 20);
+        let y = random_dna(&mut rng, 20);
+        assert_eq!(metric(&x, &y), levenshtein::<u32>(&x, &y), "{x} vs {y}");
+    }
+
+    assert_eq!(metric("", "ACGT"), 4);
+    assert_eq!(metric("ACGT", ""), 4);
+}
+
+#[test]
+fn weighted_levenshtein_dna_costs() {
+    let metric = weighted_levenshtein(dna_cost, 2);
+
+    // A transition is cheaper than a transversion, although both are one
+    // substitution for the unweighted distance.
+    assert_eq!(metric("ACGT", "GCGT"), 1);
+    assert_eq!(metric("ACGT", "CCGT"), 2);
+    assert_eq!(
+        levenshtein::<u32>("ACGT", "GCGT"),
+        levenshtein::<u32>("ACGT", "CCGT")
+    );
+
+    // Gaps cost 2 each.
+    assert_eq!(metric("ACGT", "ACG"), 2);
+    assert_eq!(metric("", "AC"), 4);
+
+    // The weighted distance is symmetric, and lies between the unweighted
+    // distance and twice the unweighted distance.
+    let mut rng = StdRng::seed_from_u64(42);
+    for _ in 0..1000 {
+        let x = random_dna(&mut rng, 20);
+        let y = random_dna(&mut rng, 20);
+        let d = metric(&x, &y);
+        let unweighted = levenshtein::<u32>(&x, &y);
+        assert_eq!(d, metric(&y, &x), "{x} vs {y}");
+        assert!(unweighted <= d && d <= 2 * unweighted, "{x} vs {y}");
+    }
+}
@@ -1,9 +1,15 @@
 use core::f32::EPSILON;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 use rand::prelude::*;
 use symagen::random_data;
 
-use distances::vectors::{chebyshev, euclidean, euclidean_sq, l3_norm, l4_norm, manhattan};
+use distances::{
+    vectors::{
+        angular, chebyshev, cosine, dtw, euclidean, euclidean_sq, l3_norm, l4_norm, manhattan,
+    },
+    Number,
+};
 
 fn l1(x: &[f32], y: &[f32]) -> f32 {
     x.iter()
@@ -42,6 +48,260 @@ fn l_inf(x: &[f32], y: &[f32]) -> f32 {
         .fold(0., |acc, (x, y)| acc.max((x - y).abs()))
 }
 
+/// Reference DTW with a full cost matrix.
+fn dtw_ref(x: &[f32], y: &[f32], band: Option<usize>) -> f32 {
+    let band = band.map_or(usize::MAX, |b| b.max(x.len().abs_diff(y.len())));
+    let mut cost = vec![vec![f32::INFINITY; y.len() + 1]; x.len() + 1];
+    cost[0][0] = 0.;
+    for i in 1..=x.len() {
+        for j in 1..=y.len() {
+            if i.abs_diff(j) <= band {
+                let best = cost[i - 1][j - 1].min(cost[i - 1][j]).min(cost[i][j - 1]);
+                cost[i][j] = (x[i - 1] - y[j - 1]).abs() + best;
+            }
+        }
+    }
+    cost[x.len()][y.len()]
+}
+
+#[test]
+fn dtw_f32() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let data = (0..20)
+        .map(|_| {
+            let len = rng.gen_range(5..20);
+            (0..len)
+                .map(|_| rng.gen_range(-10_f32..10.))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for x in data.iter() {
+        for y in data.iter() {
+            let expected = dtw_ref(x, y, None);
+            let actual: f32 = dtw(None)(x, y);
+            assert!(
+                (expected - actual).abs() <= 1e-4,
+                "DTW: expected: {}, actual: {}",
+                expected,
+                actual
+            );
+
+            for band in [0, 1, 2, 4] {
+                let expected = dtw_ref(x, y, Some(band));
+                let actual: f32 = dtw(Some(band))(x, y);
+                assert!(
+                    (expected - actual).abs() <= 1e-4,
+                    "DTW with band {}: expected: {}, actual: {}",
+                    band,
+                    expected,
+                    actual
+                );
+                assert!(actual + 1e-4 >= dtw::<f32, f32>(None)(x, y));
+            }
+
+            if x.len() == y.len() {
+                let a_l1: f32 = dtw(Some(0))(x, y);
+                assert!((l1(x, y) - a_l1).abs() <= 1e-4);
+            }
+        }
+    }
+}
+
+#[test]
+fn dtw_band_f32() {
+    // The spikes are 5 steps apart, so aligning them needs a band of at least 5.
+    let mut x = vec![0_f32; 10];
+    x[7] = 5.;
+    let mut y = vec![0_f32; 10];
+    y[2] = 5.;
+
+    let unbanded: f32 = dtw(None)(&x, &y);
+    assert!(unbanded.abs() <= f32::EPSILON);
+
+    for band in [0, 1, 2, 4] {
+        let banded: f32 = dtw(Some(band))(&x, &y);
+        assert!(
+            (banded - 10.).abs() <= f32::EPSILON,
+            "Band {}: expected: 10, actual: {}",
+            band,
+            banded
+        );
+    }
+    for band in [5, 9, 100] {
+        let banded: f32 = dtw(Some(band))(&x, &y);
+        assert!(
+            (banded - unbanded).abs() <= f32::EPSILON,
+            "Band {}: expected: {}, actual: {}",
+            band,
+            unbanded,
+            banded
+        );
+    }
+
+    // The band is widened to the difference in lengths.
+    let short = vec![0_f32, 5.];
+    let banded: f32 = dtw(Some(0))(&short, &y);
+    let unbanded: f32 = dtw(None)(&short, &y);
+    assert!((banded - unbanded).abs() <= f32::EPSILON);
+}
+
+/// The number of calls to `Counted::abs_diff`, i.e. the number of cells of the
+/// DTW cost matrix that were computed.
+static ABS_DIFF_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// An `f32` that counts the calls to `abs_diff`.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+struct Counted(f32);
+
+macro_rules! impl_counted_ops {
+    ($($op:ident, $op_fn:ident, $op_assign:ident, $op_assign_fn:ident;)*) => {
+        $(
+            impl core::ops::$op for Counted {
+                type Output = Self;
+
+                fn $op_fn(self, rhs: Self) -> Self {
+                    Self(core::ops::$op::$op_fn(self.0, rhs.0))
+                }
+            }
+
+            impl core::ops::$op_assign for Counted {
+                fn $op_assign_fn(&mut self, rhs: Self) {
+                    core::ops::$op_assign::$op_assign_fn(&mut self.0, rhs.0);
+                }
+            }
+        )*
+    };
+}
+
+impl_counted_ops! {
+    Add, add, AddAssign, add_assign;
+    Sub, sub, SubAssign, sub_assign;
+    Mul, mul, MulAssign, mul_assign;
+    Div, div, DivAssign, div_assign;
+    Rem, rem, RemAssign, rem_assign;
+}
+
+impl core::iter::Sum for Counted {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|v| v.0).sum())
+    }
+}
+
+impl core::fmt::Display for Counted {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Number for Counted {
+    fn zero() -> Self {
+        Self(0.)
+    }
+
+    fn one() -> Self {
+        Self(1.)
+    }
+
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        Self(self.0.mul_add(a.0, b.0))
+    }
+
+    fn mul_add_assign(&mut self, a: Self, b: Self) {
+        *self = Number::mul_add(*self, a, b);
+    }
+
+    fn from<T: Number>(n: T) -> Self {
+        Self(n.as_f32())
+    }
+
+    fn as_f32(self) -> f32 {
+        self.0
+    }
+
+    fn as_f64(self) -> f64 {
+        self.0.as_f64()
+    }
+
+    fn as_u64(self) -> u64 {
+        self.0.as_u64()
+    }
+
+    fn as_i64(self) -> i64 {
+        self.0.as_i64()
+    }
+
+    fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    fn abs_diff(self, other: Self) -> Self {
+        ABS_DIFF_CALLS.fetch_add(1, Ordering::Relaxed);
+        Self(Number::abs_diff(self.0, other.0))
+    }
+
+    fn powi(self, exp: i32) -> Self {
+        Self(self.0.powi(exp))
+    }
+
+    fn num_bytes() -> usize {
+        f32::num_bytes()
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        Self(<f32 as Number>::from_le_bytes(bytes))
+    }
+
+    fn to_le_bytes(self) -> Vec<u8> {
+        Number::to_le_bytes(self.0)
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Self {
+        Self(<f32 as Number>::from_be_bytes(bytes))
+    }
+
+    fn to_be_bytes(self) -> Vec<u8> {
+        Number::to_be_bytes(self.0)
+    }
+
+    fn epsilon() -> Self {
+        Self(f32::EPSILON)
+    }
+
+    fn next_random<R: Rng>(rng: &mut R) -> Self {
+        Self(rng.gen())
+    }
+}
+
+#[test]
+fn dtw_band_work() {
+    let (n, m) = (2_000, 1_990);
+    let mut rng = StdRng::seed_from_u64(42);
+    let x = (0..n).map(|_| Counted(rng.gen())).collect::<Vec<_>>();
+    let y = (0..m).map(|_| Counted(rng.gen())).collect::<Vec<_>>();
+
+    ABS_DIFF_CALLS.store(0, Ordering::Relaxed);
+    let _: f32 = dtw(None)(&x, &y);
+    assert_eq!(ABS_DIFF_CALLS.load(Ordering::Relaxed), n * m);
+
+    for band in [10, 50] {
+        // Each element of `x` is compared with at most `2 * band + 1`
+        // elements of `y`.
+        let expected = (0..n)
+            .map(|i: usize| (i + band + 1).min(m) - i.saturating_sub(band))
+            .sum::<usize>();
+        assert!(expected <= n * (2 * band + 1));
+
+        ABS_DIFF_CALLS.store(0, Ordering::Relaxed);
+        let _: f32 = dtw(Some(band))(&x, &y);
+        assert_eq!(
+            ABS_DIFF_CALLS.load(Ordering::Relaxed),
+            expected,
+            "Band {band}"
+        );
+    }
+}
+
 #[test]
 fn lp_f32() {
     let seed = 42;
@@ -114,3 +374,48 @@ fn lp_f32() {
         }
     }
 }
+
+#[test]
+fn angular_f32() {
+    let data =
+        random_data::random_tabular(30, 3, -1., 1., &mut rand::rngs::StdRng::seed_from_u64(42));
+
+    for x in data.iter() {
+        for y in data.iter() {
+            let cos = x.iter().zip(y).map(|(a, b)| a * b).sum::<f32>()
+                / (x.iter().map(|a| a * a).sum::<f32>() * y.iter().map(|b| b * b).sum::<f32>())
+                    .sqrt();
+            let expected = cos.clamp(-1., 1.).acos() / core::f32::consts::PI;
+            let actual: f32 = angular(x, y);
+            assert!(
+                (expected - actual).abs() <= 1e-3,
+                "Angular: expected: {}, actual: {}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    // The angular distance obeys the triangle inequality on every triple,
+    // while the cosine distance violates it on some.
+    let mut cosine_violations = 0;
+    for x in data.iter() {
+        for y in data.iter() {
+            for z in data.iter() {
+                let [xy, yz, xz]: [f32; 3] = [angular(x, y), angular(y, z), angular(x, z)];
+                assert!(xz <= xy + yz + f32::EPSILON, "{xz} > {xy} + {yz}");
+
+                let [xy, yz, xz]: [f32; 3] = [cosine(x, y), cosine(y, z), cosine(x, z)];
+                if xz > xy + yz + f32::EPSILON {
+                    cosine_violations += 1;
+                }
+            }
+        }
+    }
+    assert!(cosine_violations > 0);
+
+    let zero = vec![0.; 3];
+    assert_eq!(angular::<f32, f32>(&zero, &zero), 0.);
+    assert_eq!(angular::<f32, f32>(&zero, &data[0]), 0.5);
+    assert_eq!(angular::<f32, f32>(&data[0], &data[0]), 0.);
+}